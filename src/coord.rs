@@ -1,10 +1,12 @@
 use std::ops;
 
+use serde::{Deserialize, Serialize};
+
 extern crate approx;
 use approx::AbsDiffEq;
 use std::f64;
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub struct Vec3d {
     pub x: f64,
     pub y: f64,
@@ -37,6 +39,23 @@ impl Vec3d {
     pub fn sum(&self) -> f64 {
         self.x + self.y + self.z
     }
+    pub fn component(&self, axis: usize) -> f64 {
+        match axis {
+            0 => self.x,
+            1 => self.y,
+            _ => self.z,
+        }
+    }
+    //Unit vectors orthogonal to self (unit vector), along x when possible
+    pub fn orthonormal_basis(&self) -> (Vec3d, Vec3d) {
+        let axis = if self.x.abs() < 0.9 {
+            Vec3d { x: 1., y: 0., z: 0. }
+        } else {
+            Vec3d { x: 0., y: 1., z: 0. }
+        };
+        let tangent = (axis - *self * self.dot(axis)).normalize().unwrap();
+        (tangent, self.cross(tangent))
+    }
     //Axis must be unit vector
     pub fn symmetry(self, axis: Vec3d) -> Vec3d {
         //Project on axis
@@ -132,6 +151,162 @@ impl AbsDiffEq for Vec3d {
     }
 }
 
+//Affine transform in homogeneous coordinates, m[row][column]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Matrix4 {
+    pub m: [[f64; 4]; 4],
+}
+
+impl Matrix4 {
+    pub fn identity() -> Matrix4 {
+        let mut m = [[0.; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            row[i] = 1.;
+        }
+        Matrix4 { m }
+    }
+
+    pub fn translation(v: Vec3d) -> Matrix4 {
+        let mut t = Matrix4::identity();
+        t.m[0][3] = v.x;
+        t.m[1][3] = v.y;
+        t.m[2][3] = v.z;
+        t
+    }
+
+    pub fn scaling(v: Vec3d) -> Matrix4 {
+        let mut t = Matrix4::identity();
+        t.m[0][0] = v.x;
+        t.m[1][1] = v.y;
+        t.m[2][2] = v.z;
+        t
+    }
+
+    //Rotation of angle (radians) around axis, counterclockwise when the axis points to the viewer
+    pub fn rotation(axis: Vec3d, angle: f64) -> Matrix4 {
+        let Vec3d { x, y, z } = axis.normalize().unwrap();
+        let (sin, cos) = angle.sin_cos();
+        let t = 1. - cos;
+        Matrix4 {
+            m: [
+                [t * x * x + cos, t * x * y - sin * z, t * x * z + sin * y, 0.],
+                [t * x * y + sin * z, t * y * y + cos, t * y * z - sin * x, 0.],
+                [t * x * z - sin * y, t * y * z + sin * x, t * z * z + cos, 0.],
+                [0., 0., 0., 1.],
+            ],
+        }
+    }
+
+    //Rigid transform from a local frame whose z axis is the given (unit) axis
+    pub fn frame(origin: Vec3d, axis: Vec3d) -> Matrix4 {
+        let (u, v) = axis.orthonormal_basis();
+        Matrix4 {
+            m: [
+                [u.x, v.x, axis.x, origin.x],
+                [u.y, v.y, axis.y, origin.y],
+                [u.z, v.z, axis.z, origin.z],
+                [0., 0., 0., 1.],
+            ],
+        }
+    }
+
+    pub fn transpose(&self) -> Matrix4 {
+        let mut m = [[0.; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = self.m[j][i];
+            }
+        }
+        Matrix4 { m }
+    }
+
+    //Gauss-Jordan elimination, None for singular matrices
+    pub fn inverse(&self) -> Option<Matrix4> {
+        let mut a = self.m;
+        let mut inv = Matrix4::identity().m;
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+                .unwrap();
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let p = a[col][col];
+            for j in 0..4 {
+                a[col][j] /= p;
+                inv[col][j] /= p;
+            }
+            for i in 0..4 {
+                if i != col {
+                    let f = a[i][col];
+                    for j in 0..4 {
+                        a[i][j] -= f * a[col][j];
+                        inv[i][j] -= f * inv[col][j];
+                    }
+                }
+            }
+        }
+        Some(Matrix4 { m: inv })
+    }
+
+    //Normals are transformed by the inverse transpose
+    pub fn normal_matrix(&self) -> Option<Matrix4> {
+        self.inverse().map(|inv| inv.transpose())
+    }
+
+    pub fn transform_point(&self, p: Vec3d) -> Vec3d {
+        self.transform_vector(p)
+            + Vec3d {
+                x: self.m[0][3],
+                y: self.m[1][3],
+                z: self.m[2][3],
+            }
+    }
+
+    pub fn transform_vector(&self, v: Vec3d) -> Vec3d {
+        let m = &self.m;
+        Vec3d {
+            x: m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            y: m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            z: m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        }
+    }
+}
+
+//self * rhs applies rhs first
+impl ops::Mul<Matrix4> for Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, _rhs: Matrix4) -> Matrix4 {
+        let mut m = [[0.; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = (0..4).map(|k| self.m[i][k] * _rhs.m[k][j]).sum();
+            }
+        }
+        Matrix4 { m }
+    }
+}
+
+impl AbsDiffEq for Matrix4 {
+    type Epsilon = f64;
+
+    fn default_epsilon() -> f64 {
+        f64::default_epsilon()
+    }
+
+    fn abs_diff_eq(&self, other: &Self, epsilon: f64) -> bool {
+        self.m
+            .iter()
+            .flatten()
+            .zip(other.m.iter().flatten())
+            .all(|(a, b)| f64::abs_diff_eq(a, b, epsilon))
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -405,4 +580,127 @@ mod tests {
             6.
         );
     }
+
+    #[test]
+    fn translate_point_not_vector() {
+        let t = Matrix4::translation(Vec3d {
+            x: 1.,
+            y: 2.,
+            z: 3.,
+        });
+        let v = Vec3d {
+            x: 1.,
+            y: 1.,
+            z: 1.,
+        };
+        assert_eq!(
+            t.transform_point(v),
+            Vec3d {
+                x: 2.,
+                y: 3.,
+                z: 4.
+            }
+        );
+        assert_eq!(t.transform_vector(v), v);
+    }
+
+    #[test]
+    fn rotate_around_z() {
+        let r = Matrix4::rotation(
+            Vec3d {
+                x: 0.,
+                y: 0.,
+                z: 2.,
+            },
+            f64::consts::FRAC_PI_2,
+        );
+        assert_abs_diff_eq!(
+            r.transform_vector(Vec3d {
+                x: 1.,
+                y: 0.,
+                z: 0.
+            }),
+            Vec3d {
+                x: 0.,
+                y: 1.,
+                z: 0.
+            }
+        );
+    }
+
+    #[test]
+    fn compose_and_invert() {
+        let m = Matrix4::translation(Vec3d {
+            x: 1.,
+            y: -2.,
+            z: 0.5,
+        }) * Matrix4::rotation(
+            Vec3d {
+                x: 1.,
+                y: 1.,
+                z: 0.,
+            },
+            0.7,
+        ) * Matrix4::scaling(Vec3d {
+            x: 2.,
+            y: 3.,
+            z: 0.5,
+        });
+        let inv = m.inverse().unwrap();
+        assert_abs_diff_eq!(m * inv, Matrix4::identity(), epsilon = 1e-12);
+        assert_abs_diff_eq!(inv * m, Matrix4::identity(), epsilon = 1e-12);
+        assert_eq!(Matrix4::scaling(Vec3d { x: 1., y: 0., z: 1. }).inverse(), None);
+    }
+
+    #[test]
+    fn normal_stays_orthogonal() {
+        let m = Matrix4::scaling(Vec3d {
+            x: 1.,
+            y: 4.,
+            z: 1.,
+        });
+        //tangent and normal of the plane x + y = 0
+        let tangent = Vec3d {
+            x: 1.,
+            y: -1.,
+            z: 0.,
+        };
+        let normal = Vec3d {
+            x: 1.,
+            y: 1.,
+            z: 0.,
+        };
+        let tangent = m.transform_vector(tangent);
+        let normal = m.normal_matrix().unwrap().transform_vector(normal);
+        assert_abs_diff_eq!(tangent.dot(normal), 0.);
+    }
+
+    #[test]
+    fn orthonormal_basis() {
+        let z = Vec3d { x: 0., y: 0., z: 1. };
+        assert_eq!(
+            z.orthonormal_basis(),
+            (Vec3d { x: 1., y: 0., z: 0. }, Vec3d { x: 0., y: 1., z: 0. })
+        );
+        let n = Vec3d { x: 1., y: 0.5, z: -2. }.normalize().unwrap();
+        let (t, b) = n.orthonormal_basis();
+        assert_abs_diff_eq!(t.dot(n), 0.);
+        assert_abs_diff_eq!(b.dot(n), 0.);
+        assert_abs_diff_eq!(t.dot(b), 0.);
+        assert_abs_diff_eq!(b.norm(), 1.);
+    }
+
+    #[test]
+    fn local_frame() {
+        let origin = Vec3d { x: 1., y: 2., z: 3. };
+        let axis = Vec3d { x: 0., y: 1., z: 1. }.normalize().unwrap();
+        let m = Matrix4::frame(origin, axis);
+        assert_abs_diff_eq!(m.transform_point(Vec3d { x: 0., y: 0., z: 2. }), origin + axis * 2.);
+        let rotation = Matrix4::frame(Vec3d { x: 0., y: 0., z: 0. }, axis);
+        assert_abs_diff_eq!(
+            m.inverse().unwrap(),
+            rotation.transpose() * Matrix4::translation(origin * -1.),
+            epsilon = 1e-12
+        );
+    }
 }
@@ -1,5 +1,9 @@
-use clap::Parser;
-use scene::{Parallel, Scene};
+use std::ops::Range;
+use std::path::Path;
+use std::process;
+
+use clap::{Parser, Subcommand};
+use scene::{Frames, Parallel, Scene, SceneDescription};
 
 mod coord;
 mod scene;
@@ -10,6 +14,8 @@ mod scene;
 #[command(version = "1.0")]
 #[command(version, about, long_about = None)]
 struct Args {
+    /// Scene file, in JSON (.json), TOML (.toml) or XML, or a pbrt (.pbrt),
+    /// Mitsuba (XML) or glTF (.gltf, .glb) scene to import
     #[arg(short, long, default_value = "scene.xml")]
     input: String,
 
@@ -18,11 +24,161 @@ struct Args {
 
     #[arg(short, long, default_value = "no")]
     parallel: Parallel,
+
+    /// Frames of an animation (0..120, last one excluded), each one is
+    /// saved with its number after the output name (image_0001.png)
+    #[arg(short, long, value_parser = parse_frames)]
+    frames: Option<Range<u32>>,
+
+    /// Value of a scene variable (r=3.0), replacing the one of its <var>
+    /// element, may be given several times
+    #[arg(long, global = true, value_parser = parse_variable)]
+    set: Vec<(String, f64)>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Reports what may not render as expected in a scene, without rendering it
+    Validate { file: String },
+    /// Writes a scene to another file, in the format of its extension
+    /// (.json, .toml or XML). Files it uses are still found from the scene
+    Convert { file: String, output: String },
+}
+
+fn parse_frames(s: &str) -> Result<Range<u32>, String> {
+    let (start, end) = s
+        .split_once("..")
+        .ok_or(format!("frames {:?} are not a range like 0..120", s))?;
+    let bound = |b: &str| b.parse::<u32>().map_err(|e| format!("frame {:?}: {}", b, e));
+    Ok(bound(start)?..bound(end)?)
+}
+
+fn parse_variable(s: &str) -> Result<(String, f64), String> {
+    let (name, value) = s
+        .split_once('=')
+        .ok_or(format!("variable {:?} is not like r=3.0", s))?;
+    let value = value.trim().parse::<f64>().map_err(|e| format!("variable {:?}: {}", name, e))?;
+    Ok((String::from(name.trim()), value))
+}
+
+//image.png -> image_0001.png
+fn numbered(output: &str, frame: u32) -> String {
+    let path = Path::new(output);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{}_{:04}.{}", stem, frame, ext.to_string_lossy()),
+        None => format!("{}_{:04}", stem, frame),
+    };
+    path.with_file_name(name).display().to_string()
+}
+
+fn load(input: &str, frame: u32, variables: &[(String, f64)]) -> Scene {
+    let (scene, warnings) = Scene::load(input.to_string(), frame, variables).unwrap_or_else(|e| {
+        eprintln!("error in {}: {}", input, e);
+        process::exit(1)
+    });
+    for warning in warnings.iter() {
+        eprintln!("warning in {}: {}", input, warning);
+    }
+    scene
+}
+
+//scene of an animation, read once for all its frames
+fn load_frames(input: &str, variables: &[(String, f64)]) -> Frames {
+    let (frames, warnings) = Frames::load(input, variables).unwrap_or_else(|e| {
+        eprintln!("error in {}: {}", input, e);
+        process::exit(1)
+    });
+    for warning in warnings.iter() {
+        eprintln!("warning in {}: {}", input, warning);
+    }
+    frames
+}
+
+//warnings and error of a scene, exits with an error status when it cannot be loaded
+fn validate(file: &str, variables: &[(String, f64)]) {
+    let validation = Scene::validate(file, variables);
+    for warning in validation.warnings.iter() {
+        println!("warning in {}: {}", file, warning);
+    }
+    match validation.error {
+        Some(e) => {
+            eprintln!("error in {}: {}", file, e);
+            process::exit(1)
+        }
+        None if validation.warnings.is_empty() => println!("{} is valid", file),
+        None => {
+            let count = validation.warnings.len();
+            println!("{} can be rendered, with {} warning{}", file, count, if count > 1 { "s" } else { "" })
+        }
+    }
+}
+
+//animations are kept in JSON and TOML files, XML ones get the values of the first frame
+fn convert(file: &str, output: &str, variables: &[(String, f64)]) {
+    let saved = SceneDescription::load(file, 0, variables).and_then(|(description, warnings)| {
+        for warning in warnings.iter() {
+            eprintln!("warning in {}: {}", file, warning);
+        }
+        description.save(output)
+    });
+    if let Err(e) = saved {
+        eprintln!("error in {}: {}", file, e);
+        process::exit(1)
+    }
 }
 
 fn main() {
     let args = Args::parse();
 
-    let scene = Scene::load(args.input);
-    scene.render(args.parallel, args.output);
+    match &args.command {
+        Some(Command::Validate { file }) => return validate(file, &args.set),
+        Some(Command::Convert { file, output }) => return convert(file, output, &args.set),
+        None => (),
+    }
+    match args.frames {
+        None => {
+            let scene = load(&args.input, 0, &args.set);
+            scene.render(args.parallel, args.output);
+        }
+        Some(frames) => {
+            let animation = load_frames(&args.input, &args.set);
+            for frame in frames {
+                let scene = animation.at(frame).unwrap_or_else(|e| {
+                    eprintln!("error in {} at frame {}: {}", args.input, frame, e);
+                    process::exit(1)
+                });
+                scene.render(args.parallel.clone(), numbered(&args.output, frame));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn frames_range() {
+        assert_eq!(parse_frames("0..120"), Ok(0..120));
+        assert!(parse_frames("12").is_err());
+        assert!(parse_frames("a..3").is_err());
+    }
+
+    #[test]
+    fn variables() {
+        assert_eq!(parse_variable("r=3.0"), Ok((String::from("r"), 3.)));
+        assert!(parse_variable("r").is_err());
+        assert!(parse_variable("r=big").is_err());
+    }
+
+    #[test]
+    fn numbered_output() {
+        assert_eq!(numbered("image.png", 1), "image_0001.png");
+        assert_eq!(numbered("out/frame.png", 120), "out/frame_0120.png");
+    }
 }
@@ -1,18 +1,27 @@
 use core::f64;
-use std::env;
 use std::fs::{self, File};
 use std::io::BufWriter;
 use std::path::Path;
 
 use camera::Camera;
+use description::build::Files;
+pub use description::SceneDescription;
+pub use error::SceneError;
+pub use validate::{Validation, Warning};
 use light::Light;
 use object::Object;
 
+mod animation;
 mod camera;
+mod description;
+mod error;
+mod import;
 mod light;
+mod motion;
 mod object;
 mod parser;
 mod renderer;
+mod validate;
 
 pub const MAX_BOUNCES:u16 = 3;
 
@@ -31,14 +40,119 @@ pub enum Parallel {
     Rayon,
 }
 
+fn read(filename: &str) -> Result<String, SceneError> {
+    fs::read_to_string(filename).map_err(|e| SceneError::Io {
+        location: None,
+        message: e.to_string(),
+    })
+}
+
+//relative paths of the scene are resolved from its directory
+fn base_dir(filename: &str) -> &Path {
+    Path::new(filename).parent().unwrap_or(Path::new("."))
+}
+
+//format of a scene file, from its extension, Mitsuba scenes are XML too
+fn is_xml(filename: &str) -> bool {
+    !matches!(extension(filename).as_deref(), Some("json" | "toml" | "pbrt" | "gltf" | "glb"))
+}
+
+fn extension(filename: &str) -> Option<String> {
+    Path::new(filename)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+}
+
+impl SceneDescription {
+    //JSON, TOML or XML scene file, animated elements of XML files take their
+    //value at the given frame. pbrt, Mitsuba and glTF scenes are imported,
+    //with warnings about what they lose. The variables given replace the
+    //ones of XML files.
+    pub fn load(
+        filename: &str,
+        frame: u32,
+        variables: &[(String, f64)],
+    ) -> Result<(SceneDescription, Vec<Warning>), SceneError> {
+        let dir = base_dir(filename);
+        //binary glTF files are not text
+        if matches!(extension(filename).as_deref(), Some("gltf" | "glb")) {
+            let bytes = fs::read(filename).map_err(|e| SceneError::Io {
+                location: None,
+                message: e.to_string(),
+            })?;
+            return import::gltf::read(&bytes, dir);
+        }
+        let file_content = read(filename)?;
+        let description = match extension(filename).as_deref() {
+            Some("json") => SceneDescription::from_json(&file_content)?,
+            Some("toml") => SceneDescription::from_toml(&file_content)?,
+            Some("pbrt") => return import::pbrt::read(&file_content, dir),
+            _ if import::mitsuba::is_mitsuba(&file_content) => return import::mitsuba::read(&file_content, dir),
+            _ => parser::read_from_xml_string(&file_content, dir, frame as f64, variables)?,
+        };
+        Ok((description, vec![]))
+    }
+
+    //in the format of the file extension, like load
+    pub fn save(&self, filename: &str) -> Result<(), SceneError> {
+        let unwritable = |message: String| SceneError::Io {
+            location: None,
+            message: format!("{} cannot be written: {}", filename, message),
+        };
+        let file_content = match extension(filename).as_deref() {
+            Some("json") => serde_json::to_string_pretty(self).map_err(|e| unwritable(e.to_string()))?,
+            Some("toml") => toml::to_string_pretty(self).map_err(|e| unwritable(e.to_string()))?,
+            _ => parser::writer::write_xml_string(self),
+        };
+        fs::write(filename, file_content).map_err(|e| unwritable(e.to_string()))
+    }
+}
+
+//Scene file read once, and built again at each frame of its animation
+pub struct Frames {
+    description: SceneDescription,
+    files: Files,
+}
+
+impl Frames {
+    //the warnings are the ones of imported scenes
+    pub fn load(filename: &str, variables: &[(String, f64)]) -> Result<(Frames, Vec<Warning>), SceneError> {
+        let (description, warnings) = SceneDescription::load(filename, 0, variables)?;
+        let files = Files::new(base_dir(filename));
+        Ok((Frames { description, files }, warnings))
+    }
+
+    pub fn at(&self, frame: u32) -> Result<Scene, SceneError> {
+        self.description.at(frame as f64)?.build_from(&self.files)
+    }
+}
+
 impl Scene {
-    pub fn load(filename: String) -> Scene {
-        let file_content = fs::read_to_string(&filename).expect(&format!(
-            "file {} cannot be read (path {})",
-            &filename,
-            env::current_dir().unwrap().display()
-        ));
-        parser::load_from_xml_string(file_content)
+    //animated elements take their value at the given frame, the warnings are
+    //the ones of imported scenes
+    pub fn load(filename: String, frame: u32, variables: &[(String, f64)]) -> Result<(Scene, Vec<Warning>), SceneError> {
+        let (description, warnings) = SceneDescription::load(&filename, frame, variables)?;
+        Ok((description.build(base_dir(&filename))?, warnings))
+    }
+
+    //warnings about a scene file, without rendering it
+    //JSON and TOML files are just loaded, imported ones keep their warnings
+    pub fn validate(filename: &str, variables: &[(String, f64)]) -> Validation {
+        let xml = match is_xml(filename) {
+            true => read(filename).map(|file_content| (!import::mitsuba::is_mitsuba(&file_content)).then_some(file_content)),
+            false => Ok(None),
+        };
+        let result = xml.and_then(|file_content| match file_content {
+            Some(file_content) => Ok(validate::validate(&file_content, base_dir(filename), variables)),
+            None => Scene::load(String::from(filename), 0, variables).map(|(_, warnings)| Validation {
+                warnings,
+                error: None,
+            }),
+        });
+        result.unwrap_or_else(|error| Validation {
+            warnings: vec![],
+            error: Some(error),
+        })
     }
 
     fn to_png(&self, data: Vec<f64>, output:String) {
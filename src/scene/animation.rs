@@ -0,0 +1,228 @@
+use std::str::FromStr;
+
+use quick_xml::events::BytesStart;
+use serde::{Deserialize, Serialize};
+
+use super::error::{Location, SceneError};
+use super::parser::{expression, xml};
+
+//Value between keys
+#[derive(Debug, PartialEq, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Interpolation {
+    #[default]
+    Linear,
+    //smooth cubic curve through the keys, that eases in and out at the ends
+    Bezier,
+}
+
+//Values of one attribute at some frames
+#[derive(Debug, PartialEq, Clone)]
+pub struct Track {
+    keys: Vec<(f64, f64)>,
+    interpolation: Interpolation,
+}
+
+impl FromStr for Interpolation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Interpolation, String> {
+        match s {
+            "linear" => Ok(Interpolation::Linear),
+            "bezier" => Ok(Interpolation::Bezier),
+            _ => Err(format!("unknown interpolation {:?}", s)),
+        }
+    }
+}
+
+impl Track {
+    pub fn new(mut keys: Vec<(f64, f64)>, interpolation: Interpolation) -> Track {
+        assert!(!keys.is_empty(), "track without key");
+        keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Track { keys, interpolation }
+    }
+
+    //constant before the first key and after the last one
+    pub fn at(&self, frame: f64) -> f64 {
+        let next = self.keys.partition_point(|(f, _)| *f <= frame);
+        if next == 0 {
+            return self.keys[0].1;
+        }
+        if next == self.keys.len() {
+            return self.keys[next - 1].1;
+        }
+        let (f0, v0) = self.keys[next - 1];
+        let (f1, v1) = self.keys[next];
+        let t = (frame - f0) / (f1 - f0);
+        match self.interpolation {
+            Interpolation::Linear => v0 + t * (v1 - v0),
+            Interpolation::Bezier => {
+                //control points from the slopes at the keys, given by their neighbours
+                let (b1, b2) = (
+                    v0 + self.slope(next - 1) * (f1 - f0) / 3.,
+                    v1 - self.slope(next) * (f1 - f0) / 3.,
+                );
+                let s = 1. - t;
+                s * s * s * v0 + 3. * s * s * t * b1 + 3. * s * t * t * b2 + t * t * t * v1
+            }
+        }
+    }
+
+    //flat on the first and last keys
+    fn slope(&self, k: usize) -> f64 {
+        if k == 0 || k == self.keys.len() - 1 {
+            return 0.;
+        }
+        let (f0, v0) = self.keys[k - 1];
+        let (f1, v1) = self.keys[k + 1];
+        (v1 - v0) / (f1 - f0)
+    }
+}
+
+//keys of each animated attribute of an element
+pub type Tracks = Vec<(String, Vec<(f64, f64)>)>;
+
+//Element with its attributes at the given frame, given by the keys it
+//contains instead of being empty, and the keys of these attributes.
+//Attributes missing from keys keep their value:
+//<pos x="0" y="0" z="1" interpolation="bezier">
+//    <key frame="0" x="0"/>
+//    <key frame="120" x="10"/>
+//</pos>
+pub fn animate(
+    element: &BytesStart,
+    keys: &[(BytesStart, Location)],
+    frame: f64,
+    at: &Location,
+) -> Result<(BytesStart<'static>, Interpolation, Tracks), SceneError> {
+    let name = String::from_utf8_lossy(element.name().as_ref()).into_owned();
+    let mut interpolation = Interpolation::Linear;
+    let mut attributes: Vec<(String, String)> = Vec::new();
+    for (key, value) in xml::attributes(element, at)? {
+        if key == "interpolation" {
+            interpolation = value.parse().map_err(|message| SceneError::InvalidValue {
+                location: at.clone(),
+                attribute: key,
+                value,
+                message,
+            })?;
+        } else {
+            attributes.push((key, value));
+        }
+    }
+
+    //one track per attribute, from the keys that give it
+    let mut tracks: Tracks = Vec::new();
+    for (key, location) in keys {
+        let mut key_frame = None;
+        let mut values = Vec::new();
+        for (attribute, value) in xml::attributes(key, location)? {
+            let value = expression::parse::<f64>(&value).map_err(|e| SceneError::InvalidValue {
+                location: location.clone(),
+                attribute: attribute.clone(),
+                value: value.clone(),
+                message: e.to_string(),
+            })?;
+            if attribute == "frame" {
+                key_frame = Some(value);
+            } else {
+                values.push((attribute, value));
+            }
+        }
+        let key_frame = key_frame.ok_or_else(|| SceneError::MissingAttribute {
+            location: location.clone(),
+            attribute: String::from("frame"),
+        })?;
+        for (attribute, value) in values {
+            match tracks.iter_mut().find(|(a, _)| *a == attribute) {
+                Some((_, keys)) => keys.push((key_frame, value)),
+                None => tracks.push((attribute, vec![(key_frame, value)])),
+            }
+        }
+    }
+    for (attribute, keys) in tracks.iter() {
+        let value = Track::new(keys.clone(), interpolation).at(frame).to_string();
+        match attributes.iter_mut().find(|(a, _)| a == attribute) {
+            Some((_, v)) => *v = value,
+            None => attributes.push((attribute.clone(), value)),
+        }
+    }
+
+    let mut result = BytesStart::new(name);
+    for (key, value) in attributes.iter() {
+        result.push_attribute((key.as_str(), value.as_str()));
+    }
+    Ok((result.into_owned(), interpolation, tracks))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn linear_track() {
+        let track = Track::new(vec![(10., 1.), (0., 0.), (20., 5.)], Interpolation::Linear);
+        assert_eq!(track.at(-5.), 0.);
+        assert_eq!(track.at(5.), 0.5);
+        assert_eq!(track.at(15.), 3.);
+        assert_eq!(track.at(30.), 5.);
+    }
+
+    #[test]
+    fn bezier_track() {
+        let track = Track::new(vec![(0., 0.), (10., 1.)], Interpolation::Bezier);
+        //eases in and out, symmetric around the middle
+        assert_abs_diff_eq!(track.at(5.), 0.5);
+        assert!(track.at(1.) < 0.1 / 3.);
+        assert_abs_diff_eq!(track.at(1.) + track.at(9.), 1., epsilon = 1e-12);
+
+        //goes through the keys, without stopping at the middle one
+        let track = Track::new(vec![(0., 0.), (10., 1.), (20., 2.)], Interpolation::Bezier);
+        assert_abs_diff_eq!(track.at(10.), 1.);
+        assert!(track.at(9.) < 1. && track.at(11.) > 1.);
+    }
+
+    #[test]
+    fn animated_element() {
+        let element = BytesStart::from_content("pos x=\"0\" y=\"5\" z=\"1\"", 3);
+        let keys = [
+            (BytesStart::from_content("key frame=\"0\" x=\"0\"", 3), Location::default()),
+            (BytesStart::from_content("key frame=\"10\" x=\"4\" z=\"3\"", 3), Location::default()),
+        ];
+        let (pos, interpolation, tracks) = animate(&element, &keys, 5., &Location::default()).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&pos),
+            "pos x=\"2\" y=\"5\" z=\"3\""
+        );
+        assert_eq!(interpolation, Interpolation::Linear);
+        assert_eq!(
+            tracks,
+            vec![(String::from("x"), vec![(0., 0.), (10., 4.)]), (String::from("z"), vec![(10., 3.)])]
+        );
+    }
+
+    #[test]
+    fn invalid_key() {
+        let element = BytesStart::from_content("intensity", 9);
+        let location = Location {
+            file: None,
+            line: 4,
+            column: 2,
+            path: String::from("scene/ambiant_light/intensity/key"),
+        };
+        let keys = [(BytesStart::from_content("key frame=\"0\" i=\"bright\"", 3), location.clone())];
+        let Err(SceneError::InvalidValue {
+            location: at,
+            attribute,
+            value,
+            ..
+        }) = animate(&element, &keys, 0., &Location::default())
+        else {
+            panic!("invalid value expected")
+        };
+        assert_eq!(at, location);
+        assert_eq!((attribute.as_str(), value.as_str()), ("i", "bright"));
+    }
+}
@@ -3,18 +3,25 @@ extern crate approx;
 
 use crate::coord::Vec3d;
 
+//vertical of the image when the camera has no up direction
+pub const DEFAULT_UP: Vec3d = Vec3d { x: 0., y: 0., z: 1. };
+
 pub struct Camera {
     pos: Vec3d,
     dir: Vec3d,
     px_down: Vec3d,
     px_left: Vec3d,
     resolution: (u32, u32),
+    //times when the shutter opens and closes
+    shutter: (f64, f64),
 }
 
 #[derive(Clone, Copy)]
 pub struct Ray {
     pub start: Vec3d,
     pub dir: Vec3d,
+    //moving objects are intersected where they are at this time
+    pub time: f64,
 }
 
 impl Camera {
@@ -26,14 +33,7 @@ impl Camera {
         fov: Option<f64>,
     ) -> Camera {
         let dir = dir.normalize().unwrap();
-        let up = up
-            .unwrap_or(Vec3d {
-                x: 0.,
-                y: 0.,
-                z: 1.,
-            })
-            .normalize()
-            .unwrap();
+        let up = up.unwrap_or(DEFAULT_UP).normalize().unwrap();
         let resolution = resolution.unwrap_or((1024, 768));
         let fov = fov.unwrap_or(90.);
 
@@ -51,10 +51,21 @@ impl Camera {
             px_down,
             px_left,
             resolution,
+            shutter: (0., 0.),
         }
     }
 
-    pub fn ray(&self, px: (f64, f64)) -> Ray {
+    pub fn with_shutter(mut self, open: f64, close: f64) -> Camera {
+        self.shutter = (open, close);
+        self
+    }
+
+    //time while the shutter is open, sample from 0 to 1
+    pub fn shutter_time(&self, sample: f64) -> f64 {
+        self.shutter.0 + sample * (self.shutter.1 - self.shutter.0)
+    }
+
+    pub fn ray(&self, px: (f64, f64), time: f64) -> Ray {
         let dx = px.0 - (self.resolution.0 as f64) / 2.0;
         let dy = px.1 - (self.resolution.1 as f64) / 2.0;
 
@@ -63,6 +74,7 @@ impl Camera {
         Ray {
             start: self.pos,
             dir: px_window.normalize().unwrap(),
+            time,
         }
     }
 
@@ -132,7 +144,7 @@ mod tests {
             z: -1.,
         };
         let cam = Camera::new(pos, dir, None, None, None);
-        let ray = cam.ray((40., 60.));
+        let ray = cam.ray((40., 60.), 0.5);
         assert_abs_diff_eq!(
             ray.dir,
             Vec3d {
@@ -142,5 +154,20 @@ mod tests {
             }
         );
         assert_eq!(ray.start, pos);
+        assert_eq!(ray.time, 0.5);
+    }
+
+    #[test]
+    fn shutter() {
+        let dir = Vec3d {
+            x: 1.,
+            y: 0.,
+            z: 0.,
+        };
+        let cam = Camera::new(dir, dir, None, None, None);
+        assert_eq!(cam.shutter_time(0.7), 0.);
+        let cam = cam.with_shutter(1., 1.5);
+        assert_eq!(cam.shutter_time(0.), 1.);
+        assert_eq!(cam.shutter_time(0.5), 1.25);
     }
 }
@@ -0,0 +1,510 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use super::animation::{Interpolation, Track};
+use super::error::{Location, SceneError};
+use super::object::csg::Operation;
+use super::object::material::{Channel, Color, Principled};
+use super::object::sdf::Node;
+use super::object::texture::procedural::Procedural;
+use super::object::texture::{Filter, Wrap};
+use crate::coord::Vec3d;
+
+pub mod build;
+
+//Scene as it is written, whatever the format of its file. Materials and
+//library objects are referred to by name, files by their path from the
+//scene directory. It is built into the Scene that is rendered.
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct SceneDescription {
+    pub camera: CameraDescription,
+    pub ambiant_light: AmbiantLightDescription,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lights: Vec<LightDescription>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub materials: BTreeMap<String, MaterialDescription>,
+    //objects only drawn through instances
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub library: BTreeMap<String, ObjectDescription>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub objects: Vec<ObjectDescription>,
+    //meshes known to be closed can be drawn faster
+    #[serde(default)]
+    pub cull_back_faces: bool,
+    //values changed at each frame
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub animations: Vec<AnimationDescription>,
+}
+
+//Keys of a number of the description, given by the path of its field
+//(camera/pos/z, objects/2/transforms/0/rotate/angle)
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct AnimationDescription {
+    pub target: String,
+    pub keys: Vec<(f64, f64)>,
+    #[serde(default)]
+    pub interpolation: Interpolation,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct CameraDescription {
+    pub pos: Vec3d,
+    pub dir: Vec3d,
+    //times when the shutter opens and closes
+    #[serde(default)]
+    pub shutter: (f64, f64),
+    //z, 1024x768 and 90 degrees (horizontal) when not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub up: Option<Vec3d>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<(u32, u32)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fov: Option<f64>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct AmbiantLightDescription {
+    pub color: Color,
+    pub intensity: f64,
+}
+
+//point light
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct LightDescription {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub pos: Vec3d,
+    pub color: Color,
+    pub intensity: f64,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MaterialDescription {
+    Basic(BasicMaterial),
+    Principled(Principled),
+}
+
+//Shading terms of a Material, some of them read from textures
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct BasicMaterial {
+    pub diffuse: Color,
+    pub specular: Color,
+    #[serde(default)]
+    pub reflectivity: f64,
+    #[serde(default)]
+    pub roughness: f64,
+    #[serde(default)]
+    pub sheen: f64,
+    #[serde(default)]
+    pub clearcoat: f64,
+    #[serde(default = "black")]
+    pub transmission: Color,
+    #[serde(default = "one")]
+    pub ior: f64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub textures: Vec<ChannelTexture>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normal_map: Option<TextureDescription>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bump: Option<Bump>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ChannelTexture {
+    pub channel: Channel,
+    pub texture: TextureDescription,
+}
+
+//height texture and its strength
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Bump {
+    pub texture: TextureDescription,
+    #[serde(default = "one")]
+    pub strength: f64,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TextureDescription {
+    Image {
+        file: PathBuf,
+        #[serde(default = "repeat")]
+        wrap: Wrap,
+        #[serde(default = "bilinear")]
+        filter: Filter,
+        #[serde(default = "one")]
+        scale: f64,
+    },
+    Procedural(Procedural),
+}
+
+//material defined in place, or the name of one of the scene materials
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MaterialRef {
+    Named(String),
+    Inline(Box<MaterialDescription>),
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ObjectDescription {
+    #[serde(flatten)]
+    pub shape: Shape,
+    //the one of the instanced object is replaced, the one of a group or a
+    //csg is the default one of their objects
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub material: Option<MaterialRef>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Shape {
+    Sphere {
+        pos: Vec3d,
+        radius: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        velocity: Option<Vec3d>,
+    },
+    Plane {
+        pos: Vec3d,
+        normal: Vec3d,
+    },
+    Disk {
+        pos: Vec3d,
+        normal: Vec3d,
+        radius: f64,
+    },
+    Box {
+        min: Vec3d,
+        max: Vec3d,
+    },
+    Cylinder {
+        pos: Vec3d,
+        top: Vec3d,
+        radius: f64,
+    },
+    //pointed when the top radius is 0
+    Cone {
+        pos: Vec3d,
+        top: Vec3d,
+        radius: f64,
+        #[serde(default)]
+        top_radius: f64,
+    },
+    Torus {
+        pos: Vec3d,
+        axis: Vec3d,
+        radius: f64,
+        tube: f64,
+    },
+    Heightfield {
+        file: PathBuf,
+        pos: Vec3d,
+        size: Vec3d,
+    },
+    Mesh {
+        faces: Vec<FaceDescription>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        double_sided: Option<bool>,
+    },
+    Sdf {
+        node: Node,
+    },
+    //operation applied from the first object to the last one
+    Csg {
+        operation: Operation,
+        objects: Vec<ObjectDescription>,
+    },
+    //library object, transformed in the given order
+    Instance {
+        object: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        transforms: Vec<TransformDescription>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        motion: Vec<Keyframe>,
+    },
+    //the name only helps finding the group in the scene file
+    Group {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        transforms: Vec<TransformDescription>,
+        objects: Vec<ObjectDescription>,
+    },
+}
+
+//polygon, split in triangles, with optional texture coordinates and normals
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct FaceDescription {
+    pub points: Vec<Vec3d>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub uvs: Vec<(f64, f64)>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub normals: Vec<Vec3d>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransformDescription {
+    Translate(Vec3d),
+    //angle in degrees
+    Rotate { axis: Vec3d, angle: f64 },
+    Scale(Vec3d),
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Keyframe {
+    pub time: f64,
+    pub transforms: Vec<TransformDescription>,
+}
+
+fn one() -> f64 {
+    1.
+}
+
+fn black() -> Color {
+    Color::gray(0.)
+}
+
+fn repeat() -> Wrap {
+    Wrap::Repeat
+}
+
+fn bilinear() -> Filter {
+    Filter::Bilinear
+}
+
+impl Default for CameraDescription {
+    //at the origin, looking along x
+    fn default() -> CameraDescription {
+        CameraDescription {
+            pos: Vec3d { x: 0., y: 0., z: 0. },
+            dir: Vec3d { x: 1., y: 0., z: 0. },
+            shutter: (0., 0.),
+            up: None,
+            resolution: None,
+            fov: None,
+        }
+    }
+}
+
+impl Default for AmbiantLightDescription {
+    fn default() -> AmbiantLightDescription {
+        AmbiantLightDescription {
+            color: black(),
+            intensity: 0.,
+        }
+    }
+}
+
+impl ObjectDescription {
+    pub fn new(shape: Shape, material: Option<MaterialRef>) -> ObjectDescription {
+        ObjectDescription { shape, material }
+    }
+
+    //objects that can be combined by a csg, instances of the library objects included
+    pub fn is_closed(&self, library: &BTreeMap<String, ObjectDescription>) -> bool {
+        match &self.shape {
+            Shape::Sphere { .. }
+            | Shape::Box { .. }
+            | Shape::Cylinder { .. }
+            | Shape::Cone { .. }
+            | Shape::Torus { .. }
+            | Shape::Csg { .. } => true,
+            Shape::Plane { .. }
+            | Shape::Disk { .. }
+            | Shape::Heightfield { .. }
+            | Shape::Mesh { .. }
+            | Shape::Sdf { .. }
+            | Shape::Group { .. } => false,
+            Shape::Instance { object, .. } => library.get(object).is_some_and(|o| o.is_closed(library)),
+        }
+    }
+}
+
+impl SceneDescription {
+    //Description at a frame, with the animated numbers given by their keys.
+    //The targets are found in the JSON form of the description.
+    pub fn at(&self, frame: f64) -> Result<SceneDescription, SceneError> {
+        if self.animations.is_empty() {
+            return Ok(self.clone());
+        }
+        let invalid = |path: String, message: String| SceneError::Description { path, message };
+        let mut value = serde_json::to_value(self).map_err(|e| invalid(String::new(), e.to_string()))?;
+        for (i, animation) in self.animations.iter().enumerate() {
+            if animation.keys.is_empty() {
+                return Err(invalid(format!("animations/{}/keys", i), String::from("there must be at least one key")));
+            }
+            let track = Track::new(animation.keys.clone(), animation.interpolation);
+            match value.pointer_mut(&format!("/{}", animation.target)) {
+                Some(number) if number.is_number() => *number = track.at(frame).into(),
+                _ => {
+                    let message = format!("there is no number at {}", animation.target);
+                    return Err(invalid(format!("animations/{}/target", i), message));
+                }
+            }
+        }
+        serde_json::from_value(value).map_err(|e| invalid(String::from("animations"), e.to_string()))
+    }
+
+    pub fn from_json(content: &str) -> Result<SceneDescription, SceneError> {
+        serde_json::from_str(content).map_err(|e| {
+            //the location is given apart
+            let message = e.to_string();
+            let message = match message.rsplit_once(" at line ") {
+                Some((message, _)) => message.to_string(),
+                None => message,
+            };
+            SceneError::Decode {
+                location: (e.line() > 0).then(|| Location {
+                    line: e.line() as u32,
+                    column: e.column() as u32,
+                    ..Location::default()
+                }),
+                format: "JSON",
+                message,
+            }
+        })
+    }
+
+    pub fn from_toml(content: &str) -> Result<SceneDescription, SceneError> {
+        toml::from_str(content).map_err(|e| SceneError::Decode {
+            location: e.span().map(|span| Location::at(content, span.start, String::new())),
+            format: "TOML",
+            message: e.message().trim_end().to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const JSON: &str = r#"{
+        "camera": {"pos": {"x": 0, "y": 0, "z": 1}, "dir": {"x": 1, "y": 0, "z": 0}},
+        "ambiant_light": {"color": {"r": 1, "g": 1, "b": 1}, "intensity": 0.1},
+        "materials": {
+            "red": {"type": "basic", "diffuse": {"r": 1, "g": 0, "b": 0}, "specular": {"r": 1, "g": 1, "b": 1}}
+        },
+        "objects": [
+            {"type": "sphere", "pos": {"x": 5, "y": 0, "z": 1}, "radius": 1, "material": "red"},
+            {
+                "type": "group",
+                "transforms": [{"translate": {"x": 0, "y": 3, "z": 0}}],
+                "material": {"type": "principled", "metallic": 1},
+                "objects": [{"type": "box", "min": {"x": 0, "y": 0, "z": 0}, "max": {"x": 1, "y": 1, "z": 1}}]
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn json_description() {
+        let description = SceneDescription::from_json(JSON).unwrap();
+        assert_eq!(description.camera.pos.z, 1.);
+        assert_eq!(description.camera.shutter, (0., 0.));
+        let Some(MaterialDescription::Basic(red)) = description.materials.get("red") else {
+            panic!("basic material expected")
+        };
+        assert_eq!(red.ior, 1.);
+        assert_eq!(description.objects[0].material, Some(MaterialRef::Named(String::from("red"))));
+        let Shape::Group { transforms, objects, .. } = &description.objects[1].shape else {
+            panic!("group expected")
+        };
+        assert_eq!(transforms, &[TransformDescription::Translate(Vec3d { x: 0., y: 3., z: 0. })]);
+        assert_eq!(objects.len(), 1);
+        let Some(MaterialRef::Inline(material)) = &description.objects[1].material else {
+            panic!("inline material expected")
+        };
+        let MaterialDescription::Principled(principled) = material.as_ref() else {
+            panic!("principled material expected")
+        };
+        assert_eq!(principled.metallic, 1.);
+        assert_eq!(principled.ior, Principled::default().ior);
+
+        //written and read again
+        let json = serde_json::to_string(&description).unwrap();
+        assert_eq!(SceneDescription::from_json(&json).unwrap(), description);
+    }
+
+    #[test]
+    fn toml_description() {
+        let description = SceneDescription::from_toml(
+            r#"
+            [camera]
+            pos = { x = 0, y = 0, z = 1 }
+            dir = { x = 1, y = 0, z = 0 }
+            shutter = [0, 0.5]
+
+            [ambiant_light]
+            color = { r = 1, g = 1, b = 1 }
+            intensity = 0.1
+
+            [[lights]]
+            name = "key"
+            pos = { x = 2, y = -1, z = 2.5 }
+            color = { r = 1, g = 1, b = 1 }
+            intensity = 1
+
+            [[objects]]
+            type = "plane"
+            pos = { x = 0, y = 0, z = 0 }
+            normal = { x = 0, y = 0, z = 1 }
+            material = { type = "basic", diffuse = { r = 1, g = 1, b = 1 }, specular = { r = 0, g = 0, b = 0 } }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(description.camera.shutter, (0., 0.5));
+        assert_eq!(description.lights[0].name.as_deref(), Some("key"));
+        assert!(matches!(description.objects[0].shape, Shape::Plane { .. }));
+    }
+
+    #[test]
+    fn animated_description() {
+        let mut description = SceneDescription::from_json(JSON).unwrap();
+        description.animations = vec![
+            AnimationDescription {
+                target: String::from("camera/pos/z"),
+                keys: vec![(0., 1.), (10., 6.)],
+                interpolation: Interpolation::Linear,
+            },
+            AnimationDescription {
+                target: String::from("objects/1/transforms/0/translate/y"),
+                keys: vec![(10., 0.)],
+                interpolation: Interpolation::Bezier,
+            },
+        ];
+        let frame = description.at(4.).unwrap();
+        assert_eq!(frame.camera.pos.z, 3.);
+        let Shape::Group { transforms, .. } = &frame.objects[1].shape else {
+            panic!("group expected")
+        };
+        assert_eq!(transforms, &[TransformDescription::Translate(Vec3d { x: 0., y: 0., z: 0. })]);
+        assert_eq!(frame.animations, description.animations);
+
+        description.animations[1].target = String::from("objects/1/transforms/0/rotate/angle");
+        assert_eq!(
+            description.at(4.).err().unwrap().to_string(),
+            "animations/1/target: there is no number at objects/1/transforms/0/rotate/angle"
+        );
+    }
+
+    #[test]
+    fn decode_errors() {
+        let error = SceneDescription::from_json("{\n\"camera\": {}\n}").err().unwrap();
+        let SceneError::Decode { location: Some(location), format, message } = error else {
+            panic!("decode error expected")
+        };
+        assert_eq!((format, location.line), ("JSON", 2));
+        assert_eq!(message, "missing field `pos`");
+
+        let error = SceneDescription::from_toml("[camera]\npos = 3").err().unwrap();
+        let SceneError::Decode { location: Some(location), format, .. } = error else {
+            panic!("decode error expected")
+        };
+        assert_eq!((format, location.line), ("TOML", 2));
+    }
+}
@@ -0,0 +1,651 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::*;
+use crate::coord::Matrix4;
+use crate::scene::camera::{Camera, DEFAULT_UP};
+use crate::scene::light::{Light, LightType};
+use crate::scene::motion::{compose, Motion, Transform};
+use crate::scene::object::cone::Cone;
+use crate::scene::object::csg::Csg;
+use crate::scene::object::cuboid::Cuboid;
+use crate::scene::object::heightfield::Heightfield;
+use crate::scene::object::instance::Instance;
+use crate::scene::object::material::Material;
+use crate::scene::object::plane::{Disk, Plane};
+use crate::scene::object::rasterized::face::Face;
+use crate::scene::object::rasterized::polygon::triangulate;
+use crate::scene::object::rasterized::Rasterized;
+use crate::scene::object::sdf::Sdf;
+use crate::scene::object::sphere::Sphere;
+use crate::scene::object::texture::procedural::{Procedural, MAX_OCTAVES};
+use crate::scene::object::texture::{Image, ImageTexture, Texture};
+use crate::scene::object::torus::Torus;
+use crate::scene::object::Object;
+use crate::scene::Scene;
+
+//field of the description that cannot be built
+fn invalid(path: &str, message: impl Display) -> SceneError {
+    SceneError::Description {
+        path: String::from(path),
+        message: message.to_string(),
+    }
+}
+
+fn io(message: String) -> SceneError {
+    SceneError::Io {
+        location: None,
+        message,
+    }
+}
+
+//Directory of the scene, with the images already read from it: the
+//frames of an animation are built from the same files
+pub struct Files {
+    dir: PathBuf,
+    images: RefCell<HashMap<PathBuf, Arc<Image>>>,
+}
+
+impl Files {
+    pub fn new(dir: &Path) -> Files {
+        Files {
+            dir: dir.to_path_buf(),
+            images: RefCell::new(HashMap::new()),
+        }
+    }
+
+    //relative paths are resolved from the directory of the scene
+    fn image(&self, file: &Path) -> Result<Arc<Image>, String> {
+        let path = self.dir.join(file);
+        if let Some(image) = self.images.borrow().get(&path) {
+            return Ok(image.clone());
+        }
+        let image = Arc::new(Image::load(&path)?);
+        self.images.borrow_mut().insert(path, image.clone());
+        Ok(image)
+    }
+}
+
+//vector that is only used for its direction
+fn direction(v: Vec3d, path: &str) -> Result<Vec3d, SceneError> {
+    match v.normalize() {
+        Some(_) => Ok(v),
+        None => Err(invalid(path, "it cannot be null")),
+    }
+}
+
+impl SceneDescription {
+    //relative paths (textures, heightfields) are resolved from base_dir
+    pub fn build(&self, base_dir: &Path) -> Result<Scene, SceneError> {
+        self.build_from(&Files::new(base_dir))
+    }
+
+    //with the images already read from the files
+    pub fn build_from(&self, files: &Files) -> Result<Scene, SceneError> {
+        let dir = direction(self.camera.dir, "camera/dir")?;
+        let up = match self.camera.up {
+            Some(up) if direction(up, "camera/up")?.cross(dir).normalize().is_none() => {
+                return Err(invalid("camera/up", "it is parallel to the camera direction"))
+            }
+            None if DEFAULT_UP.cross(dir).normalize().is_none() => {
+                return Err(invalid("camera/dir", "it is vertical, the camera needs an up direction"))
+            }
+            up => up,
+        };
+        if matches!(self.camera.resolution, Some((0, _) | (_, 0))) {
+            return Err(invalid("camera/resolution", "it cannot be empty"));
+        }
+        if self.camera.fov.is_some_and(|fov| fov <= 0.) {
+            return Err(invalid("camera/fov", "it must be positive"));
+        }
+        let camera = Camera::new(self.camera.pos, dir, up, self.camera.resolution, self.camera.fov)
+            .with_shutter(self.camera.shutter.0, self.camera.shutter.1);
+        let lights = self
+            .lights
+            .iter()
+            .map(|l| Light {
+                color: l.color,
+                intensity: l.intensity,
+                light_type: LightType::PointLight { pos: l.pos },
+            })
+            .collect();
+
+        let mut builder = Builder {
+            description: self,
+            files,
+            materials: HashMap::new(),
+            library: HashMap::new(),
+            building: Vec::new(),
+        };
+        let mut objects = Vec::new();
+        for (i, object) in self.objects.iter().enumerate() {
+            objects.extend(builder.objects(object, None, &format!("objects/{}", i))?);
+        }
+        if self.cull_back_faces {
+            objects.iter_mut().for_each(Object::cull_back_faces);
+        }
+        Ok(Scene {
+            camera,
+            ambiant_light: Light {
+                color: self.ambiant_light.color,
+                intensity: self.ambiant_light.intensity,
+                light_type: LightType::AmbiantLight,
+            },
+            lights,
+            objects,
+        })
+    }
+}
+
+impl MaterialDescription {
+    //textures are loaded from their file
+    pub fn build(&self, files: &Files) -> Result<Material, SceneError> {
+        let basic = match self {
+            MaterialDescription::Principled(principled) => return Ok(principled.to_material()),
+            MaterialDescription::Basic(basic) => basic,
+        };
+        let mut textures = Vec::new();
+        for t in basic.textures.iter() {
+            textures.push((t.channel, t.texture.build(files)?));
+        }
+        let normal_map = match &basic.normal_map {
+            Some(t) => Some(t.build(files)?),
+            None => None,
+        };
+        let bump_map = match &basic.bump {
+            Some(bump) => Some((bump.texture.build(files)?, bump.strength)),
+            None => None,
+        };
+        Ok(Material {
+            diffuse: basic.diffuse,
+            specular: basic.specular,
+            reflectivity: basic.reflectivity,
+            roughness: basic.roughness,
+            sheen: basic.sheen,
+            clearcoat: basic.clearcoat,
+            transmission: basic.transmission,
+            ior: basic.ior,
+            textures,
+            normal_map,
+            bump_map,
+        })
+    }
+}
+
+impl TextureDescription {
+    pub fn build(&self, files: &Files) -> Result<Texture, SceneError> {
+        match self {
+            TextureDescription::Procedural(procedural) => Ok(Texture::Procedural(Procedural {
+                octaves: procedural.octaves.min(MAX_OCTAVES),
+                ..procedural.clone()
+            })),
+            TextureDescription::Image {
+                file,
+                wrap,
+                filter,
+                scale,
+            } => {
+                let mut texture = ImageTexture::new(files.image(file).map_err(io)?);
+                texture.wrap = *wrap;
+                texture.filter = *filter;
+                texture.scale = *scale;
+                Ok(Texture::Image(texture))
+            }
+        }
+    }
+}
+
+impl FaceDescription {
+    //triangles of the polygon
+    pub fn triangles(&self, path: &str) -> Result<Vec<Face>, SceneError> {
+        if !self.uvs.is_empty() && self.uvs.len() != self.points.len() {
+            return Err(invalid(&format!("{}/uvs", path), "there must be one uv for each point"));
+        }
+        if !self.normals.is_empty() && self.normals.len() != self.points.len() {
+            return Err(invalid(&format!("{}/normals", path), "there must be one normal for each point"));
+        }
+        let normals = self
+            .normals
+            .iter()
+            .enumerate()
+            .map(|(i, n)| direction(*n, &format!("{}/normals/{}", path, i)))
+            .collect::<Result<Vec<Vec3d>, SceneError>>()?;
+        let triangles = triangulate(&self.points).map_err(|message| invalid(&format!("{}/points", path), message))?;
+        let pts = &self.points;
+        Ok(triangles
+            .into_iter()
+            .map(|[a, b, c]| {
+                let mut face = Face::new(pts[a], pts[b], pts[c]);
+                if !self.uvs.is_empty() {
+                    face = face.with_uvs([self.uvs[a], self.uvs[b], self.uvs[c]]);
+                }
+                if !normals.is_empty() {
+                    face = face.with_normals([normals[a], normals[b], normals[c]]);
+                }
+                face
+            })
+            .collect())
+    }
+}
+
+impl TransformDescription {
+    //angles in radians, transforms that could not be inverted are refused
+    pub fn transform(&self, path: &str) -> Result<Transform, SceneError> {
+        let transform = match *self {
+            TransformDescription::Translate(v) => Transform::Translate(v),
+            TransformDescription::Rotate { axis, angle } => {
+                Transform::Rotate(direction(axis, &format!("{}/rotate/axis", path))?, angle.to_radians())
+            }
+            TransformDescription::Scale(s) => {
+                //objects would be flattened, without any way back
+                if s.x * s.y * s.z == 0. {
+                    return Err(invalid(&format!("{}/scale", path), "it cannot be zero"));
+                }
+                Transform::Scale(s)
+            }
+        };
+        Ok(transform)
+    }
+}
+
+//transforms of a list, composed in their order
+fn transforms(list: &[TransformDescription], path: &str) -> Result<Vec<Transform>, SceneError> {
+    list.iter()
+        .enumerate()
+        .map(|(i, t)| t.transform(&format!("{}/transforms/{}", path, i)))
+        .collect()
+}
+
+//Builds the objects of a description, sharing each named material and each
+//library object between the objects that refer to them
+struct Builder<'a> {
+    description: &'a SceneDescription,
+    files: &'a Files,
+    materials: HashMap<String, Arc<Material>>,
+    library: HashMap<String, Arc<Object>>,
+    //library objects being built, an instance cannot refer to them
+    building: Vec<String>,
+}
+
+impl Builder<'_> {
+    fn material(&mut self, material: &MaterialRef, path: &str) -> Result<Arc<Material>, SceneError> {
+        let name = match material {
+            MaterialRef::Inline(material) => return Ok(Arc::new(material.build(self.files)?)),
+            MaterialRef::Named(name) => name,
+        };
+        if let Some(material) = self.materials.get(name) {
+            return Ok(material.clone());
+        }
+        let Some(description) = self.description.materials.get(name) else {
+            return Err(invalid(path, format!("there is no material named {:?}", name)));
+        };
+        let material = Arc::new(description.build(self.files)?);
+        self.materials.insert(name.clone(), material.clone());
+        Ok(material)
+    }
+
+    //own material of an object, or the default one (from its group or csg)
+    fn object_material(
+        &mut self,
+        object: &ObjectDescription,
+        default_material: Option<&Arc<Material>>,
+        path: &str,
+    ) -> Result<Option<Arc<Material>>, SceneError> {
+        match &object.material {
+            Some(material) => Ok(Some(self.material(material, &format!("{}/material", path))?)),
+            None => Ok(default_material.cloned()),
+        }
+    }
+
+    fn library_object(&mut self, name: &str, path: &str) -> Result<Arc<Object>, SceneError> {
+        if let Some(object) = self.library.get(name) {
+            return Ok(object.clone());
+        }
+        if self.building.iter().any(|n| n == name) {
+            return Err(invalid(path, format!("the library object {:?} uses itself", name)));
+        }
+        let Some(description) = self.description.library.get(name) else {
+            return Err(invalid(path, format!("there is no library object named {:?}", name)));
+        };
+        self.building.push(String::from(name));
+        let object = self.object(description, None, &format!("library/{}", name));
+        self.building.pop();
+        let mut object = object?;
+        if self.description.cull_back_faces {
+            object.cull_back_faces();
+        }
+        let object = Arc::new(object);
+        self.library.insert(String::from(name), object.clone());
+        Ok(object)
+    }
+
+    //objects of a group, moved by its transforms, or a single object
+    fn objects(
+        &mut self,
+        object: &ObjectDescription,
+        default_material: Option<&Arc<Material>>,
+        path: &str,
+    ) -> Result<Vec<Object>, SceneError> {
+        let Shape::Group { transforms: list, objects, .. } = &object.shape else {
+            return Ok(vec![self.object(object, default_material, path)?]);
+        };
+        let material = self.object_material(object, default_material, path)?;
+        let mut built = Vec::new();
+        for (i, o) in objects.iter().enumerate() {
+            built.extend(self.objects(o, material.as_ref(), &format!("{}/objects/{}", path, i))?);
+        }
+        let transform = compose(&transforms(list, path)?);
+        if transform == Matrix4::identity() {
+            return Ok(built);
+        }
+        Ok(built.into_iter().map(|o| place(o, transform)).collect())
+    }
+
+    fn object(
+        &mut self,
+        object: &ObjectDescription,
+        default_material: Option<&Arc<Material>>,
+        path: &str,
+    ) -> Result<Object, SceneError> {
+        let at = |field: &str| format!("{}/{}", path, field);
+        //the material of an instance replaces the one of its object, it is
+        //not replaced by the default one
+        if let Shape::Instance { object: name, transforms: list, motion } = &object.shape {
+            let shared = self.library_object(name, &at("object"))?;
+            let material = self.object_material(object, None, path)?;
+            let instance = Instance::new(shared, compose(&transforms(list, path)?), material);
+            if motion.is_empty() {
+                return Ok(Object::Instance(Box::new(instance)));
+            }
+            let mut keyframes = Vec::new();
+            for (i, keyframe) in motion.iter().enumerate() {
+                keyframes.push((keyframe.time, transforms(&keyframe.transforms, &format!("{}/motion/{}", path, i))?));
+            }
+            let motion = Motion::new(keyframes).map_err(|message| invalid(&at("motion"), message))?;
+            return Ok(Object::Instance(Box::new(instance.with_motion(motion))));
+        }
+
+        let material = self.object_material(object, default_material, path)?;
+        let mat = || material.clone().ok_or_else(|| invalid(&at("material"), "the object has no material"));
+        //the top of cylinders and cones, away from their base
+        let top = |pos: Vec3d, top: Vec3d| match (top - pos).normalize() {
+            Some(_) => Ok(top),
+            None => Err(invalid(&at("top"), "it is the base position")),
+        };
+        let built = match &object.shape {
+            Shape::Sphere { pos, radius, velocity } => {
+                let sphere = Sphere::new(*pos, *radius, mat()?);
+                Object::Sphere(match velocity {
+                    Some(velocity) => sphere.with_velocity(*velocity),
+                    None => sphere,
+                })
+            }
+            Shape::Plane { pos, normal } => Object::Plane(Plane::new(*pos, direction(*normal, &at("normal"))?, mat()?)),
+            Shape::Disk { pos, normal, radius } => {
+                Object::Disk(Disk::new(*pos, direction(*normal, &at("normal"))?, *radius, mat()?))
+            }
+            Shape::Box { min, max } if (0..3).any(|axis| min.component(axis) == max.component(axis)) => {
+                return Err(invalid(&at("max"), "it is level with min on an axis, the box is flat"))
+            }
+            Shape::Box { min, max } => Object::Cuboid(Cuboid::new(*min, *max, mat()?)),
+            Shape::Cylinder { pos, top: t, radius } => Object::Cone(Cone::cylinder(*pos, top(*pos, *t)?, *radius, mat()?)),
+            Shape::Cone {
+                pos,
+                top: t,
+                radius,
+                top_radius,
+            } => Object::Cone(Cone::new(*pos, top(*pos, *t)?, *radius, *top_radius, mat()?)),
+            Shape::Torus { pos, axis, radius, tube } => {
+                Object::Torus(Torus::new(*pos, direction(*axis, &at("axis"))?, *radius, *tube, mat()?))
+            }
+            Shape::Heightfield { size, .. } if size.x <= 0. || size.y <= 0. => {
+                return Err(invalid(&at("size"), "its x and y must be positive"))
+            }
+            Shape::Heightfield { file, pos, size } => {
+                let material = mat()?;
+                let image = self.files.image(file);
+                let heightfield = image.and_then(|image| Heightfield::from_image(&image, *pos, *size, material));
+                Object::Heightfield(heightfield.map_err(|message| SceneError::Io {
+                    location: Some(Location {
+                        path: at("file"),
+                        ..Location::default()
+                    }),
+                    message,
+                })?)
+            }
+            Shape::Mesh { faces, double_sided } => {
+                let mut triangles = Vec::new();
+                for (i, face) in faces.iter().enumerate() {
+                    triangles.extend(face.triangles(&format!("{}/faces/{}", path, i))?);
+                }
+                let mut mesh = Rasterized::new(triangles, mat()?);
+                if let Some(double_sided) = double_sided {
+                    mesh.double_sided = *double_sided;
+                }
+                Object::Rasterized(mesh)
+            }
+            Shape::Sdf { node } => Object::Sdf(Sdf::new(node.clone(), mat()?)),
+            Shape::Csg { operation, objects } => {
+                if objects.len() < 2 {
+                    return Err(invalid(&at("objects"), "csg needs at least two objects"));
+                }
+                let mut operands = Vec::new();
+                for (i, o) in objects.iter().enumerate() {
+                    let path = format!("{}/objects/{}", path, i);
+                    if !o.is_closed(&self.description.library) {
+                        return Err(invalid(&path, "csg can only combine closed objects"));
+                    }
+                    operands.push(self.object(o, material.as_ref(), &path)?);
+                }
+                let mut operands = operands.into_iter();
+                let first = operands.next().unwrap();
+                operands.fold(first, |left, right| Object::Csg(Box::new(Csg::new(*operation, left, right))))
+            }
+            Shape::Group { .. } => return Err(invalid(path, "a group cannot be used as a single object")),
+            Shape::Instance { .. } => unreachable!(),
+        };
+        Ok(built)
+    }
+}
+
+//moves an object, composing transforms rather than nesting instances
+pub fn place(object: Object, transform: Matrix4) -> Object {
+    let instance = match object {
+        Object::Instance(i) => {
+            let mut instance = Instance::new(i.object.clone(), transform * i.transform(), i.material.clone());
+            instance.motion = i.motion.clone();
+            instance
+        }
+        o => Instance::new(Arc::new(o), transform, None),
+    };
+    Object::Instance(Box::new(instance))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::scene::camera::Ray;
+
+    fn white() -> MaterialRef {
+        MaterialRef::Inline(Box::new(MaterialDescription::Principled(Principled::default())))
+    }
+
+    fn sphere(x: f64) -> ObjectDescription {
+        ObjectDescription::new(
+            Shape::Sphere {
+                pos: Vec3d { x, y: 0., z: 0. },
+                radius: 1.,
+                velocity: None,
+            },
+            None,
+        )
+    }
+
+    fn build(objects: Vec<ObjectDescription>) -> Result<Scene, SceneError> {
+        SceneDescription {
+            objects,
+            ..SceneDescription::default()
+        }
+        .build(Path::new("."))
+    }
+
+    #[test]
+    fn build_objects() {
+        let mut description = SceneDescription::default();
+        description.materials.insert(String::from("white"), MaterialDescription::Principled(Principled::default()));
+        description.library.insert(String::from("ball"), ObjectDescription { material: Some(white()), ..sphere(0.) });
+        description.objects = vec![
+            ObjectDescription {
+                material: Some(MaterialRef::Named(String::from("white"))),
+                ..sphere(5.)
+            },
+            ObjectDescription::new(
+                Shape::Group {
+                    name: None,
+                    transforms: vec![TransformDescription::Translate(Vec3d { x: 0., y: 3., z: 0. })],
+                    objects: vec![
+                        sphere(5.),
+                        ObjectDescription::new(
+                            Shape::Instance {
+                                object: String::from("ball"),
+                                transforms: vec![TransformDescription::Scale(Vec3d { x: 2., y: 2., z: 2. })],
+                                motion: vec![],
+                            },
+                            None,
+                        ),
+                    ],
+                },
+                Some(MaterialRef::Named(String::from("white"))),
+            ),
+        ];
+        let scene = description.build(Path::new(".")).unwrap();
+        assert_eq!(scene.objects.len(), 3);
+        let Object::Sphere(first) = &scene.objects[0] else {
+            panic!("sphere expected")
+        };
+        let Object::Instance(second) = &scene.objects[1] else {
+            panic!("instance expected")
+        };
+        let Object::Sphere(grouped) = second.object.as_ref() else {
+            panic!("sphere expected")
+        };
+        assert!(Arc::ptr_eq(&first.material, &grouped.material));
+
+        //scaled and moved up by the group
+        let ray = Ray {
+            start: Vec3d { x: -5., y: 3., z: 0. },
+            dir: Vec3d { x: 1., y: 0., z: 0. },
+            time: 0.,
+        };
+        let i = scene.objects[2].intersect(&ray).unwrap();
+        assert!((i.dist - 3.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn build_errors() {
+        let error = |objects| build(objects).err().unwrap().to_string();
+        assert_eq!(error(vec![sphere(0.)]), "objects/0/material: the object has no material");
+        let plane = ObjectDescription::new(
+            Shape::Plane {
+                pos: Vec3d { x: 0., y: 0., z: 0. },
+                normal: Vec3d { x: 0., y: 0., z: 0. },
+            },
+            Some(white()),
+        );
+        assert_eq!(error(vec![plane.clone()]), "objects/0/normal: it cannot be null");
+        let flat = ObjectDescription::new(
+            Shape::Box {
+                min: Vec3d { x: 0., y: 0., z: 0. },
+                max: Vec3d { x: 1., y: 1., z: 0. },
+            },
+            Some(white()),
+        );
+        assert_eq!(error(vec![flat]), "objects/0/max: it is level with min on an axis, the box is flat");
+        let heightfield = |x| {
+            ObjectDescription::new(
+                Shape::Heightfield {
+                    file: PathBuf::from("raytracer_build_nowhere.png"),
+                    pos: Vec3d { x: 0., y: 0., z: 0. },
+                    size: Vec3d { x, y: 1., z: 1. },
+                },
+                Some(white()),
+            )
+        };
+        assert_eq!(error(vec![heightfield(0.)]), "objects/0/size: its x and y must be positive");
+        assert!(error(vec![heightfield(1.)]).starts_with("objects/0/file: "));
+        let csg = |objects| {
+            ObjectDescription::new(
+                Shape::Csg {
+                    operation: Operation::Union,
+                    objects,
+                },
+                Some(white()),
+            )
+        };
+        assert_eq!(error(vec![csg(vec![sphere(0.)])]), "objects/0/objects: csg needs at least two objects");
+        assert_eq!(
+            error(vec![csg(vec![sphere(0.), plane])]),
+            "objects/0/objects/1: csg can only combine closed objects"
+        );
+        let instance = ObjectDescription::new(
+            Shape::Instance {
+                object: String::from("ball"),
+                transforms: vec![TransformDescription::Scale(Vec3d { x: 1., y: 0., z: 1. })],
+                motion: vec![],
+            },
+            None,
+        );
+        assert_eq!(
+            error(vec![instance.clone()]),
+            "objects/0/object: there is no library object named \"ball\""
+        );
+
+        let mut description = SceneDescription::default();
+        description.library.insert(String::from("ball"), instance.clone());
+        description.objects = vec![instance];
+        assert_eq!(
+            description.build(Path::new(".")).err().unwrap().to_string(),
+            "library/ball/object: the library object \"ball\" uses itself"
+        );
+
+        let mut description = SceneDescription::default();
+        description.camera.up = Some(Vec3d { x: -2., y: 0., z: 0. });
+        assert_eq!(
+            description.build(Path::new(".")).err().unwrap().to_string(),
+            "camera/up: it is parallel to the camera direction"
+        );
+        description.camera.up = None;
+        description.camera.dir = Vec3d { x: 0., y: 0., z: -1. };
+        assert_eq!(
+            description.build(Path::new(".")).err().unwrap().to_string(),
+            "camera/dir: it is vertical, the camera needs an up direction"
+        );
+        description.camera.dir = Vec3d { x: 1., y: 0., z: 0. };
+        description.camera.resolution = Some((0, 10));
+        assert_eq!(
+            description.build(Path::new(".")).err().unwrap().to_string(),
+            "camera/resolution: it cannot be empty"
+        );
+    }
+
+    #[test]
+    fn build_transforms() {
+        let rotate = TransformDescription::Rotate {
+            axis: Vec3d { x: 0., y: 0., z: 1. },
+            angle: 180.,
+        };
+        let Transform::Rotate(_, angle) = rotate.transform("transforms/0").unwrap() else {
+            panic!("rotation expected")
+        };
+        assert_eq!(angle, std::f64::consts::PI);
+        let flat = TransformDescription::Scale(Vec3d { x: 1., y: 0., z: 1. });
+        assert_eq!(
+            flat.transform("transforms/1").err().unwrap().to_string(),
+            "transforms/1/scale: it cannot be zero"
+        );
+    }
+}
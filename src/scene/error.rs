@@ -0,0 +1,159 @@
+use std::error::Error;
+use std::fmt;
+
+//Where an element starts in the scene file, and the names of the elements
+//that contain it (scene/group/sphere)
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Location {
+    //included file, None for the scene file itself
+    pub file: Option<Box<str>>,
+    pub line: u32,
+    pub column: u32,
+    pub path: String,
+}
+
+impl Location {
+    //line and column (from 1) of a byte offset of the content
+    pub fn at(content: &str, offset: usize, path: String) -> Location {
+        let before = &content[..offset.min(content.len())];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Location {
+            file: None,
+            line: before.matches('\n').count() as u32 + 1,
+            column: before[line_start..].chars().count() as u32 + 1,
+            path,
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}, ", file)?;
+        }
+        //files without lines (glTF) only have a path
+        if self.line == 0 {
+            return write!(f, "{}", self.path);
+        }
+        write!(f, "line {}, column {}", self.line, self.column)?;
+        if !self.path.is_empty() {
+            write!(f, " ({})", self.path)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SceneError {
+    //file that cannot be read: the scene itself (no location) or one it refers to
+    Io {
+        location: Option<Location>,
+        message: String,
+    },
+    //malformed XML
+    Syntax { location: Location, message: String },
+    MissingElement { location: Location, element: String },
+    MissingAttribute { location: Location, attribute: String },
+    InvalidValue {
+        location: Location,
+        attribute: String,
+        value: String,
+        message: String,
+    },
+    UnknownElement { location: Location, element: String },
+    //JSON, TOML, pbrt or glTF file that is not a scene description
+    Decode {
+        location: Option<Location>,
+        format: &'static str,
+        message: String,
+    },
+    //description that cannot be built, with the path of the invalid field
+    //(objects/2/normal)
+    Description { path: String, message: String },
+}
+
+impl SceneError {
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            SceneError::Io { location, .. } | SceneError::Decode { location, .. } => location.as_ref(),
+            SceneError::Description { .. } => None,
+            SceneError::Syntax { location, .. }
+            | SceneError::MissingElement { location, .. }
+            | SceneError::MissingAttribute { location, .. }
+            | SceneError::InvalidValue { location, .. }
+            | SceneError::UnknownElement { location, .. } => Some(location),
+        }
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(location) = self.location() {
+            write!(f, "{}: ", location)?;
+        }
+        match self {
+            SceneError::Io { message, .. } => write!(f, "{}", message),
+            SceneError::Syntax { message, .. } => write!(f, "invalid XML: {}", message),
+            SceneError::MissingElement { element, .. } => write!(f, "missing <{}> element", element),
+            SceneError::MissingAttribute { attribute, .. } => write!(f, "missing {} attribute", attribute),
+            SceneError::InvalidValue {
+                attribute,
+                value,
+                message,
+                ..
+            } if value.is_empty() => write!(f, "invalid {}: {}", attribute, message),
+            SceneError::InvalidValue {
+                attribute,
+                value,
+                message,
+                ..
+            } => write!(f, "invalid {} {:?}: {}", attribute, value, message),
+            SceneError::UnknownElement { element, .. } => write!(f, "unknown element <{}>", element),
+            SceneError::Decode { format, message, .. } => write!(f, "invalid {}: {}", format, message),
+            SceneError::Description { path, message } => write!(f, "{}: {}", path, message),
+        }
+    }
+}
+
+impl Error for SceneError {}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn location_in_content() {
+        let content = "<scene>\n  <camera>\n    <pos x=\"a\"/>";
+        let offset = content.find("<pos").unwrap();
+        let location = Location::at(content, offset, String::from("scene/camera/pos"));
+        assert_eq!((location.line, location.column), (3, 5));
+        assert_eq!(location.to_string(), "line 3, column 5 (scene/camera/pos)");
+        let location = Location { path: String::from("nodes/2"), ..Location::default() };
+        assert_eq!(location.to_string(), "nodes/2");
+    }
+
+    #[test]
+    fn error_message() {
+        let error = SceneError::InvalidValue {
+            location: Location {
+                file: None,
+                line: 3,
+                column: 5,
+                path: String::from("scene/sphere/radius"),
+            },
+            attribute: String::from("r"),
+            value: String::from("big"),
+            message: String::from("invalid float literal"),
+        };
+        assert_eq!(
+            error.to_string(),
+            "line 3, column 5 (scene/sphere/radius): invalid r \"big\": invalid float literal"
+        );
+        let error = SceneError::Io {
+            location: None,
+            message: String::from("scene.xml cannot be read"),
+        };
+        assert_eq!(error.to_string(), "scene.xml cannot be read");
+    }
+}
@@ -0,0 +1,354 @@
+use std::f64::consts::PI;
+
+use super::description::{
+    CameraDescription, FaceDescription, LightDescription, MaterialDescription, SceneDescription, Shape,
+};
+use super::object::material::{Color, Principled};
+use crate::coord::{Matrix4, Vec3d};
+
+pub mod gltf;
+pub mod mitsuba;
+pub mod obj;
+pub mod pbrt;
+pub mod ply;
+
+//Scenes of other renderers, translated into descriptions. What cannot be
+//translated is left out with a warning.
+
+//Triangles read from a file or written in the scene, in the coordinates of
+//their object
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Mesh {
+    pub points: Vec<Vec3d>,
+    //none, or one for each point
+    pub uvs: Vec<(f64, f64)>,
+    pub normals: Vec<Vec3d>,
+    pub triangles: Vec<[usize; 3]>,
+}
+
+//Shape of an object, before it is placed in the scene
+#[derive(Debug, Clone)]
+pub enum Primitive {
+    Sphere { center: Vec3d, radius: f64 },
+    Mesh(std::rc::Rc<Mesh>),
+}
+
+//Camera looking along the z axis of its frame
+pub struct ImportedCamera {
+    pub to_world: Matrix4,
+    //direction of the right of the image, in the camera frame
+    pub right: Vec3d,
+    pub resolution: (u32, u32),
+    //tangent of half the horizontal field of view
+    pub half_width: f64,
+}
+
+impl Mesh {
+    pub fn new(points: Vec<Vec3d>, uvs: Vec<(f64, f64)>, triangles: Vec<[usize; 3]>) -> Result<Mesh, String> {
+        Mesh::with_normals(points, uvs, vec![], triangles)
+    }
+
+    pub fn with_normals(
+        points: Vec<Vec3d>,
+        uvs: Vec<(f64, f64)>,
+        normals: Vec<Vec3d>,
+        triangles: Vec<[usize; 3]>,
+    ) -> Result<Mesh, String> {
+        if !uvs.is_empty() && uvs.len() != points.len() {
+            return Err(format!("{} uvs are given for {} points", uvs.len(), points.len()));
+        }
+        if !normals.is_empty() && normals.len() != points.len() {
+            return Err(format!("{} normals are given for {} points", normals.len(), points.len()));
+        }
+        if let Some(i) = triangles.iter().flatten().find(|&&i| i >= points.len()) {
+            return Err(format!("point {} is used, there are {} points", i, points.len()));
+        }
+        Ok(Mesh { points, uvs, normals, triangles })
+    }
+
+    //polygons split in triangles around their first point
+    pub fn add_polygon(&mut self, polygon: &[usize]) {
+        for i in 1..polygon.len().saturating_sub(1) {
+            self.triangles.push([polygon[0], polygon[i], polygon[i + 1]]);
+        }
+    }
+}
+
+//shape of a primitive placed in the scene, and a warning about what it loses
+pub fn place(primitive: &Primitive, to_world: &Matrix4) -> (Shape, Option<String>) {
+    match primitive {
+        Primitive::Sphere { center, radius } => {
+            let scales: Vec<f64> = [(1., 0., 0.), (0., 1., 0.), (0., 0., 1.)]
+                .iter()
+                .map(|&(x, y, z)| to_world.transform_vector(Vec3d { x, y, z }).norm())
+                .collect();
+            let scale = scales.iter().sum::<f64>() / 3.;
+            let warning = scales
+                .iter()
+                .any(|s| (s - scale).abs() > 1e-6 * scale)
+                .then(|| String::from("the sphere is not scaled evenly, its mean scale is used"));
+            let shape = Shape::Sphere {
+                pos: to_world.transform_point(*center),
+                radius: radius * scale,
+                velocity: None,
+            };
+            (shape, warning)
+        }
+        Primitive::Mesh(mesh) => {
+            let points: Vec<Vec3d> = mesh.points.iter().map(|p| to_world.transform_point(*p)).collect();
+            //faces with a degenerate normal are left flat
+            let normals: Vec<Option<Vec3d>> = match to_world.normal_matrix() {
+                Some(m) => mesh.normals.iter().map(|n| m.transform_vector(*n).normalize()).collect(),
+                None => vec![],
+            };
+            let mut degenerate = 0;
+            let mut faces = Vec::with_capacity(mesh.triangles.len());
+            for &[a, b, c] in mesh.triangles.iter() {
+                if (points[b] - points[a]).cross(points[c] - points[a]).normalize().is_none() {
+                    degenerate += 1;
+                    continue;
+                }
+                faces.push(FaceDescription {
+                    points: vec![points[a], points[b], points[c]],
+                    uvs: match mesh.uvs.is_empty() {
+                        true => vec![],
+                        false => vec![mesh.uvs[a], mesh.uvs[b], mesh.uvs[c]],
+                    },
+                    normals: match (normals.get(a), normals.get(b), normals.get(c)) {
+                        (Some(Some(na)), Some(Some(nb)), Some(Some(nc))) => vec![*na, *nb, *nc],
+                        _ => vec![],
+                    },
+                });
+            }
+            let warning = (degenerate > 0).then(|| format!("{} degenerate triangles are left out", degenerate));
+            //the winding of imported faces is not known
+            let shape = Shape::Mesh {
+                faces,
+                double_sided: Some(true),
+            };
+            (shape, warning)
+        }
+    }
+}
+
+//Area lights are approximated by a point light at the center of their shape,
+//as bright as the shape seen from the front. None for shapes without area.
+pub fn area_light(shape: &Shape, radiance: Color) -> Option<LightDescription> {
+    let (pos, area) = match shape {
+        Shape::Sphere { pos, radius, .. } => (*pos, PI * radius * radius),
+        Shape::Mesh { faces, .. } => {
+            let mut center = Vec3d { x: 0., y: 0., z: 0. };
+            let mut area = 0.;
+            for face in faces.iter() {
+                let [a, b, c] = [face.points[0], face.points[1], face.points[2]];
+                let triangle = (b - a).cross(c - a).norm() / 2.;
+                center = center + (a + b + c) * (triangle / 3.);
+                area += triangle;
+            }
+            (center / area, area)
+        }
+        _ => return None,
+    };
+    if area <= 0. {
+        return None;
+    }
+    let (color, intensity) = light_color(radiance, area);
+    Some(LightDescription {
+        name: None,
+        pos,
+        color,
+        intensity,
+    })
+}
+
+//color and intensity of a light, the color is at most 1
+pub fn light_color(rgb: Color, scale: f64) -> (Color, f64) {
+    let max = rgb.r.max(rgb.g).max(rgb.b);
+    match max > 0. {
+        true => (rgb * (1. / max), max * scale),
+        false => (Color::gray(0.), 0.),
+    }
+}
+
+//camera frame of a look at, None when up is along the direction
+pub fn look_at(origin: Vec3d, target: Vec3d, up: Vec3d) -> Option<Matrix4> {
+    let dir = (target - origin).normalize()?;
+    let right = up.cross(dir).normalize()?;
+    let up = dir.cross(right);
+    Some(Matrix4 {
+        m: [
+            [right.x, up.x, dir.x, origin.x],
+            [right.y, up.y, dir.y, origin.y],
+            [right.z, up.z, dir.z, origin.z],
+            [0., 0., 0., 1.],
+        ],
+    })
+}
+
+impl ImportedCamera {
+    //the image of a Camera goes right along up x dir, scenes whose camera
+    //looks the other way have to be mirrored (see mirror)
+    pub fn description(&self) -> (CameraDescription, bool) {
+        let axis = |x, y, z| self.to_world.transform_vector(Vec3d { x, y, z });
+        let dir = axis(0., 0., 1.);
+        let up = axis(0., 1., 0.);
+        let right = self.to_world.transform_vector(self.right);
+        let camera = CameraDescription {
+            pos: self.to_world.transform_point(Vec3d { x: 0., y: 0., z: 0. }),
+            dir,
+            up: Some(up),
+            resolution: Some(self.resolution),
+            //the fov of a Camera is the angle between the pixels times the width
+            fov: Some(self.half_width * 360. / PI),
+            ..CameraDescription::default()
+        };
+        (camera, right.dot(up.cross(dir)) < 0.)
+    }
+}
+
+//Mirror image of the scene along x, the image of the camera is mirrored back
+//Only spheres and meshes are imported.
+pub fn mirror(scene: &mut SceneDescription) {
+    let flip = |v: &mut Vec3d| v.x = -v.x;
+    flip(&mut scene.camera.pos);
+    flip(&mut scene.camera.dir);
+    scene.camera.up.iter_mut().for_each(flip);
+    scene.lights.iter_mut().for_each(|l| flip(&mut l.pos));
+    for object in scene.objects.iter_mut() {
+        match &mut object.shape {
+            Shape::Sphere { pos, .. } => flip(pos),
+            Shape::Mesh { faces, .. } => faces
+                .iter_mut()
+                .flat_map(|f| f.points.iter_mut().chain(f.normals.iter_mut()))
+                .for_each(flip),
+            _ => (),
+        }
+    }
+}
+
+pub fn diffuse(color: Color) -> MaterialDescription {
+    MaterialDescription::Principled(Principled {
+        base_color: color,
+        roughness: 1.,
+        specular: 0.,
+        ..Principled::default()
+    })
+}
+
+pub fn conductor(color: Color, roughness: f64) -> MaterialDescription {
+    MaterialDescription::Principled(Principled {
+        base_color: color,
+        metallic: 1.,
+        roughness: roughness.clamp(0., 1.),
+        ..Principled::default()
+    })
+}
+
+pub fn dielectric(ior: f64, roughness: f64) -> MaterialDescription {
+    MaterialDescription::Principled(Principled {
+        base_color: Color::gray(1.),
+        roughness: roughness.clamp(0., 1.),
+        transmission: 1.,
+        ior,
+        ..Principled::default()
+    })
+}
+
+//reflectance at normal incidence of a conductor, from its complex index of
+//refraction eta + ik
+pub fn conductor_color(eta: Color, k: Color) -> Color {
+    let f0 = |n: f64, k: f64| ((n - 1.) * (n - 1.) + k * k) / ((n + 1.) * (n + 1.) + k * k);
+    Color {
+        r: f0(eta.r, k.r),
+        g: f0(eta.g, k.g),
+        b: f0(eta.b, k.b),
+    }
+}
+
+//reflectance of the metals both renderers know by name
+pub fn metal(name: &str) -> Option<Color> {
+    let (r, g, b) = match name {
+        "Ag" => (0.972, 0.960, 0.915),
+        "Al" => (0.913, 0.922, 0.924),
+        "Au" => (1.000, 0.782, 0.344),
+        "Cu" => (0.955, 0.638, 0.538),
+        "CuZn" => (0.910, 0.778, 0.423),
+        _ => return None,
+    };
+    Some(Color { r, g, b })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use approx::assert_abs_diff_eq;
+    use std::rc::Rc;
+
+    fn v(x: f64, y: f64, z: f64) -> Vec3d {
+        Vec3d { x, y, z }
+    }
+
+    #[test]
+    fn place_primitives() {
+        let to_world = Matrix4::translation(v(1., 0., 0.)) * Matrix4::scaling(v(2., 2., 2.));
+        let (sphere, warning) = place(&Primitive::Sphere { center: v(0., 0., 1.), radius: 1. }, &to_world);
+        assert_eq!(sphere, Shape::Sphere { pos: v(1., 0., 2.), radius: 2., velocity: None });
+        assert_eq!(warning, None);
+        let (_, warning) = place(&Primitive::Sphere { center: v(0., 0., 0.), radius: 1. }, &Matrix4::scaling(v(1., 2., 1.)));
+        assert!(warning.is_some());
+
+        let mesh = Mesh::new(vec![v(0., 0., 0.), v(1., 0., 0.), v(0., 1., 0.), v(2., 0., 0.)], vec![], vec![[0, 1, 2], [0, 1, 3]]).unwrap();
+        let (mesh, warning) = place(&Primitive::Mesh(Rc::new(mesh)), &to_world);
+        let Shape::Mesh { faces, .. } = &mesh else { panic!("not a mesh") };
+        assert_eq!(faces.len(), 1);
+        assert_eq!(faces[0].points[2], v(1., 2., 0.));
+        assert_eq!(warning.as_deref(), Some("1 degenerate triangles are left out"));
+
+        assert!(Mesh::new(vec![v(0., 0., 0.)], vec![], vec![[0, 1, 2]]).is_err());
+        assert!(Mesh::new(vec![v(0., 0., 0.)], vec![(0., 0.), (1., 1.)], vec![]).is_err());
+    }
+
+    #[test]
+    fn area_lights() {
+        let square = Shape::Mesh {
+            faces: vec![
+                FaceDescription { points: vec![v(0., 0., 2.), v(1., 0., 2.), v(1., 1., 2.)], uvs: vec![], normals: vec![] },
+                FaceDescription { points: vec![v(0., 0., 2.), v(1., 1., 2.), v(0., 1., 2.)], uvs: vec![], normals: vec![] },
+            ],
+            double_sided: None,
+        };
+        let light = area_light(&square, Color { r: 4., g: 2., b: 0. }).unwrap();
+        assert_abs_diff_eq!(light.pos, v(0.5, 0.5, 2.));
+        assert_eq!(light.color, Color { r: 1., g: 0.5, b: 0. });
+        assert_abs_diff_eq!(light.intensity, 4.);
+        assert_eq!(area_light(&Shape::Box { min: v(0., 0., 0.), max: v(1., 1., 1.) }, Color::gray(1.)), None);
+    }
+
+    #[test]
+    fn cameras() {
+        let to_world = look_at(v(0., 0., 0.), v(1., 0., 0.), v(0., 0., 1.)).unwrap();
+        let mut camera = ImportedCamera {
+            to_world,
+            right: v(1., 0., 0.),
+            resolution: (200, 100),
+            half_width: 1.,
+        };
+        let (description, mirrored) = camera.description();
+        assert_abs_diff_eq!(description.dir, v(1., 0., 0.));
+        assert_abs_diff_eq!(description.up.unwrap(), v(0., 0., 1.));
+        assert_abs_diff_eq!(description.fov.unwrap(), 360. / PI);
+        assert!(!mirrored);
+        camera.right = v(-1., 0., 0.);
+        assert!(camera.description().1);
+        assert_eq!(look_at(v(0., 0., 0.), v(0., 0., 1.), v(0., 0., 1.)), None);
+    }
+
+    #[test]
+    fn materials() {
+        assert_abs_diff_eq!(conductor_color(Color::gray(1.5), Color::gray(0.)).r, 0.04);
+        assert_eq!(metal("Au").unwrap().r, 1.);
+        assert_eq!(metal("Pt"), None);
+        assert_eq!(light_color(Color { r: 2., g: 1., b: 1. }, 3.), (Color { r: 1., g: 0.5, b: 0.5 }, 6.));
+    }
+}
@@ -0,0 +1,1179 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use serde::Deserialize;
+
+use super::{light_color, mirror, place, ImportedCamera, Mesh, Primitive};
+use crate::coord::{Matrix4, Vec3d};
+use crate::scene::description::{
+    BasicMaterial, ChannelTexture, LightDescription, MaterialDescription, MaterialRef, ObjectDescription,
+    SceneDescription, TextureDescription,
+};
+use crate::scene::error::{Location, SceneError};
+use crate::scene::object::material::{Channel, Color, Principled};
+use crate::scene::object::texture::{Filter, Wrap};
+use crate::scene::validate::Warning;
+
+//glTF 2.0 scenes, in a .gltf (JSON) or .glb (binary) file. Node hierarchies,
+//triangle meshes, metallic-roughness materials, perspective cameras and
+//punctual lights are imported. Skins, animations and morph targets are not.
+
+//extensions whose content is read, the scene can be loaded without the others
+const EXTENSIONS: [&str; 3] = ["KHR_lights_punctual", "KHR_materials_transmission", "KHR_materials_ior"];
+
+//chunk types of a binary file
+const JSON: u32 = 0x4E4F534A;
+const BIN: u32 = 0x004E4942;
+
+//component types of the accessors
+const BYTE: u32 = 5120;
+const UNSIGNED_BYTE: u32 = 5121;
+const SHORT: u32 = 5122;
+const UNSIGNED_SHORT: u32 = 5123;
+const UNSIGNED_INT: u32 = 5125;
+const FLOAT: u32 = 5126;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Document {
+    asset: Asset,
+    scene: Option<usize>,
+    #[serde(default)]
+    scenes: Vec<Roots>,
+    #[serde(default)]
+    nodes: Vec<Node>,
+    #[serde(default)]
+    meshes: Vec<MeshDef>,
+    #[serde(default)]
+    accessors: Vec<Accessor>,
+    #[serde(default)]
+    buffer_views: Vec<BufferView>,
+    #[serde(default)]
+    buffers: Vec<Buffer>,
+    #[serde(default)]
+    materials: Vec<MaterialDef>,
+    #[serde(default)]
+    textures: Vec<TextureDef>,
+    #[serde(default)]
+    images: Vec<ImageDef>,
+    #[serde(default)]
+    samplers: Vec<Sampler>,
+    #[serde(default)]
+    cameras: Vec<CameraDef>,
+    #[serde(default)]
+    extensions: DocumentExtensions,
+    #[serde(default)]
+    extensions_required: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Asset {
+    version: String,
+}
+
+#[derive(Deserialize)]
+struct Roots {
+    #[serde(default)]
+    nodes: Vec<usize>,
+}
+
+#[derive(Deserialize)]
+struct Node {
+    camera: Option<usize>,
+    mesh: Option<usize>,
+    skin: Option<usize>,
+    #[serde(default)]
+    children: Vec<usize>,
+    //column major
+    matrix: Option<[f64; 16]>,
+    translation: Option<[f64; 3]>,
+    //quaternion x, y, z, w
+    rotation: Option<[f64; 4]>,
+    scale: Option<[f64; 3]>,
+    #[serde(default)]
+    extensions: NodeExtensions,
+}
+
+#[derive(Deserialize, Default)]
+struct NodeExtensions {
+    #[serde(rename = "KHR_lights_punctual")]
+    light: Option<LightRef>,
+}
+
+#[derive(Deserialize)]
+struct LightRef {
+    light: usize,
+}
+
+#[derive(Deserialize)]
+struct MeshDef {
+    primitives: Vec<PrimitiveDef>,
+}
+
+#[derive(Deserialize)]
+struct PrimitiveDef {
+    attributes: HashMap<String, usize>,
+    indices: Option<usize>,
+    material: Option<usize>,
+    #[serde(default = "triangles")]
+    mode: u32,
+    #[serde(default)]
+    targets: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Accessor {
+    buffer_view: Option<usize>,
+    #[serde(default)]
+    byte_offset: usize,
+    component_type: u32,
+    #[serde(default)]
+    normalized: bool,
+    count: usize,
+    #[serde(rename = "type")]
+    kind: String,
+    sparse: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BufferView {
+    buffer: usize,
+    #[serde(default)]
+    byte_offset: usize,
+    byte_length: usize,
+    byte_stride: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Buffer {
+    uri: Option<String>,
+    byte_length: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MaterialDef {
+    name: Option<String>,
+    #[serde(default)]
+    pbr_metallic_roughness: Pbr,
+    normal_texture: Option<TextureRef>,
+    emissive_texture: Option<TextureRef>,
+    #[serde(default)]
+    emissive_factor: [f64; 3],
+    #[serde(default = "opaque")]
+    alpha_mode: String,
+    #[serde(default)]
+    extensions: MaterialExtensions,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Pbr {
+    #[serde(default = "white")]
+    base_color_factor: [f64; 4],
+    base_color_texture: Option<TextureRef>,
+    #[serde(default = "one")]
+    metallic_factor: f64,
+    #[serde(default = "one")]
+    roughness_factor: f64,
+    metallic_roughness_texture: Option<TextureRef>,
+}
+
+#[derive(Deserialize, Default)]
+struct MaterialExtensions {
+    #[serde(rename = "KHR_materials_transmission")]
+    transmission: Option<Transmission>,
+    #[serde(rename = "KHR_materials_ior")]
+    ior: Option<Ior>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Transmission {
+    #[serde(default)]
+    transmission_factor: f64,
+}
+
+#[derive(Deserialize)]
+struct Ior {
+    #[serde(default = "glass")]
+    ior: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TextureRef {
+    index: usize,
+    #[serde(default)]
+    tex_coord: usize,
+}
+
+#[derive(Deserialize)]
+struct TextureDef {
+    sampler: Option<usize>,
+    source: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImageDef {
+    uri: Option<String>,
+    mime_type: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Sampler {
+    mag_filter: Option<u32>,
+    #[serde(default = "repeat")]
+    wrap_s: u32,
+}
+
+#[derive(Deserialize)]
+struct CameraDef {
+    #[serde(rename = "type")]
+    kind: String,
+    perspective: Option<Perspective>,
+    orthographic: Option<Orthographic>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Perspective {
+    //vertical, in radians
+    yfov: f64,
+    aspect_ratio: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct Orthographic {
+    xmag: f64,
+    ymag: f64,
+}
+
+#[derive(Deserialize, Default)]
+struct DocumentExtensions {
+    #[serde(rename = "KHR_lights_punctual")]
+    lights: Option<Lights>,
+}
+
+#[derive(Deserialize)]
+struct Lights {
+    lights: Vec<LightDef>,
+}
+
+#[derive(Deserialize)]
+struct LightDef {
+    name: Option<String>,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default = "rgb_white")]
+    color: [f64; 3],
+    #[serde(default = "one")]
+    intensity: f64,
+}
+
+fn triangles() -> u32 {
+    4
+}
+
+fn opaque() -> String {
+    String::from("OPAQUE")
+}
+
+fn white() -> [f64; 4] {
+    [1.; 4]
+}
+
+fn rgb_white() -> [f64; 3] {
+    [1.; 3]
+}
+
+fn one() -> f64 {
+    1.
+}
+
+fn glass() -> f64 {
+    1.5
+}
+
+fn repeat() -> u32 {
+    10497
+}
+
+impl Default for Pbr {
+    fn default() -> Pbr {
+        Pbr {
+            base_color_factor: white(),
+            base_color_texture: None,
+            metallic_factor: 1.,
+            roughness_factor: 1.,
+            metallic_roughness_texture: None,
+        }
+    }
+}
+
+fn at(path: String) -> Location {
+    Location {
+        path,
+        ..Location::default()
+    }
+}
+
+fn invalid(path: String, message: String) -> SceneError {
+    SceneError::Decode {
+        location: Some(at(path)),
+        format: "glTF",
+        message,
+    }
+}
+
+//Standard base64, or its URL variant, padding and spaces are skipped
+fn base64(data: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(data.len() * 3 / 4);
+    let (mut bits, mut count) = (0u32, 0);
+    for c in data.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            _ if c.is_ascii_whitespace() => continue,
+            _ => return Err(format!("{:?} is not a base64 character", c as char)),
+        };
+        bits = bits << 6 | value as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            bytes.push((bits >> count) as u8);
+            bits &= (1 << count) - 1;
+        }
+    }
+    Ok(bytes)
+}
+
+//relative uris are percent encoded (%20 for spaces)
+fn unescape(uri: &str) -> PathBuf {
+    let bytes = uri.as_bytes();
+    let mut path = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| u8::from_str_radix(std::str::from_utf8(h).ok()?, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                path.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                path.push(b);
+                i += 1;
+            }
+        }
+    }
+    PathBuf::from(String::from_utf8_lossy(&path).into_owned())
+}
+
+//JSON chunk and binary chunk of a .glb file
+fn chunks(bytes: &[u8]) -> Result<(&[u8], Option<&[u8]>), SceneError> {
+    let word = |i: usize| bytes.get(i..i + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+    let error = |message: &str| invalid(String::new(), String::from(message));
+    match word(4) {
+        Some(2) => (),
+        Some(version) => return Err(error(&format!("version {} of the binary format is not supported", version))),
+        None => return Err(error("the file ends too soon")),
+    }
+    let length = (word(8).ok_or_else(|| error("the file ends too soon"))? as usize).min(bytes.len());
+    let mut json = None;
+    let mut bin = None;
+    let mut start = 12;
+    while start + 8 <= length {
+        let size = word(start).unwrap() as usize;
+        let data = bytes.get(start + 8..start + 8 + size).ok_or_else(|| error("a chunk goes past the end of the file"))?;
+        match word(start + 4).unwrap() {
+            JSON if json.is_none() => json = Some(data),
+            BIN if bin.is_none() => bin = Some(data),
+            _ => (),
+        }
+        start += 8 + size;
+    }
+    Ok((json.ok_or_else(|| error("there is no JSON chunk"))?, bin))
+}
+
+fn document(json: &[u8]) -> Result<Document, SceneError> {
+    serde_json::from_slice(json).map_err(|e| {
+        //the location is given apart
+        let message = e.to_string();
+        let message = match message.rsplit_once(" at line ") {
+            Some((message, _)) => message.to_string(),
+            None => message,
+        };
+        SceneError::Decode {
+            location: (e.line() > 0).then(|| Location {
+                line: e.line() as u32,
+                column: e.column() as u32,
+                ..Location::default()
+            }),
+            format: "glTF",
+            message,
+        }
+    })
+}
+
+//data of a buffer: the binary chunk, a data uri or a file
+fn load_buffer(buffer: &Buffer, path: String, bin: Option<&[u8]>, base_dir: &Path) -> Result<Vec<u8>, SceneError> {
+    let data = match buffer.uri.as_deref() {
+        None => bin
+            .ok_or_else(|| invalid(path.clone(), String::from("the buffer has no uri and there is no binary chunk")))?
+            .to_vec(),
+        Some(uri) if uri.starts_with("data:") => match uri.split_once(";base64,") {
+            Some((_, data)) => base64(data).map_err(|message| invalid(path.clone(), message))?,
+            None => return Err(invalid(path, String::from("only base64 data uris are supported"))),
+        },
+        Some(uri) => {
+            let file = base_dir.join(unescape(uri));
+            fs::read(&file).map_err(|e| SceneError::Io {
+                location: Some(at(path.clone())),
+                message: format!("{}: {}", file.display(), e),
+            })?
+        }
+    };
+    if data.len() < buffer.byte_length {
+        return Err(invalid(
+            path,
+            format!("the buffer has {} bytes instead of {}", data.len(), buffer.byte_length),
+        ));
+    }
+    Ok(data)
+}
+
+//local transform of a node, from its matrix or its translation, rotation and scale
+fn transform(node: &Node) -> Matrix4 {
+    if let Some(a) = node.matrix {
+        let mut m = Matrix4::identity();
+        for (i, v) in a.iter().enumerate() {
+            m.m[i % 4][i / 4] = *v;
+        }
+        return m;
+    }
+    let [tx, ty, tz] = node.translation.unwrap_or([0.; 3]);
+    let [sx, sy, sz] = node.scale.unwrap_or([1.; 3]);
+    let [x, y, z, w] = node.rotation.unwrap_or([0., 0., 0., 1.]);
+    let norm = (x * x + y * y + z * z + w * w).sqrt();
+    let (x, y, z, w) = match norm > 0. {
+        true => (x / norm, y / norm, z / norm, w / norm),
+        false => (0., 0., 0., 1.),
+    };
+    let rotation = Matrix4 {
+        m: [
+            [1. - 2. * (y * y + z * z), 2. * (x * y - z * w), 2. * (x * z + y * w), 0.],
+            [2. * (x * y + z * w), 1. - 2. * (x * x + z * z), 2. * (y * z - x * w), 0.],
+            [2. * (x * z - y * w), 2. * (y * z + x * w), 1. - 2. * (x * x + y * y), 0.],
+            [0., 0., 0., 1.],
+        ],
+    };
+    Matrix4::translation(Vec3d { x: tx, y: ty, z: tz }) * rotation * Matrix4::scaling(Vec3d { x: sx, y: sy, z: sz })
+}
+
+//value of a component, integers that are normalized are mapped to [0, 1] or [-1, 1]
+fn component(b: &[u8], component_type: u32, normalized: bool) -> f64 {
+    let scale = |v: f64, max: f64| match normalized {
+        true => (v / max).max(-1.),
+        false => v,
+    };
+    match component_type {
+        BYTE => scale(b[0] as i8 as f64, 127.),
+        UNSIGNED_BYTE => scale(b[0] as f64, 255.),
+        SHORT => scale(i16::from_le_bytes([b[0], b[1]]) as f64, 32767.),
+        UNSIGNED_SHORT => scale(u16::from_le_bytes([b[0], b[1]]) as f64, 65535.),
+        UNSIGNED_INT => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+        _ => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+    }
+}
+
+struct Importer<'a> {
+    document: &'a Document,
+    buffers: Vec<Vec<u8>>,
+    scene: SceneDescription,
+    warnings: Vec<Warning>,
+    //names of the materials in the scene
+    materials: Vec<String>,
+    //primitives of each mesh already read
+    meshes: HashMap<usize, Vec<(Rc<Mesh>, MaterialRef)>>,
+    camera: Option<ImportedCamera>,
+}
+
+impl Importer<'_> {
+    fn warn(&mut self, path: String, message: String) {
+        self.warnings.push(Warning {
+            location: at(path),
+            message,
+        });
+    }
+
+    //values of an accessor, and the number of components of each element
+    fn accessor(&self, index: usize, path: &str) -> Result<(Vec<f64>, usize), SceneError> {
+        let error = |message: String| invalid(format!("accessors/{}", index), message);
+        let accessor = self
+            .document
+            .accessors
+            .get(index)
+            .ok_or_else(|| invalid(String::from(path), format!("accessor {} does not exist", index)))?;
+        let components = match accessor.kind.as_str() {
+            "SCALAR" => 1,
+            "VEC2" => 2,
+            "VEC3" => 3,
+            "VEC4" | "MAT2" => 4,
+            "MAT3" => 9,
+            "MAT4" => 16,
+            kind => return Err(error(format!("unknown type {:?}", kind))),
+        };
+        let size = match accessor.component_type {
+            BYTE | UNSIGNED_BYTE => 1,
+            SHORT | UNSIGNED_SHORT => 2,
+            UNSIGNED_INT | FLOAT => 4,
+            t => return Err(error(format!("unknown component type {}", t))),
+        };
+        if accessor.sparse.is_some() {
+            return Err(error(String::from("sparse accessors are not supported")));
+        }
+        //accessors without data are zeros
+        let Some(view_index) = accessor.buffer_view else {
+            return Ok((vec![0.; accessor.count * components], components));
+        };
+        let view = self
+            .document
+            .buffer_views
+            .get(view_index)
+            .ok_or_else(|| error(format!("buffer view {} does not exist", view_index)))?;
+        let data = self
+            .buffers
+            .get(view.buffer)
+            .and_then(|b| b.get(view.byte_offset..view.byte_offset + view.byte_length))
+            .ok_or_else(|| error(format!("buffer view {} is outside of its buffer", view_index)))?;
+        let stride = view.byte_stride.unwrap_or(size * components);
+        let mut values = Vec::with_capacity(accessor.count * components);
+        for i in 0..accessor.count {
+            for c in 0..components {
+                let start = accessor.byte_offset + i * stride + c * size;
+                let b = data
+                    .get(start..start + size)
+                    .ok_or_else(|| error(format!("buffer view {} is too short", view_index)))?;
+                values.push(component(b, accessor.component_type, accessor.normalized));
+            }
+        }
+        Ok((values, components))
+    }
+
+    //values of an accessor whose elements must have the given size
+    fn elements(&self, index: usize, components: usize, path: &str) -> Result<Vec<f64>, SceneError> {
+        let (values, n) = self.accessor(index, path)?;
+        match n == components {
+            true => Ok(values),
+            false => Err(invalid(String::from(path), format!("accessor {} has {} components instead of {}", index, n, components))),
+        }
+    }
+
+    fn mesh(&mut self, index: usize) -> Result<Vec<(Rc<Mesh>, MaterialRef)>, SceneError> {
+        if let Some(primitives) = self.meshes.get(&index) {
+            return Ok(primitives.clone());
+        }
+        let def = self
+            .document
+            .meshes
+            .get(index)
+            .ok_or_else(|| invalid(format!("meshes/{}", index), String::from("the mesh does not exist")))?;
+        let mut primitives = Vec::new();
+        for (i, p) in def.primitives.iter().enumerate() {
+            let path = format!("meshes/{}/primitives/{}", index, i);
+            if !matches!(p.mode, 4..=6) {
+                self.warn(path, String::from("points and lines are left out"));
+                continue;
+            }
+            let Some(&positions) = p.attributes.get("POSITION") else {
+                self.warn(path, String::from("the primitive has no positions, it is left out"));
+                continue;
+            };
+            let vec3 = |v: &[f64]| Vec3d { x: v[0], y: v[1], z: v[2] };
+            let points: Vec<Vec3d> = self.elements(positions, 3, &path)?.chunks_exact(3).map(vec3).collect();
+            let normals = match p.attributes.get("NORMAL") {
+                Some(&n) => self.elements(n, 3, &path)?.chunks_exact(3).map(vec3).collect(),
+                None => vec![],
+            };
+            let uvs = match p.attributes.get("TEXCOORD_0") {
+                Some(&uv) => self.elements(uv, 2, &path)?.chunks_exact(2).map(|v| (v[0], v[1])).collect(),
+                None => vec![],
+            };
+            if p.attributes.contains_key("COLOR_0") {
+                self.warn(path.clone(), String::from("vertex colors are ignored"));
+            }
+            if !p.targets.is_empty() {
+                self.warn(path.clone(), String::from("morph targets are ignored"));
+            }
+            let indices: Vec<usize> = match p.indices {
+                Some(i) => self.elements(i, 1, &path)?.into_iter().map(|i| i as usize).collect(),
+                None => (0..points.len()).collect(),
+            };
+            let triangles = match p.mode {
+                //strips alternate their winding
+                5 => (0..indices.len().saturating_sub(2))
+                    .map(|i| match i % 2 {
+                        0 => [indices[i], indices[i + 1], indices[i + 2]],
+                        _ => [indices[i + 1], indices[i], indices[i + 2]],
+                    })
+                    .collect(),
+                6 => (1..indices.len().saturating_sub(1))
+                    .map(|i| [indices[0], indices[i], indices[i + 1]])
+                    .collect(),
+                _ => indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect(),
+            };
+            let mesh = Mesh::with_normals(points, uvs, normals, triangles).map_err(|message| invalid(path.clone(), message))?;
+            let material = match p.material {
+                Some(m) => MaterialRef::Named(
+                    self.materials
+                        .get(m)
+                        .ok_or_else(|| invalid(path.clone(), format!("material {} does not exist", m)))?
+                        .clone(),
+                ),
+                None => MaterialRef::Inline(Box::new(material(&Pbr::default(), &MaterialExtensions::default()))),
+            };
+            primitives.push((Rc::new(mesh), material));
+        }
+        self.meshes.insert(index, primitives.clone());
+        Ok(primitives)
+    }
+
+    fn texture(&mut self, r: &TextureRef, path: &str) -> Result<Option<TextureDescription>, SceneError> {
+        let texture = self
+            .document
+            .textures
+            .get(r.index)
+            .ok_or_else(|| invalid(String::from(path), format!("texture {} does not exist", r.index)))?;
+        if r.tex_coord != 0 {
+            self.warn(String::from(path), String::from("only the first texture coordinates are used"));
+        }
+        let image = match texture.source {
+            Some(i) => self
+                .document
+                .images
+                .get(i)
+                .ok_or_else(|| invalid(format!("textures/{}", r.index), format!("image {} does not exist", i)))?,
+            None => {
+                self.warn(String::from(path), String::from("the texture has no image, it is left out"));
+                return Ok(None);
+            }
+        };
+        //images are loaded from PNG files
+        let file = match &image.uri {
+            Some(uri) if !uri.starts_with("data:") => unescape(uri),
+            _ => {
+                self.warn(String::from(path), String::from("embedded images are not supported, the texture is left out"));
+                return Ok(None);
+            }
+        };
+        let png = match &image.mime_type {
+            Some(mime) => mime == "image/png",
+            None => file.extension().is_some_and(|e| e.eq_ignore_ascii_case("png")),
+        };
+        if !png {
+            self.warn(
+                String::from(path),
+                format!("{} is not a PNG image, the texture is left out", file.display()),
+            );
+            return Ok(None);
+        }
+        let sampler = match texture.sampler {
+            Some(s) => Some(
+                self.document
+                    .samplers
+                    .get(s)
+                    .ok_or_else(|| invalid(format!("textures/{}", r.index), format!("sampler {} does not exist", s)))?,
+            ),
+            None => None,
+        };
+        Ok(Some(TextureDescription::Image {
+            file,
+            wrap: match sampler.map_or(repeat(), |s| s.wrap_s) {
+                33071 => Wrap::Clamp,
+                33648 => Wrap::Mirror,
+                _ => Wrap::Repeat,
+            },
+            filter: match sampler.and_then(|s| s.mag_filter) {
+                Some(9728) => Filter::Nearest,
+                _ => Filter::Bilinear,
+            },
+            scale: 1.,
+        }))
+    }
+
+    //materials with textures are written with the shading terms of their
+    //metallic-roughness parameters
+    fn material(&mut self, index: usize) -> Result<MaterialDescription, SceneError> {
+        let def = &self.document.materials[index];
+        let path = format!("materials/{}", index);
+        let pbr = &def.pbr_metallic_roughness;
+        if def.alpha_mode != "OPAQUE" {
+            self.warn(path.clone(), format!("the {} alpha mode is ignored, the material is opaque", def.alpha_mode));
+        }
+        if def.emissive_factor.iter().any(|c| *c > 0.) || def.emissive_texture.is_some() {
+            self.warn(path.clone(), String::from("emission is not supported"));
+        }
+        if pbr.metallic_roughness_texture.is_some() {
+            self.warn(path.clone(), String::from("the metallic roughness texture is ignored, the factors are used"));
+        }
+        let base_color = match &pbr.base_color_texture {
+            Some(t) => self.texture(t, &format!("{}/baseColorTexture", path))?,
+            None => None,
+        };
+        let normal_map = match &def.normal_texture {
+            Some(t) => self.texture(t, &format!("{}/normalTexture", path))?,
+            None => None,
+        };
+        let description = material(pbr, &def.extensions);
+        if base_color.is_none() && normal_map.is_none() {
+            return Ok(description);
+        }
+        let MaterialDescription::Principled(principled) = description else {
+            unreachable!("imported materials are principled")
+        };
+        let m = principled.to_material();
+        let mut textures = Vec::new();
+        if let Some(texture) = base_color {
+            let [r, g, b, _] = pbr.base_color_factor;
+            if [r, g, b].iter().any(|c| *c != 1.) {
+                self.warn(path, String::from("the base color factor is not applied to the texture"));
+            }
+            //the base color is the one of the reflections of metals
+            let channel = match principled.metallic >= 0.5 {
+                true => Channel::Specular,
+                false => Channel::Diffuse,
+            };
+            textures.push(ChannelTexture { channel, texture });
+        }
+        Ok(MaterialDescription::Basic(BasicMaterial {
+            diffuse: m.diffuse,
+            specular: m.specular,
+            reflectivity: m.reflectivity,
+            roughness: m.roughness,
+            sheen: m.sheen,
+            clearcoat: m.clearcoat,
+            transmission: m.transmission,
+            ior: m.ior,
+            textures,
+            normal_map,
+            bump: None,
+        }))
+    }
+
+    fn camera(&mut self, index: usize, to_world: &Matrix4, path: String) -> Result<(), SceneError> {
+        let def = self
+            .document
+            .cameras
+            .get(index)
+            .ok_or_else(|| invalid(path.clone(), format!("camera {} does not exist", index)))?;
+        if self.camera.is_some() {
+            self.warn(path, String::from("only the first camera is used"));
+            return Ok(());
+        }
+        //the height of the image is the one of the default camera
+        let (aspect, yfov) = match (def.kind.as_str(), &def.perspective, &def.orthographic) {
+            ("perspective", Some(p), _) => (p.aspect_ratio.unwrap_or(4. / 3.), p.yfov),
+            ("orthographic", _, Some(o)) => {
+                self.warn(path, String::from("orthographic cameras are not supported, a perspective one is used"));
+                (o.xmag / o.ymag, 60f64.to_radians())
+            }
+            _ => return Err(invalid(format!("cameras/{}", index), format!("invalid {:?} camera", def.kind))),
+        };
+        if !(aspect > 0. && aspect.is_finite()) {
+            return Err(invalid(format!("cameras/{}", index), String::from("the aspect ratio must be positive")));
+        }
+        //glTF cameras look along -z
+        self.camera = Some(ImportedCamera {
+            to_world: *to_world * Matrix4::scaling(Vec3d { x: 1., y: 1., z: -1. }),
+            right: Vec3d { x: 1., y: 0., z: 0. },
+            resolution: ((768. * aspect).round().max(1.) as u32, 768),
+            half_width: (yfov / 2.).tan() * aspect,
+        });
+        Ok(())
+    }
+
+    fn light(&mut self, index: usize, to_world: &Matrix4, path: String) -> Result<(), SceneError> {
+        let def = self
+            .document
+            .extensions
+            .lights
+            .as_ref()
+            .and_then(|l| l.lights.get(index))
+            .ok_or_else(|| invalid(path.clone(), format!("light {} does not exist", index)))?;
+        match def.kind.as_str() {
+            "point" => (),
+            "spot" => self.warn(path.clone(), String::from("the spot light is replaced by a point light")),
+            "directional" => {
+                self.warn(path, String::from("directional lights are not supported, the light is left out"));
+                return Ok(());
+            }
+            kind => return Err(invalid(path, format!("unknown light type {:?}", kind))),
+        }
+        let [r, g, b] = def.color;
+        let (color, intensity) = light_color(Color { r, g, b }, def.intensity);
+        self.scene.lights.push(LightDescription {
+            name: def.name.clone(),
+            pos: to_world.transform_point(Vec3d { x: 0., y: 0., z: 0. }),
+            color,
+            intensity,
+        });
+        Ok(())
+    }
+
+    fn node(&mut self, index: usize, parent: &Matrix4, ancestors: &mut Vec<usize>) -> Result<(), SceneError> {
+        let path = format!("nodes/{}", index);
+        let node = self
+            .document
+            .nodes
+            .get(index)
+            .ok_or_else(|| invalid(path.clone(), String::from("the node does not exist")))?;
+        if ancestors.contains(&index) {
+            return Err(invalid(path, String::from("the node is its own ancestor")));
+        }
+        let to_world = *parent * transform(node);
+        if node.skin.is_some() {
+            self.warn(path.clone(), String::from("skins are ignored, the mesh is left in its rest pose"));
+        }
+        if let Some(mesh) = node.mesh {
+            for (mesh, material) in self.mesh(mesh)? {
+                let (shape, warning) = place(&Primitive::Mesh(mesh), &to_world);
+                if let Some(warning) = warning {
+                    self.warn(path.clone(), warning);
+                }
+                self.scene.objects.push(ObjectDescription::new(shape, Some(material)));
+            }
+        }
+        if let Some(camera) = node.camera {
+            self.camera(camera, &to_world, path.clone())?;
+        }
+        if let Some(light) = &node.extensions.light {
+            self.light(light.light, &to_world, path)?;
+        }
+        ancestors.push(index);
+        for child in node.children.iter() {
+            self.node(*child, &to_world, ancestors)?;
+        }
+        ancestors.pop();
+        Ok(())
+    }
+
+    fn finish(mut self) -> (SceneDescription, Vec<Warning>) {
+        let camera = self.camera.take().unwrap_or_else(|| {
+            self.warn(String::from("cameras"), String::from("there is no camera, the scene is seen from the origin along -z"));
+            ImportedCamera {
+                to_world: Matrix4::scaling(Vec3d { x: 1., y: 1., z: -1. }),
+                right: Vec3d { x: 1., y: 0., z: 0. },
+                resolution: (1024, 768),
+                half_width: (30f64).to_radians().tan() * 4. / 3.,
+            }
+        });
+        let (camera, mirrored) = camera.description();
+        self.scene.camera = camera;
+        if mirrored {
+            mirror(&mut self.scene);
+        }
+        (self.scene, self.warnings)
+    }
+}
+
+//metallic-roughness parameters
+fn material(pbr: &Pbr, extensions: &MaterialExtensions) -> MaterialDescription {
+    let [r, g, b, _] = pbr.base_color_factor;
+    MaterialDescription::Principled(Principled {
+        base_color: Color { r, g, b },
+        metallic: pbr.metallic_factor,
+        roughness: pbr.roughness_factor,
+        transmission: extensions.transmission.as_ref().map_or(0., |t| t.transmission_factor),
+        ior: extensions.ior.as_ref().map_or(glass(), |i| i.ior),
+        ..Principled::default()
+    })
+}
+
+//Scene of a .gltf or .glb file, whose buffers and images are found from base_dir
+pub fn read(bytes: &[u8], base_dir: &Path) -> Result<(SceneDescription, Vec<Warning>), SceneError> {
+    let (json, bin) = match bytes.starts_with(b"glTF") {
+        true => chunks(bytes)?,
+        false => (bytes, None),
+    };
+    let document = document(json)?;
+    if !document.asset.version.starts_with("2.") {
+        return Err(invalid(
+            String::from("asset/version"),
+            format!("version {} is not supported, only 2.0 is", document.asset.version),
+        ));
+    }
+    if let Some(extension) = document.extensions_required.iter().find(|e| !EXTENSIONS.contains(&e.as_str())) {
+        return Err(invalid(
+            String::from("extensionsRequired"),
+            format!("the {} extension is not supported", extension),
+        ));
+    }
+    let buffers = document
+        .buffers
+        .iter()
+        .enumerate()
+        .map(|(i, b)| load_buffer(b, format!("buffers/{}", i), bin, base_dir))
+        .collect::<Result<Vec<Vec<u8>>, SceneError>>()?;
+
+    let mut importer = Importer {
+        document: &document,
+        buffers,
+        scene: SceneDescription::default(),
+        warnings: vec![],
+        materials: vec![],
+        meshes: HashMap::new(),
+        camera: None,
+    };
+    for (i, def) in document.materials.iter().enumerate() {
+        let mut name = def.name.clone().unwrap_or_else(|| format!("material{}", i));
+        if importer.scene.materials.contains_key(&name) {
+            name = format!("{}.{}", name, i);
+        }
+        let material = importer.material(i)?;
+        importer.scene.materials.insert(name.clone(), material);
+        importer.materials.push(name);
+    }
+
+    //the nodes of the default scene, or all the nodes that are not children
+    let roots: Vec<usize> = match (document.scene, document.scenes.is_empty()) {
+        (Some(i), _) => document
+            .scenes
+            .get(i)
+            .ok_or_else(|| invalid(String::from("scene"), format!("scene {} does not exist", i)))?
+            .nodes
+            .clone(),
+        (None, false) => document.scenes[0].nodes.clone(),
+        (None, true) => {
+            let children: Vec<usize> = document.nodes.iter().flat_map(|n| n.children.iter().copied()).collect();
+            (0..document.nodes.len()).filter(|i| !children.contains(i)).collect()
+        }
+    };
+    for root in roots {
+        importer.node(root, &Matrix4::identity(), &mut vec![])?;
+    }
+    Ok(importer.finish())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::scene::description::Shape;
+    use approx::assert_abs_diff_eq;
+
+    fn v(x: f64, y: f64, z: f64) -> Vec3d {
+        Vec3d { x, y, z }
+    }
+
+    //binary file with a JSON chunk and a binary chunk
+    fn glb(json: &str, bin: &[u8]) -> Vec<u8> {
+        let pad = |data: &[u8], byte: u8| {
+            let mut data = data.to_vec();
+            while !data.len().is_multiple_of(4) {
+                data.push(byte);
+            }
+            data
+        };
+        let (json, bin) = (pad(json.as_bytes(), b' '), pad(bin, 0));
+        let mut bytes = b"glTF".to_vec();
+        bytes.extend(2u32.to_le_bytes());
+        bytes.extend(((12 + 8 + json.len() + 8 + bin.len()) as u32).to_le_bytes());
+        for (kind, data) in [(JSON, json), (BIN, bin)] {
+            bytes.extend((data.len() as u32).to_le_bytes());
+            bytes.extend(kind.to_le_bytes());
+            bytes.extend(data);
+        }
+        bytes
+    }
+
+    //a triangle with normals and uvs, and its indices
+    fn triangle() -> Vec<u8> {
+        let mut bin = Vec::new();
+        for f in [0f32, 0., 0., 1., 0., 0., 0., 1., 0.] {
+            bin.extend(f.to_le_bytes());
+        }
+        for f in [0f32, 0., 1., 0., 0., 1., 0., 0., 1.] {
+            bin.extend(f.to_le_bytes());
+        }
+        for f in [0f32, 0., 1., 0., 0., 1.] {
+            bin.extend(f.to_le_bytes());
+        }
+        for i in [0u16, 1, 2] {
+            bin.extend(i.to_le_bytes());
+        }
+        bin
+    }
+
+    const SCENE: &str = r#"{
+        "asset": {"version": "2.0"},
+        "extensionsUsed": ["KHR_lights_punctual"],
+        "scene": 0,
+        "scenes": [{"nodes": [0, 2, 3]}],
+        "nodes": [
+            {"translation": [0, 0, -5], "children": [1]},
+            {"mesh": 0, "scale": [2, 2, 2]},
+            {"camera": 0, "translation": [1, 0, 0]},
+            {"rotation": [0, 0.7071068, 0, 0.7071068], "translation": [0, 3, 0], "extensions": {"KHR_lights_punctual": {"light": 0}}}
+        ],
+        "meshes": [{"primitives": [{"attributes": {"POSITION": 0, "NORMAL": 1, "TEXCOORD_0": 2}, "indices": 3, "material": 0}]}],
+        "accessors": [
+            {"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"},
+            {"bufferView": 0, "byteOffset": 36, "componentType": 5126, "count": 3, "type": "VEC3"},
+            {"bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC2"},
+            {"bufferView": 2, "componentType": 5123, "count": 3, "type": "SCALAR"}
+        ],
+        "bufferViews": [
+            {"buffer": 0, "byteLength": 72},
+            {"buffer": 0, "byteOffset": 72, "byteLength": 24},
+            {"buffer": 0, "byteOffset": 96, "byteLength": 6}
+        ],
+        "buffers": [{"byteLength": 102}],
+        "materials": [{
+            "name": "red",
+            "pbrMetallicRoughness": {"baseColorFactor": [1, 0, 0, 1], "metallicFactor": 0, "roughnessFactor": 0.5}
+        }],
+        "cameras": [{"type": "perspective", "perspective": {"yfov": 1.0, "aspectRatio": 2.0, "znear": 0.1}}],
+        "extensions": {"KHR_lights_punctual": {"lights": [{"type": "point", "color": [1, 0.5, 0.5], "intensity": 20}]}}
+    }"#;
+
+    fn messages(warnings: &[Warning]) -> Vec<String> {
+        warnings.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn decode_base64() {
+        assert_eq!(base64("TWFu").unwrap(), b"Man");
+        assert_eq!(base64("SGVs\nbG8=").unwrap(), b"Hello");
+        assert!(base64("SGVsb*8=").is_err());
+        assert_eq!(unescape("my%20texture.png"), PathBuf::from("my texture.png"));
+    }
+
+    #[test]
+    fn binary_scene() {
+        let (scene, warnings) = read(&glb(SCENE, &triangle()), Path::new(".")).unwrap();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+
+        //glTF is right handed, the scene is mirrored along x
+        assert_abs_diff_eq!(scene.camera.pos, v(-1., 0., 0.));
+        assert_abs_diff_eq!(scene.camera.dir, v(0., 0., -1.));
+        assert_eq!(scene.camera.resolution, Some((1536, 768)));
+        assert_abs_diff_eq!(scene.camera.fov.unwrap(), (0.5f64.tan() * 2.).to_degrees() * 2., epsilon = 1e-9);
+
+        let Shape::Mesh { faces, .. } = &scene.objects[0].shape else { panic!("mesh expected") };
+        assert_eq!(faces[0].points, vec![v(0., 0., -5.), v(-2., 0., -5.), v(0., 2., -5.)]);
+        assert_eq!(faces[0].uvs[1], (1., 0.));
+        assert_abs_diff_eq!(faces[0].normals[2], v(0., 0., 1.));
+        assert_eq!(scene.objects[0].material, Some(MaterialRef::Named(String::from("red"))));
+        let Some(MaterialDescription::Principled(red)) = scene.materials.get("red") else { panic!("red material expected") };
+        assert_eq!((red.base_color, red.metallic, red.roughness), (Color { r: 1., g: 0., b: 0. }, 0., 0.5));
+
+        assert_abs_diff_eq!(scene.lights[0].pos, v(0., 3., 0.));
+        assert_eq!(scene.lights[0].color, Color { r: 1., g: 0.5, b: 0.5 });
+        assert_eq!(scene.lights[0].intensity, 20.);
+    }
+
+    #[test]
+    fn json_scene() {
+        let bin = triangle();
+        let mut data = String::from("data:application/octet-stream;base64,");
+        //base64 of the triangle, 3 bytes at a time
+        const DIGITS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        for c in bin.chunks(3) {
+            let n = (c[0] as u32) << 16 | (*c.get(1).unwrap_or(&0) as u32) << 8 | *c.get(2).unwrap_or(&0) as u32;
+            for i in 0..=c.len() {
+                data.push(DIGITS[(n >> (18 - 6 * i) & 63) as usize] as char);
+            }
+        }
+        let content = SCENE
+            .replace(r#""buffers": [{"byteLength": 102}]"#, &format!(r#""buffers": [{{"byteLength": 102, "uri": "{}"}}]"#, data))
+            .replace(r#""pbrMetallicRoughness""#, r#""alphaMode": "BLEND", "normalTexture": {"index": 0}, "pbrMetallicRoughness""#)
+            .replace(r#""cameras""#, r#""textures": [{"source": 0, "sampler": 0}], "images": [{"uri": "normal%20map.png"}], "samplers": [{"wrapS": 33071, "magFilter": 9728}], "cameras""#)
+            .replace(r#""type": "point""#, r#""type": "directional""#);
+        let (scene, warnings) = read(content.as_bytes(), Path::new(".")).unwrap();
+        assert_eq!(
+            messages(&warnings),
+            vec![
+                "materials/0: the BLEND alpha mode is ignored, the material is opaque",
+                "nodes/3: directional lights are not supported, the light is left out",
+            ]
+        );
+        assert!(scene.lights.is_empty());
+        let Some(MaterialDescription::Basic(red)) = scene.materials.get("red") else { panic!("basic material expected") };
+        assert_eq!(red.diffuse, Color { r: 1., g: 0., b: 0. });
+        assert_eq!(
+            red.normal_map,
+            Some(TextureDescription::Image {
+                file: PathBuf::from("normal map.png"),
+                wrap: Wrap::Clamp,
+                filter: Filter::Nearest,
+                scale: 1.,
+            })
+        );
+    }
+
+    #[test]
+    fn hierarchy() {
+        //a node with a matrix and its child, without scene and without camera
+        let content = r#"{
+            "asset": {"version": "2.0"},
+            "nodes": [
+                {"camera": 0, "rotation": [0, 1, 0, 0]},
+                {"matrix": [1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 4, 0, 0, 1], "children": [2]},
+                {"translation": [0, 1, 0], "extensions": {"KHR_lights_punctual": {"light": 0}}}
+            ],
+            "cameras": [{"type": "orthographic", "orthographic": {"xmag": 1, "ymag": 1, "znear": 0.1, "zfar": 10}}],
+            "extensions": {"KHR_lights_punctual": {"lights": [{"type": "spot", "spot": {}}]}}
+        }"#;
+        let (scene, warnings) = read(content.as_bytes(), Path::new(".")).unwrap();
+        assert_eq!(
+            messages(&warnings),
+            vec![
+                "nodes/0: orthographic cameras are not supported, a perspective one is used",
+                "nodes/2: the spot light is replaced by a point light",
+            ]
+        );
+        //turned around y, the camera looks along +z
+        assert_abs_diff_eq!(scene.camera.dir, v(0., 0., 1.), epsilon = 1e-12);
+        assert_eq!(scene.camera.resolution, Some((768, 768)));
+        assert_abs_diff_eq!(scene.lights[0].pos, v(-4., 1., 0.));
+
+        let (scene, warnings) = read(br#"{"asset": {"version": "2.0"}}"#, Path::new(".")).unwrap();
+        assert_eq!(messages(&warnings), vec!["cameras: there is no camera, the scene is seen from the origin along -z"]);
+        assert_abs_diff_eq!(scene.camera.fov.unwrap(), (30f64.to_radians().tan() * 4. / 3.).to_degrees() * 2.);
+    }
+
+    #[test]
+    fn import_errors() {
+        let error = |content: &str| read(content.as_bytes(), Path::new(".")).unwrap_err().to_string();
+        assert_eq!(error(r#"{"asset": {"version": "1.0"}}"#), "asset/version: invalid glTF: version 1.0 is not supported, only 2.0 is");
+        assert_eq!(
+            error(r#"{"asset": {"version": "2.0"}, "extensionsRequired": ["KHR_draco_mesh_compression"]}"#),
+            "extensionsRequired: invalid glTF: the KHR_draco_mesh_compression extension is not supported"
+        );
+        assert_eq!(
+            error(r#"{"asset": {"version": "2.0"}, "nodes": [{"children": [0]}], "scenes": [{"nodes": [0]}]}"#),
+            "nodes/0: invalid glTF: the node is its own ancestor"
+        );
+        assert_eq!(
+            error(r#"{"asset": {"version": "2.0"}, "nodes": [{"mesh": 0}], "scenes": [{"nodes": [0]}]}"#),
+            "meshes/0: invalid glTF: the mesh does not exist"
+        );
+        assert!(error("{\"asset\": {}}").starts_with("line 1, column 12: invalid glTF: missing field `version`"));
+        let truncated = SCENE.replace(r#""byteLength": 102}"#, r#""byteLength": 200}"#);
+        assert_eq!(
+            read(&glb(&truncated, &triangle()), Path::new(".")).unwrap_err().to_string(),
+            "buffers/0: invalid glTF: the buffer has 104 bytes instead of 200"
+        );
+    }
+}
@@ -0,0 +1,798 @@
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+use super::{
+    area_light, conductor, conductor_color, dielectric, diffuse, light_color, look_at, metal, mirror, obj, place, ply,
+    ImportedCamera, Mesh, Primitive,
+};
+use crate::coord::{Matrix4, Vec3d};
+use crate::scene::description::{
+    LightDescription, MaterialDescription, MaterialRef, ObjectDescription, SceneDescription, Shape,
+};
+use crate::scene::error::{Location, SceneError};
+use crate::scene::object::material::Color;
+use crate::scene::validate::Warning;
+
+//Mitsuba 0.6 and 3 scene files: XML whose root is <scene version="...">,
+//objects (shape, bsdf, emitter...) with their type and their named
+//properties (<float name="radius" value="2"/>)
+
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Element>,
+    at: Location,
+}
+
+//line of the offsets read so far
+struct Lines<'a> {
+    content: &'a str,
+    offset: usize,
+    line: u32,
+    line_start: usize,
+}
+
+struct Importer<'a> {
+    base_dir: &'a Path,
+    scene: SceneDescription,
+    warnings: Vec<Warning>,
+    //values of the $name parameters
+    defaults: HashMap<String, String>,
+    //conductors without a material are copper before Mitsuba 2
+    version: u32,
+    camera: Option<ImportedCamera>,
+    ambiant: Color,
+}
+
+impl Lines<'_> {
+    fn location(&mut self, offset: usize, path: String) -> Location {
+        for (i, b) in self.content.as_bytes()[self.offset..offset].iter().enumerate() {
+            if *b == b'\n' {
+                self.line += 1;
+                self.line_start = self.offset + i + 1;
+            }
+        }
+        self.offset = offset;
+        Location {
+            file: None,
+            line: self.line,
+            column: self.content[self.line_start..offset].chars().count() as u32 + 1,
+            path,
+        }
+    }
+}
+
+impl Element {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+
+    //child giving the property of this name
+    fn property(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.attribute("name") == Some(name))
+    }
+
+    fn kind(&self) -> &str {
+        self.attribute("type").unwrap_or_default()
+    }
+}
+
+fn syntax(location: Location, message: String) -> SceneError {
+    SceneError::Syntax { location, message }
+}
+
+//at is in the parent element, the path goes on to this one
+fn element(e: &BytesStart, mut at: Location) -> Result<Element, SceneError> {
+    let mut attributes = Vec::new();
+    for a in e.attributes() {
+        let a = a.map_err(|e| syntax(at.clone(), e.to_string()))?;
+        let value = a.unescape_value().map_err(|e| syntax(at.clone(), e.to_string()))?;
+        attributes.push((String::from_utf8_lossy(a.key.as_ref()).into_owned(), value.into_owned()));
+    }
+    let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+    at.path = match at.path.is_empty() {
+        true => name.clone(),
+        false => format!("{}/{}", at.path, name),
+    };
+    Ok(Element {
+        name,
+        attributes,
+        children: vec![],
+        at,
+    })
+}
+
+//root element of the file, with all its descendants
+fn parse(content: &str, file: Option<Box<str>>) -> Result<Element, SceneError> {
+    let mut reader = Reader::from_str(content);
+    reader.config_mut().trim_text(true);
+    let mut lines = Lines {
+        content,
+        offset: 0,
+        line: 1,
+        line_start: 0,
+    };
+    let mut open: Vec<Element> = Vec::new();
+    let mut root = None;
+    loop {
+        let position = reader.buffer_position() as usize;
+        let rest = &content[position..];
+        let start = position + rest.len() - rest.trim_start().len();
+        let path: Vec<&str> = open.iter().map(|e| e.name.as_str()).collect();
+        let mut at = lines.location(start, path.join("/"));
+        at.file = file.clone();
+        let event = reader.read_event().map_err(|e| syntax(at.clone(), e.to_string()))?;
+        let closed = match event {
+            Event::Start(e) => {
+                open.push(element(&e, at)?);
+                None
+            }
+            Event::Empty(e) => Some(element(&e, at)?),
+            Event::End(_) => open.pop(),
+            Event::Eof => match open.last() {
+                Some(e) => return Err(syntax(at, format!("end of file before the end of <{}>", e.name))),
+                None => break,
+            },
+            _ => None,
+        };
+        if let Some(element) = closed {
+            match open.last_mut() {
+                Some(parent) => parent.children.push(element),
+                None => root = Some(element),
+            }
+        }
+    }
+    root.ok_or_else(|| syntax(Location::default(), String::from("there is no root element")))
+}
+
+//whether an XML scene is a Mitsuba one rather than one of this crate
+pub fn is_mitsuba(content: &str) -> bool {
+    let mut reader = Reader::from_str(content);
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e) | Event::Empty(e)) => {
+                return e.name().as_ref() == b"scene" && e.attributes().flatten().any(|a| a.key.as_ref() == b"version")
+            }
+            Ok(Event::Eof) | Err(_) => return false,
+            _ => (),
+        }
+    }
+}
+
+//numbers separated by commas or spaces
+fn numbers(s: &str) -> Option<Vec<f64>> {
+    s.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.parse::<f64>().ok())
+        .collect()
+}
+
+//index of refraction of the materials Mitsuba knows by name
+fn named_ior(name: &str) -> Option<f64> {
+    let ior = match name {
+        "vacuum" => 1.,
+        "helium" => 1.00004,
+        "hydrogen" => 1.00013,
+        "air" => 1.00028,
+        "carbon dioxide" => 1.00045,
+        "water" => 1.333,
+        "acetone" => 1.36,
+        "ethanol" => 1.361,
+        "carbon tetrachloride" => 1.461,
+        "glycerol" => 1.4729,
+        "benzene" => 1.501,
+        "silicone oil" => 1.52045,
+        "bromine" => 1.661,
+        "water ice" => 1.31,
+        "fused quartz" => 1.458,
+        "pyrex" => 1.47,
+        "acrylic glass" => 1.49,
+        "polypropylene" => 1.49,
+        "bk7" => 1.5046,
+        "sodium chloride" => 1.544,
+        "amber" => 1.55,
+        "pet" => 1.575,
+        "diamond" => 2.419,
+        _ => return None,
+    };
+    Some(ior)
+}
+
+impl Importer<'_> {
+    fn warn(&mut self, at: &Location, message: String) {
+        self.warnings.push(Warning {
+            location: at.clone(),
+            message,
+        });
+    }
+
+    //attribute with the $name parameters replaced by their value
+    fn value(&self, e: &Element, attribute: &str) -> Option<String> {
+        let mut value = String::from(e.attribute(attribute)?);
+        if value.contains('$') {
+            //longest names first, $ab is not $a followed by b
+            let mut names: Vec<&String> = self.defaults.keys().collect();
+            names.sort_by_key(|n| std::cmp::Reverse(n.len()));
+            for name in names {
+                value = value.replace(&format!("${}", name), &self.defaults[name]);
+            }
+        }
+        Some(value)
+    }
+
+    fn invalid(&self, e: &Element, attribute: &str, message: &str) -> SceneError {
+        SceneError::InvalidValue {
+            location: e.at.clone(),
+            attribute: String::from(attribute),
+            value: self.value(e, attribute).unwrap_or_default(),
+            message: String::from(message),
+        }
+    }
+
+    fn numbers(&self, e: &Element, attribute: &str) -> Result<Option<Vec<f64>>, SceneError> {
+        match self.value(e, attribute) {
+            None => Ok(None),
+            Some(v) => numbers(&v).map(Some).ok_or_else(|| self.invalid(e, attribute, "it is not a list of numbers")),
+        }
+    }
+
+    fn float(&self, e: &Element, name: &str) -> Result<Option<f64>, SceneError> {
+        let Some(property) = e.property(name) else {
+            return Ok(None);
+        };
+        match self.numbers(property, "value")?.as_deref() {
+            Some([v]) => Ok(Some(*v)),
+            _ => Err(self.invalid(property, "value", "it is not a number")),
+        }
+    }
+
+    fn string(&self, e: &Element, name: &str) -> Option<String> {
+        self.value(e.property(name)?, "value")
+    }
+
+    //x, y and z attributes or a value with 3 numbers
+    fn vector(&self, e: &Element, default: f64) -> Result<Vec3d, SceneError> {
+        if let Some(v) = self.numbers(e, "value")? {
+            return match v[..] {
+                [x, y, z] => Ok(Vec3d { x, y, z }),
+                [v] => Ok(Vec3d { x: v, y: v, z: v }),
+                _ => Err(self.invalid(e, "value", "it needs 3 numbers")),
+            };
+        }
+        let mut v = [default; 3];
+        for (i, axis) in ["x", "y", "z"].iter().enumerate() {
+            if let Some(n) = self.numbers(e, axis)? {
+                v[i] = *n.first().ok_or_else(|| self.invalid(e, axis, "it is not a number"))?;
+            }
+        }
+        Ok(Vec3d { x: v[0], y: v[1], z: v[2] })
+    }
+
+    //None for textures, spectra are gray
+    fn color(&mut self, e: &Element, name: &str) -> Result<Option<Color>, SceneError> {
+        let Some(property) = e.property(name) else {
+            return Ok(None);
+        };
+        match property.name.as_str() {
+            "rgb" | "srgb" | "float" => match self.numbers(property, "value")?.as_deref() {
+                Some([r, g, b]) => Ok(Some(Color { r: *r, g: *g, b: *b })),
+                Some([v]) => Ok(Some(Color::gray(*v))),
+                _ => Err(self.invalid(property, "value", "it is not a color")),
+            },
+            "spectrum" => {
+                let Some(value) = self.value(property, "value") else {
+                    return Ok(Some(Color::gray(1.)));
+                };
+                //a value, or wavelength:value pairs
+                let values: Option<Vec<f64>> = value
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|w| !w.is_empty())
+                    .map(|w| w.rsplit(':').next().and_then(|v| v.parse().ok()))
+                    .collect();
+                match values {
+                    Some(values) if !values.is_empty() => Ok(Some(Color::gray(values.iter().sum::<f64>() / values.len() as f64))),
+                    _ => Err(self.invalid(property, "value", "it is not a spectrum")),
+                }
+            }
+            "blackbody" => Ok(Some(Color::gray(1.))),
+            _ => {
+                self.warn(&property.at, format!("the {} is not imported, its default color is used", property.name));
+                Ok(None)
+            }
+        }
+    }
+
+    //composition of the children of a <transform>, in their order
+    fn transform(&mut self, e: &Element) -> Result<Matrix4, SceneError> {
+        let mut m = Matrix4::identity();
+        for t in e.children.iter() {
+            let step = match t.name.as_str() {
+                "translate" => Matrix4::translation(self.vector(t, 0.)?),
+                "scale" => Matrix4::scaling(self.vector(t, 1.)?),
+                "rotate" => {
+                    let axis = self.vector(t, 0.)?;
+                    let angle = self.numbers(t, "angle")?.and_then(|a| a.first().copied()).unwrap_or(0.);
+                    if axis.normalize().is_none() {
+                        return Err(self.invalid(t, "x", "the rotation axis cannot be null"));
+                    }
+                    Matrix4::rotation(axis, angle.to_radians())
+                }
+                "matrix" => {
+                    //written row by row
+                    let v = self.numbers(t, "value")?.unwrap_or_default();
+                    let mut step = Matrix4::identity();
+                    match v.len() {
+                        16 => (0..16).for_each(|i| step.m[i / 4][i % 4] = v[i]),
+                        9 => (0..9).for_each(|i| step.m[i / 3][i % 3] = v[i]),
+                        _ => return Err(self.invalid(t, "value", "a matrix needs 16 numbers")),
+                    }
+                    step
+                }
+                "lookat" | "lookAt" => {
+                    let point = |attribute: &str| -> Result<Vec3d, SceneError> {
+                        match self.numbers(t, attribute)?.as_deref() {
+                            Some([x, y, z]) => Ok(Vec3d { x: *x, y: *y, z: *z }),
+                            None if attribute == "up" => Ok(Vec3d { x: 0., y: 1., z: 0. }),
+                            _ => Err(self.invalid(t, attribute, "it needs 3 numbers")),
+                        }
+                    };
+                    look_at(point("origin")?, point("target")?, point("up")?)
+                        .ok_or_else(|| self.invalid(t, "up", "it is along the direction"))?
+                }
+                name => {
+                    self.warn(&t.at, format!("<{}> is not imported", name));
+                    continue;
+                }
+            };
+            m = step * m;
+        }
+        Ok(m)
+    }
+
+    fn world_transform(&mut self, e: &Element) -> Result<Matrix4, SceneError> {
+        match e.property("to_world").or_else(|| e.property("toWorld")) {
+            Some(t) => self.transform(t),
+            None => Ok(Matrix4::identity()),
+        }
+    }
+
+    fn read(&mut self, root: &Element) -> Result<(), SceneError> {
+        for e in root.children.iter() {
+            match e.name.as_str() {
+                "sensor" | "camera" => self.sensor(e)?,
+                "shape" => self.shape(e)?,
+                "emitter" => self.emitter(e, None)?,
+                "bsdf" => {
+                    let material = self.bsdf(e)?;
+                    match self.value(e, "id") {
+                        Some(id) => {
+                            self.scene.materials.insert(id, material);
+                        }
+                        None => self.warn(&e.at, String::from("a material without an id is not used")),
+                    }
+                }
+                "include" => {
+                    let file = self.value(e, "filename").ok_or_else(|| SceneError::MissingAttribute {
+                        location: e.at.clone(),
+                        attribute: String::from("filename"),
+                    })?;
+                    let path = self.base_dir.join(&file);
+                    let content = fs::read_to_string(&path).map_err(|error| SceneError::Io {
+                        location: Some(e.at.clone()),
+                        message: format!("{}: {}", path.display(), error),
+                    })?;
+                    let included = parse(&content, Some(Box::from(file.as_str())))?;
+                    self.defaults(&included)?;
+                    self.read(&included)?;
+                }
+                "texture" => self.warn(&e.at, String::from("textures are not imported, the materials using them take their default color")),
+                "medium" | "phase" => self.warn(&e.at, String::from("participating media are not imported")),
+                //settings of the renderer, this crate has its own
+                "default" | "integrator" | "sampler" | "film" | "rfilter" => (),
+                name => self.warn(&e.at, format!("<{}> is not imported", name)),
+            }
+        }
+        Ok(())
+    }
+
+    fn defaults(&mut self, root: &Element) -> Result<(), SceneError> {
+        for e in root.children.iter().filter(|e| e.name == "default") {
+            let attribute = |name: &str| {
+                e.attribute(name).ok_or_else(|| SceneError::MissingAttribute {
+                    location: e.at.clone(),
+                    attribute: String::from(name),
+                })
+            };
+            let (name, value) = (attribute("name")?, attribute("value")?);
+            self.defaults.entry(String::from(name)).or_insert_with(|| String::from(value));
+        }
+        Ok(())
+    }
+
+    fn sensor(&mut self, e: &Element) -> Result<(), SceneError> {
+        if e.kind() != "perspective" {
+            self.warn(&e.at, format!("{} sensors are imported as perspective ones", e.kind()));
+        }
+        let film = e.children.iter().find(|c| c.name == "film");
+        let size = |name: &str, default: f64| -> Result<u32, SceneError> {
+            Ok(match film {
+                Some(film) => self.float(film, name)?.unwrap_or(default) as u32,
+                None => default as u32,
+            })
+        };
+        let (w, h) = (size("width", 768.)?, size("height", 576.)?);
+        //fov, or focal length of a 35mm film
+        let fov = match (self.float(e, "fov")?, self.string(e, "focal_length")) {
+            (Some(fov), _) => fov,
+            (None, Some(focal)) => {
+                let focal = focal.trim_end_matches("mm").parse::<f64>().map_err(|_| {
+                    self.invalid(e.property("focal_length").unwrap(), "value", "it is not a length in millimeters")
+                })?;
+                2. * (18. / focal).atan().to_degrees()
+            }
+            (None, None) => 90.,
+        };
+        let half = (fov * PI / 360.).tan();
+        let (wf, hf) = (w as f64, h as f64);
+        let axis = self.string(e, "fov_axis").unwrap_or(String::from("x"));
+        let half_width = match axis.as_str() {
+            "x" => half,
+            "y" => half * wf / hf,
+            "diagonal" => half * wf / (wf * wf + hf * hf).sqrt(),
+            "smaller" if w <= h => half,
+            "larger" if w >= h => half,
+            "smaller" | "larger" => half * wf / hf,
+            _ => return Err(self.invalid(e.property("fov_axis").unwrap(), "value", "it is not x, y, diagonal, smaller or larger")),
+        };
+        //the image of a Mitsuba camera goes right along the -x axis of its frame
+        self.camera = Some(ImportedCamera {
+            to_world: self.world_transform(e)?,
+            right: Vec3d { x: -1., y: 0., z: 0. },
+            resolution: (w, h),
+            half_width,
+        });
+        Ok(())
+    }
+
+    fn bsdf(&mut self, e: &Element) -> Result<MaterialDescription, SceneError> {
+        let alpha = self.float(e, "alpha")?.or(self.float(e, "alpha_u")?);
+        //alpha is about the square of the roughness
+        let roughness = |default: f64| alpha.unwrap_or(default).max(0.).sqrt();
+        let material = match e.kind() {
+            "diffuse" => diffuse(self.color(e, "reflectance")?.unwrap_or(Color::gray(0.5))),
+            kind @ ("conductor" | "roughconductor") => {
+                let color = match (self.color(e, "eta")?, self.color(e, "k")?) {
+                    (Some(eta), Some(k)) => conductor_color(eta, k),
+                    _ => {
+                        let default = if self.version < 2 { "Cu" } else { "none" };
+                        let name = self.string(e, "material").unwrap_or(String::from(default));
+                        match metal(&name) {
+                            Some(color) => color,
+                            None if name == "none" => Color::gray(1.),
+                            None => {
+                                self.warn(&e.at, format!("the metal {:?} is not known, it is imported as silver", name));
+                                metal("Ag").unwrap()
+                            }
+                        }
+                    }
+                };
+                let color = match self.color(e, "specular_reflectance")? {
+                    Some(specular) => color * specular,
+                    None => color,
+                };
+                conductor(color, if kind == "conductor" { 0. } else { roughness(0.1) })
+            }
+            kind @ ("dielectric" | "roughdielectric" | "thindielectric") => {
+                let ior = |name: &str, default: f64| -> Result<f64, SceneError> {
+                    let Some(property) = e.property(name) else {
+                        return Ok(default);
+                    };
+                    let value = self.value(property, "value").unwrap_or_default();
+                    value
+                        .parse::<f64>()
+                        .ok()
+                        .or_else(|| named_ior(&value))
+                        .ok_or_else(|| self.invalid(property, "value", "it is not an index of refraction"))
+                };
+                let ior = ior("int_ior", 1.5046)? / ior("ext_ior", 1.00028)?;
+                dielectric(ior, if kind == "roughdielectric" { roughness(0.1) } else { 0. })
+            }
+            //material of the inner bsdf
+            "twosided" => return self.inner_bsdf(e),
+            kind @ ("bumpmap" | "normalmap" | "mask") => {
+                self.warn(&e.at, format!("{} is not imported, the inner material is used", kind));
+                return self.inner_bsdf(e);
+            }
+            kind => {
+                self.warn(&e.at, format!("{} materials are imported as diffuse ones", kind));
+                let color = match self.color(e, "diffuse_reflectance")? {
+                    Some(color) => Some(color),
+                    None => self.color(e, "base_color")?,
+                };
+                diffuse(color.unwrap_or(Color::gray(0.5)))
+            }
+        };
+        Ok(material)
+    }
+
+    fn inner_bsdf(&mut self, e: &Element) -> Result<MaterialDescription, SceneError> {
+        match self.material(e)? {
+            Some(MaterialRef::Inline(material)) => Ok(*material),
+            Some(MaterialRef::Named(id)) => Ok(self.scene.materials[&id].clone()),
+            None => Ok(diffuse(Color::gray(0.5))),
+        }
+    }
+
+    //bsdf child, or reference to one of the scene
+    fn material(&mut self, e: &Element) -> Result<Option<MaterialRef>, SceneError> {
+        for child in e.children.iter() {
+            match child.name.as_str() {
+                "bsdf" => return Ok(Some(MaterialRef::Inline(Box::new(self.bsdf(child)?)))),
+                "ref" => {
+                    let id = self.value(child, "id").unwrap_or_default();
+                    if self.scene.materials.contains_key(&id) {
+                        return Ok(Some(MaterialRef::Named(id)));
+                    }
+                    self.warn(&child.at, format!("there is no material with the id {:?}", id));
+                }
+                _ => (),
+            }
+        }
+        Ok(None)
+    }
+
+    fn file(&mut self, e: &Element, read: fn(&Path) -> Result<Mesh, String>) -> Result<Primitive, SceneError> {
+        let file = self.string(e, "filename").ok_or_else(|| SceneError::MissingElement {
+            location: e.at.clone(),
+            element: String::from("filename"),
+        })?;
+        let mesh = read(&self.base_dir.join(file)).map_err(|message| SceneError::Io {
+            location: Some(e.at.clone()),
+            message,
+        })?;
+        Ok(Primitive::Mesh(Rc::new(mesh)))
+    }
+
+    fn shape(&mut self, e: &Element) -> Result<(), SceneError> {
+        let v = |x, y, z| Vec3d { x, y, z };
+        let primitive = match e.kind() {
+            "sphere" => Primitive::Sphere {
+                center: match e.property("center") {
+                    Some(center) => self.vector(center, 0.)?,
+                    None => v(0., 0., 0.),
+                },
+                radius: self.float(e, "radius")?.unwrap_or(1.),
+            },
+            //square from -1 to 1 in the xy plane
+            "rectangle" => {
+                let points = vec![v(-1., -1., 0.), v(1., -1., 0.), v(1., 1., 0.), v(-1., 1., 0.)];
+                let uvs = vec![(0., 0.), (1., 0.), (1., 1.), (0., 1.)];
+                Primitive::Mesh(Rc::new(Mesh::new(points, uvs, vec![[0, 1, 2], [0, 2, 3]]).unwrap()))
+            }
+            //from -1 to 1 along each axis
+            "cube" => {
+                let points = (0..8)
+                    .map(|i| v(if i & 1 == 0 { -1. } else { 1. }, if i & 2 == 0 { -1. } else { 1. }, if i & 4 == 0 { -1. } else { 1. }))
+                    .collect();
+                let mut mesh = Mesh::new(points, vec![], vec![]).unwrap();
+                for face in [[0, 2, 3, 1], [4, 5, 7, 6], [0, 1, 5, 4], [2, 6, 7, 3], [0, 4, 6, 2], [1, 3, 7, 5]] {
+                    mesh.add_polygon(&face);
+                }
+                Primitive::Mesh(Rc::new(mesh))
+            }
+            "ply" => self.file(e, ply::read)?,
+            "obj" => self.file(e, obj::read)?,
+            kind => {
+                self.warn(&e.at, format!("{} shapes are not imported", kind));
+                return Ok(());
+            }
+        };
+        let to_world = self.world_transform(e)?;
+        let (shape, warning) = place(&primitive, &to_world);
+        if let Some(warning) = warning {
+            self.warn(&e.at, warning);
+        }
+        if let Some(emitter) = e.children.iter().find(|c| c.name == "emitter") {
+            return self.emitter(emitter, Some(&shape));
+        }
+        let material = self.material(e)?.unwrap_or(MaterialRef::Inline(Box::new(diffuse(Color::gray(0.5)))));
+        self.scene.objects.push(ObjectDescription::new(shape, Some(material)));
+        Ok(())
+    }
+
+    //emitters of a shape are area lights, and replace it
+    fn emitter(&mut self, e: &Element, shape: Option<&Shape>) -> Result<(), SceneError> {
+        let scale = self.float(e, "scale")?.unwrap_or(1.);
+        match (e.kind(), shape) {
+            ("area", Some(shape)) => {
+                let radiance = self.color(e, "radiance")?.unwrap_or(Color::gray(1.)) * scale;
+                if let Some(light) = area_light(shape, radiance) {
+                    self.warn(&e.at, String::from("the area light is replaced by a point light at the center of its shape"));
+                    self.scene.lights.push(light);
+                }
+            }
+            ("point", None) => {
+                let pos = match e.property("position") {
+                    Some(position) => self.vector(position, 0.)?,
+                    None => self.world_transform(e)?.transform_point(Vec3d { x: 0., y: 0., z: 0. }),
+                };
+                let intensity = self.color(e, "intensity")?.unwrap_or(Color::gray(1.));
+                let (color, intensity) = light_color(intensity, scale);
+                self.scene.lights.push(LightDescription {
+                    name: self.value(e, "id"),
+                    pos,
+                    color,
+                    intensity,
+                });
+            }
+            ("constant", None) => {
+                self.ambiant = self.ambiant + self.color(e, "radiance")?.unwrap_or(Color::gray(1.)) * scale;
+            }
+            ("envmap", None) => {
+                self.warn(&e.at, String::from("the environment map is not imported, the light is uniform"));
+                self.ambiant = self.ambiant + Color::gray(scale);
+            }
+            (kind, _) => self.warn(&e.at, format!("{} lights are not imported", kind)),
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> (SceneDescription, Vec<Warning>) {
+        //default sensor, at the origin looking along z
+        let camera = self.camera.take().unwrap_or(ImportedCamera {
+            to_world: Matrix4::identity(),
+            right: Vec3d { x: -1., y: 0., z: 0. },
+            resolution: (768, 576),
+            half_width: 1.,
+        });
+        let (camera, mirrored) = camera.description();
+        self.scene.camera = camera;
+        let (color, intensity) = light_color(self.ambiant, 1.);
+        self.scene.ambiant_light.color = color;
+        self.scene.ambiant_light.intensity = intensity;
+        if mirrored {
+            mirror(&mut self.scene);
+        }
+        (self.scene, self.warnings)
+    }
+}
+
+//Scene of a Mitsuba file, whose meshes are found from base_dir
+pub fn read(content: &str, base_dir: &Path) -> Result<(SceneDescription, Vec<Warning>), SceneError> {
+    let root = parse(content, None)?;
+    let Some(version) = root.attribute("version").filter(|_| root.name == "scene") else {
+        return Err(SceneError::Decode {
+            location: Some(root.at.clone()),
+            format: "Mitsuba scene",
+            message: String::from("the root is not a <scene version=\"...\">"),
+        });
+    };
+    let mut importer = Importer {
+        base_dir,
+        scene: SceneDescription::default(),
+        warnings: vec![],
+        defaults: HashMap::new(),
+        version: version.split('.').next().and_then(|v| v.parse().ok()).unwrap_or(3),
+        camera: None,
+        ambiant: Color::gray(0.),
+    };
+    importer.defaults(&root)?;
+    importer.read(&root)?;
+    Ok(importer.finish())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    fn import(content: &str) -> (SceneDescription, Vec<Warning>) {
+        read(content, Path::new(".")).unwrap()
+    }
+
+    fn messages(warnings: &[Warning]) -> Vec<String> {
+        warnings.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn detect() {
+        assert!(is_mitsuba("<?xml version=\"1.0\"?>\n<scene version=\"3.0.0\"></scene>"));
+        assert!(!is_mitsuba("<scene cull_back_faces=\"true\"><camera/></scene>"));
+        assert!(!is_mitsuba("not xml"));
+    }
+
+    #[test]
+    fn sensor_and_emitters() {
+        let (scene, warnings) = import(
+            "<scene version=\"3.0.0\">
+                <default name=\"res\" value=\"100\"/>
+                <integrator type=\"path\"/>
+                <sensor type=\"perspective\">
+                    <float name=\"fov\" value=\"90\"/>
+                    <string name=\"fov_axis\" value=\"y\"/>
+                    <transform name=\"to_world\">
+                        <lookat origin=\"0, 0, 0\" target=\"1, 0, 0\" up=\"0, 0, 1\"/>
+                    </transform>
+                    <film type=\"hdrfilm\">
+                        <integer name=\"width\" value=\"200\"/>
+                        <integer name=\"height\" value=\"$res\"/>
+                    </film>
+                </sensor>
+                <emitter type=\"point\">
+                    <point name=\"position\" x=\"1\" y=\"2\" z=\"3\"/>
+                    <rgb name=\"intensity\" value=\"4, 2, 2\"/>
+                </emitter>
+                <emitter type=\"constant\"><spectrum name=\"radiance\" value=\"0.5\"/></emitter>
+                <emitter type=\"directional\"/>
+            </scene>",
+        );
+        assert_eq!(scene.camera.resolution, Some((200, 100)));
+        assert_abs_diff_eq!(scene.camera.fov.unwrap(), 720. / PI, epsilon = 1e-9);
+        //the scene is mirrored for the image to be seen the same way
+        assert_abs_diff_eq!(scene.camera.dir, Vec3d { x: -1., y: 0., z: 0. });
+        assert_eq!(scene.lights[0].pos, Vec3d { x: -1., y: 2., z: 3. });
+        assert_eq!(scene.lights[0].intensity, 4.);
+        assert_eq!(scene.ambiant_light.intensity, 0.5);
+        assert_eq!(messages(&warnings), vec!["line 20, column 17 (scene/emitter): directional lights are not imported"]);
+    }
+
+    #[test]
+    fn shapes_and_materials() {
+        let (scene, warnings) = import(
+            "<scene version=\"0.6.0\">
+                <bsdf type=\"twosided\" id=\"red\">
+                    <bsdf type=\"diffuse\"><rgb name=\"reflectance\" value=\"0.8 0.1 0.1\"/></bsdf>
+                </bsdf>
+                <shape type=\"sphere\">
+                    <point name=\"center\" value=\"0, 0, 1\"/>
+                    <float name=\"radius\" value=\"0.5\"/>
+                    <transform name=\"toWorld\"><scale value=\"2\"/><translate x=\"1\"/></transform>
+                    <bsdf type=\"roughconductor\"><float name=\"alpha\" value=\"0.25\"/></bsdf>
+                </shape>
+                <shape type=\"rectangle\">
+                    <ref id=\"red\"/>
+                </shape>
+                <shape type=\"cube\">
+                    <bsdf type=\"dielectric\"><string name=\"int_ior\" value=\"water\"/><float name=\"ext_ior\" value=\"1\"/></bsdf>
+                </shape>
+                <shape type=\"rectangle\">
+                    <transform name=\"to_world\"><translate z=\"4\"/></transform>
+                    <emitter type=\"area\"><rgb name=\"radiance\" value=\"1\"/></emitter>
+                </shape>
+                <shape type=\"cylinder\"/>
+            </scene>",
+        );
+        let Shape::Sphere { pos, radius, .. } = scene.objects[0].shape else { panic!("not a sphere") };
+        //mirrored along x
+        assert_eq!((pos, radius), (Vec3d { x: -1., y: 0., z: 2. }, 1.));
+        let Some(MaterialRef::Inline(copper)) = &scene.objects[0].material else { panic!("no inline material") };
+        assert_eq!(**copper, conductor(metal("Cu").unwrap(), 0.5));
+        assert_eq!(scene.materials["red"], diffuse(Color { r: 0.8, g: 0.1, b: 0.1 }));
+        assert_eq!(scene.objects[1].material, Some(MaterialRef::Named(String::from("red"))));
+        let Shape::Mesh { faces, .. } = &scene.objects[2].shape else { panic!("not a mesh") };
+        assert_eq!(faces.len(), 12);
+        let Some(MaterialRef::Inline(water)) = &scene.objects[2].material else { panic!("no inline material") };
+        assert_eq!(**water, dielectric(1.333, 0.));
+        assert_eq!(scene.objects.len(), 3);
+        assert_abs_diff_eq!(scene.lights[0].pos, Vec3d { x: 0., y: 0., z: 4. });
+        assert_eq!(scene.lights[0].intensity, 4.);
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[1].to_string(), "line 21, column 17 (scene/shape): cylinder shapes are not imported");
+    }
+
+    #[test]
+    fn import_errors() {
+        let error = |content: &str| read(content, Path::new(".")).err().unwrap().to_string();
+        assert!(error("<scene><shape type=\"sphere\"/></scene>").contains("<scene version"));
+        assert!(error("<scene version=\"3.0.0\"><shape type=\"sphere\"><float name=\"radius\" value=\"big\"/></shape></scene>").contains("big"));
+        assert!(error("<scene version=\"3.0.0\"><shape type=\"ply\"><string name=\"filename\" value=\"missing.ply\"/></shape></scene>").contains("missing.ply"));
+        assert!(error("<scene version=\"3.0.0\"><shape type=\"sphere\">").starts_with("line 1"));
+    }
+}
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use super::Mesh;
+use crate::coord::Vec3d;
+
+//Wavefront object files: vertices, texture coordinates and faces, the other
+//statements (normals, groups, materials) are skipped
+
+pub fn read(path: &Path) -> Result<Mesh, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    parse(&content).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn parse(content: &str) -> Result<Mesh, String> {
+    let mut positions: Vec<Vec3d> = Vec::new();
+    let mut coordinates: Vec<(f64, f64)> = Vec::new();
+    //a point for each different position and texture coordinate used
+    let mut mesh = Mesh::default();
+    let mut points: HashMap<(usize, Option<usize>), usize> = HashMap::new();
+    for (n, line) in content.lines().enumerate() {
+        let error = |message: String| format!("line {}: {}", n + 1, message);
+        let mut words = line.split_whitespace();
+        let numbers = |words: std::str::SplitWhitespace| -> Result<Vec<f64>, String> {
+            words.map(|w| w.parse::<f64>().map_err(|e| error(format!("{:?}: {}", w, e)))).collect()
+        };
+        match words.next() {
+            Some("v") => match numbers(words)?[..] {
+                [x, y, z, ..] => positions.push(Vec3d { x, y, z }),
+                _ => return Err(error(String::from("a vertex needs 3 coordinates"))),
+            },
+            Some("vt") => match numbers(words)?[..] {
+                [u, v, ..] => coordinates.push((u, v)),
+                [u] => coordinates.push((u, 0.)),
+                _ => return Err(error(String::from("a texture coordinate needs 2 values"))),
+            },
+            Some("f") => {
+                let mut polygon = Vec::new();
+                for word in words {
+                    //v, v/vt, v//vn or v/vt/vn, counted from 1 or from the end
+                    let mut indices = word.split('/');
+                    let index = |i: Option<&str>, count: usize| -> Result<Option<usize>, String> {
+                        let Some(i) = i.filter(|i| !i.is_empty()) else {
+                            return Ok(None);
+                        };
+                        let i = i.parse::<i64>().map_err(|e| error(format!("{:?}: {}", word, e)))?;
+                        let index = if i < 0 { count as i64 + i } else { i - 1 };
+                        match (0..count as i64).contains(&index) {
+                            true => Ok(Some(index as usize)),
+                            false => Err(error(format!("{:?} refers to nothing", word))),
+                        }
+                    };
+                    let position = index(indices.next(), positions.len())?.ok_or_else(|| error(format!("{:?} has no vertex", word)))?;
+                    let coordinate = index(indices.next(), coordinates.len())?;
+                    let next = points.len();
+                    polygon.push(*points.entry((position, coordinate)).or_insert(next));
+                }
+                mesh.add_polygon(&polygon);
+            }
+            _ => (),
+        }
+    }
+
+    let mut used: Vec<(usize, Option<usize>)> = vec![(0, None); points.len()];
+    for (key, i) in points {
+        used[i] = key;
+    }
+    let textured = used.iter().all(|(_, c)| c.is_some());
+    let uvs = match textured {
+        true => used.iter().map(|(_, c)| coordinates[c.unwrap()]).collect(),
+        false => vec![],
+    };
+    let points = used.iter().map(|(p, _)| positions[*p]).collect();
+    Mesh::new(points, uvs, mesh.triangles)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn square() {
+        let content = "# a square\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nvn 0 0 1\nf 1/1/1 2/2/1 3/3/1 4/4/1\n";
+        let mesh = parse(content).unwrap();
+        assert_eq!(mesh.points.len(), 4);
+        assert_eq!(mesh.uvs[2], (1., 1.));
+        assert_eq!(mesh.triangles, vec![[0, 1, 2], [0, 2, 3]]);
+
+        //relative indices, without texture coordinates
+        let mesh = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\n").unwrap();
+        assert_eq!(mesh.points[2], Vec3d { x: 0., y: 1., z: 0. });
+        assert!(mesh.uvs.is_empty());
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(parse("v 0 0 0\nf 1 2 3\n"), Err(String::from("line 2: \"2\" refers to nothing")));
+        assert!(parse("v 0 zero 0\n").is_err());
+    }
+}
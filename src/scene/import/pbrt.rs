@@ -0,0 +1,932 @@
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+use super::{
+    area_light, conductor, conductor_color, dielectric, diffuse, light_color, look_at, metal, mirror, place, ply,
+    ImportedCamera, Mesh, Primitive,
+};
+use crate::coord::{Matrix4, Vec3d};
+use crate::scene::description::{LightDescription, MaterialDescription, MaterialRef, ObjectDescription, SceneDescription};
+use crate::scene::error::{Location, SceneError};
+use crate::scene::object::material::Color;
+use crate::scene::validate::Warning;
+
+//pbrt-v3 and pbrt-v4 scene files: a list of directives, each one followed by
+//its arguments and its parameters ("float radius" [2])
+
+#[derive(Debug, PartialEq, Clone)]
+enum Token {
+    Directive(String),
+    Text(String),
+    Number(f64),
+    Open,
+    Close,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Arg {
+    Number(f64),
+    Text(String),
+    List(Vec<Arg>),
+}
+
+//Tokens of a file, with the place of the last one
+struct Tokens<'a> {
+    content: &'a str,
+    file: Option<Box<str>>,
+    pos: usize,
+    line: u32,
+    line_start: usize,
+    //start of the last token read
+    start: (usize, u32, usize),
+    peeked: Option<Token>,
+}
+
+struct Directive {
+    name: String,
+    at: Location,
+    args: Vec<Arg>,
+}
+
+//"type name" value
+struct Param {
+    kind: String,
+    name: String,
+    values: Vec<Arg>,
+}
+
+struct Params(Vec<Param>);
+
+//graphics state, saved by AttributeBegin
+#[derive(Clone)]
+struct State {
+    ctm: Matrix4,
+    material: MaterialRef,
+    area_light: Option<Color>,
+}
+
+//shape of an object instance, placed with the transform of the instance
+struct Instanced {
+    primitive: Primitive,
+    to_world: Matrix4,
+    material: MaterialRef,
+    area_light: Option<Color>,
+    at: Location,
+}
+
+struct Importer<'a> {
+    base_dir: &'a Path,
+    scene: SceneDescription,
+    warnings: Vec<Warning>,
+    state: State,
+    //saved states, true for the ones of TransformBegin that only keep the transform
+    stack: Vec<(State, bool)>,
+    coordinate_systems: HashMap<String, Matrix4>,
+    camera_to_world: Matrix4,
+    fov: f64,
+    resolution: (u32, u32),
+    ambiant: Color,
+    instances: HashMap<String, Vec<Instanced>>,
+    //object being defined
+    object: Option<(String, Vec<Instanced>)>,
+}
+
+impl Tokens<'_> {
+    fn new(content: &str, file: Option<Box<str>>) -> Tokens<'_> {
+        Tokens {
+            content,
+            file,
+            pos: 0,
+            line: 1,
+            line_start: 0,
+            start: (0, 1, 0),
+            peeked: None,
+        }
+    }
+
+    //location of the last token read
+    fn location(&self, path: String) -> Location {
+        let (start, line, line_start) = self.start;
+        Location {
+            file: self.file.clone(),
+            line,
+            column: self.content[line_start..start].chars().count() as u32 + 1,
+            path,
+        }
+    }
+
+    fn syntax(&self, message: String) -> SceneError {
+        syntax(&self.location(String::new()), message)
+    }
+
+    fn newline(&mut self, at: usize) {
+        self.line += 1;
+        self.line_start = at + 1;
+    }
+
+    //spaces and comments
+    fn skip_blank(&mut self) {
+        let bytes = self.content.as_bytes();
+        while self.pos < bytes.len() {
+            match bytes[self.pos] {
+                b'\n' => self.newline(self.pos),
+                b'#' => {
+                    while self.pos + 1 < bytes.len() && bytes[self.pos + 1] != b'\n' {
+                        self.pos += 1;
+                    }
+                }
+                b if b.is_ascii_whitespace() => (),
+                _ => return,
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn next(&mut self) -> Result<Option<Token>, SceneError> {
+        if let Some(token) = self.peeked.take() {
+            return Ok(Some(token));
+        }
+        self.skip_blank();
+        let bytes = self.content.as_bytes();
+        if self.pos >= bytes.len() {
+            return Ok(None);
+        }
+        self.start = (self.pos, self.line, self.line_start);
+        let token = match bytes[self.pos] {
+            b'[' => {
+                self.pos += 1;
+                Token::Open
+            }
+            b']' => {
+                self.pos += 1;
+                Token::Close
+            }
+            b'"' => {
+                let mut text = String::new();
+                let mut chars = self.content[self.pos + 1..].char_indices();
+                loop {
+                    match chars.next() {
+                        None => return Err(self.syntax(String::from("unterminated string"))),
+                        Some((i, '"')) => {
+                            self.pos += i + 2;
+                            break;
+                        }
+                        Some((i, '\n')) => {
+                            self.newline(self.pos + 1 + i);
+                            text.push('\n');
+                        }
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, 'n')) => text.push('\n'),
+                            Some((_, 't')) => text.push('\t'),
+                            Some((_, c)) => text.push(c),
+                            None => return Err(self.syntax(String::from("unterminated string"))),
+                        },
+                        Some((_, c)) => text.push(c),
+                    }
+                }
+                Token::Text(text)
+            }
+            _ => {
+                let rest = &self.content[self.pos..];
+                let len = rest
+                    .find(|c: char| c.is_ascii_whitespace() || "[]\"#".contains(c))
+                    .unwrap_or(rest.len());
+                let word = &rest[..len];
+                self.pos += len;
+                match word.parse::<f64>() {
+                    Ok(n) => Token::Number(n),
+                    //pbrt-v4 booleans are not quoted
+                    Err(_) if word == "true" || word == "false" => Token::Text(String::from(word)),
+                    Err(_) if word.starts_with(|c: char| c.is_ascii_alphabetic()) => Token::Directive(String::from(word)),
+                    Err(_) => return Err(self.syntax(format!("unexpected {:?}", word))),
+                }
+            }
+        };
+        Ok(Some(token))
+    }
+
+    //next directive with all its arguments
+    fn directive(&mut self) -> Result<Option<Directive>, SceneError> {
+        let name = match self.next()? {
+            None => return Ok(None),
+            Some(Token::Directive(name)) => name,
+            Some(_) => return Err(self.syntax(String::from("a directive is expected"))),
+        };
+        let at = self.location(name.clone());
+        let mut args = Vec::new();
+        loop {
+            match self.next()? {
+                None => break,
+                Some(Token::Directive(next)) => {
+                    self.peeked = Some(Token::Directive(next));
+                    break;
+                }
+                Some(Token::Number(n)) => args.push(Arg::Number(n)),
+                Some(Token::Text(s)) => args.push(Arg::Text(s)),
+                Some(Token::Open) => {
+                    let mut list = Vec::new();
+                    loop {
+                        match self.next()? {
+                            Some(Token::Number(n)) => list.push(Arg::Number(n)),
+                            Some(Token::Text(s)) => list.push(Arg::Text(s)),
+                            Some(Token::Close) => break,
+                            _ => return Err(self.syntax(String::from("unclosed ["))),
+                        }
+                    }
+                    args.push(Arg::List(list));
+                }
+                Some(Token::Close) => return Err(self.syntax(String::from("unexpected ]"))),
+            }
+        }
+        Ok(Some(Directive { name, at, args }))
+    }
+}
+
+fn syntax(at: &Location, message: String) -> SceneError {
+    SceneError::Decode {
+        location: Some(at.clone()),
+        format: "pbrt",
+        message,
+    }
+}
+
+impl Directive {
+    //arguments before the parameters, all numbers
+    fn numbers(&self, count: usize) -> Result<Vec<f64>, SceneError> {
+        let mut numbers = Vec::new();
+        for arg in self.args.iter() {
+            match arg {
+                Arg::Number(n) => numbers.push(*n),
+                Arg::List(list) => numbers.extend(list.iter().filter_map(|a| match a {
+                    Arg::Number(n) => Some(*n),
+                    _ => None,
+                })),
+                Arg::Text(_) => break,
+            }
+        }
+        match numbers.len() == count {
+            true => Ok(numbers),
+            false => Err(syntax(&self.at, format!("{} needs {} numbers, {} given", self.name, count, numbers.len()))),
+        }
+    }
+
+    //name and parameters (Shape "sphere" "float radius" 1)
+    fn named(&self) -> Result<(&str, Params), SceneError> {
+        match self.args.first() {
+            Some(Arg::Text(name)) => Ok((name, Params::new(&self.args[1..], &self.at)?)),
+            _ => Err(syntax(&self.at, format!("{} needs a name", self.name))),
+        }
+    }
+}
+
+impl Params {
+    fn new(args: &[Arg], at: &Location) -> Result<Params, SceneError> {
+        let mut params = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let Arg::Text(declaration) = arg else {
+                return Err(syntax(at, String::from("a parameter declaration is expected")));
+            };
+            let words: Vec<&str> = declaration.split_whitespace().collect();
+            let [kind, name] = words[..] else {
+                return Err(syntax(at, format!("{:?} is not a parameter declaration", declaration)));
+            };
+            let values = match args.next() {
+                Some(Arg::List(values)) => values.clone(),
+                Some(value) => vec![value.clone()],
+                None => return Err(syntax(at, format!("{:?} has no value", declaration))),
+            };
+            let numbers = values.iter().all(|v| matches!(v, Arg::Number(_)));
+            let texts = values.iter().all(|v| matches!(v, Arg::Text(_)));
+            let size = match kind {
+                "rgb" | "color" | "point" | "point3" | "vector" | "vector3" | "normal" | "normal3" => 3,
+                "point2" | "vector2" => 2,
+                _ => 1,
+            };
+            let valid = match kind {
+                "string" | "texture" | "bool" => texts,
+                "spectrum" => numbers || texts,
+                _ => numbers && values.len().is_multiple_of(size),
+            };
+            if !valid {
+                return Err(SceneError::InvalidValue {
+                    location: at.clone(),
+                    attribute: String::from(name),
+                    value: declaration.clone(),
+                    message: format!("the values do not fit the {} type", kind),
+                });
+            }
+            params.push(Param {
+                kind: String::from(kind),
+                name: String::from(name),
+                values,
+            });
+        }
+        Ok(Params(params))
+    }
+
+    fn get(&self, name: &str) -> Option<&Param> {
+        self.0.iter().rev().find(|p| p.name == name)
+    }
+
+    fn floats(&self, name: &str) -> Option<Vec<f64>> {
+        let param = self.get(name)?;
+        param
+            .values
+            .iter()
+            .map(|v| match v {
+                Arg::Number(n) => Some(*n),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn float(&self, name: &str) -> Option<f64> {
+        self.floats(name)?.first().copied()
+    }
+
+    fn string(&self, name: &str) -> Option<&str> {
+        match self.get(name)?.values.first()? {
+            Arg::Text(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn points(&self, name: &str) -> Option<Vec<Vec3d>> {
+        let floats = self.floats(name)?;
+        Some(floats.chunks_exact(3).map(|c| Vec3d { x: c[0], y: c[1], z: c[2] }).collect())
+    }
+
+    fn point(&self, name: &str) -> Option<Vec3d> {
+        self.points(name)?.first().copied()
+    }
+
+    //None for textures, named spectra and blackbodies are white
+    fn color(&self, name: &str) -> Option<Color> {
+        let param = self.get(name)?;
+        match param.kind.as_str() {
+            "texture" => None,
+            "spectrum" => match self.floats(name) {
+                //wavelength and value pairs
+                Some(pairs) if pairs.len() >= 2 => {
+                    let values: Vec<f64> = pairs.iter().skip(1).step_by(2).copied().collect();
+                    Some(Color::gray(values.iter().sum::<f64>() / values.len() as f64))
+                }
+                _ => Some(Color::gray(1.)),
+            },
+            "blackbody" => Some(Color::gray(1.)),
+            _ => match self.floats(name)?[..] {
+                [r, g, b] => Some(Color { r, g, b }),
+                [v] => Some(Color::gray(v)),
+                _ => None,
+            },
+        }
+    }
+}
+
+//index of refraction of the glasses pbrt-v4 knows by name
+fn named_ior(name: &str) -> Option<f64> {
+    match name {
+        "glass-BK7" => Some(1.5168),
+        "glass-BAF10" => Some(1.67),
+        "glass-FK51A" => Some(1.4866),
+        "glass-LASF9" => Some(1.85),
+        "glass-F5" => Some(1.6727),
+        "glass-F10" => Some(1.728),
+        "glass-F11" => Some(1.785),
+        _ => None,
+    }
+}
+
+impl Importer<'_> {
+    fn warn(&mut self, at: &Location, message: String) {
+        self.warnings.push(Warning {
+            location: at.clone(),
+            message,
+        });
+    }
+
+    fn read(&mut self, tokens: &mut Tokens) -> Result<(), SceneError> {
+        while let Some(d) = tokens.directive()? {
+            self.directive(&d)?;
+        }
+        Ok(())
+    }
+
+    fn transform(&mut self, m: Matrix4) {
+        self.state.ctm = self.state.ctm * m;
+    }
+
+    fn directive(&mut self, d: &Directive) -> Result<(), SceneError> {
+        let at = &d.at;
+        match d.name.as_str() {
+            "Identity" => self.state.ctm = Matrix4::identity(),
+            "Translate" => {
+                let v = d.numbers(3)?;
+                self.transform(Matrix4::translation(Vec3d { x: v[0], y: v[1], z: v[2] }));
+            }
+            "Scale" => {
+                let v = d.numbers(3)?;
+                self.transform(Matrix4::scaling(Vec3d { x: v[0], y: v[1], z: v[2] }));
+            }
+            "Rotate" => {
+                let v = d.numbers(4)?;
+                let axis = Vec3d { x: v[1], y: v[2], z: v[3] };
+                if axis.normalize().is_none() {
+                    return Err(syntax(at, String::from("the rotation axis cannot be null")));
+                }
+                self.transform(Matrix4::rotation(axis, v[0].to_radians()));
+            }
+            "LookAt" => {
+                let v = d.numbers(9)?;
+                let point = |i: usize| Vec3d { x: v[i], y: v[i + 1], z: v[i + 2] };
+                let camera = look_at(point(0), point(3), point(6))
+                    .ok_or_else(|| syntax(at, String::from("the up vector is along the direction")))?;
+                self.transform(camera.inverse().expect("a frame can be inverted"));
+            }
+            "Transform" | "ConcatTransform" => {
+                //pbrt matrices are written column by column
+                let v = d.numbers(16)?;
+                let mut m = Matrix4::identity();
+                for (i, value) in v.iter().enumerate() {
+                    m.m[i % 4][i / 4] = *value;
+                }
+                match d.name.as_str() {
+                    "Transform" => self.state.ctm = m,
+                    _ => self.transform(m),
+                }
+            }
+            "CoordinateSystem" | "CoordSysTransform" => {
+                let (name, _) = d.named()?;
+                match (d.name.as_str(), self.coordinate_systems.get(name)) {
+                    ("CoordinateSystem", _) => {
+                        self.coordinate_systems.insert(String::from(name), self.state.ctm);
+                    }
+                    (_, Some(m)) => self.state.ctm = *m,
+                    (_, None) => self.warn(at, format!("there is no coordinate system named {:?}", name)),
+                }
+            }
+            "Camera" => {
+                let (kind, params) = d.named()?;
+                if kind != "perspective" {
+                    self.warn(at, format!("{} cameras are imported as perspective cameras", kind));
+                }
+                if params.float("lensradius").is_some_and(|r| r > 0.) {
+                    self.warn(at, String::from("depth of field is not imported"));
+                }
+                self.fov = params.float("fov").unwrap_or(90.);
+                self.camera_to_world = self
+                    .state
+                    .ctm
+                    .inverse()
+                    .ok_or_else(|| syntax(at, String::from("the camera transform cannot be inverted")))?;
+                self.coordinate_systems.insert(String::from("camera"), self.camera_to_world);
+            }
+            "Film" => {
+                let (_, params) = d.named()?;
+                let size = |name: &str, default: u32| params.float(name).map_or(default, |v| v as u32);
+                self.resolution = (size("xresolution", self.resolution.0), size("yresolution", self.resolution.1));
+            }
+            "WorldBegin" => {
+                self.state.ctm = Matrix4::identity();
+                self.coordinate_systems.insert(String::from("world"), Matrix4::identity());
+            }
+            "AttributeBegin" | "TransformBegin" => self.stack.push((self.state.clone(), d.name == "TransformBegin")),
+            "AttributeEnd" | "TransformEnd" => match self.stack.pop() {
+                Some((state, true)) => self.state.ctm = state.ctm,
+                Some((state, false)) => self.state = state,
+                None => return Err(syntax(at, format!("{} without a matching begin", d.name))),
+            },
+            "Material" | "MakeNamedMaterial" => {
+                let (name, params) = d.named()?;
+                match d.name.as_str() {
+                    "Material" => {
+                        let material = self.material(name, &params, at);
+                        self.state.material = MaterialRef::Inline(Box::new(material));
+                    }
+                    _ => {
+                        let kind = params
+                            .string("type")
+                            .ok_or_else(|| syntax(at, format!("the material {:?} has no type", name)))?;
+                        let material = self.material(kind, &params, at);
+                        self.scene.materials.insert(String::from(name), material);
+                    }
+                }
+            }
+            "NamedMaterial" => {
+                let (name, _) = d.named()?;
+                match self.scene.materials.contains_key(name) {
+                    true => self.state.material = MaterialRef::Named(String::from(name)),
+                    false => self.warn(at, format!("there is no material named {:?}", name)),
+                }
+            }
+            "Texture" => {
+                let (name, _) = d.named()?;
+                self.warn(at, format!("the texture {:?} is not imported, the materials using it take their default color", name));
+            }
+            "LightSource" => self.light(d)?,
+            "AreaLightSource" => {
+                let (kind, params) = d.named()?;
+                match kind {
+                    "diffuse" => {
+                        let radiance = params.color("L").unwrap_or(Color::gray(1.));
+                        self.state.area_light = Some(radiance * params.float("scale").unwrap_or(1.));
+                    }
+                    _ => self.warn(at, format!("{} area lights are not imported", kind)),
+                }
+            }
+            "Shape" => self.shape(d)?,
+            "ObjectBegin" => {
+                let (name, _) = d.named()?;
+                self.stack.push((self.state.clone(), false));
+                self.object = Some((String::from(name), vec![]));
+            }
+            "ObjectEnd" => {
+                let (name, shapes) = self
+                    .object
+                    .take()
+                    .ok_or_else(|| syntax(at, String::from("ObjectEnd without a matching ObjectBegin")))?;
+                self.instances.insert(name, shapes);
+                if let Some((state, _)) = self.stack.pop() {
+                    self.state = state;
+                }
+            }
+            "ObjectInstance" => {
+                let (name, _) = d.named()?;
+                let Some(shapes) = self.instances.remove(name) else {
+                    self.warn(at, format!("there is no object named {:?}", name));
+                    return Ok(());
+                };
+                for s in shapes.iter() {
+                    let to_world = self.state.ctm * s.to_world;
+                    self.emit(&s.primitive, &to_world, s.material.clone(), s.area_light, &s.at);
+                }
+                self.instances.insert(String::from(name), shapes);
+            }
+            "Include" | "Import" => {
+                let (file, _) = d.named()?;
+                let path = self.base_dir.join(file);
+                let content = fs::read_to_string(&path).map_err(|e| SceneError::Io {
+                    location: Some(at.clone()),
+                    message: format!("{}: {}", path.display(), e),
+                })?;
+                self.read(&mut Tokens::new(&content, Some(Box::from(file))))?;
+            }
+            "MakeNamedMedium" | "MediumInterface" => self.warn(at, String::from("participating media are not imported")),
+            "ActiveTransform" | "TransformTimes" => self.warn(at, String::from("motion blur is not imported")),
+            //settings of the renderer, this crate has its own, and
+            //orientations that double-sided meshes do not need
+            "Sampler" | "PixelFilter" | "Accelerator" | "Integrator" | "SurfaceIntegrator" | "VolumeIntegrator"
+            | "Renderer" | "ColorSpace" | "Option" | "ReverseOrientation" | "WorldEnd" => (),
+            name => self.warn(at, format!("{} is not imported", name)),
+        }
+        Ok(())
+    }
+
+    //the name of a material is the one of pbrt-v4, or of pbrt-v3
+    fn material(&mut self, kind: &str, params: &Params, at: &Location) -> MaterialDescription {
+        let reflectance = || params.color("reflectance").or_else(|| params.color("Kd"));
+        let roughness = params.float("roughness").or_else(|| {
+            let (u, v) = (params.float("uroughness")?, params.float("vroughness")?);
+            Some((u + v) / 2.)
+        });
+        match kind {
+            "diffuse" | "matte" => diffuse(reflectance().unwrap_or(Color::gray(0.5))),
+            "conductor" | "metal" => {
+                let named = |name: &str| {
+                    let spectrum = params.get(name).filter(|p| p.kind == "spectrum")?;
+                    match spectrum.values.first()? {
+                        Arg::Text(s) => s.strip_prefix("metal-").and_then(|s| s.split('-').next()).and_then(metal),
+                        _ => None,
+                    }
+                };
+                let color = params
+                    .color("reflectance")
+                    .or_else(|| named("eta"))
+                    .or_else(|| match (params.get("eta")?.kind.as_str(), params.get("k")?.kind.as_str()) {
+                        ("rgb" | "color", "rgb" | "color") => Some(conductor_color(params.color("eta")?, params.color("k")?)),
+                        _ => None,
+                    })
+                    .or_else(|| metal("Cu"))
+                    .unwrap();
+                conductor(color, roughness.unwrap_or(0.))
+            }
+            "mirror" => conductor(params.color("Kr").unwrap_or(Color::gray(0.9)), 0.),
+            "dielectric" | "glass" | "thindielectric" => {
+                let ior = params
+                    .float("eta")
+                    .or_else(|| params.float("index"))
+                    .or_else(|| named_ior(params.string("eta")?))
+                    .unwrap_or(1.5);
+                dielectric(ior, roughness.unwrap_or(0.))
+            }
+            _ => {
+                self.warn(at, format!("{} materials are imported as diffuse ones", kind));
+                diffuse(reflectance().unwrap_or(Color::gray(0.5)))
+            }
+        }
+    }
+
+    fn light(&mut self, d: &Directive) -> Result<(), SceneError> {
+        let (kind, params) = d.named()?;
+        let scale = params.float("scale").unwrap_or(1.);
+        match kind {
+            "point" => {
+                let from = params.point("from").unwrap_or(Vec3d { x: 0., y: 0., z: 0. });
+                let (color, intensity) = light_color(params.color("I").unwrap_or(Color::gray(1.)), scale);
+                self.scene.lights.push(LightDescription {
+                    name: None,
+                    pos: self.state.ctm.transform_point(from),
+                    color,
+                    intensity,
+                });
+            }
+            "infinite" => {
+                if params.get("filename").or_else(|| params.get("mapname")).is_some() {
+                    self.warn(&d.at, String::from("the environment map is not imported, the light is uniform"));
+                }
+                self.ambiant = self.ambiant + params.color("L").unwrap_or(Color::gray(1.)) * scale;
+            }
+            _ => self.warn(&d.at, format!("{} lights are not imported", kind)),
+        }
+        Ok(())
+    }
+
+    fn shape(&mut self, d: &Directive) -> Result<(), SceneError> {
+        let (kind, params) = d.named()?;
+        let at = &d.at;
+        let invalid = |message: String| syntax(at, format!("{} shape: {}", kind, message));
+        let primitive = match kind {
+            "sphere" => {
+                if ["zmin", "zmax", "phimax"].iter().any(|p| params.get(p).is_some()) {
+                    self.warn(at, String::from("partial spheres are imported whole"));
+                }
+                Primitive::Sphere {
+                    center: Vec3d { x: 0., y: 0., z: 0. },
+                    radius: params.float("radius").unwrap_or(1.),
+                }
+            }
+            "trianglemesh" => {
+                let points = params.points("P").ok_or_else(|| invalid(String::from("it has no points")))?;
+                let indices: Vec<usize> = match params.floats("indices") {
+                    Some(indices) => indices.iter().map(|&i| i as usize).collect(),
+                    None if points.len() == 3 => vec![0, 1, 2],
+                    None => return Err(invalid(String::from("it has no indices"))),
+                };
+                if !indices.len().is_multiple_of(3) {
+                    return Err(invalid(String::from("the indices are not a list of triangles")));
+                }
+                let uvs = params
+                    .floats("uv")
+                    .or_else(|| params.floats("st"))
+                    .map(|uv| uv.chunks_exact(2).map(|c| (c[0], c[1])).collect())
+                    .unwrap_or_default();
+                let triangles = indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect();
+                Primitive::Mesh(Rc::new(Mesh::new(points, uvs, triangles).map_err(invalid)?))
+            }
+            "plymesh" => {
+                let file = params.string("filename").ok_or_else(|| invalid(String::from("it has no file")))?;
+                if file.ends_with(".gz") {
+                    self.warn(at, format!("{} is compressed, it is not imported", file));
+                    return Ok(());
+                }
+                let mesh = ply::read(&self.base_dir.join(file)).map_err(|message| SceneError::Io {
+                    location: Some(at.clone()),
+                    message,
+                })?;
+                Primitive::Mesh(Rc::new(mesh))
+            }
+            _ => {
+                self.warn(at, format!("{} shapes are not imported", kind));
+                return Ok(());
+            }
+        };
+        let (material, area_light) = (self.state.material.clone(), self.state.area_light);
+        match &mut self.object {
+            Some((_, shapes)) => shapes.push(Instanced {
+                primitive,
+                to_world: self.state.ctm,
+                material,
+                area_light,
+                at: at.clone(),
+            }),
+            None => {
+                let to_world = self.state.ctm;
+                self.emit(&primitive, &to_world, material, area_light, at);
+            }
+        }
+        Ok(())
+    }
+
+    //emitting shapes become point lights
+    fn emit(&mut self, primitive: &Primitive, to_world: &Matrix4, material: MaterialRef, radiance: Option<Color>, at: &Location) {
+        let (shape, warning) = place(primitive, to_world);
+        if let Some(warning) = warning {
+            self.warn(at, warning);
+        }
+        match radiance.and_then(|radiance| area_light(&shape, radiance)) {
+            Some(light) => {
+                self.warn(at, String::from("the area light is replaced by a point light at the center of its shape"));
+                self.scene.lights.push(light);
+            }
+            None => self.scene.objects.push(ObjectDescription::new(shape, Some(material))),
+        }
+    }
+
+    fn finish(mut self) -> (SceneDescription, Vec<Warning>) {
+        let (w, h) = self.resolution;
+        //the fov of pbrt is the one of the shorter side of the image
+        let half = (self.fov * PI / 360.).tan();
+        let camera = ImportedCamera {
+            to_world: self.camera_to_world,
+            right: Vec3d { x: 1., y: 0., z: 0. },
+            resolution: self.resolution,
+            half_width: if w >= h { half * w as f64 / h as f64 } else { half },
+        };
+        let (camera, mirrored) = camera.description();
+        self.scene.camera = camera;
+        let (color, intensity) = light_color(self.ambiant, 1.);
+        self.scene.ambiant_light.color = color;
+        self.scene.ambiant_light.intensity = intensity;
+        if mirrored {
+            mirror(&mut self.scene);
+        }
+        (self.scene, self.warnings)
+    }
+}
+
+//Scene of a pbrt file, whose included files are found from base_dir
+pub fn read(content: &str, base_dir: &Path) -> Result<(SceneDescription, Vec<Warning>), SceneError> {
+    let mut importer = Importer {
+        base_dir,
+        scene: SceneDescription::default(),
+        warnings: vec![],
+        state: State {
+            ctm: Matrix4::identity(),
+            material: MaterialRef::Inline(Box::new(diffuse(Color::gray(0.5)))),
+            area_light: None,
+        },
+        stack: vec![],
+        coordinate_systems: HashMap::new(),
+        camera_to_world: Matrix4::identity(),
+        fov: 90.,
+        //defaults of pbrt-v4
+        resolution: (1280, 720),
+        ambiant: Color::gray(0.),
+        instances: HashMap::new(),
+        object: None,
+    };
+    importer.read(&mut Tokens::new(content, None))?;
+    Ok(importer.finish())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::scene::description::Shape;
+    use approx::assert_abs_diff_eq;
+
+    fn import(content: &str) -> (SceneDescription, Vec<Warning>) {
+        read(content, Path::new(".")).unwrap()
+    }
+
+    fn messages(warnings: &[Warning]) -> Vec<String> {
+        warnings.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn tokens() {
+        let mut tokens = Tokens::new("# comment\nShape \"sphere\"\n  \"float radius\" [ 2.5 ] \"bool b\" true", None);
+        assert_eq!(tokens.next().unwrap(), Some(Token::Directive(String::from("Shape"))));
+        assert_eq!(tokens.location(String::new()).to_string(), "line 2, column 1");
+        assert_eq!(tokens.next().unwrap(), Some(Token::Text(String::from("sphere"))));
+        assert_eq!(tokens.next().unwrap(), Some(Token::Text(String::from("float radius"))));
+        assert_eq!(tokens.next().unwrap(), Some(Token::Open));
+        assert_eq!(tokens.next().unwrap(), Some(Token::Number(2.5)));
+        assert_eq!(tokens.location(String::new()).to_string(), "line 3, column 20");
+        assert_eq!(tokens.next().unwrap(), Some(Token::Close));
+        tokens.next().unwrap();
+        assert_eq!(tokens.next().unwrap(), Some(Token::Text(String::from("true"))));
+        assert_eq!(tokens.next().unwrap(), None);
+    }
+
+    #[test]
+    fn camera_and_lights() {
+        let (scene, warnings) = import(
+            "LookAt 0 0 0  1 0 0  0 0 1
+            Camera \"perspective\" \"float fov\" [90]
+            Film \"rgb\" \"integer xresolution\" [200] \"integer yresolution\" [100]
+            Sampler \"halton\"
+            WorldBegin
+            LightSource \"infinite\" \"rgb L\" [0.2 0.2 0.1]
+            AttributeBegin
+              Translate 0 0 5
+              LightSource \"point\" \"rgb I\" [10 5 5] \"point3 from\" [1 0 0]
+            AttributeEnd
+            LightSource \"spot\"",
+        );
+        assert_abs_diff_eq!(scene.camera.dir, Vec3d { x: 1., y: 0., z: 0. });
+        assert_abs_diff_eq!(scene.camera.up.unwrap(), Vec3d { x: 0., y: 0., z: 1. });
+        assert_eq!(scene.camera.resolution, Some((200, 100)));
+        //the vertical fov is 90 degrees
+        assert_abs_diff_eq!(scene.camera.fov.unwrap(), 720. / PI, epsilon = 1e-9);
+        assert_eq!(scene.ambiant_light.color, Color { r: 1., g: 1., b: 0.5 });
+        assert_eq!(scene.lights[0].pos, Vec3d { x: 1., y: 0., z: 5. });
+        assert_eq!(scene.lights[0].intensity, 10.);
+        assert_eq!(messages(&warnings), vec!["line 11, column 13 (LightSource): spot lights are not imported"]);
+    }
+
+    #[test]
+    fn shapes_and_materials() {
+        let (scene, warnings) = import(
+            "WorldBegin
+            MakeNamedMaterial \"gold\" \"string type\" \"conductor\" \"spectrum eta\" \"metal-Au-eta\" \"spectrum k\" \"metal-Au-k\"
+            Material \"dielectric\" \"float eta\" 1.33
+            AttributeBegin
+              Translate 1 2 3
+              Shape \"sphere\" \"float radius\" 2
+            AttributeEnd
+            NamedMaterial \"gold\"
+            Shape \"trianglemesh\" \"integer indices\" [0 1 2 0 2 3]
+              \"point3 P\" [0 0 0 1 0 0 1 1 0 0 1 0] \"point2 uv\" [0 0 1 0 1 1 0 1]
+            Material \"coateddiffuse\"
+            Shape \"disk\"",
+        );
+        assert_eq!(scene.objects.len(), 2);
+        assert_eq!(scene.objects[0].shape, Shape::Sphere { pos: Vec3d { x: 1., y: 2., z: 3. }, radius: 2., velocity: None });
+        let Some(MaterialRef::Inline(glass)) = &scene.objects[0].material else { panic!("no inline material") };
+        assert_eq!(**glass, dielectric(1.33, 0.));
+        assert_eq!(scene.objects[1].material, Some(MaterialRef::Named(String::from("gold"))));
+        assert_eq!(scene.materials["gold"], conductor(metal("Au").unwrap(), 0.));
+        let Shape::Mesh { faces, .. } = &scene.objects[1].shape else { panic!("not a mesh") };
+        assert_eq!(faces.len(), 2);
+        assert_eq!(faces[1].uvs, vec![(0., 0.), (1., 1.), (0., 1.)]);
+        assert_eq!(
+            messages(&warnings),
+            vec![
+                "line 11, column 13 (Material): coateddiffuse materials are imported as diffuse ones",
+                "line 12, column 13 (Shape): disk shapes are not imported",
+            ]
+        );
+    }
+
+    #[test]
+    fn instances_and_area_lights() {
+        let (scene, warnings) = import(
+            "WorldBegin
+            ObjectBegin \"ball\"
+              Shape \"sphere\" \"float radius\" 0.5
+            ObjectEnd
+            Translate 2 0 0
+            ObjectInstance \"ball\"
+            Translate 2 0 0
+            ObjectInstance \"ball\"
+            AttributeBegin
+              AreaLightSource \"diffuse\" \"rgb L\" [1 1 1]
+              Shape \"trianglemesh\" \"point3 P\" [0 0 4 1 0 4 0 1 4]
+            AttributeEnd",
+        );
+        let centers: Vec<Vec3d> = scene
+            .objects
+            .iter()
+            .map(|o| match o.shape {
+                Shape::Sphere { pos, .. } => pos,
+                _ => panic!("not a sphere"),
+            })
+            .collect();
+        assert_eq!(centers, vec![Vec3d { x: 2., y: 0., z: 0. }, Vec3d { x: 4., y: 0., z: 0. }]);
+        //after the translations of the instances
+        assert_abs_diff_eq!(scene.lights[0].pos, Vec3d { x: 4. + 1. / 3., y: 1. / 3., z: 4. });
+        assert_abs_diff_eq!(scene.lights[0].intensity, 0.5);
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn mirrored_camera() {
+        //scenes written for a right-handed camera
+        let (scene, _) = import(
+            "Scale -1 1 1
+            LookAt 0 0 0  1 0 0  0 0 1
+            Camera \"perspective\"
+            WorldBegin
+            Shape \"sphere\"",
+        );
+        assert_abs_diff_eq!(scene.camera.dir, Vec3d { x: -1., y: 0., z: 0. });
+        assert_eq!(scene.objects[0].shape, Shape::Sphere { pos: Vec3d { x: -0., y: 0., z: 0. }, radius: 1., velocity: None });
+    }
+
+    #[test]
+    fn import_errors() {
+        let error = |content: &str| read(content, Path::new(".")).err().unwrap().to_string();
+        assert_eq!(error("Translate 1 2"), "line 1, column 1 (Translate): invalid pbrt: Translate needs 3 numbers, 2 given");
+        assert_eq!(error("Shape \"sphere\" \"float radius\" [1"), "line 1, column 32: invalid pbrt: unclosed [");
+        assert_eq!(error("AttributeEnd"), "line 1, column 1 (AttributeEnd): invalid pbrt: AttributeEnd without a matching begin");
+        assert!(error("Shape \"sphere\" \"float radius\" \"one\"").contains("radius"));
+        assert!(error("Shape \"trianglemesh\" \"point3 P\" [0 0 0 1 0 0 0 1 0] \"integer indices\" [0 1 5]").contains("point 5"));
+        assert!(error("Include \"missing.pbrt\"").contains("missing.pbrt"));
+    }
+}
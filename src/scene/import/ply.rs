@@ -0,0 +1,259 @@
+use std::fs;
+use std::path::Path;
+
+use super::Mesh;
+use crate::coord::Vec3d;
+
+//Stanford polygon files, in text or binary. Only the vertex positions and
+//texture coordinates and the faces are read.
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Format {
+    Ascii,
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Type {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+#[derive(Debug)]
+enum Property {
+    Scalar(String, Type),
+    //count type, item type
+    List(String, Type, Type),
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+//values of the body, whatever the format
+struct Body<'a> {
+    format: Format,
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Type {
+    fn parse(s: &str) -> Result<Type, String> {
+        match s {
+            "char" | "int8" => Ok(Type::I8),
+            "uchar" | "uint8" => Ok(Type::U8),
+            "short" | "int16" => Ok(Type::I16),
+            "ushort" | "uint16" => Ok(Type::U16),
+            "int" | "int32" => Ok(Type::I32),
+            "uint" | "uint32" => Ok(Type::U32),
+            "float" | "float32" => Ok(Type::F32),
+            "double" | "float64" => Ok(Type::F64),
+            _ => Err(format!("unknown property type {:?}", s)),
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Type::I8 | Type::U8 => 1,
+            Type::I16 | Type::U16 => 2,
+            Type::I32 | Type::U32 | Type::F32 => 4,
+            Type::F64 => 8,
+        }
+    }
+}
+
+impl Body<'_> {
+    fn value(&mut self, t: Type) -> Result<f64, String> {
+        match self.format {
+            Format::Ascii => {
+                let rest = &self.bytes[self.pos..];
+                let start = rest.iter().position(|b| !b.is_ascii_whitespace()).ok_or("the file ends too soon")?;
+                let len = rest[start..].iter().position(|b| b.is_ascii_whitespace()).unwrap_or(rest.len() - start);
+                self.pos += start + len;
+                let word = String::from_utf8_lossy(&rest[start..start + len]);
+                word.parse::<f64>().map_err(|e| format!("{:?}: {}", word, e))
+            }
+            _ => {
+                let size = t.size();
+                let bytes = self.bytes.get(self.pos..self.pos + size).ok_or("the file ends too soon")?;
+                self.pos += size;
+                let mut b = [0u8; 8];
+                b[..size].copy_from_slice(bytes);
+                if self.format == Format::BigEndian {
+                    b[..size].reverse();
+                }
+                Ok(match t {
+                    Type::I8 => b[0] as i8 as f64,
+                    Type::U8 => b[0] as f64,
+                    Type::I16 => i16::from_le_bytes([b[0], b[1]]) as f64,
+                    Type::U16 => u16::from_le_bytes([b[0], b[1]]) as f64,
+                    Type::I32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    Type::U32 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    Type::F32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    Type::F64 => f64::from_le_bytes(b),
+                })
+            }
+        }
+    }
+}
+
+pub fn read(path: &Path) -> Result<Mesh, String> {
+    let bytes = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    parse(&bytes).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn parse(bytes: &[u8]) -> Result<Mesh, String> {
+    let end = bytes
+        .windows(10)
+        .position(|w| w == b"end_header")
+        .ok_or("not a ply file, there is no end_header")?;
+    let header = String::from_utf8_lossy(&bytes[..end]);
+    let mut body_start = end + 10;
+    while body_start < bytes.len() && bytes[body_start - 1] != b'\n' {
+        body_start += 1;
+    }
+
+    let mut lines = header.lines().map(str::split_whitespace);
+    if lines.next().and_then(|mut l| l.next()) != Some("ply") {
+        return Err(String::from("not a ply file"));
+    }
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for mut words in lines {
+        match (words.next(), words.next(), words.next()) {
+            (Some("format"), Some(f), _) => {
+                format = Some(match f {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::LittleEndian,
+                    "binary_big_endian" => Format::BigEndian,
+                    _ => return Err(format!("unknown format {:?}", f)),
+                })
+            }
+            (Some("element"), Some(name), Some(count)) => elements.push(Element {
+                name: String::from(name),
+                count: count.parse().map_err(|e| format!("element {}: {}", name, e))?,
+                properties: vec![],
+            }),
+            (Some("property"), Some("list"), Some(count)) => {
+                let (item, name) = (words.next().unwrap_or_default(), words.next().unwrap_or_default());
+                let element = elements.last_mut().ok_or("property outside of an element")?;
+                element.properties.push(Property::List(String::from(name), Type::parse(count)?, Type::parse(item)?));
+            }
+            (Some("property"), Some(t), Some(name)) => {
+                let element = elements.last_mut().ok_or("property outside of an element")?;
+                element.properties.push(Property::Scalar(String::from(name), Type::parse(t)?));
+            }
+            _ => (),
+        }
+    }
+
+    let mut body = Body {
+        format: format.ok_or("the format is not given")?,
+        bytes: &bytes[body_start.min(bytes.len())..],
+        pos: 0,
+    };
+    let mut points = Vec::new();
+    let mut uvs = Vec::new();
+    let mut polygons = Vec::new();
+    for element in elements.iter() {
+        for _ in 0..element.count {
+            let mut scalars = [None; 5];
+            for property in element.properties.iter() {
+                match property {
+                    Property::Scalar(name, t) => {
+                        let value = body.value(*t)?;
+                        let slot = match name.as_str() {
+                            "x" => 0,
+                            "y" => 1,
+                            "z" => 2,
+                            "u" | "s" | "texture_u" | "texture_s" => 3,
+                            "v" | "t" | "texture_v" | "texture_t" => 4,
+                            _ => continue,
+                        };
+                        scalars[slot] = Some(value);
+                    }
+                    Property::List(name, count, item) => {
+                        let count = body.value(*count)? as usize;
+                        let mut list = Vec::with_capacity(count);
+                        for _ in 0..count {
+                            list.push(body.value(*item)? as usize);
+                        }
+                        if element.name == "face" && matches!(name.as_str(), "vertex_indices" | "vertex_index") {
+                            polygons.push(list);
+                        }
+                    }
+                }
+            }
+            if element.name == "vertex" {
+                let coordinate = |i: usize| scalars[i].ok_or("a vertex has no x, y or z");
+                points.push(Vec3d {
+                    x: coordinate(0)?,
+                    y: coordinate(1)?,
+                    z: coordinate(2)?,
+                });
+                if let (Some(u), Some(v)) = (scalars[3], scalars[4]) {
+                    uvs.push((u, v));
+                }
+            }
+        }
+    }
+
+    let mut faces = Mesh::default();
+    for polygon in polygons.iter() {
+        faces.add_polygon(polygon);
+    }
+    Mesh::new(points, uvs, faces.triangles)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const HEADER: &str = "ply\nformat {}\ncomment a square\nelement vertex 4\nproperty float x\nproperty float y\nproperty float z\nproperty float u\nproperty float v\nelement face 1\nproperty list uchar int vertex_indices\nend_header\n";
+
+    #[test]
+    fn ascii() {
+        let content = HEADER.replace("{}", "ascii 1.0") + "0 0 0 0 0\n1 0 0 1 0\n1 1 0 1 1\n0 1 0 0 1\n4 0 1 2 3\n";
+        let mesh = parse(content.as_bytes()).unwrap();
+        assert_eq!(mesh.points[2], Vec3d { x: 1., y: 1., z: 0. });
+        assert_eq!(mesh.uvs[3], (0., 1.));
+        assert_eq!(mesh.triangles, vec![[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn binary() {
+        let mut content = HEADER.replace("{}", "binary_big_endian 1.0").into_bytes();
+        for (x, y) in [(0f32, 0f32), (1., 0.), (1., 1.), (0., 1.)] {
+            for f in [x, y, 0., x, y] {
+                content.extend(f.to_be_bytes());
+            }
+        }
+        content.push(3);
+        for i in [0i32, 1, 2] {
+            content.extend(i.to_be_bytes());
+        }
+        let mesh = parse(&content).unwrap();
+        assert_eq!(mesh.points[1], Vec3d { x: 1., y: 0., z: 0. });
+        assert_eq!(mesh.triangles, vec![[0, 1, 2]]);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(parse(b"obj\nend_header\n").is_err());
+        let truncated = HEADER.replace("{}", "binary_little_endian 1.0") + "abc";
+        assert_eq!(parse(truncated.as_bytes()), Err(String::from("the file ends too soon")));
+        let out_of_range = HEADER.replace("{}", "ascii 1.0") + "0 0 0 0 0\n1 0 0 1 0\n1 1 0 1 1\n0 1 0 0 1\n3 0 1 7\n";
+        assert!(parse(out_of_range.as_bytes()).is_err());
+    }
+}
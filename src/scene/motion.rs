@@ -0,0 +1,241 @@
+use crate::coord::{Matrix4, Vec3d};
+
+//Elementary transform, whose parameters can be interpolated
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Transform {
+    Translate(Vec3d),
+    //axis and angle in radians
+    Rotate(Vec3d, f64),
+    Scale(Vec3d),
+}
+
+impl Transform {
+    pub fn matrix(&self) -> Matrix4 {
+        match *self {
+            Transform::Translate(v) => Matrix4::translation(v),
+            Transform::Rotate(axis, angle) => Matrix4::rotation(axis, angle),
+            Transform::Scale(v) => Matrix4::scaling(v),
+        }
+    }
+
+    //f from 0 (self) to 1 (other), both transforms are of the same kind and
+    //rotations have the same axis
+    fn lerp(&self, other: &Transform, f: f64) -> Transform {
+        let mix = |a: Vec3d, b: Vec3d| a * (1. - f) + b * f;
+        match (*self, *other) {
+            (Transform::Translate(a), Transform::Translate(b)) => Transform::Translate(mix(a, b)),
+            (Transform::Rotate(axis, angle_a), Transform::Rotate(_, angle_b)) => {
+                Transform::Rotate(axis, angle_a * (1. - f) + angle_b * f)
+            }
+            (Transform::Scale(a), Transform::Scale(b)) => Transform::Scale(mix(a, b)),
+            _ => panic!("cannot interpolate {:?} and {:?}", self, other),
+        }
+    }
+}
+
+//composition of transforms, applied in the order they are given
+pub fn compose(transforms: &[Transform]) -> Matrix4 {
+    transforms
+        .iter()
+        .fold(Matrix4::identity(), |m, t| t.matrix() * m)
+}
+
+//Transform changing over time. It is given at a few times (keyframes) with
+//the same list of transforms, whose parameters are interpolated linearly in
+//between, so that rotations can turn more than half a turn. A rotation
+//keeps its axis, and a scale its sign.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Motion {
+    keyframes: Vec<(f64, Vec<Transform>)>,
+}
+
+impl Motion {
+    pub fn new(mut keyframes: Vec<(f64, Vec<Transform>)>) -> Result<Motion, String> {
+        if keyframes.is_empty() {
+            return Err(String::from("motion without keyframe"));
+        }
+        let kinds = |transforms: &[Transform]| {
+            transforms
+                .iter()
+                .map(std::mem::discriminant)
+                .collect::<Vec<_>>()
+        };
+        let first = kinds(&keyframes[0].1);
+        if !keyframes.iter().all(|(_, t)| kinds(t) == first) {
+            return Err(String::from(
+                "keyframes of a motion must have the same transforms, in the same order",
+            ));
+        }
+        //in between, the matrix must stay invertible
+        let same_axis = |a: Vec3d, b: Vec3d| match (a.normalize(), b.normalize()) {
+            (Some(a), Some(b)) => (a - b).norm() < 1e-9,
+            _ => false,
+        };
+        for (i, transform) in keyframes[0].1.iter().enumerate() {
+            for (_, transforms) in keyframes.iter() {
+                match (*transform, transforms[i]) {
+                    (Transform::Rotate(a, _), Transform::Rotate(b, _)) if !same_axis(a, b) => {
+                        return Err(String::from(
+                            "keyframes of a rotation must have the same axis, only the angle changes",
+                        ))
+                    }
+                    (Transform::Scale(a), Transform::Scale(b)) if a.x * b.x <= 0. || a.y * b.y <= 0. || a.z * b.z <= 0. => {
+                        return Err(String::from("keyframes of a scale cannot be 0 or change sign"))
+                    }
+                    _ => (),
+                }
+            }
+        }
+        keyframes.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Ok(Motion { keyframes })
+    }
+
+    //constant before the first keyframe and after the last one
+    pub fn at(&self, time: f64) -> Matrix4 {
+        let next = self.keyframes.partition_point(|(t, _)| *t <= time);
+        if next == 0 {
+            return compose(&self.keyframes[0].1);
+        }
+        if next == self.keyframes.len() {
+            return compose(&self.keyframes[next - 1].1);
+        }
+        let (t0, before) = &self.keyframes[next - 1];
+        let (t1, after) = &self.keyframes[next];
+        let f = (time - t0) / (t1 - t0);
+        let transforms: Vec<Transform> = before
+            .iter()
+            .zip(after)
+            .map(|(a, b)| a.lerp(b, f))
+            .collect();
+        compose(&transforms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use approx::assert_abs_diff_eq;
+    use std::f64::consts::PI;
+
+    fn origin() -> Vec3d {
+        Vec3d {
+            x: 0.,
+            y: 0.,
+            z: 0.,
+        }
+    }
+
+    #[test]
+    fn compose_in_order() {
+        let m = compose(&[
+            Transform::Translate(Vec3d {
+                x: 1.,
+                y: 0.,
+                z: 0.,
+            }),
+            Transform::Scale(Vec3d {
+                x: 2.,
+                y: 2.,
+                z: 2.,
+            }),
+        ]);
+        assert_eq!(
+            m.transform_point(origin()),
+            Vec3d {
+                x: 2.,
+                y: 0.,
+                z: 0.
+            }
+        );
+    }
+
+    #[test]
+    fn interpolated_motion() {
+        let z = Vec3d {
+            x: 0.,
+            y: 0.,
+            z: 1.,
+        };
+        let x = Vec3d {
+            x: 1.,
+            y: 0.,
+            z: 0.,
+        };
+        //one turn and a move along x
+        let motion = Motion::new(vec![
+            (2., vec![Transform::Rotate(z, 2. * PI), Transform::Translate(x * 4.)]),
+            (0., vec![Transform::Rotate(z, 0.), Transform::Translate(origin())]),
+        ])
+        .unwrap();
+        assert_abs_diff_eq!(motion.at(-1.).transform_point(x), x);
+        assert_abs_diff_eq!(
+            motion.at(1.).transform_point(x),
+            Vec3d {
+                x: 1.,
+                y: 0.,
+                z: 0.
+            },
+            epsilon = 1e-12
+        );
+        assert_abs_diff_eq!(
+            motion.at(0.5).transform_point(x),
+            Vec3d {
+                x: 1.,
+                y: 1.,
+                z: 0.
+            },
+            epsilon = 1e-12
+        );
+        assert_abs_diff_eq!(motion.at(3.).transform_point(x), x * 5., epsilon = 1e-12);
+    }
+
+    #[test]
+    fn mismatched_keyframes() {
+        let error = Motion::new(vec![
+            (0., vec![Transform::Translate(origin())]),
+            (1., vec![Transform::Scale(origin())]),
+        ])
+        .unwrap_err();
+        assert!(error.contains("same transforms"));
+        assert!(Motion::new(vec![]).is_err());
+    }
+
+    #[test]
+    fn singular_keyframes() {
+        let z = Vec3d {
+            x: 0.,
+            y: 0.,
+            z: 1.,
+        };
+        let rotations = |b: Vec3d| {
+            Motion::new(vec![
+                (0., vec![Transform::Rotate(z, 0.)]),
+                (1., vec![Transform::Rotate(b, PI)]),
+            ])
+        };
+        assert!(rotations(z * -1.).unwrap_err().contains("same axis"));
+        assert!(rotations(origin()).is_err());
+        //the same axis, of another length
+        let motion = rotations(z * 2.).unwrap();
+        let x = Vec3d {
+            x: 1.,
+            y: 0.,
+            z: 0.,
+        };
+        assert_abs_diff_eq!(
+            motion.at(0.5).transform_point(x),
+            Vec3d {
+                x: 0.,
+                y: 1.,
+                z: 0.
+            },
+            epsilon = 1e-12
+        );
+        let scales = Motion::new(vec![
+            (0., vec![Transform::Scale(x + z)]),
+            (1., vec![Transform::Scale(z - x)]),
+        ]);
+        assert!(scales.unwrap_err().contains("change sign"));
+    }
+}
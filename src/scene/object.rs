@@ -1,21 +1,202 @@
+pub mod csg;
+pub mod cuboid;
+pub mod heightfield;
+pub mod instance;
+pub mod cone;
 pub mod material;
+pub mod plane;
 pub mod rasterized;
+pub mod sdf;
+pub mod solver;
 pub mod sphere;
+pub mod texture;
+pub mod torus;
+
+use std::sync::Arc;
 
 use crate::coord::Vec3d;
+use crate::scene::camera::Ray;
+use cone::Cone;
+use csg::Csg;
+use cuboid::Cuboid;
+use heightfield::Heightfield;
+use instance::Instance;
 use material::Material;
+use plane::{Disk, Plane};
 use rasterized::Rasterized;
+use sdf::Sdf;
 use sphere::Sphere;
+use torus::Torus;
 
 #[derive(Debug, PartialEq)]
 pub struct Intersect {
     pub pos: Vec3d,
     pub dist: f64,
     pub normal: Vec3d,
-    pub material: Material,
+    //directions of increasing u and v on the surface
+    pub tangent: Vec3d,
+    pub bitangent: Vec3d,
+    pub uv: (f64, f64),
+    pub material: Arc<Material>,
+}
+
+impl Intersect {
+    //evaluates the material and the shading normal at the intersection point
+    pub fn with_material(mut self, material: &Arc<Material>) -> Intersect {
+        self.normal = material.shading_normal(&self);
+        self.material = material.at(self.uv, self.pos);
+        self
+    }
+}
+
+//First intersection in front of the ray among all those along its line.
+//Closed objects are not seen from inside, like spheres: the ray starts inside
+//when an odd number of intersections are behind it (or closer than min_dist).
+pub fn nearest_outside(mut hits: Vec<Intersect>, min_dist: f64) -> Option<Intersect> {
+    let behind = hits.iter().filter(|i| i.dist <= min_dist).count();
+    if behind % 2 == 1 {
+        return None;
+    }
+    hits.retain(|i| i.dist > min_dist);
+    hits.into_iter().min_by(|a, b| a.dist.total_cmp(&b.dist))
 }
 
 pub enum Object {
     Sphere(Sphere),
     Rasterized(Rasterized),
+    Plane(Plane),
+    Disk(Disk),
+    Cuboid(Cuboid),
+    Cone(Cone),
+    Torus(Torus),
+    Csg(Box<Csg>),
+    Sdf(Sdf),
+    Heightfield(Heightfield),
+    Instance(Box<Instance>),
+}
+
+impl Object {
+    pub fn intersect(&self, ray: &Ray) -> Option<Intersect> {
+        self.intersect_with(ray, None)
+    }
+
+    //intersection using the given material rather than the object's one
+    pub fn intersect_with(&self, ray: &Ray, material: Option<&Arc<Material>>) -> Option<Intersect> {
+        match self {
+            Object::Sphere(s) => match material {
+                None => s.intersect(ray),
+                Some(m) => s.hit(ray).map(|i| i.with_material(m)),
+            },
+            Object::Rasterized(r) => match material {
+                None => r.intersect(ray),
+                Some(m) => r.hit(ray).map(|i| i.with_material(m)),
+            },
+            Object::Plane(p) => match material {
+                None => p.intersect(ray),
+                Some(m) => p.hit(ray).map(|i| i.with_material(m)),
+            },
+            Object::Disk(d) => match material {
+                None => d.intersect(ray),
+                Some(m) => d.hit(ray).map(|i| i.with_material(m)),
+            },
+            Object::Cuboid(c) => match material {
+                None => c.intersect(ray),
+                Some(m) => c.hit(ray).map(|i| i.with_material(m)),
+            },
+            Object::Cone(c) => match material {
+                None => c.intersect(ray),
+                Some(m) => c.hit(ray).map(|i| i.with_material(m)),
+            },
+            Object::Torus(t) => match material {
+                None => t.intersect(ray),
+                Some(m) => t.hit(ray).map(|i| i.with_material(m)),
+            },
+            Object::Csg(c) => c.intersect_with(ray, material),
+            Object::Sdf(s) => match material {
+                None => s.intersect(ray),
+                Some(m) => s.hit(ray).map(|i| i.with_material(m)),
+            },
+            Object::Heightfield(h) => match material {
+                None => h.intersect(ray),
+                Some(m) => h.hit(ray).map(|i| i.with_material(m)),
+            },
+            Object::Instance(i) => i.intersect_with(ray, material),
+        }
+    }
+
+    //Meshes are only seen from the front of their faces, for closed meshes.
+    //Shared objects are not changed, they come from the library where
+    //they have been culled before being shared.
+    pub fn cull_back_faces(&mut self) {
+        match self {
+            Object::Rasterized(r) => r.double_sided = false,
+            Object::Instance(i) => {
+                if let Some(object) = Arc::get_mut(&mut i.object) {
+                    object.cull_back_faces()
+                }
+            }
+            _ => (),
+        }
+    }
+
+    //objects with an inside, that can be combined by csg
+    pub fn is_closed(&self) -> bool {
+        match self {
+            Object::Sphere(_) | Object::Cuboid(_) | Object::Cone(_) | Object::Torus(_) | Object::Csg(_) => true,
+            Object::Rasterized(_) | Object::Plane(_) | Object::Disk(_) | Object::Sdf(_) | Object::Heightfield(_) => {
+                false
+            }
+            Object::Instance(i) => i.object.is_closed(),
+        }
+    }
+
+    //All the intersections with the line of the ray, behind it included, sorted
+    //by distance, with the material to shade them. Rays enter and exit closed
+    //objects in turn, the first intersection is an entry.
+    pub fn intersect_all<'a>(
+        &'a self,
+        ray: &Ray,
+        material: Option<&'a Arc<Material>>,
+    ) -> Vec<(Intersect, &'a Arc<Material>)> {
+        let with_material = |hits: Vec<Intersect>, own: &'a Arc<Material>| {
+            let mut hits: Vec<(Intersect, &Arc<Material>)> =
+                hits.into_iter().map(|i| (i, material.unwrap_or(own))).collect();
+            hits.sort_by(|a, b| a.0.dist.total_cmp(&b.0.dist));
+            hits
+        };
+        match self {
+            Object::Sphere(s) => with_material(s.hits(ray), &s.material),
+            Object::Cuboid(c) => with_material(c.hits(ray), &c.material),
+            Object::Cone(c) => with_material(c.hits(ray), &c.material),
+            Object::Torus(t) => with_material(t.hits(ray), &t.material),
+            Object::Csg(c) => c.intersect_all(ray, material),
+            Object::Instance(i) => i.intersect_all(ray, material),
+            Object::Rasterized(_)
+            | Object::Plane(_)
+            | Object::Disk(_)
+            | Object::Sdf(_)
+            | Object::Heightfield(_) => {
+                panic!("only closed objects can be combined")
+            }
+        }
+    }
+
+    //a point is inside a closed object when an odd number of intersections
+    //are behind it, along any line
+    pub fn contains(&self, point: Vec3d) -> bool {
+        if !self.is_closed() {
+            return false;
+        }
+        let ray = Ray {
+            start: point,
+            dir: Vec3d {
+                x: 0.36,
+                y: 0.48,
+                z: 0.8,
+            },
+            time: 0.,
+        };
+        let behind = self.intersect_all(&ray, None).iter().filter(|(i, _)| i.dist < 0.).count();
+        behind % 2 == 1
+    }
 }
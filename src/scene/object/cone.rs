@@ -0,0 +1,309 @@
+use std::sync::Arc;
+use std::f64::consts::PI;
+
+use super::solver::solve_quadratic;
+use super::{material::Material, nearest_outside, Intersect};
+use crate::coord::{Matrix4, Vec3d};
+use crate::scene::camera::Ray;
+
+//Capped cone between a base and a top disk, cylinders have both radii equal.
+//Computations are done in a local frame: base at the origin, axis along z.
+pub struct Cone {
+    pub height: f64,
+    pub radius: f64,
+    pub top_radius: f64,
+    pub material: Arc<Material>,
+    to_local: Matrix4,
+    to_world: Matrix4,
+}
+
+impl Cone {
+    //pos and top are the centers of the base and top disks
+    pub fn new(pos: Vec3d, top: Vec3d, radius: f64, top_radius: f64, material: impl Into<Arc<Material>>) -> Cone {
+        let height = (top - pos).norm();
+        let axis = (top - pos).normalize().expect("cone top and base cannot be the same");
+        let to_world = Matrix4::frame(pos, axis);
+        Cone {
+            height,
+            radius,
+            top_radius,
+            material: material.into(),
+            to_local: to_world.inverse().unwrap(),
+            to_world,
+        }
+    }
+
+    pub fn cylinder(pos: Vec3d, top: Vec3d, radius: f64, material: impl Into<Arc<Material>>) -> Cone {
+        Cone::new(pos, top, radius, radius, material)
+    }
+
+    pub fn intersect(&self, ray: &Ray) -> Option<Intersect> {
+        self.hit(ray).map(|i| i.with_material(&self.material))
+    }
+
+    //intersection without material
+    pub fn hit(&self, ray: &Ray) -> Option<Intersect> {
+        nearest_outside(self.hits(ray), 0.)
+    }
+
+    //intersections with the side and the caps, in front of the ray or not
+    pub fn hits(&self, ray: &Ray) -> Vec<Intersect> {
+        let o = self.to_local.transform_point(ray.start);
+        let d = self.to_local.transform_vector(ray.dir);
+        let mut hits = Vec::new();
+        let mut push = |dist: f64, normal: Vec3d, tangent: Vec3d, uv: (f64, f64)| {
+            let normal = self.to_world.transform_vector(normal);
+            let tangent = self.to_world.transform_vector(tangent);
+            hits.push(Intersect {
+                pos: ray.start + dist * ray.dir,
+                dist,
+                normal,
+                tangent,
+                bitangent: normal.cross(tangent),
+                uv,
+                material: Material::unset(),
+            })
+        };
+
+        //side: x² + y² = (radius + k.z)², 0 <= z <= height
+        let k = (self.top_radius - self.radius) / self.height;
+        let r0 = self.radius + k * o.z;
+        let a = d.x * d.x + d.y * d.y - k * k * d.z * d.z;
+        let b = 2. * (o.x * d.x + o.y * d.y - k * d.z * r0);
+        let c = o.x * o.x + o.y * o.y - r0 * r0;
+        let roots = if a.abs() < 1e-12 {
+            //ray parallel to the side
+            if b == 0. { vec![] } else { vec![-c / b] }
+        } else {
+            solve_quadratic(a, b, c).map_or(vec![], |(t1, t2)| vec![t1, t2])
+        };
+        for t in roots {
+            let p = o + t * d;
+            if p.z < 0. || p.z > self.height {
+                continue;
+            }
+            let r = self.radius + k * p.z;
+            let normal = Vec3d {
+                x: p.x,
+                y: p.y,
+                z: -k * r,
+            }
+            .normalize()
+            .unwrap_or(Vec3d { x: 0., y: 0., z: 1. });
+            let u = 0.5 + p.y.atan2(p.x) / (2. * PI);
+            push(t, normal, around(p), (u, p.z / self.height));
+        }
+
+        //caps
+        for (z, r, side) in [(0., self.radius, -1.), (self.height, self.top_radius, 1.)] {
+            if r <= 0. || d.z == 0. {
+                continue;
+            }
+            let t = (z - o.z) / d.z;
+            let p = o + t * d;
+            if p.x * p.x + p.y * p.y > r * r {
+                continue;
+            }
+            let normal = Vec3d {
+                x: 0.,
+                y: 0.,
+                z: side,
+            };
+            let tangent = Vec3d {
+                x: 1.,
+                y: 0.,
+                z: 0.,
+            };
+            push(t, normal, tangent, (0.5 + p.x / (2. * r), 0.5 + p.y / (2. * r)));
+        }
+        hits
+    }
+}
+
+//direction around the z axis at p, any one on the axis
+fn around(p: Vec3d) -> Vec3d {
+    Vec3d {
+        x: -p.y,
+        y: p.x,
+        z: 0.,
+    }
+    .normalize()
+    .unwrap_or(Vec3d {
+        x: 1.,
+        y: 0.,
+        z: 0.,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    //vertical, from z=0 to z=2
+    fn create_cylinder() -> Cone {
+        Cone::cylinder(
+            Vec3d {
+                x: 3.,
+                y: 0.,
+                z: 0.,
+            },
+            Vec3d {
+                x: 3.,
+                y: 0.,
+                z: 2.,
+            },
+            1.,
+            Material::default(),
+        )
+    }
+
+    #[test]
+    fn cylinder_side() {
+        let ray = Ray {
+            start: Vec3d {
+                x: 0.,
+                y: 0.,
+                z: 1.,
+            },
+            dir: Vec3d {
+                x: 1.,
+                y: 0.,
+                z: 0.,
+            },
+            time: 0.,
+        };
+        let i = create_cylinder().intersect(&ray).unwrap();
+        assert_abs_diff_eq!(i.dist, 2.);
+        assert_abs_diff_eq!(
+            i.normal,
+            Vec3d {
+                x: -1.,
+                y: 0.,
+                z: 0.
+            }
+        );
+        assert_abs_diff_eq!(i.uv.1, 0.5);
+    }
+
+    #[test]
+    fn cylinder_cap() {
+        let ray = Ray {
+            start: Vec3d {
+                x: 3.5,
+                y: 0.,
+                z: 5.,
+            },
+            dir: Vec3d {
+                x: 0.,
+                y: 0.,
+                z: -1.,
+            },
+            time: 0.,
+        };
+        let i = create_cylinder().intersect(&ray).unwrap();
+        assert_abs_diff_eq!(i.dist, 3.);
+        assert_abs_diff_eq!(
+            i.normal,
+            Vec3d {
+                x: 0.,
+                y: 0.,
+                z: 1.
+            }
+        );
+    }
+
+    #[test]
+    fn cylinder_missed_or_inside() {
+        let above = Ray {
+            start: Vec3d {
+                x: 0.,
+                y: 0.,
+                z: 2.5,
+            },
+            dir: Vec3d {
+                x: 1.,
+                y: 0.,
+                z: 0.,
+            },
+            time: 0.,
+        };
+        let inside = Ray {
+            start: Vec3d {
+                x: 3.,
+                y: 0.,
+                z: 1.,
+            },
+            dir: Vec3d {
+                x: 1.,
+                y: 0.,
+                z: 0.,
+            },
+            time: 0.,
+        };
+        assert_eq!(create_cylinder().intersect(&above), None);
+        assert_eq!(create_cylinder().intersect(&inside), None);
+    }
+
+    #[test]
+    fn cone_side() {
+        //radius 1 at z=0 down to a point at z=1, the side is at 45°
+        let cone = Cone::new(
+            Vec3d {
+                x: 0.,
+                y: 0.,
+                z: 0.,
+            },
+            Vec3d {
+                x: 0.,
+                y: 0.,
+                z: 1.,
+            },
+            1.,
+            0.,
+            Material::default(),
+        );
+        let ray = Ray {
+            start: Vec3d {
+                x: -3.,
+                y: 0.,
+                z: 0.5,
+            },
+            dir: Vec3d {
+                x: 1.,
+                y: 0.,
+                z: 0.,
+            },
+            time: 0.,
+        };
+        let i = cone.intersect(&ray).unwrap();
+        assert_abs_diff_eq!(i.dist, 2.5);
+        let expected = Vec3d {
+            x: -1.,
+            y: 0.,
+            z: 1.,
+        }
+        .normalize()
+        .unwrap();
+        assert_abs_diff_eq!(i.normal, expected, epsilon = 1e-12);
+
+        //from below, through the base
+        let ray = Ray {
+            start: Vec3d {
+                x: 0.2,
+                y: 0.,
+                z: -1.,
+            },
+            dir: Vec3d {
+                x: 0.,
+                y: 0.,
+                z: 1.,
+            },
+            time: 0.,
+        };
+        let i = cone.intersect(&ray).unwrap();
+        assert_abs_diff_eq!(i.dist, 1.);
+        assert_abs_diff_eq!(i.normal.z, -1.);
+    }
+}
@@ -0,0 +1,242 @@
+use std::sync::Arc;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use super::{material::Material, Intersect, Object};
+use crate::scene::camera::Ray;
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+//Boolean combination of two closed objects, each surface keeps its material
+pub struct Csg {
+    pub operation: Operation,
+    pub left: Object,
+    pub right: Object,
+}
+
+impl FromStr for Operation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Operation, String> {
+        match s {
+            "union" => Ok(Operation::Union),
+            "intersection" => Ok(Operation::Intersection),
+            "difference" => Ok(Operation::Difference),
+            _ => Err(format!("unknown csg operation {:?}", s)),
+        }
+    }
+}
+
+//name read by from_str
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Operation::Union => "union",
+            Operation::Intersection => "intersection",
+            Operation::Difference => "difference",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Operation {
+    fn inside(&self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            Operation::Union => in_left || in_right,
+            Operation::Intersection => in_left && in_right,
+            Operation::Difference => in_left && !in_right,
+        }
+    }
+}
+
+impl Csg {
+    pub fn new(operation: Operation, left: Object, right: Object) -> Csg {
+        assert!(
+            left.is_closed() && right.is_closed(),
+            "csg can only combine closed objects"
+        );
+        Csg {
+            operation,
+            left,
+            right,
+        }
+    }
+
+    //not seen from inside, like the objects it is made of
+    pub fn intersect_with(&self, ray: &Ray, material: Option<&Arc<Material>>) -> Option<Intersect> {
+        let hits = self.intersect_all(ray, material);
+        let behind = hits.iter().filter(|(i, _)| i.dist <= 0.).count();
+        if behind % 2 == 1 {
+            return None;
+        }
+        hits.into_iter()
+            .find(|(i, _)| i.dist > 0.)
+            .map(|(i, m)| i.with_material(m))
+    }
+
+    //intersections of both objects where the ray enters or exits the combination
+    pub fn intersect_all<'a>(&'a self, ray: &Ray, material: Option<&'a Arc<Material>>) -> Vec<(Intersect, &'a Arc<Material>)> {
+        let left = self.left.intersect_all(ray, material);
+        let right = self.right.intersect_all(ray, material);
+        let mut events: Vec<(bool, Intersect, &Arc<Material>)> = left
+            .into_iter()
+            .map(|(i, m)| (true, i, m))
+            .chain(right.into_iter().map(|(i, m)| (false, i, m)))
+            .collect();
+        events.sort_by(|a, b| a.1.dist.total_cmp(&b.1.dist));
+
+        let (mut in_left, mut in_right) = (false, false);
+        let mut hits = Vec::new();
+        for (is_left, mut i, m) in events {
+            let was_inside = self.operation.inside(in_left, in_right);
+            if is_left {
+                in_left = !in_left;
+            } else {
+                in_right = !in_right;
+            }
+            if self.operation.inside(in_left, in_right) != was_inside {
+                //the inside of the removed object is the outside of the difference
+                if !is_left && self.operation == Operation::Difference {
+                    i.normal = i.normal * -1.;
+                }
+                hits.push((i, m));
+            }
+        }
+        hits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::coord::Vec3d;
+    use crate::scene::object::sphere::Sphere;
+    use approx::assert_abs_diff_eq;
+
+    //two unit spheres centered on x=0 and x=1
+    fn create_csg(operation: Operation) -> Csg {
+        let sphere = |x| {
+            Object::Sphere(Sphere::new(
+                Vec3d { x, y: 0., z: 0. },
+                1.,
+                Material::default(),
+            ))
+        };
+        Csg::new(operation, sphere(0.), sphere(1.))
+    }
+
+    fn ray_along_x() -> Ray {
+        Ray {
+            start: Vec3d {
+                x: -5.,
+                y: 0.,
+                z: 0.,
+            },
+            dir: Vec3d {
+                x: 1.,
+                y: 0.,
+                z: 0.,
+            },
+            time: 0.,
+        }
+    }
+
+    fn distances(csg: &Csg, ray: &Ray) -> Vec<f64> {
+        csg.intersect_all(ray, None)
+            .iter()
+            .map(|(i, _)| i.dist)
+            .collect()
+    }
+
+    #[test]
+    fn union() {
+        let csg = create_csg(Operation::Union);
+        assert_eq!(distances(&csg, &ray_along_x()), vec![4., 7.]);
+    }
+
+    #[test]
+    fn intersection() {
+        let csg = create_csg(Operation::Intersection);
+        assert_eq!(distances(&csg, &ray_along_x()), vec![5., 6.]);
+    }
+
+    #[test]
+    fn difference() {
+        let csg = create_csg(Operation::Difference);
+        assert_eq!(distances(&csg, &ray_along_x()), vec![4., 5.]);
+
+        //the cavity left by the second sphere faces the ray
+        let ray = Ray {
+            start: Vec3d {
+                x: 5.,
+                y: 0.,
+                z: 0.,
+            },
+            dir: Vec3d {
+                x: -1.,
+                y: 0.,
+                z: 0.,
+            },
+            time: 0.,
+        };
+        let i = csg.intersect_with(&ray, None).unwrap();
+        assert_eq!(i.dist, 5.);
+        assert_abs_diff_eq!(
+            i.normal,
+            Vec3d {
+                x: 1.,
+                y: 0.,
+                z: 0.
+            }
+        );
+    }
+
+    #[test]
+    fn nested() {
+        let csg = Csg::new(
+            Operation::Difference,
+            Object::Csg(Box::new(create_csg(Operation::Union))),
+            Object::Sphere(Sphere::new(
+                Vec3d {
+                    x: 0.5,
+                    y: 0.,
+                    z: 0.,
+                },
+                0.5,
+                Material::default(),
+            )),
+        );
+        assert_eq!(distances(&csg, &ray_along_x()), vec![4., 5., 6., 7.]);
+
+        //starting inside the hole
+        let ray = Ray {
+            start: Vec3d {
+                x: 0.5,
+                y: 0.,
+                z: 0.,
+            },
+            dir: Vec3d {
+                x: 1.,
+                y: 0.,
+                z: 0.,
+            },
+            time: 0.,
+        };
+        assert_eq!(csg.intersect_with(&ray, None).unwrap().dist, 0.5);
+    }
+
+    #[test]
+    fn parse_operation() {
+        assert_eq!("difference".parse::<Operation>(), Ok(Operation::Difference));
+        assert!("xor".parse::<Operation>().is_err());
+    }
+}
@@ -0,0 +1,233 @@
+use std::sync::Arc;
+use super::{material::Material, nearest_outside, Intersect};
+use crate::coord::Vec3d;
+use crate::scene::camera::Ray;
+
+//Axis-aligned box between two opposite corners
+pub struct Cuboid {
+    pub min: Vec3d,
+    pub max: Vec3d,
+    pub material: Arc<Material>,
+}
+
+impl Cuboid {
+    //corners can be given in any order
+    pub fn new(a: Vec3d, b: Vec3d, material: impl Into<Arc<Material>>) -> Cuboid {
+        Cuboid {
+            min: Vec3d {
+                x: a.x.min(b.x),
+                y: a.y.min(b.y),
+                z: a.z.min(b.z),
+            },
+            max: Vec3d {
+                x: a.x.max(b.x),
+                y: a.y.max(b.y),
+                z: a.z.max(b.z),
+            },
+            material: material.into(),
+        }
+    }
+
+    pub fn intersect(&self, ray: &Ray) -> Option<Intersect> {
+        self.hit(ray).map(|i| i.with_material(&self.material))
+    }
+
+    //intersection without material, like a sphere the box is not seen from inside
+    pub fn hit(&self, ray: &Ray) -> Option<Intersect> {
+        nearest_outside(self.hits(ray), 0.)
+    }
+
+    //entry and exit intersections with the line of the ray
+    pub fn hits(&self, ray: &Ray) -> Vec<Intersect> {
+        //slab test: the ray is inside the box between the last entry in a slab
+        //and the first exit from one
+        let mut near = (f64::NEG_INFINITY, 0);
+        let mut far = (f64::INFINITY, 0);
+        for axis in 0..3 {
+            let start = ray.start.component(axis);
+            let dir = ray.dir.component(axis);
+            let (min, max) = (self.min.component(axis), self.max.component(axis));
+            if dir == 0. {
+                if start < min || start > max {
+                    return vec![];
+                }
+                continue;
+            }
+            let (t1, t2) = ((min - start) / dir, (max - start) / dir);
+            let (t1, t2) = if t1 < t2 { (t1, t2) } else { (t2, t1) };
+            if t1 > near.0 {
+                near = (t1, axis);
+            }
+            if t2 < far.0 {
+                far = (t2, axis);
+            }
+        }
+        if near.0 > far.0 {
+            return vec![];
+        }
+
+        //normals are against the ray on entry, along it on exit
+        [(near, -1.), (far, 1.)]
+            .into_iter()
+            .map(|((dist, axis), side)| {
+                let pos = ray.start + dist * ray.dir;
+                let normal = axis_vec(axis) * (side * ray.dir.component(axis).signum());
+                //u and v follow the two other axes, from 0 to 1 across the face
+                let (u_axis, v_axis) = ((axis + 1) % 3, (axis + 2) % 3);
+                let coord = |axis: usize| {
+                    let (min, max) = (self.min.component(axis), self.max.component(axis));
+                    (pos.component(axis) - min) / (max - min)
+                };
+                Intersect {
+                    pos,
+                    dist,
+                    normal,
+                    tangent: axis_vec(u_axis),
+                    bitangent: axis_vec(v_axis),
+                    uv: (coord(u_axis), coord(v_axis)),
+                    material: Material::unset(),
+                }
+            })
+            .collect()
+    }
+}
+
+fn axis_vec(axis: usize) -> Vec3d {
+    Vec3d {
+        x: if axis == 0 { 1. } else { 0. },
+        y: if axis == 1 { 1. } else { 0. },
+        z: if axis == 2 { 1. } else { 0. },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    fn unit_box() -> Cuboid {
+        Cuboid::new(
+            Vec3d {
+                x: 3.,
+                y: 1.,
+                z: 1.,
+            },
+            Vec3d {
+                x: 1.,
+                y: -1.,
+                z: -1.,
+            },
+            Material::default(),
+        )
+    }
+
+    #[test]
+    fn box_in_front() {
+        let ray = Ray {
+            start: Vec3d {
+                x: 0.,
+                y: 0.5,
+                z: 0.,
+            },
+            dir: Vec3d {
+                x: 1.,
+                y: 0.,
+                z: 0.,
+            },
+            time: 0.,
+        };
+        let i = unit_box().intersect(&ray).unwrap();
+        assert_eq!(i.dist, 1.);
+        assert_eq!(
+            i.normal,
+            Vec3d {
+                x: -1.,
+                y: 0.,
+                z: 0.
+            }
+        );
+        assert_abs_diff_eq!(i.uv.0, 0.75);
+        assert_abs_diff_eq!(i.uv.1, 0.5);
+    }
+
+    #[test]
+    fn box_from_above() {
+        let ray = Ray {
+            start: Vec3d {
+                x: 0.,
+                y: 0.,
+                z: 3.,
+            },
+            dir: Vec3d {
+                x: 1.,
+                y: 0.,
+                z: -1.,
+            },
+            time: 0.,
+        };
+        let i = unit_box().intersect(&ray).unwrap();
+        assert_abs_diff_eq!(
+            i.pos,
+            Vec3d {
+                x: 2.,
+                y: 0.,
+                z: 1.
+            }
+        );
+        assert_eq!(
+            i.normal,
+            Vec3d {
+                x: 0.,
+                y: 0.,
+                z: 1.
+            }
+        );
+    }
+
+    #[test]
+    fn box_missed_or_inside() {
+        let beside = Ray {
+            start: Vec3d {
+                x: 0.,
+                y: 2.,
+                z: 0.,
+            },
+            dir: Vec3d {
+                x: 1.,
+                y: 0.,
+                z: 0.,
+            },
+            time: 0.,
+        };
+        let behind = Ray {
+            start: Vec3d {
+                x: 4.,
+                y: 0.,
+                z: 0.,
+            },
+            dir: Vec3d {
+                x: 1.,
+                y: 0.,
+                z: 0.,
+            },
+            time: 0.,
+        };
+        let inside = Ray {
+            start: Vec3d {
+                x: 2.,
+                y: 0.,
+                z: 0.,
+            },
+            dir: Vec3d {
+                x: 0.,
+                y: 1.,
+                z: 1.,
+            },
+            time: 0.,
+        };
+        assert_eq!(unit_box().intersect(&beside), None);
+        assert_eq!(unit_box().intersect(&behind), None);
+        assert_eq!(unit_box().intersect(&inside), None);
+    }
+}
@@ -0,0 +1,410 @@
+use std::sync::Arc;
+
+use super::texture::Image;
+use super::{material::Material, Intersect};
+use crate::coord::Vec3d;
+use crate::scene::camera::Ray;
+
+//Terrain from a grid of height samples, seen from above. Each cell between
+//four samples is split in two triangles, normals are interpolated between the
+//samples like on a smooth mesh.
+pub struct Heightfield {
+    //corner with the lowest coordinates, and extents along x, y and z
+    pub pos: Vec3d,
+    pub size: Vec3d,
+    //number of samples along x and y
+    pub nx: usize,
+    pub ny: usize,
+    pub material: Arc<Material>,
+    heights: Vec<f64>,
+    normals: Vec<Vec3d>,
+    //lowest and highest sample of each cell
+    cells: Vec<(f64, f64)>,
+}
+
+impl Heightfield {
+    //samples from 0 to 1 row by row, the first row is at the lowest y
+    pub fn new(pos: Vec3d, size: Vec3d, nx: usize, ny: usize, samples: &[f64], material: impl Into<Arc<Material>>) -> Heightfield {
+        assert!(nx >= 2 && ny >= 2, "heightfield needs at least 2x2 samples");
+        assert_eq!(samples.len(), nx * ny, "heightfield samples do not match its size");
+        let heights: Vec<f64> = samples.iter().map(|h| pos.z + h * size.z).collect();
+        let mut heightfield = Heightfield {
+            pos,
+            size,
+            nx,
+            ny,
+            material: material.into(),
+            heights,
+            normals: Vec::with_capacity(nx * ny),
+            cells: Vec::with_capacity((nx - 1) * (ny - 1)),
+        };
+
+        //normals from the slopes between the neighbours of each sample
+        let (dx, dy) = heightfield.spacing();
+        for j in 0..ny {
+            for i in 0..nx {
+                let (i0, i1) = (i.saturating_sub(1), (i + 1).min(nx - 1));
+                let (j0, j1) = (j.saturating_sub(1), (j + 1).min(ny - 1));
+                let slope_x = (heightfield.height(i1, j) - heightfield.height(i0, j)) / ((i1 - i0) as f64 * dx);
+                let slope_y = (heightfield.height(i, j1) - heightfield.height(i, j0)) / ((j1 - j0) as f64 * dy);
+                let normal = Vec3d {
+                    x: -slope_x,
+                    y: -slope_y,
+                    z: 1.,
+                };
+                heightfield.normals.push(normal.normalize().unwrap());
+            }
+        }
+        for j in 0..ny - 1 {
+            for i in 0..nx - 1 {
+                let corners = [(i, j), (i + 1, j), (i, j + 1), (i + 1, j + 1)].map(|(i, j)| heightfield.height(i, j));
+                let min = corners.iter().cloned().fold(f64::INFINITY, f64::min);
+                let max = corners.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+                heightfield.cells.push((min, max));
+            }
+        }
+        heightfield
+    }
+
+    //8 or 16 bits grayscale image, its top row is at the highest y
+    pub fn from_image(image: &Image, pos: Vec3d, size: Vec3d, material: impl Into<Arc<Material>>) -> Result<Heightfield, String> {
+        if image.width < 2 || image.height < 2 {
+            return Err(format!("{} is too small for a heightfield (at least 2x2 pixels)", image.path));
+        }
+        let samples: Vec<f64> = (0..image.height)
+            .rev()
+            .flat_map(|row| {
+                image.pixels[row * image.width..(row + 1) * image.width]
+                    .iter()
+                    .map(|p| (p.r + p.g + p.b) / 3.)
+            })
+            .collect();
+        Ok(Heightfield::new(pos, size, image.width, image.height, &samples, material))
+    }
+
+    pub fn intersect(&self, ray: &Ray) -> Option<Intersect> {
+        self.hit(ray).map(|i| i.with_material(&self.material))
+    }
+
+    //intersection without material
+    pub fn hit(&self, ray: &Ray) -> Option<Intersect> {
+        let (t_enter, t_exit) = self.bounds(ray)?;
+        let t_enter = t_enter.max(0.);
+
+        //walk the cells crossed by the ray in the xy plane (DDA),
+        //in grid coordinates where cells are 1 wide
+        let (dx, dy) = self.spacing();
+        let gx = |t: f64| (ray.start.x + t * ray.dir.x - self.pos.x) / dx;
+        let gy = |t: f64| (ray.start.y + t * ray.dir.y - self.pos.y) / dy;
+        let mut i = (gx(t_enter).floor().max(0.) as usize).min(self.nx - 2);
+        let mut j = (gy(t_enter).floor().max(0.) as usize).min(self.ny - 2);
+
+        //distance to the next cell border and between two borders, along each axis
+        let axis = |dir: f64, spacing: f64, border: usize, start: f64| {
+            if dir == 0. {
+                return (f64::INFINITY, f64::INFINITY);
+            }
+            let border = if dir > 0. { border + 1 } else { border } as f64 * spacing;
+            ((border - start) / dir, spacing / dir.abs())
+        };
+        let (mut next_x, delta_x) = axis(ray.dir.x, dx, i, ray.start.x - self.pos.x);
+        let (mut next_y, delta_y) = axis(ray.dir.y, dy, j, ray.start.y - self.pos.y);
+
+        let mut t_in = t_enter;
+        loop {
+            let t_out = next_x.min(next_y).min(t_exit);
+            //skip cells the ray passes above or below
+            let (min, max) = self.cells[j * (self.nx - 1) + i];
+            let (z_in, z_out) = (ray.start.z + t_in * ray.dir.z, ray.start.z + t_out * ray.dir.z);
+            if z_in.max(z_out) >= min && z_in.min(z_out) <= max {
+                if let Some(hit) = self.hit_cell(ray, i, j) {
+                    return Some(hit);
+                }
+            }
+            if t_out >= t_exit {
+                return None;
+            }
+            if next_x < next_y {
+                if (ray.dir.x < 0. && i == 0) || (ray.dir.x > 0. && i == self.nx - 2) {
+                    return None;
+                }
+                i = if ray.dir.x > 0. { i + 1 } else { i - 1 };
+                t_in = next_x;
+                next_x += delta_x;
+            } else {
+                if (ray.dir.y < 0. && j == 0) || (ray.dir.y > 0. && j == self.ny - 2) {
+                    return None;
+                }
+                j = if ray.dir.y > 0. { j + 1 } else { j - 1 };
+                t_in = next_y;
+                next_y += delta_y;
+            }
+        }
+    }
+
+    //nearest intersection with the two triangles of a cell
+    fn hit_cell(&self, ray: &Ray, i: usize, j: usize) -> Option<Intersect> {
+        let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+        [[corners[0], corners[1], corners[2]], [corners[0], corners[2], corners[3]]]
+            .into_iter()
+            .filter_map(|triangle| self.hit_triangle(ray, triangle))
+            .min_by(|a, b| a.dist.total_cmp(&b.dist))
+    }
+
+    //Möller-Trumbore, only from above the triangle
+    fn hit_triangle(&self, ray: &Ray, [a, b, c]: [(usize, usize); 3]) -> Option<Intersect> {
+        let (pa, pb, pc) = (self.point(a), self.point(b), self.point(c));
+        let (e1, e2) = (pb - pa, pc - pa);
+        let p = ray.dir.cross(e2);
+        let det = e1.dot(p);
+        if det <= 0. {
+            return None;
+        }
+        let s = ray.start - pa;
+        let u = s.dot(p) / det;
+        if !(0. ..=1.).contains(&u) {
+            return None;
+        }
+        let q = s.cross(e1);
+        let v = ray.dir.dot(q) / det;
+        if v < 0. || u + v > 1. {
+            return None;
+        }
+        let dist = e2.dot(q) / det;
+        if dist <= 0. {
+            return None;
+        }
+
+        let normal = (self.normal(a) * (1. - u - v) + self.normal(b) * u + self.normal(c) * v)
+            .normalize()
+            .unwrap();
+        let x_axis = Vec3d {
+            x: 1.,
+            y: 0.,
+            z: 0.,
+        };
+        let tangent = (x_axis - normal * normal.dot(x_axis)).normalize().unwrap();
+        let pos = ray.start + dist * ray.dir;
+        Some(Intersect {
+            pos,
+            dist,
+            normal,
+            tangent,
+            //v goes down the image, toward the lowest y
+            bitangent: tangent.cross(normal),
+            uv: (
+                (pos.x - self.pos.x) / self.size.x,
+                1. - (pos.y - self.pos.y) / self.size.y,
+            ),
+            material: Material::unset(),
+        })
+    }
+
+    //slab test against the bounding box, None if it is missed or behind the ray
+    fn bounds(&self, ray: &Ray) -> Option<(f64, f64)> {
+        let (mut near, mut far) = (f64::NEG_INFINITY, f64::INFINITY);
+        for axis in 0..3 {
+            let start = ray.start.component(axis);
+            let dir = ray.dir.component(axis);
+            let min = self.pos.component(axis);
+            let max = min + self.size.component(axis);
+            if dir == 0. {
+                if start < min || start > max {
+                    return None;
+                }
+                continue;
+            }
+            let (t1, t2) = ((min - start) / dir, (max - start) / dir);
+            near = near.max(t1.min(t2));
+            far = far.min(t1.max(t2));
+        }
+        if near > far || far < 0. {
+            None
+        } else {
+            Some((near, far))
+        }
+    }
+
+    fn spacing(&self) -> (f64, f64) {
+        (self.size.x / (self.nx - 1) as f64, self.size.y / (self.ny - 1) as f64)
+    }
+
+    fn height(&self, i: usize, j: usize) -> f64 {
+        self.heights[j * self.nx + i]
+    }
+
+    fn normal(&self, (i, j): (usize, usize)) -> Vec3d {
+        self.normals[j * self.nx + i]
+    }
+
+    fn point(&self, (i, j): (usize, usize)) -> Vec3d {
+        let (dx, dy) = self.spacing();
+        Vec3d {
+            x: self.pos.x + i as f64 * dx,
+            y: self.pos.y + j as f64 * dy,
+            z: self.height(i, j),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use approx::assert_abs_diff_eq;
+    use std::fs::File;
+
+    //3x3 samples over [0, 2]x[0, 2], a peak of height 1 in the middle
+    fn create_peak() -> Heightfield {
+        Heightfield::new(
+            Vec3d {
+                x: 0.,
+                y: 0.,
+                z: 0.,
+            },
+            Vec3d {
+                x: 2.,
+                y: 2.,
+                z: 1.,
+            },
+            3,
+            3,
+            &[0., 0., 0., 0., 1., 0., 0., 0., 0.],
+            Material::default(),
+        )
+    }
+
+    #[test]
+    fn heightfield_from_above() {
+        let ray = Ray {
+            start: Vec3d {
+                x: 1.,
+                y: 1.,
+                z: 5.,
+            },
+            dir: Vec3d {
+                x: 0.,
+                y: 0.,
+                z: -1.,
+            },
+            time: 0.,
+        };
+        let i = create_peak().intersect(&ray).unwrap();
+        assert_abs_diff_eq!(i.dist, 4., epsilon = 1e-12);
+        //the normal is interpolated, straight up at the top of the peak
+        assert_abs_diff_eq!(
+            i.normal,
+            Vec3d {
+                x: 0.,
+                y: 0.,
+                z: 1.
+            },
+            epsilon = 1e-12
+        );
+        assert_abs_diff_eq!(i.uv.0, 0.5, epsilon = 1e-12);
+        assert_abs_diff_eq!(i.uv.1, 0.5, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn heightfield_grazing() {
+        let heightfield = create_peak();
+        //low ray along x, crossing several cells before the slope of the peak
+        let ray = Ray {
+            start: Vec3d {
+                x: -3.,
+                y: 0.9,
+                z: 0.5,
+            },
+            dir: Vec3d {
+                x: 1.,
+                y: 0.,
+                z: 0.,
+            },
+            time: 0.,
+        };
+        let i = heightfield.intersect(&ray).unwrap();
+        assert_abs_diff_eq!(i.pos.x, 0.5, epsilon = 1e-12);
+        assert!(i.normal.x < 0.);
+
+        //above the peak, or from below the ground
+        let above = Ray {
+            start: Vec3d {
+                x: -3.,
+                y: 0.9,
+                z: 1.5,
+            },
+            ..ray
+        };
+        let below = Ray {
+            start: Vec3d {
+                x: 0.5,
+                y: 0.5,
+                z: -1.,
+            },
+            dir: Vec3d {
+                x: 0.,
+                y: 0.,
+                z: 1.,
+            },
+            time: 0.,
+        };
+        assert_eq!(heightfield.intersect(&above), None);
+        assert_eq!(heightfield.intersect(&below), None);
+    }
+
+    #[test]
+    fn heightfield_diagonal() {
+        let heightfield = create_peak();
+        //going down toward -x and -y, from outside the grid
+        let ray = Ray {
+            start: Vec3d {
+                x: 3.,
+                y: 2.8,
+                z: 0.1,
+            },
+            dir: Vec3d {
+                x: -1.,
+                y: -1.,
+                z: 0.,
+            },
+            time: 0.,
+        };
+        let i = heightfield.intersect(&ray).unwrap();
+        assert!(i.pos.x < 2. && i.pos.y < 2.);
+        assert_abs_diff_eq!(i.pos.z, 0.1, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn load_heightfield() {
+        let path = std::env::temp_dir().join("raytracer_heightfield.png");
+        {
+            //2x2 image, only the top left sample is high
+            let file = File::create(&path).unwrap();
+            let mut encoder = png::Encoder::new(file, 2, 2);
+            encoder.set_color(png::ColorType::Grayscale);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&[255, 0, 0, 0]).unwrap();
+        }
+        let heightfield = Heightfield::from_image(
+            &Image::load(&path).unwrap(),
+            Vec3d {
+                x: 0.,
+                y: 0.,
+                z: 1.,
+            },
+            Vec3d {
+                x: 1.,
+                y: 1.,
+                z: 2.,
+            },
+            Material::default(),
+        )
+        .unwrap();
+        assert_eq!((heightfield.nx, heightfield.ny), (2, 2));
+        //the top row of the image is at the highest y
+        assert_eq!(heightfield.height(0, 1), 3.);
+        assert_eq!(heightfield.height(0, 0), 1.);
+        assert_eq!(heightfield.height(1, 1), 1.);
+    }
+}
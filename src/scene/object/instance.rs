@@ -0,0 +1,301 @@
+use std::sync::Arc;
+
+use super::{material::Material, Intersect, Object};
+use crate::coord::Matrix4;
+use crate::scene::camera::Ray;
+use crate::scene::motion::Motion;
+
+//Shared geometry placed in the scene with its own transform,
+//and optionally its own material. A moving instance is first moved by its
+//motion at the time of the ray, then by its transform.
+pub struct Instance {
+    pub object: Arc<Object>,
+    pub material: Option<Arc<Material>>,
+    pub motion: Option<Motion>,
+    transform: Matrix4,
+    inverse: Matrix4,
+    normal_matrix: Matrix4,
+}
+
+impl Instance {
+    pub fn new(object: Arc<Object>, transform: Matrix4, material: Option<Arc<Material>>) -> Instance {
+        let inverse = transform.inverse().expect("instance transform cannot be inverted");
+        Instance {
+            object,
+            material,
+            motion: None,
+            transform,
+            inverse,
+            normal_matrix: transform.normal_matrix().unwrap(),
+        }
+    }
+
+    pub fn with_motion(mut self, motion: Motion) -> Instance {
+        self.motion = Some(motion);
+        self
+    }
+
+    pub fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    //the material of an outer instance takes precedence over this one
+    pub fn intersect_with(&self, ray: &Ray, material: Option<&Arc<Material>>) -> Option<Intersect> {
+        let material = material.or(self.material.as_ref());
+        let frame = self.frame(ray.time);
+        let i = self.object.intersect_with(&frame.local_ray(ray), material)?;
+        Some(frame.to_world(i))
+    }
+
+    pub fn intersect_all<'a>(&'a self, ray: &Ray, material: Option<&'a Arc<Material>>) -> Vec<(Intersect, &'a Arc<Material>)> {
+        let material = material.or(self.material.as_ref());
+        let frame = self.frame(ray.time);
+        self.object
+            .intersect_all(&frame.local_ray(ray), material)
+            .into_iter()
+            .map(|(i, m)| (frame.to_world(i), m))
+            .collect()
+    }
+
+    //matrices of the instance at a given time
+    fn frame(&self, time: f64) -> Frame {
+        match &self.motion {
+            None => Frame {
+                transform: self.transform,
+                inverse: self.inverse,
+                normal_matrix: self.normal_matrix,
+            },
+            Some(motion) => {
+                let transform = self.transform * motion.at(time);
+                Frame {
+                    transform,
+                    inverse: transform.inverse().expect("motions keep their scales away from 0"),
+                    normal_matrix: transform.normal_matrix().unwrap(),
+                }
+            }
+        }
+    }
+}
+
+struct Frame {
+    transform: Matrix4,
+    inverse: Matrix4,
+    normal_matrix: Matrix4,
+}
+
+impl Frame {
+    //the direction is not normalized, so that distances along the ray stay the same
+    fn local_ray(&self, ray: &Ray) -> Ray {
+        Ray {
+            start: self.inverse.transform_point(ray.start),
+            dir: self.inverse.transform_vector(ray.dir),
+            time: ray.time,
+        }
+    }
+
+    fn to_world(&self, i: Intersect) -> Intersect {
+        let normal = self.normal_matrix.transform_vector(i.normal);
+        Intersect {
+            pos: self.transform.transform_point(i.pos),
+            normal: normal.normalize().unwrap(),
+            tangent: self.transform.transform_vector(i.tangent).normalize().unwrap(),
+            bitangent: self.transform.transform_vector(i.bitangent).normalize().unwrap(),
+            ..i
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::coord::Vec3d;
+    use crate::scene::object::sphere::Sphere;
+    use approx::assert_abs_diff_eq;
+    use std::f64::consts::PI;
+
+    fn unit_sphere() -> Arc<Object> {
+        Arc::new(Object::Sphere(Sphere::new(
+            Vec3d {
+                x: 0.,
+                y: 0.,
+                z: 0.,
+            },
+            1.,
+            Material::default(),
+        )))
+    }
+
+    fn ray_along_x() -> Ray {
+        Ray {
+            start: Vec3d {
+                x: 0.,
+                y: 0.,
+                z: 0.,
+            },
+            dir: Vec3d {
+                x: 1.,
+                y: 0.,
+                z: 0.,
+            },
+            time: 0.,
+        }
+    }
+
+    #[test]
+    fn translated_sphere() {
+        let instance = Instance::new(
+            unit_sphere(),
+            Matrix4::translation(Vec3d {
+                x: 3.,
+                y: 0.,
+                z: 0.,
+            }),
+            None,
+        );
+        let i = instance.intersect_with(&ray_along_x(), None).unwrap();
+        assert_abs_diff_eq!(i.dist, 2.);
+        assert_abs_diff_eq!(
+            i.pos,
+            Vec3d {
+                x: 2.,
+                y: 0.,
+                z: 0.
+            }
+        );
+        assert_abs_diff_eq!(
+            i.normal,
+            Vec3d {
+                x: -1.,
+                y: 0.,
+                z: 0.
+            }
+        );
+    }
+
+    #[test]
+    fn scaled_sphere() {
+        //ellipsoid, 4 long on x
+        let transform = Matrix4::translation(Vec3d {
+            x: 10.,
+            y: 0.,
+            z: 0.,
+        }) * Matrix4::scaling(Vec3d {
+            x: 4.,
+            y: 1.,
+            z: 1.,
+        });
+        let instance = Instance::new(unit_sphere(), transform, None);
+        let i = instance.intersect_with(&ray_along_x(), None).unwrap();
+        assert_abs_diff_eq!(i.dist, 6.);
+        assert_abs_diff_eq!(i.pos.x, 6.);
+    }
+
+    #[test]
+    fn rotated_normal() {
+        //rotating the sphere does not change its shape
+        let transform = Matrix4::translation(Vec3d {
+            x: 3.,
+            y: 0.,
+            z: 0.,
+        }) * Matrix4::rotation(
+            Vec3d {
+                x: 0.,
+                y: 1.,
+                z: 1.,
+            },
+            PI / 3.,
+        );
+        let instance = Instance::new(unit_sphere(), transform, None);
+        let i = instance.intersect_with(&ray_along_x(), None).unwrap();
+        assert_abs_diff_eq!(i.dist, 2., epsilon = 1e-12);
+        assert_abs_diff_eq!(
+            i.normal,
+            Vec3d {
+                x: -1.,
+                y: 0.,
+                z: 0.
+            },
+            epsilon = 1e-12
+        );
+    }
+
+    #[test]
+    fn material_override() {
+        let mut red = Material::default();
+        red.diffuse.r = 1.;
+        let inner = Instance::new(unit_sphere(), Matrix4::identity(), Some(Arc::new(red.clone())));
+        let mut green = Material::default();
+        green.diffuse.g = 1.;
+        let outer = Instance::new(
+            Arc::new(Object::Instance(Box::new(inner))),
+            Matrix4::translation(Vec3d {
+                x: 3.,
+                y: 0.,
+                z: 0.,
+            }),
+            Some(Arc::new(green.clone())),
+        );
+        let i = outer.intersect_with(&ray_along_x(), None).unwrap();
+        assert_eq!(*i.material, green);
+
+        let Object::Instance(inner) = outer.object.as_ref() else {
+            panic!("instance expected")
+        };
+        let ray = Ray {
+            start: Vec3d {
+                x: -3.,
+                y: 0.,
+                z: 0.,
+            },
+            ..ray_along_x()
+        };
+        let i = inner.intersect_with(&ray, None).unwrap();
+        assert_eq!(*i.material, red);
+    }
+
+    #[test]
+    fn missed() {
+        let instance = Instance::new(
+            unit_sphere(),
+            Matrix4::translation(Vec3d {
+                x: 3.,
+                y: 3.,
+                z: 0.,
+            }),
+            None,
+        );
+        assert_eq!(instance.intersect_with(&ray_along_x(), None), None);
+    }
+
+    #[test]
+    fn moving_instance() {
+        use crate::scene::motion::Transform;
+        //moves up along y while the shutter is open
+        let position = |y| {
+            vec![Transform::Translate(Vec3d {
+                x: 3.,
+                y,
+                z: 0.,
+            })]
+        };
+        let instance = Instance::new(unit_sphere(), Matrix4::identity(), None)
+            .with_motion(Motion::new(vec![(0., position(0.)), (1., position(4.))]).unwrap());
+        let at = |time| Ray {
+            time,
+            ..ray_along_x()
+        };
+        assert_abs_diff_eq!(instance.intersect_with(&at(0.), None).unwrap().dist, 2.);
+        assert_eq!(instance.intersect_with(&at(0.5), None), None);
+
+        let ray = Ray {
+            start: Vec3d {
+                x: 0.,
+                y: 2.,
+                z: 0.,
+            },
+            ..at(0.5)
+        };
+        assert_abs_diff_eq!(instance.intersect_with(&ray, None).unwrap().dist, 2.);
+    }
+}
@@ -1,10 +1,17 @@
 use std::ops;
+use std::sync::{Arc, OnceLock};
+
+use serde::{Deserialize, Serialize};
+
+use super::texture::Texture;
+use super::Intersect;
+use crate::coord::Vec3d;
 
 extern crate approx;
 use approx::AbsDiffEq;
 use std::f64;
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub struct Color {
     pub r: f64,
     pub g: f64,
@@ -17,6 +24,43 @@ pub struct Material {
     pub specular: Color,
     pub reflectivity: f64,
     pub roughness: f64,
+    pub sheen: f64,
+    pub clearcoat: f64,
+    pub transmission: Color,
+    pub ior: f64,
+    pub textures: Vec<(Channel, Texture)>,
+    pub normal_map: Option<Texture>,
+    //height texture and its strength
+    pub bump_map: Option<(Texture, f64)>,
+}
+
+//Material parameters that can be read from a texture
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Channel {
+    Diffuse,
+    Specular,
+    Reflectivity,
+    Roughness,
+    Sheen,
+    Clearcoat,
+    Transmission,
+    Ior,
+}
+
+//Principled (Disney-like) parameters, as authored in DCC tools
+//converted into the shading terms of a Material
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(default = "Principled::default")]
+pub struct Principled {
+    pub base_color: Color,
+    pub metallic: f64,
+    pub roughness: f64,
+    pub specular: f64,
+    pub sheen: f64,
+    pub clearcoat: f64,
+    pub transmission: f64,
+    pub ior: f64,
 }
 
 impl Material {
@@ -38,10 +82,125 @@ impl Material {
             specular,
             reflectivity,
             roughness,
+            sheen: 0.,
+            clearcoat: 0.,
+            transmission: Color {
+                r: 0.,
+                g: 0.,
+                b: 0.,
+            },
+            ior: 1.,
+            textures: vec![],
+            normal_map: None,
+            bump_map: None,
+        }
+    }
+
+    //normal of the intersection perturbed by the normal and bump maps
+    pub fn shading_normal(&self, i: &Intersect) -> Vec3d {
+        let mut normal = i.normal;
+        if let Some(texture) = &self.normal_map {
+            //tangent space, green pointing to decreasing v (OpenGL convention)
+            let c = texture.sample(i.uv, i.pos);
+            let n = i.tangent * (2. * c.r - 1.) - i.bitangent * (2. * c.g - 1.)
+                + normal * (2. * c.b - 1.);
+            normal = n.normalize().unwrap_or(normal);
+        }
+        if let Some((texture, strength)) = &self.bump_map {
+            let slope = texture.slope(i.uv, i.pos, i.tangent, i.bitangent);
+            let slope = slope - normal * normal.dot(slope);
+            normal = (normal - slope * *strength).normalize().unwrap_or(normal);
+        }
+        normal
+    }
+
+    //placeholder of intersections whose material is not known yet, shared
+    //so that they do not allocate
+    pub fn unset() -> Arc<Material> {
+        static UNSET: OnceLock<Arc<Material>> = OnceLock::new();
+        UNSET.get_or_init(|| Arc::new(Material::default())).clone()
+    }
+
+    //Material with its textured channels evaluated at an intersection point,
+    //the material itself when it has no texture
+    pub fn at(self: &Arc<Material>, uv: (f64, f64), pos: Vec3d) -> Arc<Material> {
+        if self.textures.is_empty() {
+            return self.clone();
+        }
+        let mut material = Material::clone(self);
+        for (channel, texture) in self.textures.iter() {
+            match channel {
+                Channel::Diffuse => material.diffuse = texture.sample(uv, pos),
+                Channel::Specular => material.specular = texture.sample(uv, pos),
+                Channel::Reflectivity => material.reflectivity = texture.sample_scalar(uv, pos),
+                Channel::Roughness => material.roughness = texture.sample_scalar(uv, pos),
+                Channel::Sheen => material.sheen = texture.sample_scalar(uv, pos),
+                Channel::Clearcoat => material.clearcoat = texture.sample_scalar(uv, pos),
+                Channel::Transmission => material.transmission = texture.sample(uv, pos),
+                Channel::Ior => material.ior = texture.sample_scalar(uv, pos),
+            }
+        }
+        Arc::new(material)
+    }
+}
+
+impl Principled {
+    pub fn default() -> Principled {
+        Principled {
+            base_color: Color {
+                r: 0.8,
+                g: 0.8,
+                b: 0.8,
+            },
+            metallic: 0.,
+            roughness: 0.5,
+            specular: 0.5,
+            sheen: 0.,
+            clearcoat: 0.,
+            transmission: 0.,
+            ior: 1.45,
+        }
+    }
+
+    pub fn to_material(&self) -> Material {
+        let white = Color {
+            r: 1.,
+            g: 1.,
+            b: 1.,
+        };
+        let metallic = self.metallic.clamp(0., 1.);
+        let dielectric = 1. - metallic;
+        let transmission = self.transmission.clamp(0., 1.);
+
+        //specular = 0.5 is a 4% reflectance at normal incidence
+        let dielectric_reflectance = 0.08 * self.specular.clamp(0., 1.);
+
+        Material {
+            diffuse: self.base_color * (dielectric * (1. - transmission)),
+            specular: white * dielectric + self.base_color * metallic,
+            reflectivity: dielectric_reflectance * dielectric + metallic,
+            roughness: self.roughness,
+            sheen: self.sheen * dielectric,
+            clearcoat: self.clearcoat.clamp(0., 1.),
+            transmission: self.base_color * (dielectric * transmission),
+            ior: self.ior,
+            textures: vec![],
+            normal_map: None,
+            bump_map: None,
         }
     }
 }
 
+impl Color {
+    pub fn gray(v: f64) -> Color {
+        Color { r: v, g: v, b: v }
+    }
+
+    pub fn is_black(&self) -> bool {
+        self.r <= 0. && self.g <= 0. && self.b <= 0.
+    }
+}
+
 impl ops::Add<Color> for Color {
     type Output = Color;
 
@@ -108,7 +267,10 @@ impl AbsDiffEq for Color {
 mod tests {
 
     use super::*;
+    use crate::scene::object::texture::procedural::{Pattern, Procedural};
+    use crate::scene::object::texture::{Image, ImageTexture};
     use approx::assert_abs_diff_eq;
+    use std::sync::Arc;
 
     #[test]
     fn default_material() {
@@ -116,6 +278,157 @@ mod tests {
         let _ = Material::default();
     }
 
+    #[test]
+    fn textured_material() {
+        let white = Color {
+            r: 1.,
+            g: 1.,
+            b: 1.,
+        };
+        let image = Image {
+            path: String::from("test"),
+            width: 1,
+            height: 1,
+            pixels: vec![white],
+        };
+        let mut material = Material::default();
+        let texture = Texture::Image(ImageTexture::new(Arc::new(image)));
+        material.textures.push((Channel::Diffuse, texture.clone()));
+        material.textures.push((Channel::Roughness, texture));
+        let material = Arc::new(material);
+
+        let textured = material.at((0.3, 0.6), Vec3d { x: 0., y: 0., z: 0. });
+        assert_eq!(textured.diffuse, white);
+        assert_eq!(textured.roughness, 1.);
+        assert!(textured.specular.is_black());
+    }
+
+    #[test]
+    fn procedural_material() {
+        let mut checker = Procedural::new(Pattern::Checker, (Color::gray(0.1), Color::gray(0.9)));
+        checker.scale = 2.;
+        let mut material = Material::default();
+        material.textures.push((Channel::Reflectivity, Texture::Procedural(checker)));
+        let material = Arc::new(material);
+
+        let dark = material.at((0., 0.), Vec3d { x: 1., y: 1., z: 1. });
+        let light = material.at((0., 0.), Vec3d { x: 3., y: 1., z: 1. });
+        assert_abs_diff_eq!(dark.reflectivity, 0.1);
+        assert_abs_diff_eq!(light.reflectivity, 0.9);
+    }
+
+    fn create_intersect() -> Intersect {
+        Intersect {
+            pos: Vec3d { x: 0., y: 0., z: 0. },
+            dist: 1.,
+            normal: Vec3d { x: 0., y: 0., z: 1. },
+            tangent: Vec3d { x: 1., y: 0., z: 0. },
+            bitangent: Vec3d { x: 0., y: 1., z: 0. },
+            uv: (0.5, 0.5),
+            material: Material::unset(),
+        }
+    }
+
+    fn single_color_texture(color: Color) -> Texture {
+        let image = Image {
+            path: String::from("test"),
+            width: 1,
+            height: 1,
+            pixels: vec![color],
+        };
+        Texture::Image(ImageTexture::new(Arc::new(image)))
+    }
+
+    #[test]
+    fn flat_normal_map() {
+        let mut material = Material::default();
+        material.normal_map = Some(single_color_texture(Color { r: 0.5, g: 0.5, b: 1. }));
+        let i = create_intersect();
+        assert_abs_diff_eq!(material.shading_normal(&i), i.normal);
+    }
+
+    #[test]
+    fn tilted_normal_map() {
+        let mut material = Material::default();
+        material.normal_map = Some(single_color_texture(Color { r: 1., g: 0.5, b: 1. }));
+        let i = create_intersect();
+        let sq = 0.5_f64.sqrt();
+        assert_abs_diff_eq!(material.shading_normal(&i), Vec3d { x: sq, y: 0., z: sq });
+    }
+
+    #[test]
+    fn procedural_bump() {
+        //height growing along z, the bitangent of a surface facing -x
+        let mut gradient = Procedural::new(Pattern::Gradient, (Color::gray(0.), Color::gray(1.)));
+        gradient.scale = 1.;
+        let mut material = Material::default();
+        material.bump_map = Some((Texture::Procedural(gradient), 1.));
+        let mut i = create_intersect();
+        i.pos = Vec3d { x: 0., y: 0., z: 0.5 };
+        i.normal = Vec3d { x: -1., y: 0., z: 0. };
+        i.tangent = Vec3d { x: 0., y: 1., z: 0. };
+        i.bitangent = Vec3d { x: 0., y: 0., z: 1. };
+        //the surface rises toward +z, the normal leans toward -z
+        let normal = material.shading_normal(&i);
+        let sq = 0.5_f64.sqrt();
+        assert_abs_diff_eq!(normal, Vec3d { x: -sq, y: 0., z: -sq }, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn principled_dielectric() {
+        let mut principled = Principled::default();
+        principled.base_color = Color {
+            r: 1.,
+            g: 0.5,
+            b: 0.,
+        };
+        let mat = principled.to_material();
+        assert_abs_diff_eq!(mat.diffuse, principled.base_color);
+        assert_abs_diff_eq!(
+            mat.specular,
+            Color {
+                r: 1.,
+                g: 1.,
+                b: 1.
+            }
+        );
+        assert_abs_diff_eq!(mat.reflectivity, 0.04);
+        assert!(mat.transmission.is_black());
+    }
+
+    #[test]
+    fn principled_metal() {
+        let mut principled = Principled::default();
+        principled.base_color = Color {
+            r: 1.,
+            g: 0.8,
+            b: 0.3,
+        };
+        principled.metallic = 1.;
+        principled.sheen = 1.;
+        let mat = principled.to_material();
+        assert!(mat.diffuse.is_black());
+        assert_abs_diff_eq!(mat.specular, principled.base_color);
+        assert_abs_diff_eq!(mat.reflectivity, 1.);
+        assert_eq!(mat.sheen, 0.);
+    }
+
+    #[test]
+    fn principled_glass() {
+        let mut principled = Principled::default();
+        principled.base_color = Color {
+            r: 1.,
+            g: 1.,
+            b: 1.,
+        };
+        principled.transmission = 1.;
+        principled.ior = 1.5;
+        let mat = principled.to_material();
+        assert!(mat.diffuse.is_black());
+        assert_abs_diff_eq!(mat.transmission, principled.base_color);
+        assert_eq!(mat.ior, 1.5);
+    }
+
     #[test]
     fn add_color() {
         let light1 = Color {
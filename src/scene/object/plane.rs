@@ -0,0 +1,217 @@
+use std::sync::Arc;
+use super::{material::Material, Intersect};
+use crate::coord::Vec3d;
+use crate::scene::camera::Ray;
+
+//Infinite plane, seen from the side its normal points to
+pub struct Plane {
+    pub pos: Vec3d,
+    pub normal: Vec3d,
+    pub material: Arc<Material>,
+}
+
+//Disk of the plane around its center
+pub struct Disk {
+    pub center: Vec3d,
+    pub normal: Vec3d,
+    pub radius: f64,
+    pub material: Arc<Material>,
+}
+
+impl Plane {
+    pub fn new(pos: Vec3d, normal: Vec3d, material: impl Into<Arc<Material>>) -> Plane {
+        Plane {
+            pos,
+            normal: normal.normalize().expect("plane normal cannot be null"),
+            material: material.into(),
+        }
+    }
+
+    pub fn intersect(&self, ray: &Ray) -> Option<Intersect> {
+        self.hit(ray).map(|i| i.with_material(&self.material))
+    }
+
+    //intersection without material, uv are the coordinates in the plane from pos
+    pub fn hit(&self, ray: &Ray) -> Option<Intersect> {
+        hit_plane(ray, self.pos, self.normal)
+    }
+}
+
+impl Disk {
+    pub fn new(center: Vec3d, normal: Vec3d, radius: f64, material: impl Into<Arc<Material>>) -> Disk {
+        Disk {
+            center,
+            normal: normal.normalize().expect("disk normal cannot be null"),
+            radius,
+            material: material.into(),
+        }
+    }
+
+    pub fn intersect(&self, ray: &Ray) -> Option<Intersect> {
+        self.hit(ray).map(|i| i.with_material(&self.material))
+    }
+
+    //intersection without material, uv cover the square around the disk
+    pub fn hit(&self, ray: &Ray) -> Option<Intersect> {
+        let i = hit_plane(ray, self.center, self.normal)?;
+        if (i.pos - self.center).normsq() > self.radius * self.radius {
+            return None;
+        }
+        let diameter = 2. * self.radius;
+        Some(Intersect {
+            uv: (0.5 + i.uv.0 / diameter, 0.5 + i.uv.1 / diameter),
+            ..i
+        })
+    }
+}
+
+fn hit_plane(ray: &Ray, pos: Vec3d, normal: Vec3d) -> Option<Intersect> {
+    //ray is parallel to the plane or would hit it from the back
+    let cos = ray.dir.dot(normal);
+    if cos >= 0. {
+        return None;
+    }
+
+    let dist = (pos - ray.start).dot(normal) / cos;
+    if dist <= 0. {
+        return None;
+    }
+
+    let intersect_point = ray.start + dist * ray.dir;
+    let (tangent, bitangent) = normal.orthonormal_basis();
+    let local = intersect_point - pos;
+    Some(Intersect {
+        pos: intersect_point,
+        dist,
+        normal,
+        tangent,
+        bitangent,
+        uv: (local.dot(tangent), local.dot(bitangent)),
+        material: Material::unset(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    fn floor() -> Plane {
+        Plane::new(
+            Vec3d {
+                x: 0.,
+                y: 0.,
+                z: 0.,
+            },
+            Vec3d {
+                x: 0.,
+                y: 0.,
+                z: 2.,
+            },
+            Material::default(),
+        )
+    }
+
+    #[test]
+    fn plane_from_above() {
+        let ray = Ray {
+            start: Vec3d {
+                x: 1.,
+                y: 2.,
+                z: 3.,
+            },
+            dir: Vec3d {
+                x: 1.,
+                y: 0.,
+                z: -1.,
+            }
+            .normalize()
+            .unwrap(),
+            time: 0.,
+        };
+        let i = floor().intersect(&ray).unwrap();
+        assert_abs_diff_eq!(i.dist, 3. * 2f64.sqrt());
+        assert_abs_diff_eq!(
+            i.pos,
+            Vec3d {
+                x: 4.,
+                y: 2.,
+                z: 0.
+            }
+        );
+        assert_eq!(
+            i.normal,
+            Vec3d {
+                x: 0.,
+                y: 0.,
+                z: 1.
+            }
+        );
+        assert_abs_diff_eq!(i.uv.0, 4.);
+        assert_abs_diff_eq!(i.uv.1, 2.);
+    }
+
+    #[test]
+    fn plane_from_below_or_parallel() {
+        let start = Vec3d {
+            x: 0.,
+            y: 0.,
+            z: -1.,
+        };
+        let up = Ray {
+            start,
+            dir: Vec3d {
+                x: 0.,
+                y: 0.,
+                z: 1.,
+            },
+            time: 0.,
+        };
+        let parallel = Ray {
+            start,
+            dir: Vec3d {
+                x: 1.,
+                y: 0.,
+                z: 0.,
+            },
+            time: 0.,
+        };
+        assert_eq!(floor().intersect(&up), None);
+        assert_eq!(floor().intersect(&parallel), None);
+    }
+
+    #[test]
+    fn disk_radius() {
+        let disk = Disk::new(
+            Vec3d {
+                x: 5.,
+                y: 0.,
+                z: 0.,
+            },
+            Vec3d {
+                x: -1.,
+                y: 0.,
+                z: 0.,
+            },
+            1.,
+            Material::default(),
+        );
+        let ray = |y| Ray {
+            start: Vec3d { x: 0., y, z: 0. },
+            dir: Vec3d {
+                x: 1.,
+                y: 0.,
+                z: 0.,
+            },
+            time: 0.,
+        };
+
+        let i = disk.intersect(&ray(0.)).unwrap();
+        assert_eq!(i.dist, 5.);
+        assert_abs_diff_eq!(i.uv.0, 0.5);
+        assert_abs_diff_eq!(i.uv.1, 0.5);
+        assert!(disk.intersect(&ray(0.9)).is_some());
+        assert_eq!(disk.intersect(&ray(1.1)), None);
+    }
+}
@@ -1,18 +1,31 @@
+use std::sync::Arc;
 use face::Face;
 use crate::scene::camera::Ray;
 use super::{material::Material, Intersect};
 
 pub mod face;
+pub mod polygon;
 
 pub struct Rasterized{
     pub faces: Vec<Face>,
-    pub material: Material,
+    pub material: Arc<Material>,
+    //faces are also seen from the back, so that the winding does not matter
+    pub double_sided: bool,
 }
 
 impl Rasterized {
+    pub fn new(faces: Vec<Face>, material: impl Into<Arc<Material>>) -> Rasterized {
+        Rasterized { faces, material: material.into(), double_sided: false }
+    }
+
     pub fn intersect(&self, ray: &Ray) -> Option<Intersect> {
-        let intersects: Vec<Intersect>= self.faces.iter().filter_map(|f| f.intersect(ray)).collect();
-        let final_intersect = intersects.into_iter().fold(None, |a, b| {
+        self.hit(ray).map(|i| i.with_material(&self.material))
+    }
+
+    //nearest face intersection, without material
+    pub fn hit(&self, ray: &Ray) -> Option<Intersect> {
+        let intersects: Vec<Intersect>= self.faces.iter().filter_map(|f| f.intersect(ray, self.double_sided)).collect();
+        intersects.into_iter().fold(None, |a, b| {
             match a {
                 None => Some(b),
                 Some(i) => {
@@ -23,11 +36,7 @@ impl Rasterized {
                     }
                 }
             }
-        });
-        match final_intersect { 
-            None => None,
-            Some(i) => Some(Intersect{pos: i.pos, dist: i.dist, normal: i.normal, material: self.material.clone()})
-        }
+        })
     }
 }
 
@@ -53,7 +62,7 @@ mod tests {
             Vec3d{x: 0.5, y:  0., z:  1.},
             Vec3d{x: 0.5, y: -1., z: -1.}
         );
-        Rasterized{faces: vec![f1, f2, f3], material: Material::default()}
+        Rasterized::new(vec![f1, f2, f3], Material::default())
     }
 
     #[test]
@@ -70,6 +79,7 @@ mod tests {
                 y: 0.,
                 z: 0.,
             },
+            time: 0.,
         };
         let i = object.intersect(&ray).unwrap();
         assert_eq!(
@@ -96,7 +106,30 @@ mod tests {
                 y: 0.,
                 z: 0.,
             },
+            time: 0.,
         };
         assert_eq!(object.intersect(&ray), None);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn double_sided(){
+        let mut object = create_object();
+        //from behind all the faces
+        let ray = Ray {
+            start: Vec3d {
+                x: 3.,
+                y: 0.,
+                z: 0.,
+            },
+            dir: Vec3d {
+                x: -1.,
+                y: 0.,
+                z: 0.,
+            },
+            time: 0.,
+        };
+        assert_eq!(object.intersect(&ray), None);
+        object.double_sided = true;
+        assert_eq!(object.intersect(&ray).unwrap().dist, 1.);
+    }
+}
@@ -6,11 +6,16 @@ pub struct Face {
     coords: [Vec3d; 3],
     normal: Vec3d,
     inside_vecs: [Vec3d; 3],
+    uvs: [(f64, f64); 3],
+    tangents: (Vec3d, Vec3d),
+    //normals of a, b and c, interpolated to smooth the surface
+    normals: Option<[Vec3d; 3]>,
 }
 
 impl Face {
     pub fn new(a: Vec3d, b: Vec3d, c: Vec3d) -> Face {
         let normal = Face::compute_normal(a, b, c);
+        let uvs = [(0., 0.); 3];
         Face{
             coords: [a, b, c], 
             normal,
@@ -18,15 +23,37 @@ impl Face {
                 [(b - a).cross(normal),
                  (c - b).cross(normal),
                  (a - c).cross(normal)],
+            uvs,
+            tangents: Face::compute_tangents([a, b, c], normal, uvs),
+            normals: None,
         }
     }
-    pub fn intersect(&self, ray:&Ray) -> Option<Intersect> {
+
+    //texture coordinates of a, b and c
+    pub fn with_uvs(mut self, uvs: [(f64, f64); 3]) -> Face {
+        self.uvs = uvs;
+        self.tangents = Face::compute_tangents(self.coords, self.normal, uvs);
+        self
+    }
+
+    //normals of a, b and c, turned toward the front of the face
+    pub fn with_normals(mut self, normals: [Vec3d; 3]) -> Face {
+        self.normals = Some(normals.map(|n| match n.dot(self.normal) < 0. {
+            true => n * -1.,
+            false => n,
+        }));
+        self
+    }
+
+    //double sided faces are also hit from the back, their normal and tangents are then flipped
+    pub fn intersect(&self, ray:&Ray, double_sided: bool) -> Option<Intersect> {
         //ray is parallel to the face
         if ray.dir.dot(self.normal) == 0. {
             return None;
         }
         //ray would hit face from the back
-        if ray.dir.dot(self.normal) > 0. {
+        let back = ray.dir.dot(self.normal) > 0.;
+        if back && !double_sided {
             return None;
         }
         
@@ -60,17 +87,57 @@ impl Face {
         if ab_side < 0. || bc_side < 0. || ca_side < 0. {
             None
         } else {
+            //each side value is proportional to the barycentric weight of the opposite vertex
+            let total = ab_side + bc_side + ca_side;
+            let [uv_a, uv_b, uv_c] = self.uvs;
+            let (wa, wb, wc) = (bc_side / total, ca_side / total, ab_side / total);
+            let uv = (wa * uv_a.0 + wb * uv_b.0 + wc * uv_c.0,
+                      wa * uv_a.1 + wb * uv_b.1 + wc * uv_c.1);
+            let (normal, tangent, bitangent) = match self.normals {
+                Some([na, nb, nc]) => {
+                    let normal = (na * wa + nb * wb + nc * wc).normalize().unwrap_or(self.normal);
+                    //tangents follow the smoothed normal
+                    let (t, b) = self.tangents;
+                    let tangent = (t - normal * normal.dot(t)).normalize().unwrap_or(t);
+                    let bitangent = (b - normal * normal.dot(b)).normalize().unwrap_or(b);
+                    (normal, tangent, bitangent)
+                }
+                None => (self.normal, self.tangents.0, self.tangents.1),
+            };
+            let side = if back { -1. } else { 1. };
             Some(Intersect{
                 pos: intersect_point, 
                 dist, 
-                normal: self.normal, 
-                material: Material::default()})
+                normal: normal * side,
+                tangent: tangent * side,
+                bitangent: bitangent * side,
+                uv,
+                material: Material::unset()})
         }
     }
 
     pub fn compute_normal(a: Vec3d, b: Vec3d, c: Vec3d) -> Vec3d{
         (b - a).cross(b - c).normalize().unwrap()
     }
+
+    //directions of increasing u and v in the plane of the face,
+    //along the first edge when the uvs are degenerated
+    fn compute_tangents([a, b, c]: [Vec3d; 3], normal: Vec3d, [uv_a, uv_b, uv_c]: [(f64, f64); 3]) -> (Vec3d, Vec3d) {
+        let (e1, e2) = (b - a, c - a);
+        let (du1, dv1) = (uv_b.0 - uv_a.0, uv_b.1 - uv_a.1);
+        let (du2, dv2) = (uv_c.0 - uv_a.0, uv_c.1 - uv_a.1);
+        let det = du1 * dv2 - du2 * dv1;
+
+        let (dpdu, dpdv) = if det.abs() < 1e-12 {
+            (e1, normal.cross(e1))
+        } else {
+            ((e1 * dv2 - e2 * dv1) / det, (e2 * du1 - e1 * du2) / det)
+        };
+        //project on the plane of the face
+        let tangent = (dpdu - normal * normal.dot(dpdu)).normalize().unwrap();
+        let bitangent = (dpdv - normal * normal.dot(dpdv)).normalize().unwrap_or(normal.cross(tangent));
+        (tangent, bitangent)
+    }
 }
 
 
@@ -78,6 +145,7 @@ impl Face {
 mod tests {
 
     use super::*;
+    use approx::assert_abs_diff_eq;
     fn create_face() -> Face {
         Face::new(
             Vec3d{x: 1., y:  1., z: -1.},
@@ -113,8 +181,9 @@ mod tests {
                 y: 0.,
                 z: 0.,
             },
+            time: 0.,
         };
-        let i = face.intersect(&ray).unwrap();
+        let i = face.intersect(&ray, false).unwrap();
         assert_eq!(i.dist, 1.0);
         assert_eq!(
             i.pos,
@@ -126,6 +195,50 @@ mod tests {
         );
     }
 
+    #[test]
+    fn face_uv() {
+        let face = create_face().with_uvs([(0., 0.), (0.5, 1.), (1., 0.)]);
+        let ray = Ray {
+            start: Vec3d {
+                x: 0.,
+                y: 0.,
+                z: 0.,
+            },
+            dir: Vec3d {
+                x: 1.,
+                y: 0.,
+                z: 0.,
+            },
+            time: 0.,
+        };
+        let i = face.intersect(&ray, false).unwrap();
+        assert_abs_diff_eq!(i.uv.0, 0.5);
+        assert_abs_diff_eq!(i.uv.1, 0.5);
+        assert_abs_diff_eq!(i.tangent, Vec3d{x: 0., y: -1., z: 0.});
+        assert_abs_diff_eq!(i.bitangent, Vec3d{x: 0., y: 0., z: 1.});
+    }
+
+    #[test]
+    fn face_normals() {
+        let v = |x, y, z| Vec3d { x, y, z };
+        //the normal of c is turned toward the front
+        let face = create_face().with_normals([v(-1., 1., 0.), v(-1., 0., 0.), v(1., 0., 0.)]);
+        let ray = Ray {
+            start: v(0., 0., 0.),
+            dir: v(1., 0., 0.),
+            time: 0.,
+        };
+        let i = face.intersect(&ray, false).unwrap();
+        assert_abs_diff_eq!(i.normal, v(-1., 0.25, 0.).normalize().unwrap());
+        assert_abs_diff_eq!(i.tangent.dot(i.normal), 0.);
+        let back = Ray {
+            start: v(2., 0., 0.),
+            dir: v(-1., 0., 0.),
+            time: 0.,
+        };
+        assert_abs_diff_eq!(face.intersect(&back, true).unwrap().normal, v(1., -0.25, 0.).normalize().unwrap());
+    }
+
     #[test]
     fn face_behind() {
         let face = create_face();
@@ -140,8 +253,9 @@ mod tests {
                 y:  0.,
                 z:  0.,
             },
+            time: 0.,
         };
-        assert_eq!(face.intersect(&ray), None);
+        assert_eq!(face.intersect(&ray, false), None);
     }
 
     #[test]
@@ -158,8 +272,9 @@ mod tests {
                 y: 0.,
                 z: 1.,
             },
+            time: 0.,
         };
-        assert_eq!(face.intersect(&ray), None);
+        assert_eq!(face.intersect(&ray, false), None);
     }
 
     #[test]
@@ -176,8 +291,9 @@ mod tests {
                 y: 0.,
                 z: 0.,
             },
+            time: 0.,
         };
-        assert_eq!(face.intersect(&ray), None);
+        assert_eq!(face.intersect(&ray, false), None);
     }
 
     #[test]
@@ -194,7 +310,23 @@ mod tests {
                 y: 0.,
                 z: 0.,
             },
+            time: 0.,
         };
-        assert_eq!(face.intersect(&ray), None);
+        assert_eq!(face.intersect(&ray, false), None);
+
+        let i = face.intersect(&ray, true).unwrap();
+        assert_eq!(i.dist, 1.0);
+        assert_eq!(
+            i.normal,
+            Vec3d {
+                x: 1.,
+                y: 0.,
+                z: 0.
+            }
+        );
+        //the tangent frame turns with the normal, normal maps are not mirrored
+        let front = Ray { start: Vec3d { x: 0., y: 0., z: 0. }, dir: Vec3d { x: 1., y: 0., z: 0. }, time: 0. };
+        let front = face.intersect(&front, false).unwrap();
+        assert_eq!((i.tangent, i.bitangent), (front.tangent * -1., front.bitangent * -1.));
     }
 }
\ No newline at end of file
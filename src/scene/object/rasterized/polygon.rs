@@ -0,0 +1,142 @@
+use crate::coord::Vec3d;
+
+//Splits a planar polygon, convex or not, in triangles by ear clipping.
+//Triangles are given as indices of the points, in the order of the polygon.
+pub fn triangulate(points: &[Vec3d]) -> Result<Vec<[usize; 3]>, String> {
+    if points.len() < 3 {
+        return Err(format!("face needs at least 3 points, {} given", points.len()));
+    }
+    //tolerances are relative to the size of the polygon
+    let size = points.iter().map(|p| (*p - points[0]).norm()).fold(0., f64::max);
+
+    //Newell's method, the normal of the plane that fits best the points
+    let mut normal = Vec3d {
+        x: 0.,
+        y: 0.,
+        z: 0.,
+    };
+    for (i, a) in points.iter().enumerate() {
+        normal = normal + a.cross(points[(i + 1) % points.len()]);
+    }
+    if normal.norm() <= 1e-12 * size * size {
+        return Err(format!("face with {} points is degenerate (no area)", points.len()));
+    }
+    let normal = normal.normalize().unwrap();
+    if points.iter().any(|p| (*p - points[0]).dot(normal).abs() > 1e-6 * size) {
+        return Err(format!("face with {} points is not planar", points.len()));
+    }
+
+    //in the plane of the polygon, the points turn counterclockwise
+    let (u, v) = normal.orthonormal_basis();
+    let flat: Vec<(f64, f64)> = points.iter().map(|p| (p.dot(u), p.dot(v))).collect();
+    let epsilon = 1e-12 * size * size;
+
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+    while remaining.len() >= 3 {
+        let n = remaining.len();
+        let ear = (0..n).find_map(|k| {
+            let (a, b, c) = (remaining[(k + n - 1) % n], remaining[k], remaining[(k + 1) % n]);
+            let (pa, pb, pc) = (flat[a], flat[b], flat[c]);
+            let turn = cross(pa, pb, pc);
+            if turn.abs() <= epsilon {
+                //aligned points: the middle one is dropped if it lies between
+                //the two others, without a triangle
+                let forward = (pb.0 - pa.0) * (pc.0 - pb.0) + (pb.1 - pa.1) * (pc.1 - pb.1) > 0.;
+                return forward.then_some((k, None));
+            }
+            if turn < 0. {
+                return None;
+            }
+            //no other point of the polygon inside the ear
+            let empty = remaining
+                .iter()
+                .filter(|i| ![a, b, c].contains(i))
+                .all(|i| !inside_triangle(flat[*i], pa, pb, pc));
+            empty.then_some((k, Some([a, b, c])))
+        });
+        match ear {
+            Some((k, triangle)) => {
+                triangles.extend(triangle);
+                remaining.remove(k);
+            }
+            None => return Err(format!("face with {} points is self-intersecting", points.len())),
+        }
+    }
+    Ok(triangles)
+}
+
+//positive when a, b, c turn counterclockwise
+fn cross(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> f64 {
+    (b.0 - a.0) * (c.1 - b.1) - (b.1 - a.1) * (c.0 - b.0)
+}
+
+//borders included
+fn inside_triangle(p: (f64, f64), a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> bool {
+    cross(a, b, p) >= 0. && cross(b, c, p) >= 0. && cross(c, a, p) >= 0.
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn points(coords: &[(f64, f64, f64)]) -> Vec<Vec3d> {
+        coords.iter().map(|&(x, y, z)| Vec3d { x, y, z }).collect()
+    }
+
+    fn area(points: &[Vec3d], triangles: &[[usize; 3]]) -> f64 {
+        triangles
+            .iter()
+            .map(|[a, b, c]| (points[*b] - points[*a]).cross(points[*c] - points[*a]).norm() / 2.)
+            .sum()
+    }
+
+    #[test]
+    fn triangulate_convex() {
+        let square = points(&[(0., 0., 1.), (1., 0., 1.), (1., 1., 1.), (0., 1., 1.)]);
+        let triangles = triangulate(&square).unwrap();
+        assert_eq!(triangles.len(), 2);
+        assert_eq!(area(&square, &triangles), 1.);
+    }
+
+    #[test]
+    fn triangulate_concave() {
+        //L shape in a vertical plane, clockwise seen from +y
+        let l = points(&[
+            (0., 0., 0.),
+            (0., 0., 2.),
+            (1., 0., 2.),
+            (1., 0., 1.),
+            (2., 0., 1.),
+            (2., 0., 0.),
+        ]);
+        let triangles = triangulate(&l).unwrap();
+        assert_eq!(triangles.len(), 4);
+        assert_eq!(area(&l, &triangles), 3.);
+        //same winding as the polygon
+        for [a, b, c] in triangles {
+            let n = (l[b] - l[a]).cross(l[c] - l[a]);
+            assert!(n.y > 0.);
+        }
+    }
+
+    #[test]
+    fn triangulate_aligned_points() {
+        //the middle of the bottom side is not a corner
+        let square = points(&[(0., 0., 0.), (1., 0., 0.), (2., 0., 0.), (2., 2., 0.), (0., 2., 0.)]);
+        let triangles = triangulate(&square).unwrap();
+        assert_eq!(area(&square, &triangles), 4.);
+    }
+
+    #[test]
+    fn invalid_faces() {
+        let line = points(&[(0., 0., 0.), (1., 1., 1.), (2., 2., 2.)]);
+        let warped = points(&[(0., 0., 0.), (1., 0., 0.), (1., 1., 0.5), (0., 1., 0.)]);
+        let bow_tie = points(&[(0., 0., 0.), (1., 1., 0.), (1., 0., 0.), (0., 1., 0.), (-1., 2., 0.)]);
+        assert!(triangulate(&line[..2]).unwrap_err().contains("at least 3 points"));
+        assert!(triangulate(&line).unwrap_err().contains("degenerate"));
+        assert!(triangulate(&warped).unwrap_err().contains("not planar"));
+        assert!(triangulate(&bow_tie).is_err());
+    }
+}
@@ -0,0 +1,478 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use super::solver::solve_quadratic;
+use super::{material::Material, Intersect};
+use crate::coord::Vec3d;
+use crate::scene::camera::Ray;
+
+const MAX_STEPS: usize = 512;
+const MAX_DIST: f64 = 1000.;
+//the surface is reached below this distance
+const EPSILON: f64 = 1e-5;
+//a ray starting on the surface (reflection...) has to leave it first
+const MIN_DIST: f64 = 10. * EPSILON;
+
+//Expression tree of a signed distance field: negative inside, positive outside
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Node {
+    Sphere {
+        center: Vec3d,
+        radius: f64,
+    },
+    //half sizes, with edges rounded by rounding
+    Box {
+        center: Vec3d,
+        size: Vec3d,
+        rounding: f64,
+    },
+    //around the z axis
+    Torus {
+        center: Vec3d,
+        radius: f64,
+        tube: f64,
+    },
+    //along the z axis, with half height
+    Cylinder {
+        center: Vec3d,
+        radius: f64,
+        height: f64,
+    },
+    //blended over a distance smooth, sharp when it is 0
+    Union(Vec<Node>, f64),
+    Intersection(Vec<Node>, f64),
+    Difference(Vec<Node>, f64),
+    //rotation around the z axis proportional to z, in radians per unit
+    Twist(Box<Node>, f64),
+    //infinite copies with the given period, none along axes with a 0 period
+    Repetition(Box<Node>, Vec3d),
+    Translation(Box<Node>, Vec3d),
+}
+
+//Shape defined by a distance field, intersected by sphere tracing
+pub struct Sdf {
+    pub root: Node,
+    pub material: Arc<Material>,
+    bound: Option<(Vec3d, f64)>,
+}
+
+impl Node {
+    pub fn distance(&self, p: Vec3d) -> f64 {
+        match self {
+            Node::Sphere { center, radius } => (p - *center).norm() - radius,
+            Node::Box {
+                center,
+                size,
+                rounding,
+            } => {
+                let q = p - *center;
+                let q = Vec3d {
+                    x: q.x.abs() - size.x + rounding,
+                    y: q.y.abs() - size.y + rounding,
+                    z: q.z.abs() - size.z + rounding,
+                };
+                let outside = Vec3d {
+                    x: q.x.max(0.),
+                    y: q.y.max(0.),
+                    z: q.z.max(0.),
+                };
+                outside.norm() + q.x.max(q.y).max(q.z).min(0.) - rounding
+            }
+            Node::Torus {
+                center,
+                radius,
+                tube,
+            } => {
+                let q = p - *center;
+                let rho = (q.x * q.x + q.y * q.y).sqrt() - radius;
+                (rho * rho + q.z * q.z).sqrt() - tube
+            }
+            Node::Cylinder {
+                center,
+                radius,
+                height,
+            } => {
+                let q = p - *center;
+                let dr = (q.x * q.x + q.y * q.y).sqrt() - radius;
+                let dz = q.z.abs() - height;
+                dr.max(dz).min(0.) + (dr.max(0.).powi(2) + dz.max(0.).powi(2)).sqrt()
+            }
+            Node::Union(children, k) => children
+                .iter()
+                .map(|c| c.distance(p))
+                .reduce(|a, b| smooth_min(a, b, *k))
+                .unwrap_or(f64::INFINITY),
+            Node::Intersection(children, k) => children
+                .iter()
+                .map(|c| c.distance(p))
+                .reduce(|a, b| -smooth_min(-a, -b, *k))
+                .unwrap_or(f64::INFINITY),
+            Node::Difference(children, k) => children
+                .iter()
+                .map(|c| c.distance(p))
+                .reduce(|a, b| -smooth_min(-a, b, *k))
+                .unwrap_or(f64::INFINITY),
+            Node::Twist(child, rate) => {
+                let (sin, cos) = (-rate * p.z).sin_cos();
+                let q = Vec3d {
+                    x: cos * p.x - sin * p.y,
+                    y: sin * p.x + cos * p.y,
+                    z: p.z,
+                };
+                //twisting stretches distances away from the axis
+                let rho = (p.x * p.x + p.y * p.y).sqrt();
+                child.distance(q) / (1. + (rate * rho).powi(2)).sqrt()
+            }
+            Node::Repetition(child, period) => {
+                let cell = |x: f64, period: f64| {
+                    if period == 0. {
+                        x
+                    } else {
+                        x - period * (x / period).round()
+                    }
+                };
+                child.distance(Vec3d {
+                    x: cell(p.x, period.x),
+                    y: cell(p.y, period.y),
+                    z: cell(p.z, period.z),
+                })
+            }
+            Node::Translation(child, offset) => child.distance(p - *offset),
+        }
+    }
+
+    //center and radius of a sphere containing the shape, None if it is infinite
+    pub fn bound(&self) -> Option<(Vec3d, f64)> {
+        match self {
+            Node::Sphere { center, radius } => Some((*center, *radius)),
+            Node::Box { center, size, .. } => Some((*center, size.norm())),
+            Node::Torus {
+                center,
+                radius,
+                tube,
+            } => Some((*center, radius + tube)),
+            Node::Cylinder {
+                center,
+                radius,
+                height,
+            } => Some((*center, (radius * radius + height * height).sqrt())),
+            //smoothing can only add k/4 to the shapes
+            Node::Union(children, k) => {
+                let bounds = children.iter().map(|c| c.bound()).collect::<Option<Vec<_>>>()?;
+                let (center, _) = *bounds.first()?;
+                let radius = bounds
+                    .iter()
+                    .map(|(c, r)| (*c - center).norm() + r)
+                    .fold(0., f64::max);
+                Some((center, radius + k))
+            }
+            //intersections and differences are smaller than their operands
+            Node::Intersection(children, _) => children
+                .iter()
+                .filter_map(|c| c.bound())
+                .min_by(|a, b| a.1.total_cmp(&b.1)),
+            Node::Difference(children, _) => children.first()?.bound(),
+            //whatever the twist, the shape stays around the axis
+            Node::Twist(child, _) => {
+                let (c, r) = child.bound()?;
+                let center = Vec3d {
+                    x: 0.,
+                    y: 0.,
+                    z: c.z,
+                };
+                Some((center, (c.x * c.x + c.y * c.y).sqrt() + r))
+            }
+            Node::Repetition(..) => None,
+            Node::Translation(child, offset) => child.bound().map(|(c, r)| (c + *offset, r)),
+        }
+    }
+}
+
+//polynomial smooth minimum, blending where a and b are closer than k
+fn smooth_min(a: f64, b: f64, k: f64) -> f64 {
+    if k <= 0. {
+        return a.min(b);
+    }
+    let h = (k - (a - b).abs()).max(0.) / k;
+    a.min(b) - h * h * k / 4.
+}
+
+impl Sdf {
+    pub fn new(root: Node, material: impl Into<Arc<Material>>) -> Sdf {
+        let bound = root.bound();
+        Sdf {
+            root,
+            material: material.into(),
+            bound,
+        }
+    }
+
+    pub fn intersect(&self, ray: &Ray) -> Option<Intersect> {
+        self.hit(ray).map(|i| i.with_material(&self.material))
+    }
+
+    //intersection without material, not seen from inside
+    pub fn hit(&self, ray: &Ray) -> Option<Intersect> {
+        //marching is done along a unit direction, dist is along ray.dir
+        let norm = ray.dir.norm();
+        let dir = ray.dir / norm;
+        if self.root.distance(ray.start) < -EPSILON {
+            return None;
+        }
+
+        //marching is limited to the bounding sphere
+        let (mut t, end) = match self.bound {
+            None => (0., MAX_DIST),
+            Some((center, radius)) => {
+                let radius = radius + MIN_DIST;
+                let oc = ray.start - center;
+                let (t0, t1) = solve_quadratic(1., 2. * oc.dot(dir), oc.normsq() - radius * radius)?;
+                (t0.max(0.), t1)
+            }
+        };
+        for _ in 0..MAX_STEPS {
+            let pos = ray.start + t * dir;
+            let d = self.root.distance(pos);
+            if d < EPSILON && t > MIN_DIST {
+                let t = self.refine(ray.start, dir, t);
+                let pos = ray.start + t * dir;
+                let normal = self.normal(pos);
+                let (tangent, bitangent) = normal.orthonormal_basis();
+                return Some(Intersect {
+                    pos,
+                    dist: t / norm,
+                    normal,
+                    tangent,
+                    bitangent,
+                    uv: (0., 0.),
+                    material: Material::unset(),
+                });
+            }
+            t += d.max(EPSILON);
+            if t > end {
+                break;
+            }
+        }
+        None
+    }
+
+    //Newton's method along the ray, so that the intersection is as precise as
+    //analytic ones (shadow rays coming from lights must find the same point)
+    fn refine(&self, start: Vec3d, dir: Vec3d, mut t: f64) -> f64 {
+        let f = |t: f64| self.root.distance(start + t * dir);
+        let h = EPSILON / 10.;
+        for _ in 0..8 {
+            let d = f(t);
+            let slope = (f(t + h) - f(t - h)) / (2. * h);
+            //grazing rays are left as they are
+            if d.abs() < 1e-13 || slope > -1e-3 {
+                break;
+            }
+            t -= d / slope;
+        }
+        t
+    }
+
+    //gradient of the field, by central differences
+    fn normal(&self, p: Vec3d) -> Vec3d {
+        let d = |dx, dy, dz| self.root.distance(p + Vec3d { x: dx, y: dy, z: dz });
+        let h = EPSILON;
+        Vec3d {
+            x: d(h, 0., 0.) - d(-h, 0., 0.),
+            y: d(0., h, 0.) - d(0., -h, 0.),
+            z: d(0., 0., h) - d(0., 0., -h),
+        }
+        .normalize()
+        .unwrap_or(Vec3d {
+            x: 0.,
+            y: 0.,
+            z: 1.,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    fn origin() -> Vec3d {
+        Vec3d {
+            x: 0.,
+            y: 0.,
+            z: 0.,
+        }
+    }
+
+    fn ray_along_x() -> Ray {
+        Ray {
+            start: Vec3d {
+                x: -5.,
+                y: 0.,
+                z: 0.,
+            },
+            dir: Vec3d {
+                x: 1.,
+                y: 0.,
+                z: 0.,
+            },
+            time: 0.,
+        }
+    }
+
+    #[test]
+    fn distances() {
+        let sphere = Node::Sphere {
+            center: origin(),
+            radius: 1.,
+        };
+        let cube = Node::Box {
+            center: origin(),
+            size: Vec3d {
+                x: 1.,
+                y: 1.,
+                z: 1.,
+            },
+            rounding: 0.,
+        };
+        let p = Vec3d {
+            x: 2.,
+            y: 2.,
+            z: 0.,
+        };
+        assert_abs_diff_eq!(sphere.distance(p), 8f64.sqrt() - 1.);
+        assert_abs_diff_eq!(cube.distance(p), 2f64.sqrt());
+        assert_abs_diff_eq!(cube.distance(origin()), -1.);
+        let torus = Node::Torus {
+            center: origin(),
+            radius: 2.,
+            tube: 0.5,
+        };
+        assert_abs_diff_eq!(torus.distance(origin()), 1.5);
+    }
+
+    #[test]
+    fn operators() {
+        let sphere = |x| Node::Sphere {
+            center: Vec3d { x, y: 0., z: 0. },
+            radius: 1.,
+        };
+        let p = Vec3d {
+            x: 0.5,
+            y: 0.,
+            z: 0.,
+        };
+        let union = Node::Union(vec![sphere(0.), sphere(1.)], 0.);
+        let difference = Node::Difference(vec![sphere(0.), sphere(1.)], 0.);
+        let smooth = Node::Union(vec![sphere(0.), sphere(1.)], 0.5);
+        assert_abs_diff_eq!(union.distance(p), -0.5);
+        assert_abs_diff_eq!(difference.distance(p), 0.5);
+        assert!(smooth.distance(p) < union.distance(p));
+
+        let repeated = Node::Repetition(
+            Box::new(sphere(0.)),
+            Vec3d {
+                x: 4.,
+                y: 0.,
+                z: 0.,
+            },
+        );
+        let far = Vec3d {
+            x: 40.5,
+            y: 0.,
+            z: 0.,
+        };
+        assert_abs_diff_eq!(repeated.distance(far), -0.5);
+    }
+
+    #[test]
+    fn sphere_tracing() {
+        let sdf = Sdf::new(
+            Node::Sphere {
+                center: origin(),
+                radius: 1.,
+            },
+            Material::default(),
+        );
+        let i = sdf.intersect(&ray_along_x()).unwrap();
+        assert_abs_diff_eq!(i.dist, 4., epsilon = 1e-12);
+        assert_abs_diff_eq!(
+            i.normal,
+            Vec3d {
+                x: -1.,
+                y: 0.,
+                z: 0.
+            },
+            epsilon = 1e-4
+        );
+
+        //missed, and from inside
+        let ray = Ray {
+            start: Vec3d {
+                x: -5.,
+                y: 2.,
+                z: 0.,
+            },
+            ..ray_along_x()
+        };
+        assert_eq!(sdf.intersect(&ray), None);
+        let ray = Ray {
+            start: origin(),
+            ..ray_along_x()
+        };
+        assert_eq!(sdf.intersect(&ray), None);
+    }
+
+    #[test]
+    fn bounds() {
+        let sphere = |x| Node::Sphere {
+            center: Vec3d { x, y: 0., z: 0. },
+            radius: 1.,
+        };
+        let union = Node::Union(vec![sphere(0.), sphere(2.)], 0.);
+        assert_eq!(union.bound(), Some((origin(), 3.)));
+        let moved = Node::Translation(
+            Box::new(sphere(0.)),
+            Vec3d {
+                x: 0.,
+                y: 5.,
+                z: 0.,
+            },
+        );
+        assert_eq!(moved.bound().unwrap().0.y, 5.);
+        let repeated = Node::Repetition(Box::new(sphere(0.)), moved.bound().unwrap().0);
+        assert_eq!(Node::Union(vec![repeated, sphere(0.)], 0.).bound(), None);
+    }
+
+    #[test]
+    fn twisted_box() {
+        let twisted = Node::Twist(
+            Box::new(Node::Box {
+                center: origin(),
+                size: Vec3d {
+                    x: 1.,
+                    y: 1.,
+                    z: 2.,
+                },
+                rounding: 0.1,
+            }),
+            std::f64::consts::PI / 4.,
+        );
+        let sdf = Sdf::new(twisted, Material::default());
+        //turned by 45° at z=1, a rounded edge is then along the x axis
+        let ray = Ray {
+            start: Vec3d {
+                x: -5.,
+                y: 0.,
+                z: 1.,
+            },
+            ..ray_along_x()
+        };
+        let i = sdf.intersect(&ray).unwrap();
+        assert_abs_diff_eq!(i.dist, 5. - (0.9 * 2f64.sqrt() + 0.1), epsilon = 1e-3);
+        assert_abs_diff_eq!(sdf.root.distance(i.pos), 0., epsilon = 1e-4);
+    }
+}
@@ -0,0 +1,179 @@
+use std::f64::consts::PI;
+
+//solves ax²+bx+c=0
+//in case of two solutions, the lowest one comes first
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    let delta = b * b - 4. * a * c;
+    if delta < 0.0 {
+        return None;
+    }
+
+    let (s1, s2) = if a > 0. {
+        ((-b - delta.sqrt()) / (2. * a),
+         (-b + delta.sqrt()) / (2. * a))
+    } else {
+        ((-b + delta.sqrt()) / (2. * a),
+         (-b - delta.sqrt()) / (2. * a))
+    };
+
+    Some((s1, s2))
+}
+
+//real solutions of ax³+bx²+cx+d=0 (a not null), in no particular order
+pub fn solve_cubic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    let (b, c, d) = (b / a, c / a, d / a);
+    //x = y - b/3 gives y³ - 3qy + 2r = 0
+    let q = (b * b - 3. * c) / 9.;
+    let r = (2. * b * b * b - 9. * b * c + 27. * d) / 54.;
+    let shift = b / 3.;
+
+    let roots = if r * r < q * q * q {
+        //three real roots, trigonometric form
+        let theta = (r / (q * q * q).sqrt()).clamp(-1., 1.).acos();
+        let m = -2. * q.sqrt();
+        vec![
+            m * (theta / 3.).cos() - shift,
+            m * ((theta + 2. * PI) / 3.).cos() - shift,
+            m * ((theta - 2. * PI) / 3.).cos() - shift,
+        ]
+    } else {
+        let s = -r.signum() * (r.abs() + (r * r - q * q * q).sqrt()).cbrt();
+        let t = if s == 0. { 0. } else { q / s };
+        vec![s + t - shift]
+    };
+    roots
+        .into_iter()
+        .map(|x| polish(&[1., b, c, d], x))
+        .collect()
+}
+
+//real solutions of ax⁴+bx³+cx²+dx+e=0 (a not null), sorted, using Ferrari's method
+pub fn solve_quartic(a: f64, b: f64, c: f64, d: f64, e: f64) -> Vec<f64> {
+    let (b, c, d, e) = (b / a, c / a, d / a, e / a);
+    //x = y - b/4 gives the depressed quartic y⁴ + py² + qy + r = 0
+    let p = c - 3. * b * b / 8.;
+    let q = d - b * c / 2. + b * b * b / 8.;
+    let r = e - b * d / 4. + b * b * c / 16. - 3. * b * b * b * b / 256.;
+    let shift = b / 4.;
+
+    let mut roots = Vec::new();
+    if q.abs() < 1e-12 {
+        //biquadratic: z² + pz + r = 0 with z = y²
+        if let Some((z1, z2)) = solve_quadratic(1., p, r) {
+            for z in [z1, z2] {
+                if z >= 0. {
+                    roots.push(z.sqrt() - shift);
+                    roots.push(-z.sqrt() - shift);
+                }
+            }
+        }
+    } else {
+        //(y² + p/2 + m)² = 2m(y - q/4m)² for m root of the resolvent cubic
+        let m = solve_cubic(1., p, p * p / 4. - r, -q * q / 8.)
+            .into_iter()
+            .fold(f64::NEG_INFINITY, f64::max);
+        if m <= 0. {
+            return roots;
+        }
+        let s = (2. * m).sqrt();
+        for (sign, offset) in [(-1., q / (2. * s)), (1., -q / (2. * s))] {
+            if let Some((y1, y2)) = solve_quadratic(1., sign * s, p / 2. + m + offset) {
+                roots.push(y1 - shift);
+                roots.push(y2 - shift);
+            }
+        }
+    }
+    let mut roots: Vec<f64> = roots
+        .into_iter()
+        .map(|x| polish(&[1., b, c, d, e], x))
+        .collect();
+    roots.sort_by(f64::total_cmp);
+    roots
+}
+
+//a few Newton iterations on the polynomial (highest degree first)
+fn polish(coefs: &[f64], mut x: f64) -> f64 {
+    for _ in 0..2 {
+        let (value, derivative) = coefs
+            .iter()
+            .fold((0., 0.), |(v, dv), c| (v * x + c, dv * x + v));
+        if derivative == 0. {
+            break;
+        }
+        x -= value / derivative;
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn quadratic_no_sol() {
+        assert_eq!(solve_quadratic(1., 0., 1.), None);
+    }
+
+    #[test]
+    fn quadratic_both_positive_sol() {
+        assert_eq!(solve_quadratic(-4., 5., -1.), Some((0.25, 1.)));
+    }
+
+    #[test]
+    fn quadratic_one_negative_sol() {
+        assert_eq!(solve_quadratic(4., -5., -12.), Some((-1.2163649828320293, 2.4663649828320295)));
+    }
+
+    #[test]
+    fn quadratic_both_negative_sol() {
+        assert_eq!(solve_quadratic(4., 5., 1.), Some((-1., -0.25)));
+    }
+
+    #[test]
+    fn cubic() {
+        //(x-1)(x-2)(x+3)
+        let mut roots = solve_cubic(2., 0., -14., 12.);
+        roots.sort_by(f64::total_cmp);
+        assert_eq!(roots.len(), 3);
+        assert_abs_diff_eq!(roots[0], -3., epsilon = 1e-12);
+        assert_abs_diff_eq!(roots[1], 1., epsilon = 1e-12);
+        assert_abs_diff_eq!(roots[2], 2., epsilon = 1e-12);
+
+        //x³ + x + 2 = (x+1)(x²-x+2)
+        let roots = solve_cubic(1., 0., 1., 2.);
+        assert_eq!(roots.len(), 1);
+        assert_abs_diff_eq!(roots[0], -1., epsilon = 1e-12);
+    }
+
+    #[test]
+    fn quartic_four_sol() {
+        //(x-1)(x-2)(x-3)(x+0.5)
+        let roots = solve_quartic(1., -5.5, 8., -0.5, -3.);
+        assert_eq!(roots.len(), 4);
+        for (root, expected) in roots.iter().zip([-0.5, 1., 2., 3.]) {
+            assert_abs_diff_eq!(*root, expected, epsilon = 1e-9);
+        }
+    }
+
+    #[test]
+    fn quartic_two_sol() {
+        //(x²+1)(x-1)(x-4)
+        let roots = solve_quartic(2., -10., 10., -10., 8.);
+        assert_eq!(roots.len(), 2);
+        assert_abs_diff_eq!(roots[0], 1., epsilon = 1e-9);
+        assert_abs_diff_eq!(roots[1], 4., epsilon = 1e-9);
+    }
+
+    #[test]
+    fn quartic_biquadratic() {
+        //(x²-1)(x²-4)
+        let roots = solve_quartic(1., 0., -5., 0., 4.);
+        assert_eq!(roots.len(), 4);
+        for (root, expected) in roots.iter().zip([-2., -1., 1., 2.]) {
+            assert_abs_diff_eq!(*root, expected, epsilon = 1e-12);
+        }
+        assert!(solve_quartic(1., 0., 1., 0., 1.).is_empty());
+    }
+}
@@ -1,23 +1,54 @@
-use super::{material::Material, Intersect};
+use std::sync::Arc;
+use std::f64::consts::PI;
+
+use super::solver::solve_quadratic;
+use super::{material::Material, nearest_outside, Intersect};
 use crate::coord::Vec3d;
 use crate::scene::camera::Ray;
 
+//A moving sphere is centered on center at time 0 and moves in a straight line
 pub struct Sphere {
     pub center: Vec3d,
     pub radius: f64,
-    pub material: Material,
+    pub material: Arc<Material>,
+    //distance per unit of time
+    pub velocity: Vec3d,
 }
 
 impl Sphere {
-    pub fn new(center: Vec3d, radius: f64, material: Material) -> Sphere {
+    pub fn new(center: Vec3d, radius: f64, material: impl Into<Arc<Material>>) -> Sphere {
         Sphere {
             center,
             radius,
-            material,
+            material: material.into(),
+            velocity: Vec3d {
+                x: 0.,
+                y: 0.,
+                z: 0.,
+            },
         }
     }
 
+    pub fn with_velocity(mut self, velocity: Vec3d) -> Sphere {
+        self.velocity = velocity;
+        self
+    }
+
+    pub fn center_at(&self, time: f64) -> Vec3d {
+        self.center + self.velocity * time
+    }
+
     pub fn intersect(&self, ray: &Ray) -> Option<Intersect> {
+        self.hit(ray).map(|i| i.with_material(&self.material))
+    }
+
+    //intersection without material
+    pub fn hit(&self, ray: &Ray) -> Option<Intersect> {
+        nearest_outside(self.hits(ray), 0.)
+    }
+
+    //both intersections with the line of the ray, entry first
+    pub fn hits(&self, ray: &Ray) -> Vec<Intersect> {
         //Formule demi-droite
         //(x+k×dx)² + (y+k×dy)² + (z+k×dz)², k ∈ ℝ⁺
 
@@ -25,45 +56,55 @@ impl Sphere {
         //Equation du point d’intersection k tel que (x+k×dx)² + (y+k×dy)² + (z+k×dz)² = (RT+alt)²
         //->k²×(dx²+dy²+dz²)  +  k×2×(x×dx+y×dy+z×dz)  + (x²+y²+z²-RT²)=0
         //     \     a     /       \        b       /    \     c      /
+        let center = self.center_at(ray.time);
         let a = ray.dir.normsq();
-        let b = 2.0 * (ray.start - self.center).dot(ray.dir);
-        let c = (ray.start - self.center).normsq() - self.radius * self.radius;
-        let k_opt = solve_quadratic(a, b, c);
-
-        match k_opt {
-            None => None,
-            Some((dist, _)) if dist <= 0. => None,
-            Some((dist, _)) => {
+        let b = 2.0 * (ray.start - center).dot(ray.dir);
+        let c = (ray.start - center).normsq() - self.radius * self.radius;
+        let Some((k1, k2)) = solve_quadratic(a, b, c) else {
+            return vec![];
+        };
+
+        [k1, k2]
+            .into_iter()
+            .map(|dist| {
                 let pos = ray.start + (dist * ray.dir);
-                let normal = (pos - self.center).normalize().unwrap();
-                Some(Intersect {
+                let normal = (pos - center).normalize().unwrap();
+                let uv = Sphere::uv(normal);
+                let tangent = Sphere::tangent(normal);
+                Intersect {
                     pos,
                     dist,
                     normal,
-                    material: self.material.clone(),
-                })
-            }
-        }
+                    tangent,
+                    bitangent: tangent.cross(normal),
+                    uv,
+                    material: Material::unset(),
+                }
+            })
+            .collect()
     }
-}
 
-//solves ax²+bx+c=0
-//in case of several solutions, returns the lowest positive one
-fn solve_quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
-    let delta = b * b - 4. * a * c;
-    if delta < 0.0 {
-        return None;
+    //spherical mapping: u goes around the z axis, v from the top pole to the bottom one
+    fn uv(normal: Vec3d) -> (f64, f64) {
+        let u = 0.5 + normal.y.atan2(normal.x) / (2. * PI);
+        let v = normal.z.clamp(-1., 1.).acos() / PI;
+        (u, v)
     }
 
-    let (s1, s2) = if a > 0. {
-        ((-b - delta.sqrt()) / (2. * a),
-         (-b + delta.sqrt()) / (2. * a))
-    } else {
-        ((-b + delta.sqrt()) / (2. * a),
-         (-b - delta.sqrt()) / (2. * a))
-    };
-
-    Some((s1, s2))
+    //direction of increasing u, any horizontal one at the poles
+    fn tangent(normal: Vec3d) -> Vec3d {
+        Vec3d {
+            x: -normal.y,
+            y: normal.x,
+            z: 0.,
+        }
+        .normalize()
+        .unwrap_or(Vec3d {
+            x: 1.,
+            y: 0.,
+            z: 0.,
+        })
+    }
 }
 
 #[cfg(test)]
@@ -73,26 +114,6 @@ mod tests {
     extern crate approx;
     use approx::assert_abs_diff_eq;
 
-    #[test]
-    fn quadratic_no_sol() {
-        assert_eq!(solve_quadratic(1., 0., 1.), None);
-    }
-
-    #[test]
-    fn quadratic_both_positive_sol() {
-        assert_eq!(solve_quadratic(-4., 5., -1.), Some((0.25, 1.)));
-    }
-
-    #[test]
-    fn quadratic_one_negative_sol() {
-        assert_eq!(solve_quadratic(4., -5., -12.), Some((-1.2163649828320293, 2.4663649828320295)));
-    }
-
-    #[test]
-    fn quadratic_both_negative_sol() {
-        assert_eq!(solve_quadratic(4., 5., 1.), Some((-1., -0.25)));
-    }
-
     #[test]
     fn unit_sphere_in_front() {
         let sphere = Sphere::new(
@@ -115,6 +136,7 @@ mod tests {
                 y: 0.,
                 z: 0.,
             },
+            time: 0.,
         };
 
         let i = sphere.intersect(&ray).unwrap();
@@ -151,6 +173,7 @@ mod tests {
                 y: 0.,
                 z: 0.,
             },
+            time: 0.,
         };
         assert_eq!(sphere.intersect(&ray), None);
     }
@@ -177,6 +200,7 @@ mod tests {
                 y: 0.,
                 z: 0.,
             },
+            time: 0.,
         };
 
         assert_eq!(sphere.intersect(&ray), None);
@@ -204,11 +228,59 @@ mod tests {
                 y: 0.,
                 z: 0.,
             },
+            time: 0.,
         };
 
         assert_eq!(sphere.intersect(&ray), None);
     }
 
+    #[test]
+    fn sphere_uv() {
+        let sphere = Sphere::new(
+            Vec3d {
+                x: 2.,
+                y: 0.,
+                z: 0.,
+            },
+            1.0,
+            Material::default(),
+        );
+        let ray = Ray {
+            start: Vec3d {
+                x: 0.,
+                y: 0.,
+                z: 0.,
+            },
+            dir: Vec3d {
+                x: 1.,
+                y: 0.,
+                z: 0.,
+            },
+            time: 0.,
+        };
+
+        let i = sphere.intersect(&ray).unwrap();
+        assert_abs_diff_eq!(i.uv.0, 1.);
+        assert_abs_diff_eq!(i.uv.1, 0.5);
+        //u increases toward -y, v toward -z
+        assert_abs_diff_eq!(
+            i.tangent,
+            Vec3d {
+                x: 0.,
+                y: -1.,
+                z: 0.
+            }
+        );
+        assert_abs_diff_eq!(
+            i.bitangent,
+            Vec3d {
+                x: 0.,
+                y: 0.,
+                z: -1.
+            }
+        );
+    }
+
     #[test]
     fn general_sphere() {
         let sphere = Sphere::new(
@@ -233,6 +305,7 @@ mod tests {
             }
             .normalize()
             .unwrap(),
+            time: 0.,
         };
 
         let i = sphere.intersect(&ray).unwrap();
@@ -246,4 +319,39 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn moving_sphere() {
+        let sphere = Sphere::new(
+            Vec3d {
+                x: 3.,
+                y: 0.,
+                z: 0.,
+            },
+            1.,
+            Material::default(),
+        )
+        .with_velocity(Vec3d {
+            x: 0.,
+            y: 0.,
+            z: 4.,
+        });
+        let ray = |time| Ray {
+            start: Vec3d {
+                x: 0.,
+                y: 0.,
+                z: 2.,
+            },
+            dir: Vec3d {
+                x: 1.,
+                y: 0.,
+                z: 0.,
+            },
+            time,
+        };
+        assert_eq!(sphere.intersect(&ray(0.)), None);
+        let i = sphere.intersect(&ray(0.5)).unwrap();
+        assert_eq!(i.dist, 2.);
+        assert_eq!(i.normal.x, -1.);
+    }
 }
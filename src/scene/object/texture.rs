@@ -0,0 +1,386 @@
+use std::fmt;
+use std::fs::File;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use super::material::Color;
+use crate::coord::Vec3d;
+use procedural::Procedural;
+
+pub mod procedural;
+
+//Behaviour of texture coordinates outside [0, 1]
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Wrap {
+    Repeat,
+    Mirror,
+    Clamp,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+}
+
+#[derive(PartialEq)]
+pub struct Image {
+    pub path: String,
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Texture {
+    Image(ImageTexture),
+    Procedural(Procedural),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ImageTexture {
+    pub image: Arc<Image>,
+    pub wrap: Wrap,
+    pub filter: Filter,
+    pub scale: f64,
+}
+
+impl FromStr for Wrap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Wrap, String> {
+        match s {
+            "repeat" => Ok(Wrap::Repeat),
+            "mirror" => Ok(Wrap::Mirror),
+            "clamp" => Ok(Wrap::Clamp),
+            _ => Err(format!("unknown wrap mode {:?}", s)),
+        }
+    }
+}
+
+//name read by from_str
+impl fmt::Display for Wrap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Wrap::Repeat => "repeat",
+            Wrap::Mirror => "mirror",
+            Wrap::Clamp => "clamp",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Filter, String> {
+        match s {
+            "nearest" => Ok(Filter::Nearest),
+            "bilinear" => Ok(Filter::Bilinear),
+            _ => Err(format!("unknown filter {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Filter::Nearest => "nearest",
+            Filter::Bilinear => "bilinear",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Debug for Image {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Image({:?}, {}x{})", self.path, self.width, self.height)
+    }
+}
+
+impl Image {
+    //Loads a PNG file, whatever its color type and depth, as [0..1] colors
+    pub fn load(path: &Path) -> Result<Image, String> {
+        let file = File::open(path).map_err(|e| format!("{} cannot be read: {}", path.display(), e))?;
+        let mut decoder = png::Decoder::new(file);
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let invalid = |e: png::DecodingError| format!("{} is not a valid PNG image: {}", path.display(), e);
+        let mut reader = decoder.read_info().map_err(invalid)?;
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data).map_err(invalid)?;
+
+        let max = match info.bit_depth {
+            png::BitDepth::Sixteen => 65535.,
+            _ => 255.,
+        };
+        let samples: Vec<f64> = match info.bit_depth {
+            png::BitDepth::Sixteen => data[..info.buffer_size()]
+                .chunks_exact(2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]) as f64 / max)
+                .collect(),
+            _ => data[..info.buffer_size()]
+                .iter()
+                .map(|b| *b as f64 / max)
+                .collect(),
+        };
+
+        let pixels = match info.color_type {
+            png::ColorType::Grayscale => samples
+                .iter()
+                .map(|l| Color { r: *l, g: *l, b: *l })
+                .collect(),
+            png::ColorType::GrayscaleAlpha => samples
+                .chunks_exact(2)
+                .map(|p| Color { r: p[0], g: p[0], b: p[0] })
+                .collect(),
+            png::ColorType::Rgb => samples
+                .chunks_exact(3)
+                .map(|p| Color { r: p[0], g: p[1], b: p[2] })
+                .collect(),
+            png::ColorType::Rgba => samples
+                .chunks_exact(4)
+                .map(|p| Color { r: p[0], g: p[1], b: p[2] })
+                .collect(),
+            png::ColorType::Indexed => panic!("palette should have been expanded"),
+        };
+
+        Ok(Image {
+            path: path.display().to_string(),
+            width: info.width as usize,
+            height: info.height as usize,
+            pixels,
+        })
+    }
+
+    fn texel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+}
+
+impl Texture {
+    //image textures are looked up by uv, procedural ones by position
+    pub fn sample(&self, uv: (f64, f64), pos: Vec3d) -> Color {
+        match self {
+            Texture::Image(t) => t.sample(uv),
+            Texture::Procedural(t) => t.sample(pos),
+        }
+    }
+
+    //Scalar channels (roughness...) use the mean of the three components
+    pub fn sample_scalar(&self, uv: (f64, f64), pos: Vec3d) -> f64 {
+        let c = self.sample(uv, pos);
+        (c.r + c.g + c.b) / 3.
+    }
+
+    //gradient of the scalar texture, used as a height field:
+    //height difference between neighbour texels for images,
+    //height per unit of length for procedural textures
+    pub fn slope(&self, uv: (f64, f64), pos: Vec3d, tangent: Vec3d, bitangent: Vec3d) -> Vec3d {
+        match self {
+            Texture::Image(t) => {
+                let du = 1. / (t.image.width as f64 * t.scale);
+                let dv = 1. / (t.image.height as f64 * t.scale);
+                let height = |uv| self.sample_scalar(uv, pos);
+                let slope_u = (height((uv.0 + du, uv.1)) - height((uv.0 - du, uv.1))) / 2.;
+                let slope_v = (height((uv.0, uv.1 + dv)) - height((uv.0, uv.1 - dv))) / 2.;
+                tangent * slope_u + bitangent * slope_v
+            }
+            Texture::Procedural(t) => {
+                let eps = 1e-4 * t.scale;
+                let height = |dx, dy, dz| self.sample_scalar(uv, pos + Vec3d { x: dx, y: dy, z: dz });
+                Vec3d {
+                    x: height(eps, 0., 0.) - height(-eps, 0., 0.),
+                    y: height(0., eps, 0.) - height(0., -eps, 0.),
+                    z: height(0., 0., eps) - height(0., 0., -eps),
+                } / (2. * eps)
+            }
+        }
+    }
+}
+
+impl ImageTexture {
+    pub fn new(image: Arc<Image>) -> ImageTexture {
+        ImageTexture {
+            image,
+            wrap: Wrap::Repeat,
+            filter: Filter::Bilinear,
+            scale: 1.,
+        }
+    }
+
+    //(0, 0) is the top left corner of the image
+    pub fn sample(&self, uv: (f64, f64)) -> Color {
+        let width = self.image.width;
+        let height = self.image.height;
+        //texel centers are at half coordinates
+        let x = uv.0 * self.scale * width as f64 - 0.5;
+        let y = uv.1 * self.scale * height as f64 - 0.5;
+
+        match self.filter {
+            Filter::Nearest => {
+                let x = wrap(x.round() as i64, width, self.wrap);
+                let y = wrap(y.round() as i64, height, self.wrap);
+                self.image.texel(x, y)
+            }
+            Filter::Bilinear => {
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let x1 = wrap(x0 as i64 + 1, width, self.wrap);
+                let y1 = wrap(y0 as i64 + 1, height, self.wrap);
+                let x0 = wrap(x0 as i64, width, self.wrap);
+                let y0 = wrap(y0 as i64, height, self.wrap);
+
+                let top = self.image.texel(x0, y0) * (1. - fx) + self.image.texel(x1, y0) * fx;
+                let bottom = self.image.texel(x0, y1) * (1. - fx) + self.image.texel(x1, y1) * fx;
+                top * (1. - fy) + bottom * fy
+            }
+        }
+    }
+}
+
+//Maps any texel coordinate into [0, size)
+fn wrap(i: i64, size: usize, mode: Wrap) -> usize {
+    let size = size as i64;
+    match mode {
+        Wrap::Repeat => i.rem_euclid(size) as usize,
+        Wrap::Clamp => i.clamp(0, size - 1) as usize,
+        Wrap::Mirror => {
+            let i = i.rem_euclid(2 * size);
+            if i < size {
+                i as usize
+            } else {
+                (2 * size - 1 - i) as usize
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    //2x1 image, black then white
+    fn create_texture() -> ImageTexture {
+        ImageTexture::new(Arc::new(Image {
+            path: String::from("test"),
+            width: 2,
+            height: 1,
+            pixels: vec![
+                Color {
+                    r: 0.,
+                    g: 0.,
+                    b: 0.,
+                },
+                Color {
+                    r: 1.,
+                    g: 1.,
+                    b: 1.,
+                },
+            ],
+        }))
+    }
+
+    #[test]
+    fn wrap_modes() {
+        assert_eq!(wrap(5, 4, Wrap::Repeat), 1);
+        assert_eq!(wrap(-1, 4, Wrap::Repeat), 3);
+        assert_eq!(wrap(5, 4, Wrap::Clamp), 3);
+        assert_eq!(wrap(-1, 4, Wrap::Clamp), 0);
+        assert_eq!(wrap(4, 4, Wrap::Mirror), 3);
+        assert_eq!(wrap(-1, 4, Wrap::Mirror), 0);
+    }
+
+    #[test]
+    fn sample_texel_centers() {
+        let texture = create_texture();
+        assert_eq!(texture.sample((0.25, 0.5)).r, 0.);
+        assert_eq!(texture.sample((0.75, 0.5)).r, 1.);
+    }
+
+    #[test]
+    fn sample_bilinear() {
+        let texture = create_texture();
+        assert_abs_diff_eq!(texture.sample((0.5, 0.5)).g, 0.5);
+        let texture = Texture::Image(texture);
+        let pos = Vec3d {
+            x: 0.,
+            y: 0.,
+            z: 0.,
+        };
+        assert_abs_diff_eq!(texture.sample_scalar((0.375, 0.5), pos), 0.25);
+    }
+
+    #[test]
+    fn sample_nearest_clamped() {
+        let mut texture = create_texture();
+        texture.filter = Filter::Nearest;
+        texture.wrap = Wrap::Clamp;
+        assert_eq!(texture.sample((0.6, 0.5)).b, 1.);
+        assert_eq!(texture.sample((2.0, 0.5)).b, 1.);
+        assert_eq!(texture.sample((-1.0, 0.5)).b, 0.);
+    }
+
+    #[test]
+    fn image_slope() {
+        let mut texture = create_texture();
+        texture.wrap = Wrap::Clamp;
+        let texture = Texture::Image(texture);
+        let tangent = Vec3d {
+            x: 1.,
+            y: 0.,
+            z: 0.,
+        };
+        let bitangent = Vec3d {
+            x: 0.,
+            y: 1.,
+            z: 0.,
+        };
+        let origin = Vec3d {
+            x: 0.,
+            y: 0.,
+            z: 0.,
+        };
+        assert_abs_diff_eq!(
+            texture.slope((0.5, 0.5), origin, tangent, bitangent),
+            tangent * 0.5
+        );
+    }
+
+    #[test]
+    fn load_grayscale_16_bits() {
+        let path = std::env::temp_dir().join("raytracer_texture_gray16.png");
+        {
+            let file = File::create(&path).unwrap();
+            let mut encoder = png::Encoder::new(file, 2, 1);
+            encoder.set_color(png::ColorType::Grayscale);
+            encoder.set_depth(png::BitDepth::Sixteen);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&[0, 0, 0xff, 0xff]).unwrap();
+        }
+        let image = Image::load(&path).unwrap();
+        assert_eq!(image.width, 2);
+        assert_eq!(image.height, 1);
+        assert_eq!(image.pixels[0].g, 0.);
+        assert_eq!(image.pixels[1].g, 1.);
+    }
+
+    #[test]
+    fn load_missing_image() {
+        let error = Image::load(Path::new("no_such_texture.png")).unwrap_err();
+        assert!(error.starts_with("no_such_texture.png cannot be read"));
+    }
+
+    #[test]
+    fn parse_modes() {
+        assert_eq!("mirror".parse::<Wrap>(), Ok(Wrap::Mirror));
+        assert_eq!("nearest".parse::<Filter>(), Ok(Filter::Nearest));
+        assert!("bicubic".parse::<Filter>().is_err());
+    }
+}
@@ -0,0 +1,315 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::coord::Vec3d;
+use crate::scene::object::material::Color;
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Pattern {
+    Checker,
+    Noise,
+    Fbm,
+    Turbulence,
+    Marble,
+    Wood,
+    Gradient,
+}
+
+//3D texture evaluated at the intersection position,
+//blending two colors with a pattern value in [0, 1]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Procedural {
+    pub pattern: Pattern,
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+    #[serde(default = "default_octaves")]
+    pub octaves: u32,
+    pub colors: (Color, Color),
+}
+
+impl FromStr for Pattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Pattern, String> {
+        match s {
+            "checker" => Ok(Pattern::Checker),
+            "noise" => Ok(Pattern::Noise),
+            "fbm" => Ok(Pattern::Fbm),
+            "turbulence" => Ok(Pattern::Turbulence),
+            "marble" => Ok(Pattern::Marble),
+            "wood" => Ok(Pattern::Wood),
+            "gradient" => Ok(Pattern::Gradient),
+            _ => Err(format!("unknown pattern {:?}", s)),
+        }
+    }
+}
+
+//name read by from_str
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Pattern::Checker => "checker",
+            Pattern::Noise => "noise",
+            Pattern::Fbm => "fbm",
+            Pattern::Turbulence => "turbulence",
+            Pattern::Marble => "marble",
+            Pattern::Wood => "wood",
+            Pattern::Gradient => "gradient",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+fn default_scale() -> f64 {
+    1.
+}
+
+fn default_octaves() -> u32 {
+    4
+}
+
+//finer octaves are far below a pixel and only slow the render
+pub const MAX_OCTAVES: u32 = 30;
+
+impl Procedural {
+    pub fn new(pattern: Pattern, colors: (Color, Color)) -> Procedural {
+        Procedural {
+            pattern,
+            scale: default_scale(),
+            octaves: default_octaves(),
+            colors,
+        }
+    }
+
+    pub fn sample(&self, pos: Vec3d) -> Color {
+        let t = self.value(pos / self.scale).clamp(0., 1.);
+        self.colors.0 * (1. - t) + self.colors.1 * t
+    }
+
+    //scale is the size of a pattern feature (checker square, wood ring...)
+    fn value(&self, p: Vec3d) -> f64 {
+        match self.pattern {
+            Pattern::Checker => {
+                let parity = p.x.floor() + p.y.floor() + p.z.floor();
+                parity.rem_euclid(2.)
+            }
+            Pattern::Noise => 0.5 + 0.5 * perlin(p),
+            Pattern::Fbm => 0.5 + 0.5 * fbm(p, self.octaves),
+            Pattern::Turbulence => turbulence(p, self.octaves),
+            Pattern::Marble => {
+                0.5 + 0.5 * (p.x * std::f64::consts::PI + 5. * turbulence(p, self.octaves)).sin()
+            }
+            Pattern::Wood => {
+                //rings around the z axis, slightly distorted
+                let r = (p.x * p.x + p.y * p.y).sqrt() + 0.3 * fbm(p, self.octaves);
+                r.rem_euclid(1.)
+            }
+            Pattern::Gradient => p.z,
+        }
+    }
+}
+
+//Sum of octaves of noise, in about [-1, 1]
+pub fn fbm(p: Vec3d, octaves: u32) -> f64 {
+    (0..octaves)
+        .map(|o| {
+            let f = 2f64.powi(o as i32);
+            perlin(p * f) / f
+        })
+        .sum::<f64>()
+        / 2.
+}
+
+//Sum of octaves of absolute noise, in about [0, 1]
+pub fn turbulence(p: Vec3d, octaves: u32) -> f64 {
+    (0..octaves)
+        .map(|o| {
+            let f = 2f64.powi(o as i32);
+            perlin(p * f).abs() / f
+        })
+        .sum::<f64>()
+        / 2.
+}
+
+//Ken Perlin's improved noise, in [-1, 1], null on integer coordinates
+pub fn perlin(p: Vec3d) -> f64 {
+    let (xf, yf, zf) = (p.x.floor(), p.y.floor(), p.z.floor());
+    let (x, y, z) = (p.x - xf, p.y - yf, p.z - zf);
+    let xi = (xf as i64).rem_euclid(256) as usize;
+    let yi = (yf as i64).rem_euclid(256) as usize;
+    let zi = (zf as i64).rem_euclid(256) as usize;
+
+    let (u, v, w) = (fade(x), fade(y), fade(z));
+
+    let perm = |i: usize| PERMUTATION[i % 256] as usize;
+    let a = perm(xi) + yi;
+    let aa = perm(a) + zi;
+    let ab = perm(a + 1) + zi;
+    let b = perm(xi + 1) + yi;
+    let ba = perm(b) + zi;
+    let bb = perm(b + 1) + zi;
+
+    lerp(
+        w,
+        lerp(
+            v,
+            lerp(u, grad(perm(aa), x, y, z), grad(perm(ba), x - 1., y, z)),
+            lerp(u, grad(perm(ab), x, y - 1., z), grad(perm(bb), x - 1., y - 1., z)),
+        ),
+        lerp(
+            v,
+            lerp(
+                u,
+                grad(perm(aa + 1), x, y, z - 1.),
+                grad(perm(ba + 1), x - 1., y, z - 1.),
+            ),
+            lerp(
+                u,
+                grad(perm(ab + 1), x, y - 1., z - 1.),
+                grad(perm(bb + 1), x - 1., y - 1., z - 1.),
+            ),
+        ),
+    )
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6. - 15.) + 10.)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+//dot product with one of 12 gradient directions
+fn grad(hash: usize, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+const PERMUTATION: [u8; 256] = [
+    151, 160, 137, 91, 90, 15, 131, 13, 201, 95, 96, 53, 194, 233, 7, 225, 140, 36, 103, 30, 69,
+    142, 8, 99, 37, 240, 21, 10, 23, 190, 6, 148, 247, 120, 234, 75, 0, 26, 197, 62, 94, 252, 219,
+    203, 117, 35, 11, 32, 57, 177, 33, 88, 237, 149, 56, 87, 174, 20, 125, 136, 171, 168, 68, 175,
+    74, 165, 71, 134, 139, 48, 27, 166, 77, 146, 158, 231, 83, 111, 229, 122, 60, 211, 133, 230,
+    220, 105, 92, 41, 55, 46, 245, 40, 244, 102, 143, 54, 65, 25, 63, 161, 1, 216, 80, 73, 209, 76,
+    132, 187, 208, 89, 18, 169, 200, 196, 135, 130, 116, 188, 159, 86, 164, 100, 109, 198, 173,
+    186, 3, 64, 52, 217, 226, 250, 124, 123, 5, 202, 38, 147, 118, 126, 255, 82, 85, 212, 207, 206,
+    59, 227, 47, 16, 58, 17, 182, 189, 28, 42, 223, 183, 170, 213, 119, 248, 152, 2, 44, 154, 163,
+    70, 221, 153, 101, 155, 167, 43, 172, 9, 129, 22, 39, 253, 19, 98, 108, 110, 79, 113, 224, 232,
+    178, 185, 112, 104, 218, 246, 97, 228, 251, 34, 242, 193, 238, 210, 144, 12, 191, 179, 162,
+    241, 81, 51, 145, 235, 249, 14, 239, 107, 49, 192, 214, 31, 181, 199, 106, 157, 184, 84, 204,
+    176, 115, 121, 50, 45, 127, 4, 150, 254, 138, 236, 205, 93, 222, 114, 67, 29, 24, 72, 243, 141,
+    128, 195, 78, 66, 215, 61, 156, 180,
+];
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    fn black_white() -> (Color, Color) {
+        (
+            Color {
+                r: 0.,
+                g: 0.,
+                b: 0.,
+            },
+            Color {
+                r: 1.,
+                g: 1.,
+                b: 1.,
+            },
+        )
+    }
+
+    #[test]
+    fn checker() {
+        let mut checker = Procedural::new(Pattern::Checker, black_white());
+        checker.scale = 0.5;
+        let pos = Vec3d {
+            x: 0.25,
+            y: 0.25,
+            z: 0.25,
+        };
+        assert_eq!(checker.sample(pos).r, 0.);
+        assert_eq!(checker.sample(pos + Vec3d { x: 0.5, y: 0., z: 0. }).r, 1.);
+        assert_eq!(checker.sample(pos + Vec3d { x: -0.5, y: 0.5, z: 0. }).r, 0.);
+    }
+
+    #[test]
+    fn perlin_range() {
+        for i in 0..1000 {
+            let f = i as f64;
+            let n = perlin(Vec3d {
+                x: f * 0.37,
+                y: f * -0.11,
+                z: f * 0.053,
+            });
+            assert!((-1. ..=1.).contains(&n));
+        }
+        assert_eq!(perlin(Vec3d { x: 3., y: -2., z: 7. }), 0.);
+    }
+
+    #[test]
+    fn perlin_continuous() {
+        let p = Vec3d {
+            x: 1.3,
+            y: 2.7,
+            z: -0.4,
+        };
+        let delta = Vec3d {
+            x: 1e-6,
+            y: 0.,
+            z: 0.,
+        };
+        assert_abs_diff_eq!(perlin(p), perlin(p + delta), epsilon = 1e-4);
+    }
+
+    #[test]
+    fn many_octaves() {
+        let p = Vec3d {
+            x: 1.3,
+            y: 2.7,
+            z: -0.4,
+        };
+        assert!(fbm(p, 40).is_finite() && turbulence(p, 40).is_finite());
+        assert_abs_diff_eq!(fbm(p, MAX_OCTAVES), fbm(p, MAX_OCTAVES - 1), epsilon = 1e-8);
+    }
+
+    #[test]
+    fn gradient() {
+        let mut gradient = Procedural::new(Pattern::Gradient, black_white());
+        gradient.scale = 2.;
+        let pos = Vec3d {
+            x: 5.,
+            y: 5.,
+            z: 1.,
+        };
+        assert_abs_diff_eq!(gradient.sample(pos).g, 0.5);
+    }
+
+    #[test]
+    fn patterns_in_range() {
+        for pattern in ["noise", "fbm", "turbulence", "marble", "wood"] {
+            let procedural = Procedural::new(pattern.parse().unwrap(), black_white());
+            for i in 0..100 {
+                let f = i as f64 * 0.21;
+                let c = procedural.sample(Vec3d { x: f, y: -f, z: 0.5 * f });
+                assert!((0. ..=1.).contains(&c.r));
+            }
+        }
+        assert!("plaid".parse::<Pattern>().is_err());
+    }
+}
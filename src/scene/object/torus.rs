@@ -0,0 +1,236 @@
+use std::sync::Arc;
+use std::f64::consts::PI;
+
+use super::solver::{solve_quadratic, solve_quartic};
+use super::{material::Material, nearest_outside, Intersect};
+use crate::coord::{Matrix4, Vec3d};
+use crate::scene::camera::Ray;
+
+//Tube of radius tube around the circle of radius radius centered on pos,
+//in the plane orthogonal to axis. Computations are done in a local frame
+//where the torus is centered at the origin around the z axis.
+pub struct Torus {
+    pub radius: f64,
+    pub tube: f64,
+    pub material: Arc<Material>,
+    to_local: Matrix4,
+    to_world: Matrix4,
+}
+
+impl Torus {
+    pub fn new(pos: Vec3d, axis: Vec3d, radius: f64, tube: f64, material: impl Into<Arc<Material>>) -> Torus {
+        let axis = axis.normalize().expect("torus axis cannot be null");
+        let to_world = Matrix4::frame(pos, axis);
+        Torus {
+            radius,
+            tube,
+            material: material.into(),
+            to_local: to_world.inverse().unwrap(),
+            to_world,
+        }
+    }
+
+    pub fn intersect(&self, ray: &Ray) -> Option<Intersect> {
+        self.hit(ray).map(|i| i.with_material(&self.material))
+    }
+
+    //intersection without material
+    pub fn hit(&self, ray: &Ray) -> Option<Intersect> {
+        //roots of the quartic are not exact: intersections this close
+        //are the surface the ray starts from
+        let min_dist = 1e-7 * (self.radius + self.tube) / ray.dir.norm();
+        nearest_outside(self.hits(ray), min_dist)
+    }
+
+    //intersections in front of the ray or not
+    pub fn hits(&self, ray: &Ray) -> Vec<Intersect> {
+        let d = self.to_local.transform_vector(ray.dir);
+        let o = self.to_local.transform_point(ray.start);
+        let (big, small) = (self.radius * self.radius, self.tube * self.tube);
+
+        //the quartic is solved from the bounding sphere, to keep its
+        //coefficients small when the ray starts far away
+        let bound = self.radius + self.tube;
+        let Some((shift, _)) = solve_quadratic(d.normsq(), 2. * o.dot(d), o.normsq() - bound * bound) else {
+            return vec![];
+        };
+        let o = o + shift * d;
+
+        //(|p|² + R² - r²)² = 4R²(x² + y²) with p = o + t.d
+        let (dd, od) = (d.normsq(), o.dot(d));
+        let k = o.normsq() + big - small;
+        let roots = solve_quartic(
+            dd * dd,
+            4. * dd * od,
+            4. * od * od + 2. * dd * k - 4. * big * (d.x * d.x + d.y * d.y),
+            4. * od * k - 8. * big * (o.x * d.x + o.y * d.y),
+            k * k - 4. * big * (o.x * o.x + o.y * o.y),
+        );
+
+        roots
+            .into_iter()
+            .map(|t| {
+                let p = o + t * d;
+                //nearest point of the tube center circle
+                let rho = (p.x * p.x + p.y * p.y).sqrt();
+                let center = Vec3d {
+                    x: p.x,
+                    y: p.y,
+                    z: 0.,
+                } * (self.radius / rho);
+                let normal = (p - center).normalize().unwrap();
+                let tangent = Vec3d {
+                    x: -p.y,
+                    y: p.x,
+                    z: 0.,
+                } / rho;
+                //u goes around the axis, v around the tube
+                let u = 0.5 + p.y.atan2(p.x) / (2. * PI);
+                let v = 0.5 + p.z.atan2(rho - self.radius) / (2. * PI);
+
+                let dist = t + shift;
+                let normal = self.to_world.transform_vector(normal);
+                let tangent = self.to_world.transform_vector(tangent);
+                Intersect {
+                    pos: ray.start + dist * ray.dir,
+                    dist,
+                    normal,
+                    tangent,
+                    bitangent: normal.cross(tangent),
+                    uv: (u, v),
+                    material: Material::unset(),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    //lying on the floor, radius 2, tube 0.5
+    fn create_torus() -> Torus {
+        Torus::new(
+            Vec3d {
+                x: 10.,
+                y: 0.,
+                z: 0.,
+            },
+            Vec3d {
+                x: 0.,
+                y: 0.,
+                z: 1.,
+            },
+            2.,
+            0.5,
+            Material::default(),
+        )
+    }
+
+    #[test]
+    fn torus_side() {
+        let ray = Ray {
+            start: Vec3d {
+                x: 0.,
+                y: 0.,
+                z: 0.,
+            },
+            dir: Vec3d {
+                x: 1.,
+                y: 0.,
+                z: 0.,
+            },
+            time: 0.,
+        };
+        let i = create_torus().intersect(&ray).unwrap();
+        assert_abs_diff_eq!(i.dist, 7.5, epsilon = 1e-9);
+        assert_abs_diff_eq!(
+            i.normal,
+            Vec3d {
+                x: -1.,
+                y: 0.,
+                z: 0.
+            },
+            epsilon = 1e-9
+        );
+        assert_abs_diff_eq!(i.uv.1, 0.5, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn torus_hole() {
+        let torus = create_torus();
+        //through the hole
+        let ray = Ray {
+            start: Vec3d {
+                x: 10.,
+                y: 0.,
+                z: 5.,
+            },
+            dir: Vec3d {
+                x: 0.,
+                y: 0.,
+                z: -1.,
+            },
+            time: 0.,
+        };
+        assert_eq!(torus.intersect(&ray), None);
+
+        //from the top of the tube
+        let ray = Ray {
+            start: Vec3d {
+                x: 12.,
+                y: 0.,
+                z: 5.,
+            },
+            dir: Vec3d {
+                x: 0.,
+                y: 0.,
+                z: -1.,
+            },
+            time: 0.,
+        };
+        let i = torus.intersect(&ray).unwrap();
+        assert_abs_diff_eq!(i.dist, 4.5, epsilon = 1e-9);
+        assert_abs_diff_eq!(i.normal.z, 1., epsilon = 1e-9);
+    }
+
+    #[test]
+    fn torus_from_its_surface() {
+        let torus = create_torus();
+        //leaving the inner side of the tube, toward the other side of the hole
+        let ray = Ray {
+            start: Vec3d {
+                x: 8.5,
+                y: 0.,
+                z: 0.,
+            },
+            dir: Vec3d {
+                x: 1.,
+                y: 0.,
+                z: 0.,
+            },
+            time: 0.,
+        };
+        let i = torus.intersect(&ray).unwrap();
+        assert_abs_diff_eq!(i.dist, 3., epsilon = 1e-9);
+
+        //inside the tube
+        let ray = Ray {
+            start: Vec3d {
+                x: 8.,
+                y: 0.,
+                z: 0.,
+            },
+            dir: Vec3d {
+                x: 0.,
+                y: 1.,
+                z: 0.,
+            },
+            time: 0.,
+        };
+        assert_eq!(torus.intersect(&ray), None);
+    }
+}
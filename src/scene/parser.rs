@@ -1,327 +1,1226 @@
-use quick_xml::events::attributes::Attribute;
 use quick_xml::events::{BytesStart, Event};
-use quick_xml::name::QName;
-use quick_xml::reader::Reader;
 
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use std::path::Path;
 use std::str::FromStr;
 
-use super::camera::Camera;
-use super::light::{Light, LightType};
-use super::object::material::{Color, Material};
-use super::object::rasterized::face::Face;
-use super::object::rasterized::Rasterized;
-use super::object::sphere::Sphere;
-use super::object::Object;
+use super::description::{
+    AmbiantLightDescription, BasicMaterial, Bump, CameraDescription, ChannelTexture, FaceDescription, Keyframe,
+    LightDescription, MaterialDescription, MaterialRef, ObjectDescription, SceneDescription, Shape,
+    TextureDescription, TransformDescription,
+};
+use super::camera::DEFAULT_UP;
+use super::error::{Location, SceneError};
+use super::motion::Motion;
+use super::object::material::{Channel, Color, Principled};
+use super::object::texture::procedural::{Pattern, Procedural, MAX_OCTAVES};
+use super::object::texture::{Filter, Wrap};
+use super::object::csg::Operation;
+use super::object::rasterized::polygon::triangulate;
+use super::object::sdf::Node;
 use crate::coord::Vec3d;
+use xml::XmlReader;
 
 use super::Scene;
 
-pub fn load_from_xml_string(file_content: String) -> Scene {
-    let mut reader = Reader::from_str(&file_content);
-    reader.config_mut().trim_text(true);
+pub mod expression;
+pub mod writer;
+pub mod xml;
 
-    let mut camera: Option<Camera> = None;
-    let mut lights: Vec<Light> = vec![];
-    let mut objects: Vec<Object> = vec![];
-    let mut ambiant_light: Option<Light> = None;
+//Relative paths (textures, included files...) are resolved from base_dir,
+//or from the included file they are written in. Animated elements take
+//their value at the given frame
+pub fn load_from_xml_string(
+    file_content: &str,
+    base_dir: &Path,
+    frame: f64,
+    variables: &[(String, f64)],
+) -> Result<Scene, SceneError> {
+    read_from_xml_string(file_content, base_dir, frame, variables)?.build(base_dir)
+}
+
+//Description of the scene, with the checks that can be located in the file.
+//Files are given from base_dir, whatever the file they are written in.
+//The variables given replace the ones of the file.
+pub fn read_from_xml_string(
+    file_content: &str,
+    base_dir: &Path,
+    frame: f64,
+    variables: &[(String, f64)],
+) -> Result<SceneDescription, SceneError> {
+    let mut reader = XmlReader::new(file_content, base_dir, frame).with_variables(variables);
+
+    let mut scene = SceneDescription::default();
+    let mut camera: Option<CameraDescription> = None;
+    let mut ambiant_light: Option<AmbiantLightDescription> = None;
+    let mut root = Location::default();
 
-    let mut buf = Vec::new();
     loop {
-        match reader.read_event_into(&mut buf) {
-            Err(e) => panic!("Error at position {}: {:?}", reader.error_position(), e),
+        let e = match reader.next()? {
             // exits the loop when reaching end of file
-            Ok(Event::Eof) => break,
-            Ok(Event::Start(e)) => {
-                match e.name().as_ref() {
-                    b"scene" => (), //Nothing to do, root.
-                    b"camera" => camera = Some(read_camera(&mut reader)),
-                    b"point_light" => lights.push(read_point_light(&mut reader)),
-                    b"ambiant_light" => ambiant_light = Some(read_ambiant_light(&mut reader)),
-                    b"sphere" => objects.push(read_sphere(&mut reader)),
-                    b"object" => objects.push(read_object(&mut reader)),
-                    _ => (),
-                }
+            Event::Eof => break,
+            Event::Start(e) => e,
+            //read like the elements with children
+            Event::Empty(e) => {
+                reader.expand(&e);
+                e
+            }
+            _ => continue,
+        };
+        match e.name().as_ref() {
+            b"scene" => {
+                root = reader.location();
+                scene.cull_back_faces = read_property(&e, b"cull_back_faces", &root)?.unwrap_or(false)
+            }
+            b"camera" => {
+                reader.enter(String::from("camera"));
+                camera = Some(read_camera(&mut reader)?);
+                reader.leave()
+            }
+            b"point_light" => {
+                reader.enter(format!("lights/{}", scene.lights.len()));
+                scene.lights.push(read_point_light(&mut reader, &e)?);
+                reader.leave()
+            }
+            b"ambiant_light" => {
+                reader.enter(String::from("ambiant_light"));
+                ambiant_light = Some(read_ambiant_light(&mut reader)?);
+                reader.leave()
+            }
+            b"library" => read_library(&mut reader, &mut scene)?,
+            b"material" => read_named_material(&mut reader, &e, &mut scene.materials)?,
+            _ => {
+                reader.enter(format!("objects/{}", scene.objects.len()));
+                let object = match e.name().as_ref() {
+                    b"group" => read_group(&mut reader, &e, &scene, false)?,
+                    _ => match read_primitive(&mut reader, &e, &scene, false)? {
+                        Some(object) => object,
+                        None => return Err(reader.unknown(&e)),
+                    },
+                };
+                scene.objects.push(object);
+                reader.leave()
             }
-            //Ok(Event::End(e)) => {
-            //    println!("Exiting {:?}", String::from_utf8_lossy(e.name().as_ref()))
-            //}
-            _ => (),
         }
-        // if we don't keep a borrow elsewhere, we can clear the buffer to keep memory usage low
-        buf.clear();
     }
 
-    Scene {
-        camera: camera.unwrap(),
-        ambiant_light: ambiant_light.unwrap(),
-        lights,
-        objects,
-    }
+    scene.animations = reader.animations()?;
+    scene.camera = required(camera, &root, "camera")?;
+    scene.ambiant_light = required(ambiant_light, &root, "ambiant_light")?;
+    Ok(scene)
 }
 
-fn read_value_as_f64(a: &[u8]) -> f64 {
-    String::from_utf8_lossy(a).trim().parse::<f64>().unwrap()
+//element that must be given in the element at the location
+fn required<T>(value: Option<T>, at: &Location, element: &str) -> Result<T, SceneError> {
+    value.ok_or_else(|| SceneError::MissingElement {
+        location: at.clone(),
+        element: String::from(element),
+    })
 }
 
-fn read_vec3d(e: BytesStart) -> Vec3d {
-    let mut x = 0.0;
-    let mut y = 0.0;
-    let mut z = 0.0;
-    for a in e.attributes() {
-        let Attribute { key: k, value } = a.unwrap();
-        match k {
-            QName(b"x") => x = read_value_as_f64(&value),
-            QName(b"y") => y = read_value_as_f64(&value),
-            QName(b"z") => z = read_value_as_f64(&value),
-            _ => (),
-        }
+fn invalid(at: &Location, attribute: &str, value: String, message: impl Display) -> SceneError {
+    SceneError::InvalidValue {
+        location: at.clone(),
+        attribute: String::from(attribute),
+        value,
+        message: message.to_string(),
     }
-    Vec3d { x, y, z }
 }
 
-fn read_color(e: BytesStart) -> Color {
-    let mut r = 0.0;
-    let mut g = 0.0;
-    let mut b = 0.0;
-    for a in e.attributes() {
-        let Attribute { key: k, value } = a.unwrap();
-        match k {
-            QName(b"r") => r = read_value_as_f64(&value),
-            QName(b"g") => g = read_value_as_f64(&value),
-            QName(b"b") => b = read_value_as_f64(&value),
-            _ => (),
-        }
+fn read_vec3d(e: &BytesStart, at: &Location) -> Result<Vec3d, SceneError> {
+    Ok(Vec3d {
+        x: read_property(e, b"x", at)?.unwrap_or(0.),
+        y: read_property(e, b"y", at)?.unwrap_or(0.),
+        z: read_property(e, b"z", at)?.unwrap_or(0.),
+    })
+}
+
+//attributes of animated elements and the fields of their value they give
+const VECTOR: &[(&str, &str)] = &[("x", "x"), ("y", "y"), ("z", "z")];
+const COLOR: &[(&str, &str)] = &[("r", "r"), ("g", "g"), ("b", "b")];
+
+//name in a path of the description, escaped like in JSON pointers
+fn field_name(name: &str) -> String {
+    name.replace('~', "~0").replace('/', "~1")
+}
+
+//vector that is only used for its direction
+fn read_direction(e: &BytesStart, at: &Location) -> Result<Vec3d, SceneError> {
+    let v = read_vec3d(e, at)?;
+    match v.normalize() {
+        Some(_) => Ok(v),
+        None => Err(invalid(at, "direction", String::from("0 0 0"), "it cannot be null")),
     }
-    r = r.clamp(0., 1.);
-    g = g.clamp(0., 1.);
-    b = b.clamp(0., 1.);
-    Color { r, g, b }
 }
 
-fn read_property<T: FromStr>(e: &BytesStart, property_name: &[u8]) -> Option<T> {
-    match e
-        .attributes()
-        .find(|a| a.as_ref().unwrap().key == QName(property_name))
+fn read_color(e: &BytesStart, at: &Location) -> Result<Color, SceneError> {
+    let channel = |key| Ok::<f64, SceneError>(read_property::<f64>(e, key, at)?.unwrap_or(0.).clamp(0., 1.));
+    Ok(Color {
+        r: channel(b"r")?,
+        g: channel(b"g")?,
+        b: channel(b"b")?,
+    })
+}
+
+//None when the attribute is missing, an error when it cannot be parsed,
+//numbers can be written as expressions
+fn read_property<T: FromStr>(e: &BytesStart, property_name: &[u8], at: &Location) -> Result<Option<T>, SceneError>
+where
+    T::Err: Display,
+{
+    match xml::attributes(e, at)?
+        .into_iter()
+        .find(|(key, _)| key.as_bytes() == property_name)
     {
-        None => None,
-        Some(a) => match String::from_utf8_lossy(a.unwrap().value.as_ref()).parse::<T>() {
-            Err(_) => None,
-            Ok(v) => Some(v),
+        None => Ok(None),
+        Some((key, value)) => match expression::parse::<T>(&value) {
+            Ok(v) => Ok(Some(v)),
+            Err(message) => Err(invalid(at, &key, value, message)),
         },
     }
 }
 
-fn read_camera(reader: &mut Reader<&[u8]>) -> Camera {
-    let mut buf = Vec::new();
+fn require_property<T: FromStr>(e: &BytesStart, property_name: &[u8], at: &Location) -> Result<T, SceneError>
+where
+    T::Err: Display,
+{
+    read_property(e, property_name, at)?.ok_or_else(|| SceneError::MissingAttribute {
+        location: at.clone(),
+        attribute: String::from_utf8_lossy(property_name).into_owned(),
+    })
+}
+
+fn read_camera(reader: &mut XmlReader) -> Result<CameraDescription, SceneError> {
+    let at = reader.location();
     let mut pos: Option<Vec3d> = None;
-    let mut dir: Option<Vec3d> = None;
+    let mut dir: Option<(Vec3d, Location)> = None;
+    let mut shutter = (0., 0.);
+    let mut up: Option<(Vec3d, Location)> = None;
+    let mut resolution: Option<(u32, u32)> = None;
+    let mut fov: Option<f64> = None;
     loop {
-        match reader.read_event_into(&mut buf) {
-            Err(e) => panic!("Error at position {}: {:?}", reader.error_position(), e),
-            Ok(Event::Eof) => panic!("Unexpected EOF"),
-            Ok(Event::Empty(e)) => match e.name().as_ref() {
-                b"pos" => pos = Some(read_vec3d(e)),
-                b"dir" => dir = Some(read_vec3d(e)),
-                _ => (),
-            },
-            Ok(Event::Start(e)) => panic!("unexpected block begin named {:?}", e.name().as_ref()),
-            Ok(Event::End(e)) => match e.name().as_ref() {
-                b"camera" => break,
-                name => panic!("unexpected end {:?}", name),
-            },
+        match reader.next()? {
+            Event::Empty(e) => {
+                let at = &reader.location();
+                match e.name().as_ref() {
+                    b"pos" => {
+                        pos = Some(read_vec3d(&e, at)?);
+                        reader.animate("pos", VECTOR)?
+                    }
+                    b"dir" => {
+                        dir = Some((read_direction(&e, at)?, at.clone()));
+                        reader.animate("dir", VECTOR)?
+                    }
+                    b"shutter" => {
+                        shutter = (
+                            read_property::<f64>(&e, b"open", at)?.unwrap_or(0.),
+                            read_property::<f64>(&e, b"close", at)?.unwrap_or(0.),
+                        )
+                    }
+                    b"up" => {
+                        up = Some((read_direction(&e, at)?, at.clone()));
+                        reader.animate("up", VECTOR)?
+                    }
+                    b"resolution" => {
+                        resolution = Some((
+                            require_property::<u32>(&e, b"width", at)?,
+                            require_property::<u32>(&e, b"height", at)?,
+                        ))
+                    }
+                    b"fov" => {
+                        fov = Some(require_property::<f64>(&e, b"angle", at)?);
+                        reader.animate("fov", &[("angle", "")])?
+                    }
+                    _ => (),
+                }
+            }
+            Event::Start(e) => return Err(reader.unknown(&e)),
+            Event::End(_) => break,
             _ => (),
         }
-        buf.clear();
     }
-    Camera::new(pos.unwrap(), dir.unwrap(), None, None, None)
+    //the vertical of the image is up without its part along dir
+    let (dir, dir_at) = required(dir, &at, "dir")?;
+    let xyz = |v: Vec3d| format!("{} {} {}", v.x, v.y, v.z);
+    match &up {
+        Some((up, up_at)) if up.cross(dir).normalize().is_none() => {
+            return Err(invalid(up_at, "up", xyz(*up), "it is parallel to the camera direction"))
+        }
+        None if DEFAULT_UP.cross(dir).normalize().is_none() => {
+            return Err(invalid(&dir_at, "dir", xyz(dir), "it is vertical, the camera needs an <up> direction"))
+        }
+        _ => (),
+    }
+    Ok(CameraDescription {
+        pos: required(pos, &at, "pos")?,
+        dir,
+        shutter,
+        up: up.map(|(up, _)| up),
+        resolution,
+        fov,
+    })
 }
 
-fn read_point_light(reader: &mut Reader<&[u8]>) -> Light {
-    let mut buf = Vec::new();
+fn read_point_light(reader: &mut XmlReader, e: &BytesStart) -> Result<LightDescription, SceneError> {
+    let at = reader.location();
+    let name = read_property::<String>(e, b"name", &at)?;
     let mut pos: Option<Vec3d> = None;
     let mut color: Option<Color> = None;
     let mut intensity = 0.;
     loop {
-        match reader.read_event_into(&mut buf) {
-            Err(e) => panic!("Error at position {}: {:?}", reader.error_position(), e),
-            Ok(Event::Eof) => panic!("Unexpected EOF"),
-            Ok(Event::Empty(e)) => match e.name().as_ref() {
-                b"color" => color = Some(read_color(e)),
-                b"intensity" => intensity = read_property::<f64>(&e, b"i").unwrap(),
-                b"pos" => pos = Some(read_vec3d(e)),
-                _ => (),
-            },
-            Ok(Event::Start(e)) => panic!("unexpected block begin named {:?}", e.name().as_ref()),
-            Ok(Event::End(e)) => match e.name().as_ref() {
-                b"point_light" => break,
-                name => panic!("unexpected end {:?}", name),
-            },
+        match reader.next()? {
+            Event::Empty(e) => {
+                let at = &reader.location();
+                match e.name().as_ref() {
+                    b"color" => {
+                        color = Some(read_color(&e, at)?);
+                        reader.animate("color", COLOR)?
+                    }
+                    b"intensity" => {
+                        intensity = require_property::<f64>(&e, b"i", at)?;
+                        reader.animate("intensity", &[("i", "")])?
+                    }
+                    b"pos" => {
+                        pos = Some(read_vec3d(&e, at)?);
+                        reader.animate("pos", VECTOR)?
+                    }
+                    _ => (),
+                }
+            }
+            Event::Start(e) => return Err(reader.unknown(&e)),
+            Event::End(_) => break,
             _ => (),
         }
-        buf.clear();
     }
-    Light {
-        color: color.unwrap(),
+    Ok(LightDescription {
+        name,
+        pos: required(pos, &at, "pos")?,
+        color: required(color, &at, "color")?,
         intensity,
-        light_type: LightType::PointLight { pos: pos.unwrap() },
-    }
+    })
 }
 
-fn read_ambiant_light(reader: &mut Reader<&[u8]>) -> Light {
-    let mut buf = Vec::new();
+fn read_ambiant_light(reader: &mut XmlReader) -> Result<AmbiantLightDescription, SceneError> {
+    let at = reader.location();
     let mut color: Option<Color> = None;
     let mut intensity = 0.;
     loop {
-        match reader.read_event_into(&mut buf) {
-            Err(e) => panic!("Error at position {}: {:?}", reader.error_position(), e),
-            Ok(Event::Eof) => panic!("Unexpected EOF"),
-            Ok(Event::Empty(e)) => match e.name().as_ref() {
-                b"color" => color = Some(read_color(e)),
-                b"intensity" => intensity = read_property::<f64>(&e, b"i").unwrap(),
-                _ => (),
-            },
-            Ok(Event::Start(e)) => match e.name().as_ref() {
-                b"ambiant_light" => (), //tests will have that line
-                name => panic!("unexpected block begin named {:?}", name),
-            },
-            Ok(Event::End(e)) => match e.name().as_ref() {
-                b"ambiant_light" => break,
-                name => panic!("unexpected end {:?}", name),
-            },
+        match reader.next()? {
+            Event::Empty(e) => {
+                let at = &reader.location();
+                match e.name().as_ref() {
+                    b"color" => {
+                        color = Some(read_color(&e, at)?);
+                        reader.animate("color", COLOR)?
+                    }
+                    b"intensity" => {
+                        intensity = require_property::<f64>(&e, b"i", at)?;
+                        reader.animate("intensity", &[("i", "")])?
+                    }
+                    _ => (),
+                }
+            }
+            Event::Start(e) => return Err(reader.unknown(&e)),
+            Event::End(_) => break,
             _ => (),
         }
-        buf.clear();
     }
-    Light {
-        color: color.unwrap(),
+    Ok(AmbiantLightDescription {
+        color: required(color, &at, "color")?,
         intensity,
-        light_type: LightType::AmbiantLight,
+    })
+}
+
+//the texture attribute is either a procedural pattern name or an image path,
+//procedural textures blend the given colors
+fn read_texture(
+    e: &BytesStart,
+    reader: &XmlReader,
+    colors: (Color, Color),
+    at: &Location,
+) -> Result<Option<TextureDescription>, SceneError> {
+    let Some(name) = read_property::<String>(e, b"texture", at)? else {
+        return Ok(None);
+    };
+    let texture = match name.parse::<Pattern>() {
+        Ok(pattern) => {
+            let mut procedural = Procedural::new(pattern, colors);
+            if let Some(scale) = read_property(e, b"scale", at)? {
+                procedural.scale = scale;
+            }
+            if let Some(octaves) = read_property::<u32>(e, b"octaves", at)? {
+                procedural.octaves = octaves.min(MAX_OCTAVES);
+            }
+            TextureDescription::Procedural(procedural)
+        }
+        //images are told from patterns by their extension
+        Err(message) if Path::new(&name).extension().is_none() => {
+            return Err(invalid(at, "texture", name, format!("{}, image files need an extension", message)))
+        }
+        Err(_) => TextureDescription::Image {
+            file: reader.file_path(&name),
+            wrap: read_property(e, b"wrap", at)?.unwrap_or(Wrap::Repeat),
+            filter: read_property(e, b"filter", at)?.unwrap_or(Filter::Bilinear),
+            scale: read_property(e, b"scale", at)?.unwrap_or(1.),
+        },
+    };
+    Ok(Some(texture))
+}
+
+//second color of procedural textures
+fn read_second_color(e: &BytesStart, at: &Location) -> Result<Color, SceneError> {
+    let channel = |key| Ok::<f64, SceneError>(read_property::<f64>(e, key, at)?.unwrap_or(0.).clamp(0., 1.));
+    Ok(Color {
+        r: channel(b"r2")?,
+        g: channel(b"g2")?,
+        b: channel(b"b2")?,
+    })
+}
+
+fn read_color_channel(
+    e: &BytesStart,
+    channel: Channel,
+    reader: &XmlReader,
+    textures: &mut Vec<ChannelTexture>,
+    at: &Location,
+) -> Result<Color, SceneError> {
+    let color = read_color(e, at)?;
+    if let Some(texture) = read_texture(e, reader, (color, read_second_color(e, at)?), at)? {
+        textures.push(ChannelTexture { channel, texture });
+    }
+    Ok(color)
+}
+
+//value of the key attribute, which becomes optional when textured
+//(procedural textures blend the values of key and key2)
+fn read_scalar_channel(
+    e: &BytesStart,
+    key: &[u8],
+    channel: Channel,
+    reader: &XmlReader,
+    textures: &mut Vec<ChannelTexture>,
+    at: &Location,
+) -> Result<f64, SceneError> {
+    let value = read_property::<f64>(e, key, at)?;
+    let value2 = read_property::<f64>(e, &[key, b"2"].concat(), at)?.unwrap_or(0.);
+    let colors = (Color::gray(value.unwrap_or(0.)), Color::gray(value2));
+    match read_texture(e, reader, colors, at)? {
+        Some(texture) => {
+            textures.push(ChannelTexture { channel, texture });
+            Ok(value.unwrap_or(0.))
+        }
+        None => require_property(e, key, at),
+    }
+}
+
+fn read_material(reader: &mut XmlReader, e: &BytesStart) -> Result<MaterialDescription, SceneError> {
+    let at = reader.location();
+    match read_property::<String>(e, b"type", &at)?.as_deref() {
+        None | Some("basic") => Ok(MaterialDescription::Basic(read_basic_material(reader)?)),
+        Some("principled") => Ok(MaterialDescription::Principled(read_principled_material(reader)?)),
+        Some(t) => Err(invalid(&at, "type", String::from(t), "unknown material type")),
+    }
+}
+
+//material="name" attribute, referring to a named material
+fn read_material_reference(
+    e: &BytesStart,
+    materials: &BTreeMap<String, MaterialDescription>,
+    at: &Location,
+) -> Result<Option<MaterialRef>, SceneError> {
+    let Some(name) = read_property::<String>(e, b"material", at)? else {
+        return Ok(None);
+    };
+    match materials.contains_key(&name) {
+        true => Ok(Some(MaterialRef::Named(name))),
+        false => Err(invalid(at, "material", name, "there is no material with this name before")),
     }
 }
 
-fn read_material(reader: &mut Reader<&[u8]>) -> Material {
-    let mut buf = Vec::new();
+//<material name="gold"> definition, shared by the objects that refer to it
+fn read_named_material(
+    reader: &mut XmlReader,
+    e: &BytesStart,
+    materials: &mut BTreeMap<String, MaterialDescription>,
+) -> Result<(), SceneError> {
+    let name = require_property::<String>(e, b"name", &reader.location())?;
+    reader.enter(format!("materials/{}", field_name(&name)));
+    let material = read_material(reader, e)?;
+    reader.leave();
+    materials.insert(name, material);
+    Ok(())
+}
+
+//material written in an object, a group or an instance
+fn read_inline_material(reader: &mut XmlReader, e: &BytesStart) -> Result<MaterialDescription, SceneError> {
+    reader.enter(String::from("material"));
+    let material = read_material(reader, e)?;
+    reader.leave();
+    Ok(material)
+}
+
+fn read_basic_material(reader: &mut XmlReader) -> Result<BasicMaterial, SceneError> {
+    let at = reader.location();
     let mut diffuse: Option<Color> = None;
     let mut specular: Option<Color> = None;
-    let mut reflectivity = 0.;
-    let mut roughness = 0.;
+    let mut material = BasicMaterial {
+        diffuse: Color::gray(0.),
+        specular: Color::gray(0.),
+        reflectivity: 0.,
+        roughness: 0.,
+        sheen: 0.,
+        clearcoat: 0.,
+        transmission: Color::gray(0.),
+        ior: 1.,
+        textures: vec![],
+        normal_map: None,
+        bump: None,
+    };
+    let textures = &mut material.textures;
 
     loop {
-        match reader.read_event_into(&mut buf) {
-            Err(e) => panic!("Error at position {}: {:?}", reader.error_position(), e),
-            Ok(Event::Eof) => panic!("Unexpected EOF"),
-            Ok(Event::Empty(e)) => match e.name().as_ref() {
-                b"diffuse" => diffuse = Some(read_color(e)),
-                b"specular" => specular = Some(read_color(e)),
-                b"reflectivity" => reflectivity = read_property::<f64>(&e, b"r").unwrap(),
-                b"roughness" => roughness = read_property::<f64>(&e, b"r").unwrap(),
-                _ => (),
-            },
-            Ok(Event::Start(e)) => panic!("unexpected block begin named {:?}", e.name().as_ref()),
-            Ok(Event::End(e)) => match e.name().as_ref() {
-                b"material" => break,
-                name => panic!("unexpected end {:?}", name),
-            },
+        match reader.next()? {
+            Event::Empty(e) => {
+                let at = &reader.location();
+                let channels = &*reader;
+                match e.name().as_ref() {
+                    b"diffuse" => {
+                        diffuse = Some(read_color_channel(&e, Channel::Diffuse, channels, textures, at)?)
+                    }
+                    b"specular" => {
+                        specular = Some(read_color_channel(&e, Channel::Specular, channels, textures, at)?)
+                    }
+                    b"reflectivity" => {
+                        material.reflectivity =
+                            read_scalar_channel(&e, b"r", Channel::Reflectivity, channels, textures, at)?
+                    }
+                    b"roughness" => {
+                        material.roughness =
+                            read_scalar_channel(&e, b"r", Channel::Roughness, channels, textures, at)?
+                    }
+                    b"sheen" => {
+                        material.sheen = read_scalar_channel(&e, b"s", Channel::Sheen, channels, textures, at)?
+                    }
+                    b"clearcoat" => {
+                        material.clearcoat =
+                            read_scalar_channel(&e, b"c", Channel::Clearcoat, channels, textures, at)?
+                    }
+                    b"transmission" => {
+                        material.transmission =
+                            read_color_channel(&e, Channel::Transmission, channels, textures, at)?
+                    }
+                    b"ior" => {
+                        material.ior = read_scalar_channel(&e, b"i", Channel::Ior, channels, textures, at)?
+                    }
+                    b"normal_map" => {
+                        material.normal_map =
+                            read_texture(&e, channels, (Color::gray(0.), Color::gray(1.)), at)?
+                    }
+                    b"bump" => {
+                        let strength = read_property::<f64>(&e, b"strength", at)?.unwrap_or(1.);
+                        material.bump = read_texture(&e, channels, (Color::gray(0.), Color::gray(1.)), at)?
+                            .map(|texture| Bump { texture, strength })
+                    }
+                    _ => (),
+                }
+                //values of procedural textures are not animated
+                let fields: &[(&str, &str)] = match e.name().as_ref() {
+                    b"diffuse" | b"specular" | b"transmission" => COLOR,
+                    b"reflectivity" | b"roughness" => &[("r", "")],
+                    b"sheen" => &[("s", "")],
+                    b"clearcoat" => &[("c", "")],
+                    b"ior" => &[("i", "")],
+                    _ => &[],
+                };
+                reader.animate(&xml::element_name(&e), fields)?
+            }
+            Event::Start(e) => return Err(reader.unknown(&e)),
+            Event::End(_) => break,
             _ => (),
         }
-        buf.clear();
     }
-    Material {
-        diffuse: diffuse.unwrap(),
-        specular: specular.unwrap(),
-        reflectivity,
-        roughness,
+    Ok(BasicMaterial {
+        diffuse: required(diffuse, &at, "diffuse")?,
+        specular: required(specular, &at, "specular")?,
+        ..material
+    })
+}
+
+fn read_principled_material(reader: &mut XmlReader) -> Result<Principled, SceneError> {
+    let mut principled = Principled::default();
+
+    loop {
+        match reader.next()? {
+            Event::Empty(e) => {
+                let at = &reader.location();
+                match e.name().as_ref() {
+                    b"base_color" => principled.base_color = read_color(&e, at)?,
+                    b"metallic" => principled.metallic = require_property(&e, b"m", at)?,
+                    b"roughness" => principled.roughness = require_property(&e, b"r", at)?,
+                    b"specular" => principled.specular = require_property(&e, b"s", at)?,
+                    b"sheen" => principled.sheen = require_property(&e, b"s", at)?,
+                    b"clearcoat" => principled.clearcoat = require_property(&e, b"c", at)?,
+                    b"transmission" => principled.transmission = require_property(&e, b"t", at)?,
+                    b"ior" => principled.ior = require_property(&e, b"i", at)?,
+                    _ => (),
+                }
+                let fields: &[(&str, &str)] = match e.name().as_ref() {
+                    b"base_color" => COLOR,
+                    b"metallic" => &[("m", "")],
+                    b"roughness" => &[("r", "")],
+                    b"specular" | b"sheen" => &[("s", "")],
+                    b"clearcoat" => &[("c", "")],
+                    b"transmission" => &[("t", "")],
+                    b"ior" => &[("i", "")],
+                    _ => &[],
+                };
+                reader.animate(&xml::element_name(&e), fields)?
+            }
+            Event::Start(e) => return Err(reader.unknown(&e)),
+            Event::End(_) => break,
+            _ => (),
+        }
+    }
+    Ok(principled)
+}
+
+//material of an object, that can only be omitted when there is a default one
+fn object_material(
+    material: Option<MaterialDescription>,
+    default_material: bool,
+    at: &Location,
+) -> Result<Option<MaterialRef>, SceneError> {
+    match material {
+        Some(material) => Ok(Some(MaterialRef::Inline(Box::new(material)))),
+        None if default_material => Ok(None),
+        None => Err(SceneError::MissingElement {
+            location: at.clone(),
+            element: String::from("material"),
+        }),
     }
 }
 
-fn read_sphere(reader: &mut Reader<&[u8]>) -> Object {
-    let mut buf = Vec::new();
+//single object (shape, mesh, instance or csg), None for any other element
+//objects without material use the default one (from their group), unless
+//they refer to a named one
+fn read_primitive(
+    reader: &mut XmlReader,
+    e: &BytesStart,
+    scene: &SceneDescription,
+    default_material: bool,
+) -> Result<Option<ObjectDescription>, SceneError> {
+    let named = read_material_reference(e, &scene.materials, &reader.location())?;
+    let default_material = default_material || named.is_some();
+    let name = e.name();
+    let name = name.as_ref();
+    let mut object = match name {
+        b"instance" => read_instance(reader, e, scene)?,
+        b"csg" => read_csg(reader, e, scene, default_material)?,
+        b"sdf" => read_sdf(reader, default_material)?,
+        b"sphere" => read_sphere(reader, default_material)?,
+        b"object" => read_object(reader, e, default_material)?,
+        b"plane" | b"disk" | b"box" | b"cylinder" | b"cone" | b"torus" | b"heightfield" => {
+            read_shape(reader, e, default_material)?
+        }
+        _ => return Ok(None),
+    };
+    object.material = object.material.or(named);
+    Ok(Some(object))
+}
+
+fn read_sphere(reader: &mut XmlReader, default_material: bool) -> Result<ObjectDescription, SceneError> {
+    let at = reader.location();
     let mut pos: Option<Vec3d> = None;
-    let mut r = 0.;
-    let mut mat: Option<Material> = None;
+    let mut r: Option<f64> = None;
+    let mut velocity: Option<Vec3d> = None;
+    let mut mat: Option<MaterialDescription> = None;
     loop {
-        match reader.read_event_into(&mut buf) {
-            Err(e) => panic!("Error at position {}: {:?}", reader.error_position(), e),
-            Ok(Event::Eof) => panic!("Unexpected EOF"),
-            Ok(Event::Empty(e)) => match e.name().as_ref() {
-                b"pos" => pos = Some(read_vec3d(e)),
-                b"radius" => r = read_property::<f64>(&e, b"r").unwrap(),
-                _ => (),
+        match reader.next()? {
+            Event::Empty(e) => {
+                let at = &reader.location();
+                match e.name().as_ref() {
+                    b"pos" => pos = Some(read_vec3d(&e, at)?),
+                    b"radius" => r = Some(require_property(&e, b"r", at)?),
+                    b"velocity" => velocity = Some(read_vec3d(&e, at)?),
+                    _ => (),
+                }
+                let fields = if e.name().as_ref() == b"radius" { &[("r", "")] } else { VECTOR };
+                reader.animate(&xml::element_name(&e), fields)?
+            }
+            Event::Start(e) => match e.name().as_ref() {
+                b"material" => mat = Some(read_inline_material(reader, &e)?),
+                _ => return Err(reader.unknown(&e)),
             },
-            Ok(Event::Start(e)) => match e.name().as_ref() {
-                b"material" => mat = Some(read_material(reader)),
-                name => panic!("unexpected block begin named {:?}", name),
+            Event::End(_) => break,
+            _ => (),
+        }
+    }
+    let material = object_material(mat, default_material, &at)?;
+    let shape = Shape::Sphere {
+        pos: required(pos, &at, "pos")?,
+        radius: required(r, &at, "radius")?,
+        velocity,
+    };
+    Ok(ObjectDescription::new(shape, material))
+}
+
+
+//shape defined by a few single elements (pos, normal...) and a material
+fn read_shape(
+    reader: &mut XmlReader,
+    e: &BytesStart,
+    default_material: bool,
+) -> Result<ObjectDescription, SceneError> {
+    let at = reader.location();
+    let name = e.name();
+    let name = name.as_ref();
+    //the file is read once the scene is built, without knowing where it is
+    //written, so a missing one is reported here
+    let file = match read_property::<String>(e, b"file", &at)? {
+        Some(file) if !reader.base_dir().join(&file).is_file() => {
+            return Err(SceneError::Io {
+                location: Some(at),
+                message: format!("{}: there is no such file", reader.base_dir().join(&file).display()),
+            })
+        }
+        file => file.map(|file| reader.file_path(&file)),
+    };
+    let mut elements: HashMap<Vec<u8>, (BytesStart, Location)> = HashMap::new();
+    let mut mat: Option<MaterialDescription> = None;
+    loop {
+        match reader.next()? {
+            Event::Empty(e) => {
+                //the elements are named like the fields of the shape
+                let fields = match e.name().as_ref() {
+                    b"radius" | b"top_radius" | b"tube" => &[("r", "")],
+                    _ => VECTOR,
+                };
+                reader.animate(&xml::element_name(&e), fields)?;
+                elements.insert(e.name().as_ref().to_vec(), (e.into_owned(), reader.location()));
+            }
+            Event::Start(e) => match e.name().as_ref() {
+                b"material" => mat = Some(read_inline_material(reader, &e)?),
+                _ => return Err(reader.unknown(&e)),
             },
-            Ok(Event::End(e)) => match e.name().as_ref() {
-                b"sphere" => break,
-                name => panic!("unexpected end {:?}", name),
+            Event::End(_) => break,
+            _ => (),
+        }
+    }
+    let material = object_material(mat, default_material, &at)?;
+    let element = |key: &str| required(elements.get(key.as_bytes()), &at, key);
+    let vec = |key| element(key).and_then(|(e, at)| read_vec3d(e, at));
+    let direction = |key| element(key).and_then(|(e, at)| read_direction(e, at));
+    let scalar = |key, attribute: &[u8]| element(key).and_then(|(e, at)| require_property::<f64>(e, attribute, at));
+    //the top of cylinders and cones, away from their base
+    let top = |pos: Vec3d| {
+        let (e, at) = element("top")?;
+        let top = read_vec3d(e, at)?;
+        match (top - pos).normalize() {
+            Some(_) => Ok(top),
+            None => Err(invalid(at, "top", format!("{} {} {}", top.x, top.y, top.z), "it is the base position")),
+        }
+    };
+
+    let shape = match name {
+        b"plane" => Shape::Plane {
+            pos: vec("pos")?,
+            normal: direction("normal")?,
+        },
+        b"disk" => Shape::Disk {
+            pos: vec("pos")?,
+            normal: direction("normal")?,
+            radius: scalar("radius", b"r")?,
+        },
+        b"box" => {
+            //boxes are not flat, their uvs divide by each size
+            let min = vec("min")?;
+            let (e, at) = element("max")?;
+            let max = read_vec3d(e, at)?;
+            if (0..3).any(|axis| min.component(axis) == max.component(axis)) {
+                let value = format!("{} {} {}", max.x, max.y, max.z);
+                return Err(invalid(at, "max", value, "it is level with min on an axis, the box is flat"));
+            }
+            Shape::Box { min, max }
+        }
+        b"cylinder" => {
+            let pos = vec("pos")?;
+            Shape::Cylinder {
+                pos,
+                top: top(pos)?,
+                radius: scalar("radius", b"r")?,
+            }
+        }
+        b"cone" => {
+            //pointed unless a top radius is given
+            let top_radius = match elements.get(b"top_radius".as_slice()) {
+                Some((e, at)) => require_property::<f64>(e, b"r", at)?,
+                None => 0.,
+            };
+            let pos = vec("pos")?;
+            Shape::Cone {
+                pos,
+                top: top(pos)?,
+                radius: scalar("radius", b"r")?,
+                top_radius,
+            }
+        }
+        b"torus" => Shape::Torus {
+            pos: vec("pos")?,
+            axis: direction("axis")?,
+            radius: scalar("radius", b"r")?,
+            tube: scalar("tube", b"r")?,
+        },
+        b"heightfield" => {
+            let (e, size_at) = element("size")?;
+            let size = read_vec3d(e, size_at)?;
+            if size.x <= 0. || size.y <= 0. {
+                let value = format!("{} {} {}", size.x, size.y, size.z);
+                return Err(invalid(size_at, "size", value, "its x and y must be positive"));
+            }
+            Shape::Heightfield {
+                file: file.ok_or_else(|| SceneError::MissingAttribute {
+                    location: at.clone(),
+                    attribute: String::from("file"),
+                })?,
+                pos: vec("pos")?,
+                size,
+            }
+        }
+        _ => return Err(xml::unknown(e, &at)),
+    };
+    Ok(ObjectDescription::new(shape, material))
+}
+
+//polygon with any number of points, that must be split in triangles
+fn read_face(reader: &mut XmlReader) -> Result<FaceDescription, SceneError> {
+    let at = reader.location();
+    let mut points = Vec::new();
+    let mut uvs = Vec::new();
+    let mut normals = Vec::new();
+    //uvs and normals are only kept when they are given
+    let mut textured = false;
+    let mut smooth = false;
+    loop {
+        match reader.next()? {
+            Event::Empty(e) if e.name().as_ref() == b"pos" => {
+                let at = &reader.location();
+                let u = read_property::<f64>(&e, b"u", at)?;
+                let v = read_property::<f64>(&e, b"v", at)?;
+                textured = textured || u.is_some() || v.is_some();
+                uvs.push((u.unwrap_or(0.), v.unwrap_or(0.)));
+                let nx = read_property::<f64>(&e, b"nx", at)?;
+                let ny = read_property::<f64>(&e, b"ny", at)?;
+                let nz = read_property::<f64>(&e, b"nz", at)?;
+                smooth = smooth || nx.is_some() || ny.is_some() || nz.is_some();
+                normals.push(Vec3d { x: nx.unwrap_or(0.), y: ny.unwrap_or(0.), z: nz.unwrap_or(0.) });
+                points.push(read_vec3d(&e, at)?)
+            }
+            Event::Start(e) => return Err(reader.unknown(&e)),
+            Event::End(_) => break,
+            _ => (),
+        }
+    }
+    triangulate(&points).map_err(|message| invalid(&at, "points", String::new(), message))?;
+    if !textured {
+        uvs.clear();
+    }
+    if !smooth {
+        normals.clear();
+    }
+    Ok(FaceDescription { points, uvs, normals })
+}
+
+fn read_object(
+    reader: &mut XmlReader,
+    e: &BytesStart,
+    default_material: bool,
+) -> Result<ObjectDescription, SceneError> {
+    let at = reader.location();
+    let double_sided = read_property(e, b"double_sided", &at)?;
+    let mut mat: Option<MaterialDescription> = None;
+    let mut faces = Vec::new();
+    loop {
+        match reader.next()? {
+            Event::Start(e) => match e.name().as_ref() {
+                b"face" => faces.push(read_face(reader)?),
+                b"material" => mat = Some(read_inline_material(reader, &e)?),
+                _ => return Err(reader.unknown(&e)),
             },
+            Event::End(_) => break,
             _ => (),
         }
-        buf.clear();
     }
-    Object::Sphere(Sphere::new(pos.unwrap(), r, mat.unwrap()))
+    let material = object_material(mat, default_material, &at)?;
+    Ok(ObjectDescription::new(Shape::Mesh { faces, double_sided }, material))
+}
+
+//translate, rotate (angle in degrees around an axis) or scale element
+fn read_transform(e: &BytesStart, at: &Location) -> Result<Option<TransformDescription>, SceneError> {
+    let transform = match e.name().as_ref() {
+        b"translate" => TransformDescription::Translate(read_vec3d(e, at)?),
+        b"rotate" => {
+            let angle = require_property::<f64>(e, b"angle", at)?;
+            TransformDescription::Rotate {
+                axis: read_direction(e, at)?,
+                angle,
+            }
+        }
+        b"scale" => {
+            let s = match read_property::<f64>(e, b"s", at)? {
+                Some(s) => Vec3d { x: s, y: s, z: s },
+                None => read_vec3d(e, at)?,
+            };
+            //objects would be flattened, without any way back
+            if s.x * s.y * s.z == 0. {
+                return Err(invalid(at, "scale", format!("{} {} {}", s.x, s.y, s.z), "it cannot be zero"));
+            }
+            TransformDescription::Scale(s)
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(transform))
 }
 
+//transform pushed at the end of a list, which keeps the keys of its
+//element when it is animated
+fn push_transform(
+    reader: &mut XmlReader,
+    transforms: &mut Vec<TransformDescription>,
+    transform: TransformDescription,
+) -> Result<(), SceneError> {
+    let (name, fields): (&str, &[(&str, &str)]) = match transform {
+        TransformDescription::Translate(_) => ("translate", VECTOR),
+        TransformDescription::Rotate { .. } => {
+            ("rotate", &[("angle", "angle"), ("x", "axis/x"), ("y", "axis/y"), ("z", "axis/z")])
+        }
+        TransformDescription::Scale(_) => ("scale", &[("s", "x"), ("s", "y"), ("s", "z"), ("x", "x"), ("y", "y"), ("z", "z")]),
+    };
+    reader.animate(&format!("transforms/{}/{}", transforms.len(), name), fields)?;
+    transforms.push(transform);
+    Ok(())
+}
 
-fn read_face(reader: &mut Reader<&[u8]>) -> Face {
-    let mut buf = Vec::new();
-    let mut pts = Vec::new();
+//keyframes of transforms, interpolated over time
+fn read_motion(reader: &mut XmlReader) -> Result<Vec<Keyframe>, SceneError> {
+    let at = reader.location();
+    let mut keyframes = Vec::new();
     loop {
-        match reader.read_event_into(&mut buf) {
-            Err(e) => panic!("Error at position {}: {:?}", reader.error_position(), e),
-            Ok(Event::Eof) => panic!("Unexpected EOF"),
-            Ok(Event::Empty(e)) => match e.name().as_ref() {
-                b"pos" => pts.push(read_vec3d(e)),
-                _ => (),
+        match reader.next()? {
+            Event::Start(e) => match e.name().as_ref() {
+                b"keyframe" => {
+                    let time = require_property::<f64>(&e, b"time", &reader.location())?;
+                    reader.enter(format!("motion/{}", keyframes.len()));
+                    keyframes.push(Keyframe {
+                        time,
+                        transforms: read_keyframe(reader)?,
+                    });
+                    reader.leave();
+                }
+                _ => return Err(reader.unknown(&e)),
             },
-            Ok(Event::End(e)) => match e.name().as_ref() {
-                b"face" => break,
-                name => panic!("unexpected end {:?}", name),
+            Event::End(_) => break,
+            _ => (),
+        }
+    }
+    let mut transforms = Vec::new();
+    for keyframe in keyframes.iter() {
+        let list = keyframe.transforms.iter().map(|t| t.transform(&at.path));
+        transforms.push((keyframe.time, list.collect::<Result<Vec<_>, SceneError>>()?));
+    }
+    Motion::new(transforms).map_err(|message| invalid(&at, "keyframes", String::new(), message))?;
+    Ok(keyframes)
+}
+
+fn read_keyframe(reader: &mut XmlReader) -> Result<Vec<TransformDescription>, SceneError> {
+    let mut transforms = Vec::new();
+    loop {
+        match reader.next()? {
+            Event::Empty(e) => match read_transform(&e, &reader.location())? {
+                Some(t) => push_transform(reader, &mut transforms, t)?,
+                None => return Err(reader.unknown(&e)),
             },
+            Event::Start(e) => return Err(reader.unknown(&e)),
+            Event::End(_) => break,
             _ => (),
         }
-        buf.clear();
     }
-    Face::new(pts[0], pts[1], pts[2])
+    Ok(transforms)
 }
 
-fn read_object(reader: &mut Reader<&[u8]>) -> Object {
-    let mut buf = Vec::new();
-    let mut mat: Option<Material> = None;
-    let mut faces = Vec::new();
+//named objects, only drawn through instances
+fn read_library(reader: &mut XmlReader, scene: &mut SceneDescription) -> Result<(), SceneError> {
     loop {
-        match reader.read_event_into(&mut buf) {
-            Err(e) => panic!("Error at position {}: {:?}", reader.error_position(), e),
-            Ok(Event::Eof) => panic!("Unexpected EOF"),
-            Ok(Event::Empty(e)) => match e.name().as_ref() {
-                _ => (),
+        let e = match reader.next()? {
+            Event::Start(e) => e,
+            Event::Empty(e) => {
+                reader.expand(&e);
+                e
+            }
+            Event::End(_) => break,
+            _ => continue,
+        };
+        if e.name().as_ref() == b"material" {
+            read_named_material(reader, &e, &mut scene.materials)?;
+            continue;
+        }
+        let name = require_property::<String>(&e, b"name", &reader.location())?;
+        reader.enter(format!("library/{}", field_name(&name)));
+        let Some(object) = read_primitive(reader, &e, scene, false)? else {
+            return Err(reader.unknown(&e));
+        };
+        reader.leave();
+        scene.library.insert(name, object);
+    }
+    Ok(())
+}
+
+//transforms are applied in the order they are written,
+//the material replaces the one of the object
+fn read_instance(
+    reader: &mut XmlReader,
+    e: &BytesStart,
+    scene: &SceneDescription,
+) -> Result<ObjectDescription, SceneError> {
+    let at = reader.location();
+    let name = require_property::<String>(e, b"object", &at)?;
+    if !scene.library.contains_key(&name) {
+        return Err(invalid(&at, "object", name, "there is no library object with this name"));
+    }
+    let mut transforms = Vec::new();
+    let mut mat: Option<MaterialDescription> = None;
+    let mut motion = Vec::new();
+    loop {
+        match reader.next()? {
+            Event::Empty(e) => {
+                if let Some(t) = read_transform(&e, &reader.location())? {
+                    push_transform(reader, &mut transforms, t)?
+                }
+            }
+            Event::Start(e) => match e.name().as_ref() {
+                b"material" => mat = Some(read_inline_material(reader, &e)?),
+                b"motion" => motion = read_motion(reader)?,
+                _ => return Err(reader.unknown(&e)),
+            },
+            Event::End(_) => break,
+            _ => (),
+        }
+    }
+    let shape = Shape::Instance {
+        object: name,
+        transforms,
+        motion,
+    };
+    Ok(ObjectDescription::new(shape, mat.map(|m| MaterialRef::Inline(Box::new(m)))))
+}
+
+//Objects of a group, moved by its transform. The group material is the default
+//one of the objects written after it, nested groups included.
+fn read_group(
+    reader: &mut XmlReader,
+    e: &BytesStart,
+    scene: &SceneDescription,
+    default_material: bool,
+) -> Result<ObjectDescription, SceneError> {
+    let name = read_property::<String>(e, b"name", &reader.location())?;
+    let mut transforms = Vec::new();
+    let named = read_material_reference(e, &scene.materials, &reader.location())?;
+    let mut mat: Option<MaterialDescription> = None;
+    let mut objects = Vec::new();
+    loop {
+        let default_material = default_material || named.is_some() || mat.is_some();
+        let e = match reader.next()? {
+            Event::Empty(e) => match read_transform(&e, &reader.location())? {
+                Some(t) => {
+                    push_transform(reader, &mut transforms, t)?;
+                    continue;
+                }
+                //objects without children, like <instance object="a"/>
+                None => {
+                    reader.expand(&e);
+                    e
+                }
+            },
+            Event::Start(e) => e,
+            Event::End(_) => break,
+            _ => continue,
+        };
+        if e.name().as_ref() == b"material" {
+            mat = Some(read_inline_material(reader, &e)?);
+            continue;
+        }
+        reader.enter(format!("objects/{}", objects.len()));
+        match e.name().as_ref() {
+            b"group" => objects.push(read_group(reader, &e, scene, default_material)?),
+            _ => match read_primitive(reader, &e, scene, default_material)? {
+                Some(object) => objects.push(object),
+                None => return Err(reader.unknown(&e)),
             },
-            Ok(Event::Start(e)) => match e.name().as_ref() {
-                b"face" => faces.push(read_face(reader)),
-                b"material" => mat = Some(read_material(reader)),
-                name => panic!("unexpected block begin named {:?}", name),
+        }
+        reader.leave();
+    }
+    let material = mat.map(|m| MaterialRef::Inline(Box::new(m))).or(named);
+    Ok(ObjectDescription::new(Shape::Group { name, transforms, objects }, material))
+}
+
+//Boolean operation applied from the first object to the last one:
+//a difference removes all the others from the first object
+fn read_csg(
+    reader: &mut XmlReader,
+    e: &BytesStart,
+    scene: &SceneDescription,
+    default_material: bool,
+) -> Result<ObjectDescription, SceneError> {
+    let at = reader.location();
+    let operation = require_property::<Operation>(e, b"op", &at)?;
+    let mut mat: Option<MaterialDescription> = None;
+    let mut objects = Vec::new();
+    loop {
+        let e = match reader.next()? {
+            Event::Start(e) => e,
+            Event::Empty(e) => {
+                reader.expand(&e);
+                e
+            }
+            Event::End(_) => break,
+            _ => continue,
+        };
+        match e.name().as_ref() {
+            b"material" => mat = Some(read_inline_material(reader, &e)?),
+            _ => {
+                let object_at = reader.location();
+                reader.enter(format!("objects/{}", objects.len()));
+                let object = read_primitive(reader, &e, scene, default_material || mat.is_some())?;
+                reader.leave();
+                match object {
+                    Some(object) if object.is_closed(&scene.library) => objects.push(object),
+                    Some(_) => {
+                        return Err(invalid(&object_at, "object", String::new(), "csg can only combine closed objects"))
+                    }
+                    None => return Err(xml::unknown(&e, &object_at)),
+                }
+            }
+        }
+    }
+    if objects.len() < 2 {
+        return Err(invalid(&at, "objects", String::new(), "csg needs at least two objects"));
+    }
+    Ok(ObjectDescription::new(Shape::Csg { operation, objects }, mat.map(|m| MaterialRef::Inline(Box::new(m)))))
+}
+
+//Distance field made of sdf nodes, unioned when there are several
+fn read_sdf(reader: &mut XmlReader, default_material: bool) -> Result<ObjectDescription, SceneError> {
+    let at = reader.location();
+    let mut mat: Option<MaterialDescription> = None;
+    let mut nodes = Vec::new();
+    loop {
+        match reader.next()? {
+            Event::Empty(e) => nodes.push(read_sdf_primitive(&e, &reader.location())?),
+            Event::Start(e) => match e.name().as_ref() {
+                b"material" => mat = Some(read_inline_material(reader, &e)?),
+                _ => nodes.push(read_sdf_operator(reader, &e)?),
             },
-            Ok(Event::End(e)) => match e.name().as_ref() {
-                b"object" => break,
-                name => panic!("unexpected end {:?}", name),
+            Event::End(_) => break,
+            _ => (),
+        }
+    }
+    let material = object_material(mat, default_material, &at)?;
+    let node = sdf_union(nodes, &at)?;
+    Ok(ObjectDescription::new(Shape::Sdf { node }, material))
+}
+
+fn sdf_union(mut nodes: Vec<Node>, at: &Location) -> Result<Node, SceneError> {
+    match nodes.len() {
+        0 => Err(invalid(at, "nodes", String::new(), "there is no sdf primitive")),
+        1 => Ok(nodes.pop().unwrap()),
+        _ => Ok(Node::Union(nodes, 0.)),
+    }
+}
+
+//primitive centered on x, y, z
+fn read_sdf_primitive(e: &BytesStart, at: &Location) -> Result<Node, SceneError> {
+    let center = read_vec3d(e, at)?;
+    let value = |key: &[u8]| require_property::<f64>(e, key, at);
+    let node = match e.name().as_ref() {
+        b"sphere" => Node::Sphere {
+            center,
+            radius: value(b"r")?,
+        },
+        b"box" => Node::Box {
+            center,
+            size: Vec3d {
+                x: value(b"sx")?,
+                y: value(b"sy")?,
+                z: value(b"sz")?,
             },
+            rounding: read_property(e, b"round", at)?.unwrap_or(0.),
+        },
+        b"torus" => Node::Torus {
+            center,
+            radius: value(b"r")?,
+            tube: value(b"t")?,
+        },
+        b"cylinder" => Node::Cylinder {
+            center,
+            radius: value(b"r")?,
+            height: value(b"h")?,
+        },
+        _ => return Err(xml::unknown(e, at)),
+    };
+    Ok(node)
+}
+
+//operator applied to the nodes it contains, k is the smoothing distance
+fn read_sdf_operator(reader: &mut XmlReader, e: &BytesStart) -> Result<Node, SceneError> {
+    let at = reader.location();
+    let mut nodes = Vec::new();
+    loop {
+        match reader.next()? {
+            Event::Empty(e) => nodes.push(read_sdf_primitive(&e, &reader.location())?),
+            Event::Start(e) => nodes.push(read_sdf_operator(reader, &e)?),
+            Event::End(_) => break,
             _ => (),
         }
-        buf.clear();
     }
-    Object::Rasterized(Rasterized{faces: faces, material: mat.unwrap()})
+    let smooth = read_property::<f64>(e, b"k", &at)?.unwrap_or(0.);
+    let node = match e.name().as_ref() {
+        b"union" => Node::Union(nodes, smooth),
+        b"intersection" => Node::Intersection(nodes, smooth),
+        b"difference" => Node::Difference(nodes, smooth),
+        b"twist" => Node::Twist(Box::new(sdf_union(nodes, &at)?), require_property(e, b"rate", &at)?),
+        b"repetition" => Node::Repetition(Box::new(sdf_union(nodes, &at)?), read_vec3d(e, &at)?),
+        b"translate" => Node::Translation(Box::new(sdf_union(nodes, &at)?), read_vec3d(e, &at)?),
+        _ => return Err(xml::unknown(e, &at)),
+    };
+    Ok(node)
 }
 
 #[cfg(test)]
 mod tests {
 
     use super::*;
+    use crate::scene::animation::Interpolation;
+    use crate::scene::camera::Ray;
+    use crate::scene::description::build::Files;
+    use crate::scene::object::material::Material;
+    use crate::scene::object::texture::Texture;
+    use crate::scene::object::Object;
+    use approx::assert_abs_diff_eq;
+    use std::sync::Arc;
+
+    //objects of a group, built as those of a scene
+    fn build_group(group: ObjectDescription, base_dir: &Path) -> Vec<Object> {
+        let scene = SceneDescription {
+            objects: vec![group],
+            ..SceneDescription::default()
+        };
+        scene.build(base_dir).unwrap().objects
+    }
 
     #[test]
     fn parse_vec() {
         let bs = BytesStart::from_content("pos x=\"1.2\" y=\"3.4\" z=\"5.6\"", 3);
-        let vec = read_vec3d(bs);
+        let vec = read_vec3d(&bs, &Location::default()).unwrap();
         assert_eq!(
             vec,
             Vec3d {
@@ -335,7 +1234,7 @@ mod tests {
     #[test]
     fn parse_color() {
         let bs = BytesStart::from_content("color r=\"0.8\" g=\"0\" b=\"0.1\"", 5);
-        let col = read_color(bs);
+        let col = read_color(&bs, &Location::default()).unwrap();
         assert_eq!(
             col,
             Color {
@@ -349,7 +1248,7 @@ mod tests {
     #[test]
     fn parse_funny_color() {
         let bs = BytesStart::from_content("color r=\"2.8\" g=\"-0.5\" toto=\"abc\"", 5);
-        let col = read_color(bs);
+        let col = read_color(&bs, &Location::default()).unwrap();
         assert_eq!(
             col,
             Color {
@@ -363,21 +1262,28 @@ mod tests {
     #[test]
     fn parse_multi_attributes() {
         let bs = BytesStart::from_content("color r=\"2.8\" g=\"-0.5\" toto=\"abc\"", 5);
-        assert_eq!(read_property::<f64>(&bs, b"r"), Some(2.8));
-        assert_eq!(read_property::<f64>(&bs, b"b"), None);
-        assert_eq!(read_property::<f64>(&bs, b"toto"), None);
+        let at = Location::default();
+        assert_eq!(read_property::<f64>(&bs, b"r", &at), Ok(Some(2.8)));
+        assert_eq!(read_property::<f64>(&bs, b"b", &at), Ok(None));
+        let Err(SceneError::InvalidValue { attribute, value, .. }) = read_property::<f64>(&bs, b"toto", &at) else {
+            panic!("invalid value expected")
+        };
+        assert_eq!((attribute.as_str(), value.as_str()), ("toto", "abc"));
     }
 
     #[test]
     fn parse_ambiant_light() {
-        let mut reader = Reader::from_str(
+        let mut reader = XmlReader::new(
             "<ambiant_light>
             <color r=\"0.6\" g=\"0.8\" b=\"1\"/>
             <intensity i=\"0.3\"/>
             </ambiant_light>",
+            Path::new("."),
+            0.,
         );
 
-        let light = read_ambiant_light(&mut reader);
+        reader.next().unwrap();
+        let light = read_ambiant_light(&mut reader).unwrap();
         assert_eq!(
             light.color,
             Color {
@@ -389,5 +1295,925 @@ mod tests {
         assert_eq!(light.intensity, 0.3);
     }
 
+    #[test]
+    fn parse_principled_material() {
+        let mut reader = XmlReader::new(
+            "<material type=\"principled\">
+            <base_color r=\"1\" g=\"0.8\" b=\"0.3\"/>
+            <metallic m=\"1\"/>
+            <roughness r=\"0.2\"/>
+            <clearcoat c=\"0.5\"/>
+            </material>",
+            Path::new("."),
+            0.,
+        );
+        let Ok(Event::Start(bs)) = reader.next() else {
+            panic!("material block expected")
+        };
+
+        let mat = read_material(&mut reader, &bs.into_owned()).unwrap().build(&Files::new(Path::new("."))).unwrap();
+        assert_eq!(
+            mat.specular,
+            Color {
+                r: 1.,
+                g: 0.8,
+                b: 0.3
+            }
+        );
+        assert_eq!(mat.reflectivity, 1.);
+        assert_eq!(mat.roughness, 0.2);
+        assert_eq!(mat.clearcoat, 0.5);
+    }
+
+    #[test]
+    fn parse_procedural_textures() {
+        let mut reader = XmlReader::new(
+            "<material>
+            <diffuse texture=\"checker\" scale=\"0.5\" r=\"1\" g=\"1\" b=\"1\"/>
+            <specular r=\"1\" g=\"1\" b=\"1\"/>
+            <roughness texture=\"marble\" r=\"0.1\" r2=\"0.9\" octaves=\"6\"/>
+            </material>",
+            Path::new("."),
+            0.,
+        );
+        let Ok(Event::Start(bs)) = reader.next() else {
+            panic!("material block expected")
+        };
+
+        let mat = read_material(&mut reader, &bs.into_owned()).unwrap().build(&Files::new(Path::new("."))).unwrap();
+        assert_eq!(mat.textures.len(), 2);
+        let (channel, Texture::Procedural(checker)) = &mat.textures[0] else {
+            panic!("procedural texture expected")
+        };
+        assert_eq!(*channel, Channel::Diffuse);
+        assert_eq!(checker.pattern, Pattern::Checker);
+        assert_eq!(checker.scale, 0.5);
+        assert_eq!(checker.colors.0, Color::gray(1.));
+        assert_eq!(checker.colors.1, Color::gray(0.));
+        let (channel, Texture::Procedural(marble)) = &mat.textures[1] else {
+            panic!("procedural texture expected")
+        };
+        assert_eq!(*channel, Channel::Roughness);
+        assert_eq!(marble.octaves, 6);
+        assert_eq!(marble.colors.1, Color::gray(0.9));
+    }
+
+    #[test]
+    fn parse_basic_layers() {
+        let mut reader = XmlReader::new(
+            "<material>
+            <diffuse r=\"1\" g=\"1\" b=\"1\"/>
+            <specular r=\"1\" g=\"1\" b=\"1\"/>
+            <sheen s=\"0.2\"/>
+            <clearcoat texture=\"noise\" c=\"0\" c2=\"1\"/>
+            <transmission r=\"0.5\" g=\"0.5\" b=\"0.5\"/>
+            <ior i=\"1.5\"/>
+            </material>",
+            Path::new("."),
+            0.,
+        );
+        let Ok(Event::Start(bs)) = reader.next() else {
+            panic!("material block expected")
+        };
+
+        let mat = read_material(&mut reader, &bs.into_owned()).unwrap().build(&Files::new(Path::new("."))).unwrap();
+        assert_eq!(mat.sheen, 0.2);
+        assert_eq!(mat.transmission, Color::gray(0.5));
+        assert_eq!(mat.ior, 1.5);
+        let (channel, Texture::Procedural(noise)) = &mat.textures[0] else {
+            panic!("procedural texture expected")
+        };
+        assert_eq!(*channel, Channel::Clearcoat);
+        assert_eq!(noise.colors.1, Color::gray(1.));
+    }
+
+    #[test]
+    fn parse_bump() {
+        let mut reader = XmlReader::new(
+            "<material>
+            <diffuse r=\"1\" g=\"1\" b=\"1\"/>
+            <specular r=\"1\" g=\"1\" b=\"1\"/>
+            <bump texture=\"noise\" scale=\"0.1\" strength=\"0.3\"/>
+            </material>",
+            Path::new("."),
+            0.,
+        );
+        let Ok(Event::Start(bs)) = reader.next() else {
+            panic!("material block expected")
+        };
+
+        let mat = read_material(&mut reader, &bs.into_owned()).unwrap().build(&Files::new(Path::new("."))).unwrap();
+        let Some((Texture::Procedural(noise), strength)) = mat.bump_map else {
+            panic!("procedural bump expected")
+        };
+        assert_eq!(noise.pattern, Pattern::Noise);
+        assert_eq!(noise.scale, 0.1);
+        assert_eq!(strength, 0.3);
+        assert_eq!(mat.normal_map, None);
+    }
+
+    #[test]
+    fn parse_face_uvs() {
+        let mut reader = XmlReader::new(
+            "<face>
+            <pos x=\"1\" y=\"1\" z=\"-1\" u=\"0\" v=\"0\"/>
+            <pos x=\"1\" y=\"0\" z=\"1\" u=\"0.5\" v=\"1\"/>
+            <pos x=\"1\" y=\"-1\" z=\"-1\" u=\"1\"/>
+            </face>",
+            Path::new("."),
+            0.,
+        );
+        reader.next().unwrap();
+        let faces = read_face(&mut reader).unwrap().triangles("face").unwrap();
+        assert_eq!(faces.len(), 1);
+        let ray = Ray {
+            start: Vec3d {
+                x: 0.,
+                y: 0.,
+                z: 0.,
+            },
+            dir: Vec3d {
+                x: 1.,
+                y: 0.,
+                z: 0.,
+            },
+            time: 0.,
+        };
+        let i = faces[0].intersect(&ray, false).unwrap();
+        assert!((i.uv.0 - 0.5).abs() < 1e-12);
+        assert!((i.uv.1 - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn parse_face_normals() {
+        let mut reader = XmlReader::new(
+            "<face>
+            <pos x=\"1\" y=\"1\" z=\"-1\" nx=\"-1\" ny=\"1\"/>
+            <pos x=\"1\" y=\"0\" z=\"1\" nx=\"-1\"/>
+            <pos x=\"1\" y=\"-1\" z=\"-1\" nx=\"-1\"/>
+            </face>",
+            Path::new("."),
+            0.,
+        );
+        reader.next().unwrap();
+        let face = read_face(&mut reader).unwrap();
+        assert!(face.uvs.is_empty());
+        assert_eq!(face.normals[0], Vec3d { x: -1., y: 1., z: 0. });
+        assert_eq!(face.triangles("face").unwrap().len(), 1);
+
+        let missing = FaceDescription { normals: vec![face.normals[0]], ..face.clone() };
+        assert!(missing.triangles("face").is_err());
+    }
+
+    #[test]
+    fn parse_polygon_face() {
+        //house shape in the x=1 plane, with a notch in its bottom side
+        let mut reader = XmlReader::new(
+            "<face>
+            <pos x=\"1\" y=\"1\" z=\"-1\"/>
+            <pos x=\"1\" y=\"1\" z=\"1\"/>
+            <pos x=\"1\" y=\"0\" z=\"2\"/>
+            <pos x=\"1\" y=\"-1\" z=\"1\"/>
+            <pos x=\"1\" y=\"-1\" z=\"-1\"/>
+            <pos x=\"1\" y=\"0\" z=\"0\"/>
+            </face>",
+            Path::new("."),
+            0.,
+        );
+        reader.next().unwrap();
+        let faces = read_face(&mut reader).unwrap().triangles("face").unwrap();
+        assert_eq!(faces.len(), 4);
+    }
+
+    #[test]
+    fn parse_warped_face() {
+        let mut reader = XmlReader::new(
+            "<face>
+            <pos x=\"0\" y=\"0\" z=\"0\"/>
+            <pos x=\"1\" y=\"0\" z=\"0\"/>
+            <pos x=\"1\" y=\"1\" z=\"1\"/>
+            <pos x=\"0\" y=\"1\" z=\"0\"/>
+            </face>",
+            Path::new("."),
+            0.,
+        );
+        reader.next().unwrap();
+        let Err(SceneError::InvalidValue { location, message, .. }) = read_face(&mut reader) else {
+            panic!("invalid face expected")
+        };
+        assert_eq!(message, "face with 4 points is not planar");
+        assert_eq!(location.path, "face");
+    }
+
+    #[test]
+    fn parse_instances() {
+        let scene = load_from_xml_string(
+            "<scene>
+                <camera><pos x=\"0\" y=\"0\" z=\"0\"/><dir x=\"1\" y=\"0\" z=\"0\"/></camera>
+                <ambiant_light><color r=\"1\" g=\"1\" b=\"1\"/><intensity i=\"1\"/></ambiant_light>
+                <library>
+                    <sphere name=\"ball\">
+                        <pos x=\"0\" y=\"0\" z=\"0\"/>
+                        <radius r=\"1\"/>
+                        <material><diffuse r=\"1\" g=\"0\" b=\"0\"/><specular r=\"1\" g=\"1\" b=\"1\"/></material>
+                    </sphere>
+                </library>
+                <instance object=\"ball\">
+                    <scale s=\"2\"/>
+                    <rotate x=\"0\" y=\"0\" z=\"1\" angle=\"90\"/>
+                    <translate x=\"5\" y=\"0\" z=\"0\"/>
+                </instance>
+                <instance object=\"ball\">
+                    <translate x=\"0\" y=\"5\" z=\"0\"/>
+                    <material><diffuse r=\"0\" g=\"1\" b=\"0\"/><specular r=\"1\" g=\"1\" b=\"1\"/></material>
+                </instance>
+                </scene>",
+            Path::new("."),
+            0.,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(scene.objects.len(), 2);
+
+        let ray = Ray {
+            start: Vec3d { x: 0., y: 0., z: 0. },
+            dir: Vec3d { x: 1., y: 0., z: 0. },
+            time: 0.,
+        };
+        let i = scene.objects[0].intersect(&ray).unwrap();
+        assert!((i.dist - 3.).abs() < 1e-12);
+        assert_eq!(i.material.diffuse.r, 1.);
+
+        let ray = Ray {
+            start: Vec3d { x: 0., y: 0., z: 0. },
+            dir: Vec3d { x: 0., y: 1., z: 0. },
+            time: 0.,
+        };
+        let i = scene.objects[1].intersect(&ray).unwrap();
+        assert!((i.dist - 4.).abs() < 1e-12);
+        assert_eq!(i.material.diffuse.g, 1.);
+    }
+
+    #[test]
+    fn parse_motion() {
+        let scene = load_from_xml_string(
+            "<scene>
+                <camera><pos x=\"0\" y=\"0\" z=\"0\"/><dir x=\"1\" y=\"0\" z=\"0\"/><shutter open=\"0\" close=\"1\"/></camera>
+                <ambiant_light><color r=\"1\" g=\"1\" b=\"1\"/><intensity i=\"1\"/></ambiant_light>
+                <library>
+                    <sphere name=\"ball\">
+                        <pos x=\"0\" y=\"0\" z=\"0\"/>
+                        <radius r=\"1\"/>
+                        <material><diffuse r=\"1\" g=\"0\" b=\"0\"/><specular r=\"1\" g=\"1\" b=\"1\"/></material>
+                    </sphere>
+                </library>
+                <sphere>
+                    <pos x=\"5\" y=\"0\" z=\"0\"/>
+                    <radius r=\"1\"/>
+                    <velocity x=\"0\" y=\"0\" z=\"10\"/>
+                    <material><diffuse r=\"1\" g=\"0\" b=\"0\"/><specular r=\"1\" g=\"1\" b=\"1\"/></material>
+                </sphere>
+                <instance object=\"ball\">
+                    <translate x=\"5\" y=\"0\" z=\"0\"/>
+                    <motion>
+                        <keyframe time=\"0\"><translate x=\"0\" y=\"0\" z=\"0\"/></keyframe>
+                        <keyframe time=\"1\"><translate x=\"0\" y=\"10\" z=\"0\"/></keyframe>
+                    </motion>
+                </instance>
+                </scene>",
+            Path::new("."),
+            0.,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(scene.camera.shutter_time(0.5), 0.5);
+        let ray = |time| Ray {
+            start: Vec3d { x: 0., y: 0., z: 0. },
+            dir: Vec3d { x: 1., y: 0., z: 0. },
+            time,
+        };
+        for object in scene.objects.iter() {
+            assert!((object.intersect(&ray(0.)).unwrap().dist - 4.).abs() < 1e-12);
+            assert_eq!(object.intersect(&ray(0.5)), None);
+        }
+    }
+
+    #[test]
+    fn parse_back_face_culling() {
+        let mesh = |attributes: &str| {
+            format!(
+                "<object {}>
+                    <face><pos x=\"1\" y=\"1\" z=\"-1\"/><pos x=\"1\" y=\"0\" z=\"1\"/><pos x=\"1\" y=\"-1\" z=\"-1\"/></face>
+                    <material><diffuse r=\"1\" g=\"1\" b=\"1\"/><specular r=\"0\" g=\"0\" b=\"0\"/></material>
+                </object>",
+                attributes
+            )
+        };
+        let scene = |culling: &str| {
+            load_from_xml_string(
+                &format!(
+                    "<scene {}>
+                    <camera><pos x=\"0\" y=\"0\" z=\"0\"/><dir x=\"1\" y=\"0\" z=\"0\"/></camera>
+                    <ambiant_light><color r=\"1\" g=\"1\" b=\"1\"/><intensity i=\"1\"/></ambiant_light>
+                    <library>{}</library>
+                    <instance object=\"triangle\"></instance>
+                    {}
+                    {}
+                    </scene>",
+                    culling,
+                    mesh("name=\"triangle\" double_sided=\"true\""),
+                    mesh("double_sided=\"true\""),
+                    mesh(""),
+                ),
+                Path::new("."),
+                0.,
+                &[],
+            )
+            .unwrap()
+        };
+        //from the back of the faces
+        let ray = Ray {
+            start: Vec3d { x: 2., y: 0., z: 0. },
+            dir: Vec3d { x: -1., y: 0., z: 0. },
+            time: 0.,
+        };
+        let hits = |scene: &Scene| scene.objects.iter().map(|o| o.intersect(&ray).is_some()).collect::<Vec<bool>>();
+        assert_eq!(hits(&scene("")), vec![true, true, false]);
+        assert_eq!(hits(&scene("cull_back_faces=\"true\"")), vec![false, false, false]);
+    }
+
+    #[test]
+    fn parse_nested_groups() {
+        let mut reader = XmlReader::new(
+            "<group name=\"outer\">
+                <translate x=\"5\" y=\"0\" z=\"0\"/>
+                <material><diffuse r=\"1\" g=\"0\" b=\"0\"/><specular r=\"1\" g=\"1\" b=\"1\"/></material>
+                <sphere><pos x=\"0\" y=\"0\" z=\"0\"/><radius r=\"1\"/></sphere>
+                <group name=\"inner\">
+                    <translate x=\"0\" y=\"0\" z=\"3\"/>
+                    <sphere>
+                        <pos x=\"0\" y=\"0\" z=\"0\"/>
+                        <radius r=\"1\"/>
+                        <material><diffuse r=\"0\" g=\"0\" b=\"1\"/><specular r=\"1\" g=\"1\" b=\"1\"/></material>
+                    </sphere>
+                </group>
+            </group>",
+            Path::new("."),
+            0.,
+        );
+        let Ok(Event::Start(group)) = reader.next() else {
+            panic!("group expected")
+        };
+        let description = read_group(&mut reader, &group, &SceneDescription::default(), false).unwrap();
+        let Shape::Group { name, objects, .. } = &description.shape else {
+            panic!("group expected")
+        };
+        assert_eq!(name.as_deref(), Some("outer"));
+        assert!(matches!(&objects[1].shape, Shape::Group { name: Some(inner), .. } if inner == "inner"));
+        let objects = build_group(description, Path::new("."));
+        assert_eq!(objects.len(), 2);
+
+        let ray = Ray {
+            start: Vec3d { x: 0., y: 0., z: 0. },
+            dir: Vec3d { x: 1., y: 0., z: 0. },
+            time: 0.,
+        };
+        let i = objects[0].intersect(&ray).unwrap();
+        assert!((i.dist - 4.).abs() < 1e-12);
+        assert_eq!(i.material.diffuse.r, 1.);
+
+        //both translations are composed in a single instance
+        let Object::Instance(inner) = &objects[1] else {
+            panic!("instance expected")
+        };
+        assert!(matches!(inner.object.as_ref(), Object::Sphere(_)));
+        let ray = Ray {
+            start: Vec3d { x: 0., y: 0., z: 3. },
+            dir: Vec3d { x: 1., y: 0., z: 0. },
+            time: 0.,
+        };
+        let i = objects[1].intersect(&ray).unwrap();
+        assert!((i.dist - 4.).abs() < 1e-12);
+        assert_eq!(i.material.diffuse.b, 1.);
+    }
+
+    #[test]
+    fn parse_empty_elements() {
+        let content = "<scene>
+            <camera><pos x=\"0\" y=\"0\" z=\"0\"/><dir x=\"1\" y=\"0\" z=\"0\"/></camera>
+            <ambiant_light><color r=\"1\" g=\"1\" b=\"1\"/><intensity i=\"1\"/></ambiant_light>
+            <library>
+                <sphere name=\"ball\"><pos x=\"5\" y=\"0\" z=\"0\"/><radius r=\"1\"/><material><diffuse/><specular/></material></sphere>
+                <instance name=\"pair\" object=\"ball\"/>
+            </library>
+            <instance object=\"ball\"/>
+            <group><translate x=\"0\" y=\"3\" z=\"0\"/><instance object=\"pair\"/></group>
+            </scene>";
+        let description = read_from_xml_string(content, Path::new("."), 0., &[]).unwrap();
+        assert!(matches!(&description.library["pair"].shape, Shape::Instance { object, .. } if object == "ball"));
+        assert!(matches!(&description.objects[0].shape, Shape::Instance { object, .. } if object == "ball"));
+        let Shape::Group { objects, .. } = &description.objects[1].shape else {
+            panic!("group expected")
+        };
+        assert!(matches!(&objects[0].shape, Shape::Instance { object, .. } if object == "pair"));
+
+        //read like the elements with children
+        let error = |content: &str| load_from_xml_string(content, Path::new("."), 0., &[]).err().unwrap();
+        let unknown = error(&content.replace("<instance object=\"ball\"/>", "<instance object=\"cube\"/>"));
+        assert!(matches!(unknown, SceneError::InvalidValue { ref value, .. } if value == "cube"));
+        let teapot = error(&content.replace("<instance object=\"pair\"/>", "<teapot/>"));
+        assert!(matches!(teapot, SceneError::UnknownElement { ref element, .. } if element == "teapot"));
+        let sphere = error(&content.replace("<instance object=\"ball\"/>", "<sphere/>"));
+        assert!(matches!(sphere, SceneError::MissingElement { .. }));
+    }
+
+    #[test]
+    fn parse_shapes() {
+        let content = String::from(
+            "<scene>
+                <camera><pos x=\"0\" y=\"0\" z=\"1\"/><dir x=\"1\" y=\"0\" z=\"0\"/></camera>
+                <ambiant_light><color r=\"1\" g=\"1\" b=\"1\"/><intensity i=\"0.1\"/></ambiant_light>
+                <plane>
+                    <pos x=\"0\" y=\"0\" z=\"0\"/>
+                    <normal x=\"0\" y=\"0\" z=\"1\"/>
+                    <material><diffuse r=\"1\" g=\"1\" b=\"1\"/><specular r=\"0\" g=\"0\" b=\"0\"/></material>
+                </plane>
+                <group>
+                    <material><diffuse r=\"1\" g=\"0\" b=\"0\"/><specular r=\"0\" g=\"0\" b=\"0\"/></material>
+                    <disk><pos x=\"5\" y=\"0\" z=\"1\"/><normal x=\"-1\" y=\"0\" z=\"0\"/><radius r=\"0.5\"/></disk>
+                    <box><min x=\"3\" y=\"2\" z=\"0\"/><max x=\"4\" y=\"3\" z=\"1\"/></box>
+                </group>
+            </scene>",
+        );
+        let scene = load_from_xml_string(&content, Path::new("."), 0., &[]).unwrap();
+        assert_eq!(scene.objects.len(), 3);
+        assert!(matches!(scene.objects[0], Object::Plane(_)));
+        let Object::Disk(disk) = &scene.objects[1] else {
+            panic!("disk expected")
+        };
+        assert_eq!(disk.radius, 0.5);
+        assert_eq!(disk.material.diffuse.r, 1.);
+        let Object::Cuboid(cuboid) = &scene.objects[2] else {
+            panic!("box expected")
+        };
+        assert_eq!(cuboid.max, Vec3d { x: 4., y: 3., z: 1. });
+    }
+
+    #[test]
+    fn parse_round_shapes() {
+        let mut reader = XmlReader::new(
+            "<group>
+                <material><diffuse r=\"1\" g=\"1\" b=\"1\"/><specular r=\"0\" g=\"0\" b=\"0\"/></material>
+                <cylinder><pos x=\"0\" y=\"0\" z=\"0\"/><top x=\"0\" y=\"0\" z=\"2\"/><radius r=\"1\"/></cylinder>
+                <cone>
+                    <pos x=\"0\" y=\"0\" z=\"0\"/><top x=\"0\" y=\"3\" z=\"0\"/>
+                    <radius r=\"1\"/><top_radius r=\"0.5\"/>
+                </cone>
+                <torus><pos x=\"0\" y=\"0\" z=\"0\"/><axis x=\"0\" y=\"0\" z=\"1\"/><radius r=\"2\"/><tube r=\"0.3\"/></torus>
+            </group>",
+            Path::new("."),
+            0.,
+        );
+        let Ok(Event::Start(group)) = reader.next() else {
+            panic!("group expected")
+        };
+        let objects = build_group(read_group(&mut reader, &group, &SceneDescription::default(), false).unwrap(), Path::new("."));
+        let [Object::Cone(cylinder), Object::Cone(cone), Object::Torus(torus)] = &objects[..] else {
+            panic!("cylinder, cone and torus expected")
+        };
+        assert_eq!((cylinder.height, cylinder.radius, cylinder.top_radius), (2., 1., 1.));
+        assert_eq!((cone.height, cone.radius, cone.top_radius), (3., 1., 0.5));
+        assert_eq!((torus.radius, torus.tube), (2., 0.3));
+    }
+
+    #[test]
+    fn parse_heightfield() {
+        let dir = std::env::temp_dir();
+        {
+            let file = std::fs::File::create(dir.join("raytracer_parse_heightfield.png")).unwrap();
+            let mut encoder = png::Encoder::new(file, 3, 2);
+            encoder.set_color(png::ColorType::Grayscale);
+            encoder.set_depth(png::BitDepth::Sixteen);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&[0xff; 12]).unwrap();
+        }
+        let mut reader = XmlReader::new(
+            "<group>
+                <material><diffuse r=\"1\" g=\"1\" b=\"1\"/><specular r=\"0\" g=\"0\" b=\"0\"/></material>
+                <heightfield file=\"raytracer_parse_heightfield.png\">
+                    <pos x=\"-10\" y=\"-10\" z=\"0\"/><size x=\"20\" y=\"20\" z=\"3\"/>
+                </heightfield>
+            </group>",
+            &dir,
+            0.,
+        );
+        let Ok(Event::Start(group)) = reader.next() else {
+            panic!("group expected")
+        };
+        let objects = build_group(read_group(&mut reader, &group, &SceneDescription::default(), false).unwrap(), &dir);
+        let [Object::Heightfield(heightfield)] = &objects[..] else {
+            panic!("heightfield expected")
+        };
+        assert_eq!((heightfield.nx, heightfield.ny), (3, 2));
+        assert_eq!(heightfield.size, Vec3d { x: 20., y: 20., z: 3. });
+
+        let error = |content: &str| {
+            let mut reader = XmlReader::new(content, &dir, 0.);
+            let Ok(Event::Start(e)) = reader.next() else {
+                panic!("heightfield expected")
+            };
+            read_shape(&mut reader, &e, true).unwrap_err()
+        };
+        let missing = error("<heightfield file=\"raytracer_parse_nowhere.png\">\n<pos/><size x=\"1\" y=\"1\" z=\"1\"/></heightfield>");
+        assert!(matches!(missing, SceneError::Io { location: Some(ref at), .. } if at.path == "heightfield"));
+        let flat = error("<heightfield file=\"raytracer_parse_heightfield.png\">\n<pos/><size x=\"0\" y=\"1\" z=\"1\"/></heightfield>");
+        assert_eq!(
+            flat.to_string(),
+            "line 2, column 7 (heightfield/size): invalid size \"0 1 1\": its x and y must be positive"
+        );
+    }
+
+    #[test]
+    fn parse_csg() {
+        let mut reader = XmlReader::new(
+            "<group>
+                <material><diffuse r=\"1\" g=\"1\" b=\"1\"/><specular r=\"0\" g=\"0\" b=\"0\"/></material>
+                <csg op=\"difference\">
+                    <box><min x=\"-1\" y=\"-1\" z=\"-1\"/><max x=\"1\" y=\"1\" z=\"1\"/></box>
+                    <cylinder><pos x=\"-2\" y=\"0\" z=\"0\"/><top x=\"2\" y=\"0\" z=\"0\"/><radius r=\"0.5\"/></cylinder>
+                    <csg op=\"union\">
+                        <sphere><pos x=\"0\" y=\"1\" z=\"0\"/><radius r=\"0.2\"/></sphere>
+                        <sphere><pos x=\"0\" y=\"-1\" z=\"0\"/><radius r=\"0.2\"/></sphere>
+                    </csg>
+                </csg>
+            </group>",
+            Path::new("."),
+            0.,
+        );
+        let Ok(Event::Start(group)) = reader.next() else {
+            panic!("group expected")
+        };
+        let objects = build_group(read_group(&mut reader, &group, &SceneDescription::default(), false).unwrap(), Path::new("."));
+        let [Object::Csg(csg)] = &objects[..] else {
+            panic!("csg expected")
+        };
+        //(box - cylinder) - spheres
+        assert_eq!(csg.operation, Operation::Difference);
+        assert!(matches!(csg.left, Object::Csg(_)));
+        assert!(matches!(csg.right, Object::Csg(_)));
+
+        //through the hole
+        let ray = Ray {
+            start: Vec3d { x: -5., y: 0., z: 0. },
+            dir: Vec3d { x: 1., y: 0., z: 0. },
+            time: 0.,
+        };
+        assert_eq!(objects[0].intersect(&ray), None);
+        //in the bottom of the cut sphere
+        let ray = Ray {
+            start: Vec3d { x: 0., y: -5., z: 0. },
+            dir: Vec3d { x: 0., y: 1., z: 0. },
+            time: 0.,
+        };
+        let i = objects[0].intersect(&ray).unwrap();
+        assert!((i.dist - 4.2).abs() < 1e-12);
+    }
+
+    #[test]
+    fn parse_sdf() {
+        let mut reader = XmlReader::new(
+            "<sdf>
+                <material><diffuse r=\"1\" g=\"1\" b=\"1\"/><specular r=\"0\" g=\"0\" b=\"0\"/></material>
+                <union k=\"0.2\">
+                    <sphere x=\"0\" y=\"0\" z=\"1\" r=\"1\"/>
+                    <twist rate=\"0.5\"><box sx=\"1\" sy=\"1\" sz=\"1\" round=\"0.1\"/></twist>
+                </union>
+                <repetition x=\"3\" y=\"3\" z=\"0\"><torus r=\"1\" t=\"0.2\"/></repetition>
+                <translate x=\"2\"><twist rate=\"1\"><cylinder r=\"1\" h=\"2\"/></twist></translate>
+            </sdf>",
+            Path::new("."),
+            0.,
+        );
+        reader.next().unwrap();
+        let Shape::Sdf { node } = read_sdf(&mut reader, false).unwrap().shape else {
+            panic!("sdf expected")
+        };
+        let Node::Union(nodes, 0.) = &node else {
+            panic!("union of the top nodes expected")
+        };
+        let [Node::Union(blend, k), Node::Repetition(torus, period), Node::Translation(_, offset)] = &nodes[..] else {
+            panic!("smooth union, repetition and translation expected")
+        };
+        assert_eq!(offset.x, 2.);
+        assert_eq!(*k, 0.2);
+        assert!(matches!(blend[1], Node::Twist(_, rate) if rate == 0.5));
+        assert_eq!(*period, Vec3d { x: 3., y: 3., z: 0. });
+        assert_eq!(
+            **torus,
+            Node::Torus {
+                center: Vec3d { x: 0., y: 0., z: 0. },
+                radius: 1.,
+                tube: 0.2
+            }
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |content: &str| load_from_xml_string(content, Path::new("."), 0., &[]).err().unwrap();
+        let camera = "<camera><pos x=\"0\" y=\"0\" z=\"0\"/><dir x=\"1\" y=\"0\" z=\"0\"/></camera>";
+
+        let missing = error("<scene>\n<ambiant_light><color r=\"1\"/></ambiant_light>\n</scene>");
+        assert_eq!(missing.to_string(), "line 1, column 1 (scene): missing <camera> element");
+
+        let malformed = error(&format!(
+            "<scene>\n{}\n<sphere>\n  <radius r=\"1,5\"/>\n</sphere>\n</scene>",
+            camera
+        ));
+        let Some(location) = malformed.location() else {
+            panic!("location expected")
+        };
+        assert_eq!((location.line, location.column), (4, 3));
+        assert_eq!(location.path, "scene/sphere/radius");
+        assert!(matches!(malformed, SceneError::InvalidValue { ref value, .. } if value == "1,5"));
+
+        let unknown = error(&format!("<scene>\n{}\n<group><teapot></teapot></group>\n</scene>", camera));
+        assert!(matches!(unknown, SceneError::UnknownElement { ref element, .. } if element == "teapot"));
+
+        let flat = error(&format!(
+            "<scene>\n{}\n<box>\n<min x=\"0\" y=\"0\" z=\"0\"/>\n<max x=\"1\" y=\"0\" z=\"1\"/>\n<material><diffuse/><specular/></material>\n</box>\n</scene>",
+            camera
+        ));
+        assert_eq!(
+            flat.to_string(),
+            "line 5, column 1 (scene/box/max): invalid max \"1 0 1\": it is level with min on an axis, the box is flat"
+        );
+
+        let down = "<camera>\n<pos x=\"0\" y=\"0\" z=\"5\"/>\n<dir x=\"0\" y=\"0\" z=\"-1\"/>\n</camera>";
+        let vertical = error(&format!("<scene>\n{}\n</scene>", down));
+        assert_eq!(
+            vertical.to_string(),
+            "line 4, column 1 (scene/camera/dir): invalid dir \"0 0 -1\": it is vertical, the camera needs an <up> direction"
+        );
+        let parallel = error(&format!("<scene>\n{}\n</scene>", down.replace("</camera>", "<up x=\"0\" y=\"0\" z=\"2\"/></camera>")));
+        assert!(matches!(parallel, SceneError::InvalidValue { ref location, .. } if location.path == "scene/camera/up"));
+
+        let syntax = error("<scene>\n<camera>\n</scene>");
+        assert!(matches!(syntax, SceneError::Syntax { .. }));
+
+        let texture = error(&format!(
+            "<scene>\n{}\n<ambiant_light><color r=\"1\"/></ambiant_light>\n<plane>{}<material>{}</material></plane>\n</scene>",
+            camera,
+            "<pos/><normal z=\"1\"/>",
+            "<diffuse texture=\"missing.png\"/><specular/>"
+        ));
+        //images are loaded once the scene is read
+        assert!(matches!(texture, SceneError::Io { location: None, .. }));
+        let typo = error(&format!(
+            "<scene>\n{}\n<ambiant_light><color r=\"1\"/></ambiant_light>\n<plane>{}<material>\n{}</material></plane>\n</scene>",
+            camera,
+            "<pos/><normal z=\"1\"/>",
+            "<diffuse texture=\"chekcer\"/><specular/>"
+        ));
+        assert_eq!(
+            typo.to_string(),
+            "line 5, column 1 (scene/plane/material/diffuse): invalid texture \"chekcer\": unknown pattern \"chekcer\", image files need an extension"
+        );
+    }
+
+    #[test]
+    fn parse_named_materials() {
+        let content = "<scene>
+            <camera><pos x=\"0\" y=\"0\" z=\"0\"/><dir x=\"1\" y=\"0\" z=\"0\"/></camera>
+            <ambiant_light><color r=\"1\" g=\"1\" b=\"1\"/><intensity i=\"1\"/></ambiant_light>
+            <material name=\"gold\"><diffuse r=\"1\" g=\"0.8\" b=\"0\"/><specular r=\"1\" g=\"1\" b=\"1\"/></material>
+            <library>
+                <material name=\"red\"><diffuse r=\"1\" g=\"0\" b=\"0\"/><specular r=\"1\" g=\"1\" b=\"1\"/></material>
+            </library>
+            <sphere material=\"gold\"><pos x=\"0\" y=\"0\" z=\"0\"/><radius r=\"1\"/></sphere>
+            <group material=\"gold\">
+                <sphere><pos x=\"3\" y=\"0\" z=\"0\"/><radius r=\"1\"/></sphere>
+            </group>
+            <sphere material=\"red\"><pos x=\"6\" y=\"0\" z=\"0\"/><radius r=\"1\"/></sphere>
+            </scene>";
+        let scene = load_from_xml_string(content, Path::new("."), 0., &[]).unwrap();
+        let materials: Vec<&Arc<Material>> = scene
+            .objects
+            .iter()
+            .map(|o| match o {
+                Object::Sphere(s) => &s.material,
+                _ => panic!("sphere expected"),
+            })
+            .collect();
+        assert_eq!(materials.len(), 3);
+        assert!(Arc::ptr_eq(materials[0], materials[1]));
+        assert_eq!(materials[0].diffuse, Color { r: 1., g: 0.8, b: 0. });
+        assert_eq!(materials[2].diffuse, Color { r: 1., g: 0., b: 0. });
+
+        let unknown = load_from_xml_string(&content.replace("\"red\"><pos", "\"blue\"><pos"), Path::new("."), 0., &[]);
+        assert!(matches!(unknown, Err(SceneError::InvalidValue { ref value, .. }) if value == "blue"));
+    }
+
+    #[test]
+    fn parse_included_scene() {
+        let dir = std::env::temp_dir().join("raytracer_parse_studio");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("studio.xml"),
+            "<scene>
+            <camera><pos x=\"0\" y=\"0\" z=\"0\"/><dir x=\"1\" y=\"0\" z=\"0\"/></camera>
+            <ambiant_light><color r=\"1\" g=\"1\" b=\"1\"/><intensity i=\"1\"/></ambiant_light>
+            <material name=\"floor\"><diffuse r=\"1\" g=\"1\" b=\"1\"/><specular r=\"0\" g=\"0\" b=\"0\"/></material>
+            <plane material=\"floor\"><pos x=\"0\" y=\"0\" z=\"0\"/><normal x=\"0\" y=\"0\" z=\"1\"/></plane>
+            </scene>",
+        )
+        .unwrap();
+        let content = "<scene>
+            <include file=\"raytracer_parse_studio/studio.xml\">
+                <camera><pos x=\"0\" y=\"0\" z=\"2\"/><dir x=\"1\" y=\"0\" z=\"0\"/></camera>
+            </include>
+            <define name=\"lamp\">
+                <sphere material=\"floor\"><pos x=\"5\" y=\"0\" z=\"1\"/><radius r=\"0.5\"/></sphere>
+            </define>
+            <use name=\"lamp\"/>
+            <group><translate x=\"0\" y=\"3\" z=\"0\"/><use name=\"lamp\"/></group>
+            </scene>";
+        let scene = load_from_xml_string(content, &std::env::temp_dir(), 0., &[]).unwrap();
+        assert_eq!(scene.camera.ray((0., 0.), 0.).start.z, 2.);
+        assert_eq!(scene.objects.len(), 3);
+        let Object::Sphere(lamp) = &scene.objects[1] else {
+            panic!("sphere expected")
+        };
+        assert_eq!(lamp.center.x, 5.);
+        assert!(matches!(scene.objects[2], Object::Instance(_)));
+    }
+
+    #[test]
+    fn read_description() {
+        let dir = std::env::temp_dir().join("raytracer_parse_wood");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("wood.xml"),
+            "<scene><material name=\"wood\"><diffuse texture=\"wood.png\"/><specular/></material></scene>",
+        )
+        .unwrap();
+        let content = "<scene>
+            <camera><pos x=\"0\" y=\"0\" z=\"1\"/><dir x=\"1\" y=\"0\" z=\"0\"/></camera>
+            <ambiant_light><color r=\"1\" g=\"1\" b=\"1\"/><intensity i=\"0.1\"/></ambiant_light>
+            <include file=\"raytracer_parse_wood/wood.xml\"/>
+            <group material=\"wood\">
+                <translate x=\"0\" y=\"3\" z=\"0\"/>
+                <sphere><pos x=\"5\" y=\"0\" z=\"1\"/><radius r=\"1\"/></sphere>
+            </group>
+            </scene>";
+        let description = read_from_xml_string(content, &std::env::temp_dir(), 0., &[]).unwrap();
+        //the texture is found from the scene directory
+        let Some(MaterialDescription::Basic(wood)) = description.materials.get("wood") else {
+            panic!("basic material expected")
+        };
+        let TextureDescription::Image { file, .. } = &wood.textures[0].texture else {
+            panic!("image expected")
+        };
+        assert_eq!(file, Path::new("raytracer_parse_wood/wood.png"));
+
+        //the same scene in JSON
+        let json = r#"{
+            "camera": {"pos": {"x": 0, "y": 0, "z": 1}, "dir": {"x": 1, "y": 0, "z": 0}},
+            "ambiant_light": {"color": {"r": 1, "g": 1, "b": 1}, "intensity": 0.1},
+            "materials": {"wood": {
+                "type": "basic",
+                "diffuse": {"r": 0, "g": 0, "b": 0},
+                "specular": {"r": 0, "g": 0, "b": 0},
+                "textures": [{"channel": "diffuse", "texture": {"type": "image", "file": "raytracer_parse_wood/wood.png"}}]
+            }},
+            "objects": [{
+                "type": "group",
+                "material": "wood",
+                "transforms": [{"translate": {"x": 0, "y": 3, "z": 0}}],
+                "objects": [{"type": "sphere", "pos": {"x": 5, "y": 0, "z": 1}, "radius": 1}]
+            }]
+        }"#;
+        assert_eq!(SceneDescription::from_json(json).unwrap(), description);
+    }
+
+    #[test]
+    fn parse_animated_scene() {
+        let content = "<scene>
+            <camera>
+                <pos x=\"0\" y=\"0\" z=\"0\"><key frame=\"0\" z=\"0\"/><key frame=\"10\" z=\"5\"/></pos>
+                <dir x=\"1\" y=\"0\" z=\"0\"/>
+            </camera>
+            <ambiant_light><color r=\"1\" g=\"1\" b=\"1\"/><intensity i=\"1\"/></ambiant_light>
+            </scene>";
+        let scene = load_from_xml_string(content, Path::new("."), 4., &[]).unwrap();
+        assert_eq!(scene.camera.ray((0., 0.), 0.).start.z, 2.);
+    }
+
+    #[test]
+    fn parse_animations() {
+        let content = "<scene>
+            <camera><pos x=\"0\" y=\"0\" z=\"0\"/><dir x=\"1\" y=\"0\" z=\"0\"/></camera>
+            <ambiant_light><color r=\"1\" g=\"1\" b=\"1\"/><intensity i=\"1\"><key frame=\"0\" i=\"0\"/><key frame=\"10\" i=\"1\"/></intensity></ambiant_light>
+            <material name=\"a/b\"><diffuse r=\"1\" g=\"1\" b=\"1\"/><specular r=\"0\" g=\"0\" b=\"0\"/>
+                <roughness r=\"0\"><key frame=\"10\" r=\"0.5\"/></roughness>
+            </material>
+            <group material=\"a/b\">
+                <sphere><pos x=\"5\" y=\"0\" z=\"0\"/><radius r=\"1\"/></sphere>
+                <group>
+                    <rotate x=\"0\" y=\"0\" z=\"1\" angle=\"0\" interpolation=\"bezier\"><key frame=\"0\" angle=\"0\"/><key frame=\"10\" angle=\"90\"/></rotate>
+                    <scale s=\"1\"><key frame=\"10\" s=\"2\"/></scale>
+                    <sphere><pos x=\"5\" y=\"0\" z=\"0\"/><radius r=\"1\"><key frame=\"10\" r=\"3\"/></radius></sphere>
+                </group>
+            </group>
+            </scene>";
+        let description = read_from_xml_string(content, Path::new("."), 0., &[]).unwrap();
+        let targets: Vec<&str> = description.animations.iter().map(|a| a.target.as_str()).collect();
+        assert_eq!(
+            targets,
+            vec![
+                "ambiant_light/intensity",
+                "materials/a~1b/roughness",
+                "objects/0/objects/1/transforms/0/rotate/angle",
+                "objects/0/objects/1/transforms/1/scale/x",
+                "objects/0/objects/1/transforms/1/scale/y",
+                "objects/0/objects/1/transforms/1/scale/z",
+                "objects/0/objects/1/objects/0/radius",
+            ]
+        );
+        assert_eq!(description.animations[2].interpolation, Interpolation::Bezier);
+        //the frames of the description are the ones of the file
+        for frame in [0., 3., 10.] {
+            assert_eq!(
+                description.at(frame).unwrap(),
+                SceneDescription {
+                    animations: description.animations.clone(),
+                    ..read_from_xml_string(content, Path::new("."), frame, &[]).unwrap()
+                }
+            );
+        }
+
+        let error = |element: &str| {
+            let content = format!(
+                "<scene>\n<camera><pos x=\"0\" y=\"0\" z=\"0\"/><dir x=\"1\" y=\"0\" z=\"0\"/>\n{}</camera></scene>",
+                element
+            );
+            read_from_xml_string(&content, Path::new("."), 0., &[]).unwrap_err().to_string()
+        };
+        assert_eq!(
+            error("<resolution width=\"4\" height=\"3\"><key frame=\"1\" width=\"8\"/></resolution>"),
+            "line 3, column 1 (scene/camera/resolution): unknown element <key>"
+        );
+        assert_eq!(
+            error("<fov angle=\"60\"><key frame=\"1\" angle=\"90\" zoom=\"2\"/></fov>"),
+            "line 3, column 1 (scene/camera/fov): invalid zoom: it cannot be animated"
+        );
+    }
+
+    #[test]
+    fn parse_variables() {
+        let content = "<scene>
+            <var name=\"r\" value=\"2\"/>
+            <camera><pos x=\"0\" y=\"0\" z=\"${r} / 2\"/><dir x=\"1\" y=\"0\" z=\"0\"/></camera>
+            <ambiant_light><color r=\"1\" g=\"1\" b=\"1\"/><intensity i=\"1\"/></ambiant_light>
+            <material name=\"floor\"><diffuse r=\"1\" g=\"1\" b=\"1\"/><specular r=\"0\" g=\"0\" b=\"0\"/></material>
+            <sphere material=\"floor\"><pos x=\"${r}*cos(60deg)\" y=\"2^3\" z=\"0\"/><radius r=\"${r / 4}\"/></sphere>
+            </scene>";
+        let sphere = |variables: &[(String, f64)]| {
+            let scene = load_from_xml_string(content, Path::new("."), 0., variables).unwrap();
+            let Object::Sphere(sphere) = &scene.objects[0] else {
+                panic!("sphere expected")
+            };
+            (sphere.center, sphere.radius)
+        };
+        let (center, radius) = sphere(&[]);
+        assert_abs_diff_eq!(center.x, 1., epsilon = 1e-12);
+        assert_eq!((center.y, radius), (8., 0.5));
+        let (center, radius) = sphere(&[(String::from("r"), 4.)]);
+        assert_abs_diff_eq!(center.x, 2., epsilon = 1e-12);
+        assert_eq!(radius, 1.);
+        //expressions without ${} do not know the variables
+        assert!(load_from_xml_string(&content.replace("${r} / 2", "r / 2"), Path::new("."), 0., &[]).is_err());
+    }
+
+    #[test]
+    fn parse_repeat() {
+        let content = "<scene>
+            <camera><pos x=\"0\" y=\"0\" z=\"0\"/><dir x=\"1\" y=\"0\" z=\"0\"/></camera>
+            <ambiant_light><color r=\"1\" g=\"1\" b=\"1\"/><intensity i=\"1\"/></ambiant_light>
+            <material name=\"floor\"><diffuse r=\"1\" g=\"1\" b=\"1\"/><specular r=\"0\" g=\"0\" b=\"0\"/></material>
+            <repeat count=\"10\" var=\"i\">
+                <repeat count=\"10\" var=\"j\">
+                    <sphere material=\"floor\"><pos x=\"10\" y=\"${i * 2}\" z=\"${j * 2}\"/><radius r=\"0.5\"/></sphere>
+                </repeat>
+            </repeat>
+            </scene>";
+        let scene = load_from_xml_string(content, Path::new("."), 0., &[]).unwrap();
+        assert_eq!(scene.objects.len(), 100);
+        let Object::Sphere(sphere) = &scene.objects[34] else {
+            panic!("sphere expected")
+        };
+        assert_eq!((sphere.center.y, sphere.center.z), (6., 8.));
+    }
+
     //TODO: faire les autres parseurs
 }
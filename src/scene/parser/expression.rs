@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+//Arithmetic in attribute values: + - * / % ^, parentheses, functions
+//(cos(x), min(a, b)...), the pi constant, variables and angles in degrees
+//(30deg). Trigonometric functions work in radians.
+//Variables are written ${r} in the scene, ${...} holds any expression.
+
+pub fn evaluate(expression: &str, variables: &HashMap<String, f64>) -> Result<f64, String> {
+    let mut parser = Parser {
+        chars: expression.chars().collect(),
+        pos: 0,
+        variables,
+    };
+    let value = parser.expression()?;
+    if let Some(c) = parser.peek() {
+        return Err(format!("unexpected {:?}", c));
+    }
+    match value.is_finite() {
+        true => Ok(value),
+        false => Err(format!("{} is not a finite number", expression.trim())),
+    }
+}
+
+//value of a numeric attribute, written as a number or as an expression
+pub fn parse<T: FromStr>(value: &str) -> Result<T, T::Err> {
+    value.trim().parse::<T>().or_else(|e| match evaluate(value, &HashMap::new()) {
+        Ok(v) => v.to_string().parse::<T>().map_err(|_| e),
+        Err(_) => Err(e),
+    })
+}
+
+//attribute value with its variables replaced: the number of the whole
+//value when it is an expression (x="${r}*2"), otherwise each ${...} is
+//replaced by its number (name="ball${i}")
+pub fn substitute(value: &str, variables: &HashMap<String, f64>) -> Result<String, String> {
+    if !value.contains("${") {
+        return Ok(String::from(value));
+    }
+    if let Ok(v) = evaluate(value, variables) {
+        return Ok(v.to_string());
+    }
+    let mut result = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        let end = rest[start..].find('}').ok_or("${ is not closed")? + start;
+        result.push_str(&rest[..start]);
+        result.push_str(&evaluate(&rest[start + 2..end], variables)?.to_string());
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+//names that can be used in expressions
+pub fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_') && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    variables: &'a HashMap<String, f64>,
+}
+
+impl Parser<'_> {
+    //next character after the spaces
+    fn peek(&mut self) -> Option<char> {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        match (self.eat(c), self.peek()) {
+            (true, _) => Ok(()),
+            (false, Some(found)) => Err(format!("{:?} expected instead of {:?}", c, found)),
+            (false, None) => Err(format!("{:?} expected at the end", c)),
+        }
+    }
+
+    fn expression(&mut self) -> Result<f64, String> {
+        let mut value = self.product()?;
+        loop {
+            if self.eat('+') {
+                value += self.product()?;
+            } else if self.eat('-') {
+                value -= self.product()?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn product(&mut self) -> Result<f64, String> {
+        let mut value = self.unary()?;
+        loop {
+            if self.eat('*') {
+                value *= self.unary()?;
+            } else if self.eat('/') {
+                value /= self.unary()?;
+            } else if self.eat('%') {
+                value = value.rem_euclid(self.unary()?);
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    //powers go first: -2^2 is -4, 2^3^2 is 2^9
+    fn unary(&mut self) -> Result<f64, String> {
+        if self.eat('-') {
+            return Ok(-self.unary()?);
+        }
+        if self.eat('+') {
+            return self.unary();
+        }
+        let base = self.atom()?;
+        match self.eat('^') {
+            true => Ok(base.powf(self.unary()?)),
+            false => Ok(base),
+        }
+    }
+
+    //number, name or parentheses, in degrees when followed by deg
+    fn atom(&mut self) -> Result<f64, String> {
+        let value = match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let value = self.expression()?;
+                self.expect(')')?;
+                value
+            }
+            Some('$') => {
+                self.pos += 1;
+                self.expect('{')?;
+                let value = self.expression()?;
+                self.expect('}')?;
+                value
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => self.number()?,
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                let name = self.name();
+                self.named(&name)?
+            }
+            Some(c) => return Err(format!("unexpected {:?}", c)),
+            None => return Err(String::from("the expression ends too soon")),
+        };
+        let start = self.pos;
+        if self.peek().is_some_and(|c| c.is_alphabetic()) {
+            match self.name().as_str() {
+                "deg" => return Ok(value.to_radians()),
+                _ => self.pos = start,
+            }
+        }
+        Ok(value)
+    }
+
+    fn number(&mut self) -> Result<f64, String> {
+        let start = self.pos;
+        let digit = |i: usize| self.chars.get(i).is_some_and(|c| c.is_ascii_digit());
+        while digit(self.pos) || self.chars.get(self.pos) == Some(&'.') {
+            self.pos += 1;
+        }
+        //exponent, 1e-3
+        if matches!(self.chars.get(self.pos), Some('e' | 'E')) {
+            let sign = matches!(self.chars.get(self.pos + 1), Some('+' | '-')) as usize;
+            if digit(self.pos + 1 + sign) {
+                self.pos += 1 + sign;
+                while digit(self.pos) {
+                    self.pos += 1;
+                }
+            }
+        }
+        let number: String = self.chars[start..self.pos].iter().collect();
+        number.parse::<f64>().map_err(|e| format!("{:?}: {}", number, e))
+    }
+
+    fn name(&mut self) -> String {
+        let start = self.pos;
+        while self.chars.get(self.pos).is_some_and(|c| c.is_alphanumeric() || *c == '_') {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    //function call, variable or constant
+    fn named(&mut self, name: &str) -> Result<f64, String> {
+        if !self.eat('(') {
+            return match (self.variables.get(name), name) {
+                (Some(value), _) => Ok(*value),
+                (None, "pi") => Ok(PI),
+                (None, _) => Err(format!("unknown variable {}", name)),
+            };
+        }
+        let mut args = Vec::new();
+        if !self.eat(')') {
+            loop {
+                args.push(self.expression()?);
+                if self.eat(')') {
+                    break;
+                }
+                self.expect(',')?;
+            }
+        }
+        let one = |f: fn(f64) -> f64| match args[..] {
+            [x] => Ok(f(x)),
+            _ => Err(format!("{} takes 1 argument", name)),
+        };
+        let two = |f: fn(f64, f64) -> f64| match args[..] {
+            [x, y] => Ok(f(x, y)),
+            _ => Err(format!("{} takes 2 arguments", name)),
+        };
+        match name {
+            "sin" => one(f64::sin),
+            "cos" => one(f64::cos),
+            "tan" => one(f64::tan),
+            "asin" => one(f64::asin),
+            "acos" => one(f64::acos),
+            "atan" => one(f64::atan),
+            "sqrt" => one(f64::sqrt),
+            "abs" => one(f64::abs),
+            "floor" => one(f64::floor),
+            "ceil" => one(f64::ceil),
+            "round" => one(f64::round),
+            "exp" => one(f64::exp),
+            "ln" => one(f64::ln),
+            "atan2" => two(f64::atan2),
+            "min" => two(f64::min),
+            "max" => two(f64::max),
+            "pow" => two(f64::powf),
+            _ => Err(format!("unknown function {}", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    fn variables() -> HashMap<String, f64> {
+        HashMap::from([(String::from("r"), 2.5), (String::from("i"), -2.)])
+    }
+
+    #[test]
+    fn arithmetic() {
+        let value = |s: &str| evaluate(s, &variables()).unwrap();
+        assert_eq!(value("1 + 2 * 3"), 7.);
+        assert_eq!(value("(1 + 2) * 3 - 4 / 2"), 7.);
+        assert_eq!(value("-2^2"), -4.);
+        assert_eq!(value("2^3^2"), 512.);
+        assert_eq!(value("7 % 3 + -1 % 3"), 3.);
+        assert_eq!(value("1e-3 * 2E3"), 2.);
+        assert_abs_diff_eq!(value("${r}*cos(60deg)"), 1.25, epsilon = 1e-12);
+        assert_abs_diff_eq!(value("r * sin(pi / 2) + ${i^2}"), 6.5);
+        assert_eq!(value("max(min(1, 2), atan2(0, 1))"), 1.);
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        let error = |s: &str| evaluate(s, &variables()).unwrap_err();
+        assert_eq!(error("2 * x"), "unknown variable x");
+        assert_eq!(error("cosh(1)"), "unknown function cosh");
+        assert_eq!(error("min(1)"), "min takes 2 arguments");
+        assert_eq!(error("(1 + 2"), "')' expected at the end");
+        assert_eq!(error("1 2"), "unexpected '2'");
+        assert_eq!(error("1 / 0"), "1 / 0 is not a finite number");
+        assert_eq!(error(""), "the expression ends too soon");
+    }
+
+    #[test]
+    fn attribute_values() {
+        assert_eq!(substitute("${r}*2", &variables()), Ok(String::from("5")));
+        assert_eq!(substitute("ball${i + 3}", &variables()), Ok(String::from("ball1")));
+        assert_eq!(substitute("red", &variables()), Ok(String::from("red")));
+        assert_eq!(substitute("ball${j}", &variables()), Err(String::from("unknown variable j")));
+        assert_eq!(parse::<f64>(" 2*3 "), Ok(6.));
+        assert_eq!(parse::<u32>("10/2"), Ok(5));
+        assert!(parse::<u32>("10/4").is_err());
+        assert!(parse::<f64>("big").is_err());
+        assert!(is_name("r_2") && !is_name("2r") && !is_name("a b"));
+    }
+}
@@ -0,0 +1,560 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use quick_xml::escape::escape;
+
+use crate::coord::Vec3d;
+use crate::scene::description::{
+    BasicMaterial, FaceDescription, MaterialDescription, MaterialRef, ObjectDescription, SceneDescription, Shape,
+    TextureDescription, TransformDescription,
+};
+use crate::scene::object::material::{Channel, Color};
+use crate::scene::object::sdf::Node;
+
+//Indented XML text, elements are written with their attributes
+struct Writer {
+    text: String,
+    depth: usize,
+}
+
+type Attributes = Vec<(&'static str, String)>;
+
+impl Writer {
+    fn tag(&mut self, name: &str, attributes: &Attributes) {
+        self.text.push_str(&"    ".repeat(self.depth));
+        self.text.push('<');
+        self.text.push_str(name);
+        for (key, value) in attributes {
+            self.text.push_str(&format!(" {}=\"{}\"", key, escape(value.as_str())));
+        }
+    }
+
+    fn empty(&mut self, name: &str, attributes: Attributes) {
+        self.tag(name, &attributes);
+        self.text.push_str("/>\n");
+    }
+
+    fn start(&mut self, name: &str, attributes: Attributes) {
+        self.tag(name, &attributes);
+        self.text.push_str(">\n");
+        self.depth += 1;
+    }
+
+    fn end(&mut self, name: &str) {
+        self.depth -= 1;
+        self.text.push_str(&format!("{}</{}>\n", "    ".repeat(self.depth), name));
+    }
+}
+
+fn value(v: impl Display) -> String {
+    v.to_string()
+}
+
+fn vec3d(v: Vec3d) -> Attributes {
+    vec![("x", value(v.x)), ("y", value(v.y)), ("z", value(v.z))]
+}
+
+fn color(c: Color) -> Attributes {
+    vec![("r", value(c.r)), ("g", value(c.g)), ("b", value(c.b))]
+}
+
+//Scene file read by read_from_xml_string as the same description. Named
+//materials and library objects are written before the objects using them.
+pub fn write_xml_string(scene: &SceneDescription) -> String {
+    let mut w = Writer {
+        text: String::new(),
+        depth: 0,
+    };
+    let mut attributes = vec![];
+    if scene.cull_back_faces {
+        attributes.push(("cull_back_faces", value(true)));
+    }
+    w.start("scene", attributes);
+
+    w.start("camera", vec![]);
+    w.empty("pos", vec3d(scene.camera.pos));
+    w.empty("dir", vec3d(scene.camera.dir));
+    if scene.camera.shutter != (0., 0.) {
+        let (open, close) = scene.camera.shutter;
+        w.empty("shutter", vec![("open", value(open)), ("close", value(close))]);
+    }
+    if let Some(up) = scene.camera.up {
+        w.empty("up", vec3d(up));
+    }
+    if let Some((width, height)) = scene.camera.resolution {
+        w.empty("resolution", vec![("width", value(width)), ("height", value(height))]);
+    }
+    if let Some(fov) = scene.camera.fov {
+        w.empty("fov", vec![("angle", value(fov))]);
+    }
+    w.end("camera");
+
+    w.start("ambiant_light", vec![]);
+    w.empty("color", color(scene.ambiant_light.color));
+    w.empty("intensity", vec![("i", value(scene.ambiant_light.intensity))]);
+    w.end("ambiant_light");
+
+    for light in scene.lights.iter() {
+        w.start("point_light", light.name.iter().map(|n| ("name", n.clone())).collect());
+        w.empty("pos", vec3d(light.pos));
+        w.empty("color", color(light.color));
+        w.empty("intensity", vec![("i", value(light.intensity))]);
+        w.end("point_light");
+    }
+
+    for (name, material) in scene.materials.iter() {
+        write_material(&mut w, material, Some(name));
+    }
+
+    if !scene.library.is_empty() {
+        w.start("library", vec![]);
+        let mut written = vec![];
+        for name in scene.library.keys() {
+            write_library_object(&mut w, &scene.library, name, &mut written);
+        }
+        w.end("library");
+    }
+
+    for object in scene.objects.iter() {
+        write_object(&mut w, object, vec![]);
+    }
+    w.end("scene");
+    w.text
+}
+
+//library object, after the ones it instances
+fn write_library_object<'a>(
+    w: &mut Writer,
+    library: &'a BTreeMap<String, ObjectDescription>,
+    name: &'a str,
+    written: &mut Vec<&'a str>,
+) {
+    if written.contains(&name) {
+        return;
+    }
+    written.push(name);
+    let Some(object) = library.get(name) else {
+        return;
+    };
+    for used in instanced(object) {
+        write_library_object(w, library, used, written);
+    }
+    write_object(w, object, vec![("name", String::from(name))]);
+}
+
+//names of the library objects instanced by an object
+fn instanced(object: &ObjectDescription) -> Vec<&str> {
+    match &object.shape {
+        Shape::Instance { object, .. } => vec![object.as_str()],
+        Shape::Csg { objects, .. } | Shape::Group { objects, .. } => objects.iter().flat_map(instanced).collect(),
+        _ => vec![],
+    }
+}
+
+fn write_material(w: &mut Writer, material: &MaterialDescription, name: Option<&str>) {
+    let mut attributes: Attributes = name.iter().map(|n| ("name", String::from(*n))).collect();
+    match material {
+        MaterialDescription::Basic(basic) => {
+            w.start("material", attributes);
+            write_basic_material(w, basic);
+        }
+        MaterialDescription::Principled(p) => {
+            attributes.push(("type", String::from("principled")));
+            w.start("material", attributes);
+            w.empty("base_color", color(p.base_color));
+            w.empty("metallic", vec![("m", value(p.metallic))]);
+            w.empty("roughness", vec![("r", value(p.roughness))]);
+            w.empty("specular", vec![("s", value(p.specular))]);
+            w.empty("sheen", vec![("s", value(p.sheen))]);
+            w.empty("clearcoat", vec![("c", value(p.clearcoat))]);
+            w.empty("transmission", vec![("t", value(p.transmission))]);
+            w.empty("ior", vec![("i", value(p.ior))]);
+        }
+    }
+    w.end("material");
+}
+
+//element of a channel and the attribute of its value, for scalar channels
+fn channel_element(channel: Channel) -> (&'static str, Option<&'static str>) {
+    match channel {
+        Channel::Diffuse => ("diffuse", None),
+        Channel::Specular => ("specular", None),
+        Channel::Transmission => ("transmission", None),
+        Channel::Reflectivity => ("reflectivity", Some("r")),
+        Channel::Roughness => ("roughness", Some("r")),
+        Channel::Sheen => ("sheen", Some("s")),
+        Channel::Clearcoat => ("clearcoat", Some("c")),
+        Channel::Ior => ("ior", Some("i")),
+    }
+}
+
+//Textured channels are written in the order of their textures, the value of
+//a channel is the one of its last element
+fn write_basic_material(w: &mut Writer, m: &BasicMaterial) {
+    //gray colors are read back exactly
+    let scalar = |c: Color| match c.r == c.g && c.g == c.b {
+        true => c.r,
+        false => (c.r + c.g + c.b) / 3.,
+    };
+    for t in m.textures.iter() {
+        let (element, key) = channel_element(t.channel);
+        let mut attributes = match (&t.texture, key) {
+            //the values blended by procedural textures
+            (TextureDescription::Procedural(p), None) => {
+                let (c, c2) = p.colors;
+                let mut attributes = color(c);
+                attributes.extend([("r2", value(c2.r)), ("g2", value(c2.g)), ("b2", value(c2.b))]);
+                attributes
+            }
+            (TextureDescription::Procedural(p), Some(key)) => {
+                let key2 = match key {
+                    "r" => "r2",
+                    "s" => "s2",
+                    "c" => "c2",
+                    _ => "i2",
+                };
+                vec![(key, value(scalar(p.colors.0))), (key2, value(scalar(p.colors.1)))]
+            }
+            (TextureDescription::Image { .. }, None) => color(channel_color(m, t.channel)),
+            (TextureDescription::Image { .. }, Some(key)) => vec![(key, value(channel_scalar(m, t.channel)))],
+        };
+        attributes.extend(texture(&t.texture));
+        w.empty(element, attributes);
+    }
+    let textured = |channel| m.textures.iter().any(|t| t.channel == channel);
+    for channel in [Channel::Diffuse, Channel::Specular, Channel::Transmission] {
+        if !textured(channel) && (channel != Channel::Transmission || m.transmission != Color::gray(0.)) {
+            w.empty(channel_element(channel).0, color(channel_color(m, channel)));
+        }
+    }
+    for channel in [
+        Channel::Reflectivity,
+        Channel::Roughness,
+        Channel::Sheen,
+        Channel::Clearcoat,
+        Channel::Ior,
+    ] {
+        let default = if channel == Channel::Ior { 1. } else { 0. };
+        if !textured(channel) && channel_scalar(m, channel) != default {
+            let (element, key) = channel_element(channel);
+            w.empty(element, vec![(key.unwrap(), value(channel_scalar(m, channel)))]);
+        }
+    }
+    if let Some(normal_map) = &m.normal_map {
+        w.empty("normal_map", texture(normal_map));
+    }
+    if let Some(bump) = &m.bump {
+        let mut attributes = texture(&bump.texture);
+        attributes.push(("strength", value(bump.strength)));
+        w.empty("bump", attributes);
+    }
+}
+
+fn channel_color(m: &BasicMaterial, channel: Channel) -> Color {
+    match channel {
+        Channel::Specular => m.specular,
+        Channel::Transmission => m.transmission,
+        _ => m.diffuse,
+    }
+}
+
+fn channel_scalar(m: &BasicMaterial, channel: Channel) -> f64 {
+    match channel {
+        Channel::Reflectivity => m.reflectivity,
+        Channel::Roughness => m.roughness,
+        Channel::Sheen => m.sheen,
+        Channel::Clearcoat => m.clearcoat,
+        _ => m.ior,
+    }
+}
+
+//texture attributes, the path of images or the name of procedural patterns
+fn texture(t: &TextureDescription) -> Attributes {
+    match t {
+        TextureDescription::Image {
+            file,
+            wrap,
+            filter,
+            scale,
+        } => vec![
+            ("texture", file.to_string_lossy().into_owned()),
+            ("wrap", value(wrap)),
+            ("filter", value(filter)),
+            ("scale", value(scale)),
+        ],
+        TextureDescription::Procedural(p) => vec![
+            ("texture", value(p.pattern)),
+            ("scale", value(p.scale)),
+            ("octaves", value(p.octaves)),
+        ],
+    }
+}
+
+fn write_transform(w: &mut Writer, t: &TransformDescription) {
+    match *t {
+        TransformDescription::Translate(v) => w.empty("translate", vec3d(v)),
+        TransformDescription::Rotate { axis, angle } => {
+            let mut attributes = vec3d(axis);
+            attributes.push(("angle", value(angle)));
+            w.empty("rotate", attributes)
+        }
+        TransformDescription::Scale(v) => w.empty("scale", vec3d(v)),
+    }
+}
+
+//object with its own attributes (the name of library objects), inline
+//materials are written before the content of the object
+fn write_object(w: &mut Writer, object: &ObjectDescription, mut attributes: Attributes) {
+    let element = match &object.shape {
+        Shape::Sphere { .. } => "sphere",
+        Shape::Plane { .. } => "plane",
+        Shape::Disk { .. } => "disk",
+        Shape::Box { .. } => "box",
+        Shape::Cylinder { .. } => "cylinder",
+        Shape::Cone { .. } => "cone",
+        Shape::Torus { .. } => "torus",
+        Shape::Heightfield { .. } => "heightfield",
+        Shape::Mesh { .. } => "object",
+        Shape::Sdf { .. } => "sdf",
+        Shape::Csg { .. } => "csg",
+        Shape::Instance { .. } => "instance",
+        Shape::Group { .. } => "group",
+    };
+    match &object.shape {
+        Shape::Heightfield { file, .. } => attributes.push(("file", file.to_string_lossy().into_owned())),
+        Shape::Mesh {
+            double_sided: Some(double_sided),
+            ..
+        } => attributes.push(("double_sided", value(double_sided))),
+        Shape::Csg { operation, .. } => attributes.push(("op", value(operation))),
+        Shape::Instance { object, .. } => attributes.push(("object", object.clone())),
+        //library groups are named by the library
+        Shape::Group { name: Some(name), .. } if !attributes.iter().any(|(key, _)| *key == "name") => {
+            attributes.push(("name", name.clone()))
+        }
+        _ => (),
+    }
+    if let Some(MaterialRef::Named(name)) = &object.material {
+        attributes.push(("material", name.clone()));
+    }
+    w.start(element, attributes);
+    if let Some(MaterialRef::Inline(material)) = &object.material {
+        write_material(w, material, None);
+    }
+
+    match &object.shape {
+        Shape::Sphere { pos, radius, velocity } => {
+            w.empty("pos", vec3d(*pos));
+            w.empty("radius", vec![("r", value(radius))]);
+            if let Some(velocity) = velocity {
+                w.empty("velocity", vec3d(*velocity));
+            }
+        }
+        Shape::Plane { pos, normal } => {
+            w.empty("pos", vec3d(*pos));
+            w.empty("normal", vec3d(*normal));
+        }
+        Shape::Disk { pos, normal, radius } => {
+            w.empty("pos", vec3d(*pos));
+            w.empty("normal", vec3d(*normal));
+            w.empty("radius", vec![("r", value(radius))]);
+        }
+        Shape::Box { min, max } => {
+            w.empty("min", vec3d(*min));
+            w.empty("max", vec3d(*max));
+        }
+        Shape::Cylinder { pos, top, radius } => {
+            w.empty("pos", vec3d(*pos));
+            w.empty("top", vec3d(*top));
+            w.empty("radius", vec![("r", value(radius))]);
+        }
+        Shape::Cone {
+            pos,
+            top,
+            radius,
+            top_radius,
+        } => {
+            w.empty("pos", vec3d(*pos));
+            w.empty("top", vec3d(*top));
+            w.empty("radius", vec![("r", value(radius))]);
+            w.empty("top_radius", vec![("r", value(top_radius))]);
+        }
+        Shape::Torus { pos, axis, radius, tube } => {
+            w.empty("pos", vec3d(*pos));
+            w.empty("axis", vec3d(*axis));
+            w.empty("radius", vec![("r", value(radius))]);
+            w.empty("tube", vec![("r", value(tube))]);
+        }
+        Shape::Heightfield { pos, size, .. } => {
+            w.empty("pos", vec3d(*pos));
+            w.empty("size", vec3d(*size));
+        }
+        Shape::Mesh { faces, .. } => faces.iter().for_each(|face| write_face(w, face)),
+        Shape::Sdf { node } => write_node(w, node),
+        Shape::Csg { objects, .. } => objects.iter().for_each(|o| write_object(w, o, vec![])),
+        Shape::Instance { transforms, motion, .. } => {
+            transforms.iter().for_each(|t| write_transform(w, t));
+            if !motion.is_empty() {
+                w.start("motion", vec![]);
+                for keyframe in motion.iter() {
+                    w.start("keyframe", vec![("time", value(keyframe.time))]);
+                    keyframe.transforms.iter().for_each(|t| write_transform(w, t));
+                    w.end("keyframe");
+                }
+                w.end("motion");
+            }
+        }
+        Shape::Group { transforms, objects, .. } => {
+            transforms.iter().for_each(|t| write_transform(w, t));
+            objects.iter().for_each(|o| write_object(w, o, vec![]));
+        }
+    }
+    w.end(element);
+}
+
+fn write_face(w: &mut Writer, face: &FaceDescription) {
+    w.start("face", vec![]);
+    for (i, point) in face.points.iter().enumerate() {
+        let mut attributes = vec3d(*point);
+        if let Some((u, v)) = face.uvs.get(i) {
+            attributes.extend([("u", value(u)), ("v", value(v))]);
+        }
+        if let Some(n) = face.normals.get(i) {
+            attributes.extend([("nx", value(n.x)), ("ny", value(n.y)), ("nz", value(n.z))]);
+        }
+        w.empty("pos", attributes);
+    }
+    w.end("face");
+}
+
+fn write_node(w: &mut Writer, node: &Node) {
+    match node {
+        Node::Sphere { center, radius } => {
+            let mut attributes = vec3d(*center);
+            attributes.push(("r", value(radius)));
+            w.empty("sphere", attributes)
+        }
+        Node::Box { center, size, rounding } => {
+            let mut attributes = vec3d(*center);
+            attributes.extend([
+                ("sx", value(size.x)),
+                ("sy", value(size.y)),
+                ("sz", value(size.z)),
+                ("round", value(rounding)),
+            ]);
+            w.empty("box", attributes)
+        }
+        Node::Torus { center, radius, tube } => {
+            let mut attributes = vec3d(*center);
+            attributes.extend([("r", value(radius)), ("t", value(tube))]);
+            w.empty("torus", attributes)
+        }
+        Node::Cylinder { center, radius, height } => {
+            let mut attributes = vec3d(*center);
+            attributes.extend([("r", value(radius)), ("h", value(height))]);
+            w.empty("cylinder", attributes)
+        }
+        Node::Union(nodes, k) => write_operator(w, "union", nodes, vec![("k", value(k))]),
+        Node::Intersection(nodes, k) => write_operator(w, "intersection", nodes, vec![("k", value(k))]),
+        Node::Difference(nodes, k) => write_operator(w, "difference", nodes, vec![("k", value(k))]),
+        Node::Twist(node, rate) => write_operator(w, "twist", [node.as_ref()], vec![("rate", value(rate))]),
+        Node::Repetition(node, period) => write_operator(w, "repetition", [node.as_ref()], vec3d(*period)),
+        Node::Translation(node, offset) => write_operator(w, "translate", [node.as_ref()], vec3d(*offset)),
+    }
+}
+
+fn write_operator<'a>(w: &mut Writer, element: &str, nodes: impl IntoIterator<Item = &'a Node>, attributes: Attributes) {
+    w.start(element, attributes);
+    nodes.into_iter().for_each(|n| write_node(w, n));
+    w.end(element);
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::path::Path;
+
+    use super::*;
+    use crate::scene::parser::read_from_xml_string;
+
+    #[test]
+    fn write_scene() {
+        let content = "<scene cull_back_faces=\"true\">
+            <camera><pos x=\"0\" y=\"0\" z=\"1\"/><dir x=\"1\" y=\"0\" z=\"0\"/><shutter open=\"0\" close=\"0.5\"/>
+                <up x=\"0\" y=\"0\" z=\"1\"/><resolution width=\"320\" height=\"200\"/><fov angle=\"60\"/></camera>
+            <ambiant_light><color r=\"1\" g=\"1\" b=\"1\"/><intensity i=\"0.1\"/></ambiant_light>
+            <point_light name=\"key\">
+                <pos x=\"2\" y=\"-1\" z=\"2.5\"/><color r=\"1\" g=\"0.9\" b=\"0.8\"/><intensity i=\"1\"/>
+            </point_light>
+            <material name=\"gold\" type=\"principled\"><base_color r=\"1\" g=\"0.8\" b=\"0.3\"/><metallic m=\"1\"/></material>
+            <material name=\"floor\">
+                <diffuse r=\"1\" g=\"1\" b=\"1\" texture=\"checker\" r2=\"0\" g2=\"0\" b2=\"0\" scale=\"0.5\"/>
+                <specular r=\"0\" g=\"0\" b=\"0\"/>
+                <roughness texture=\"marble\" r=\"0.1\" r2=\"0.9\"/>
+                <ior i=\"1.5\"/>
+                <bump texture=\"noise\" strength=\"0.3\"/>
+            </material>
+            <library>
+                <sphere name=\"wheel\" material=\"gold\"><pos x=\"0\" y=\"0\" z=\"0\"/><radius r=\"1\"/></sphere>
+                <instance name=\"pair\" object=\"wheel\"><translate x=\"1\" y=\"0\" z=\"0\"/></instance>
+            </library>
+            <plane material=\"floor\"><pos x=\"0\" y=\"0\" z=\"0\"/><normal x=\"0\" y=\"0\" z=\"1\"/></plane>
+            <group name=\"spinner\" material=\"gold\">
+                <rotate x=\"0\" y=\"0\" z=\"1\" angle=\"45\"/>
+                <cone><pos x=\"3\" y=\"0\" z=\"0\"/><top x=\"3\" y=\"0\" z=\"2\"/><radius r=\"1\"/></cone>
+                <torus><pos x=\"0\" y=\"3\" z=\"0\"/><axis x=\"0\" y=\"0\" z=\"1\"/><radius r=\"1\"/><tube r=\"0.2\"/></torus>
+                <csg op=\"difference\">
+                    <box><min x=\"-1\" y=\"-1\" z=\"-1\"/><max x=\"1\" y=\"1\" z=\"1\"/></box>
+                    <instance object=\"pair\"></instance>
+                </csg>
+            </group>
+            <object double_sided=\"false\">
+                <material><diffuse r=\"0\" g=\"1\" b=\"0\"/><specular r=\"1\" g=\"1\" b=\"1\"/></material>
+                <face><pos x=\"1\" y=\"1\" z=\"-1\" u=\"0\" v=\"0\"/><pos x=\"1\" y=\"0\" z=\"1\" u=\"0.5\" v=\"1\"/><pos x=\"1\" y=\"-1\" z=\"-1\" u=\"1\"/></face>
+            </object>
+            <instance object=\"wheel\" material=\"floor\">
+                <motion>
+                    <keyframe time=\"0\"><translate x=\"0\" y=\"0\" z=\"0\"/></keyframe>
+                    <keyframe time=\"1\"><translate x=\"0\" y=\"10\" z=\"0\"/></keyframe>
+                </motion>
+            </instance>
+            <sdf material=\"gold\">
+                <union k=\"0.2\">
+                    <sphere x=\"0\" y=\"0\" z=\"1\" r=\"1\"/>
+                    <twist rate=\"0.5\"><box sx=\"1\" sy=\"1\" sz=\"1\" round=\"0.1\"/></twist>
+                </union>
+                <translate x=\"2\"><cylinder r=\"1\" h=\"2\"/></translate>
+            </sdf>
+            </scene>";
+        let description = read_from_xml_string(content, Path::new("."), 0., &[]).unwrap();
+        let written = write_xml_string(&description);
+        assert!(written.contains("<group name=\"spinner\" material=\"gold\">"));
+        assert_eq!(read_from_xml_string(&written, Path::new("."), 0., &[]).unwrap(), description);
+        //written again identically
+        assert_eq!(write_xml_string(&read_from_xml_string(&written, Path::new("."), 0., &[]).unwrap()), written);
+    }
+
+    #[test]
+    fn escaped_names() {
+        let content = "<scene>
+            <camera><pos x=\"0\" y=\"0\" z=\"0\"/><dir x=\"1\" y=\"0\" z=\"0\"/></camera>
+            <ambiant_light><color r=\"1\" g=\"1\" b=\"1\"/><intensity i=\"1\"/></ambiant_light>
+            <material name=\"r&amp;d &lt;&quot;1&quot;&gt;\"><diffuse r=\"1\" g=\"1\" b=\"1\"/><specular r=\"0\" g=\"0\" b=\"0\"/></material>
+            <sphere material=\"r&amp;d &lt;&quot;1&quot;&gt;\"><pos x=\"5\" y=\"0\" z=\"0\"/><radius r=\"1\"/></sphere>
+            </scene>";
+        let description = read_from_xml_string(content, Path::new("."), 0., &[]).unwrap();
+        assert!(description.materials.contains_key("r&d <\"1\">"));
+        let written = write_xml_string(&description);
+        assert_eq!(read_from_xml_string(&written, Path::new("."), 0., &[]).unwrap(), description);
+        assert_eq!(write_xml_string(&read_from_xml_string(&written, Path::new("."), 0., &[]).unwrap()), written);
+    }
+
+    #[test]
+    fn write_example_scene() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let content = std::fs::read_to_string(dir.join("scene.xml")).unwrap();
+        let description = read_from_xml_string(&content, dir, 0., &[]).unwrap();
+        let written = write_xml_string(&description);
+        assert_eq!(read_from_xml_string(&written, dir, 0., &[]).unwrap(), description);
+    }
+}
@@ -0,0 +1,1038 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use quick_xml::events::{BytesEnd, BytesStart, Event};
+use quick_xml::reader::Reader;
+
+use super::super::animation::{animate, Interpolation, Tracks};
+use super::super::description::AnimationDescription;
+use super::super::error::{Location, SceneError};
+use super::expression;
+
+//A scene file, or a file it includes
+struct Text {
+    content: String,
+    //name in the messages, None for the scene itself
+    file: Option<Box<str>>,
+    //relative paths written in the file are resolved from it
+    dir: PathBuf,
+}
+
+//bytes of a text, for the XML reader
+struct Shared(Rc<Text>);
+
+impl AsRef<[u8]> for Shared {
+    fn as_ref(&self) -> &[u8] {
+        self.0.content.as_bytes()
+    }
+}
+
+//part of a text read again: the body of a define or a repeat, or an
+//element given in an include to replace one of the included file
+#[derive(Clone)]
+struct Fragment {
+    text: Rc<Text>,
+    start: usize,
+    end: usize,
+}
+
+//element replacing the one of the included file with the same element
+//name and the same name attribute
+struct Override {
+    element: Vec<u8>,
+    name: Option<String>,
+    fragment: Fragment,
+}
+
+enum Kind {
+    Scene,
+    //canonical path of the file, and the elements that were not replaced yet
+    Include(PathBuf, Vec<Override>),
+    Define(String),
+    Override,
+    Repeat(Repeat),
+}
+
+//pass of a repeat, its variable goes from 0 to count - 1
+struct Repeat {
+    fragment: Fragment,
+    var: Option<String>,
+    index: usize,
+    count: usize,
+    //value of the variable before the repeat
+    outer: Option<f64>,
+}
+
+//text being read
+struct Source {
+    reader: Reader<Cursor<Shared>>,
+    text: Rc<Text>,
+    start: usize,
+    //end of a fragment
+    end: Option<usize>,
+    kind: Kind,
+    //elements opened in this source
+    open: Vec<String>,
+}
+
+impl Source {
+    fn new(text: Rc<Text>, start: usize, end: Option<usize>, kind: Kind) -> Source {
+        let mut cursor = Cursor::new(Shared(text.clone()));
+        cursor.set_position(start as u64);
+        let mut reader = Reader::from_reader(cursor);
+        reader.config_mut().trim_text(true);
+        Source {
+            reader,
+            text,
+            start,
+            end,
+            kind,
+            open: Vec::new(),
+        }
+    }
+
+    fn fragment(fragment: &Fragment, kind: Kind) -> Source {
+        Source::new(fragment.text.clone(), fragment.start, Some(fragment.end), kind)
+    }
+
+    //offset just after the last event
+    fn position(&self) -> usize {
+        self.start + self.reader.buffer_position() as usize
+    }
+
+    //offset of the next event, after the spaces
+    fn next_offset(&self) -> usize {
+        let position = self.position();
+        let rest = &self.text.content[position..];
+        position + rest.len() - rest.trim_start().len()
+    }
+
+    fn finished(&self) -> bool {
+        self.end.is_some_and(|end| self.next_offset() >= end)
+    }
+
+    //root element of an included file, that is not read
+    fn is_included_root(&self) -> bool {
+        matches!(self.kind, Kind::Include(..)) && self.open.is_empty()
+    }
+
+    //element of an included file that is replaced
+    fn take_override(&mut self, e: &BytesStart) -> Option<Fragment> {
+        let Kind::Include(_, overrides) = &mut self.kind else {
+            return None;
+        };
+        if self.open.len() != 1 {
+            return None;
+        }
+        let name = name_attribute(e);
+        let i = overrides
+            .iter()
+            .position(|o| o.element == e.name().as_ref() && o.name == name)?;
+        Some(overrides.remove(i).fragment)
+    }
+}
+
+//where an event starts
+#[derive(Clone)]
+struct Place {
+    text: Rc<Text>,
+    offset: usize,
+}
+
+//Reader of the scene file that knows where it is, for error messages.
+//Animated elements are read as single elements, with their values at the
+//frame being rendered. Their keys are kept for the field of the description
+//they give, so that other frames do not read the file again.
+//Included files (<include file="studio.xml">, whose children replace the
+//elements of the file with the same name) and defines (<define name="lamp">
+//used by <use name="lamp"/>) are read in place.
+//Variables (<var name="r" value="2.5"/>) are replaced in the attributes
+//that follow them, where they are written ${r}. The children of a repeat
+//(<repeat count="10" var="i">) are read count times, with i from 0.
+pub struct XmlReader {
+    sources: Vec<Source>,
+    defines: HashMap<String, Fragment>,
+    variables: HashMap<String, f64>,
+    //variables given apart, that the file does not change
+    fixed: Vec<String>,
+    buffer: Vec<u8>,
+    frame: f64,
+    //open elements
+    open: Vec<String>,
+    //last event read: where it starts and its path
+    place: Place,
+    path: String,
+    //event read ahead, with its place
+    pending: Option<(Event<'static>, Place)>,
+    //directory of the scene file
+    dir: PathBuf,
+    //keys of the last animated element, until the field it gives is known
+    keyed: Option<(Interpolation, Tracks, Location)>,
+    //first animated element that gives no field
+    unclaimed: Option<Location>,
+    //path in the description of the value being read
+    targets: Vec<String>,
+    animations: Vec<AnimationDescription>,
+}
+
+impl XmlReader {
+    pub fn new(content: &str, base_dir: &Path, frame: f64) -> XmlReader {
+        let text = Rc::new(Text {
+            content: String::from(content),
+            file: None,
+            dir: base_dir.to_path_buf(),
+        });
+        XmlReader {
+            sources: vec![Source::new(text.clone(), 0, None, Kind::Scene)],
+            defines: HashMap::new(),
+            variables: HashMap::new(),
+            fixed: Vec::new(),
+            buffer: Vec::new(),
+            frame,
+            open: Vec::new(),
+            place: Place { text, offset: 0 },
+            path: String::new(),
+            pending: None,
+            dir: base_dir.to_path_buf(),
+            keyed: None,
+            unclaimed: None,
+            targets: Vec::new(),
+            animations: Vec::new(),
+        }
+    }
+
+    //values replacing the ones of the <var> elements of the file
+    pub fn with_variables(mut self, variables: &[(String, f64)]) -> XmlReader {
+        for (name, value) in variables {
+            self.variables.insert(name.clone(), *value);
+            self.fixed.push(name.clone());
+        }
+        self
+    }
+
+    //location of the last element read
+    pub fn location(&self) -> Location {
+        self.location_at(&self.place, self.path.clone())
+    }
+
+    //directory of the file of the last element read
+    pub fn base_dir(&self) -> PathBuf {
+        self.place.text.dir.clone()
+    }
+
+    //file written in the last element read, from the directory of the scene
+    //file rather than the one of the included file
+    pub fn file_path(&self, file: &str) -> PathBuf {
+        let path = self.base_dir().join(file);
+        match path.strip_prefix(&self.dir) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => path,
+        }
+    }
+
+    //error about the last element read
+    pub fn unknown(&self, e: &BytesStart) -> SceneError {
+        unknown(e, &self.location())
+    }
+
+    //the empty element just read is read again as a start followed by its
+    //end, so that <instance object="a"/> is parsed like <instance object="a"></instance>
+    pub fn expand(&mut self, e: &BytesStart) {
+        self.open.push(element_name(e));
+        self.path = self.open_path();
+        let end = BytesEnd::new(element_name(e));
+        self.pending = Some((Event::End(end), self.place.clone()));
+    }
+
+    //the values read are in this field of the one being read (objects/2),
+    //until leave
+    pub fn enter(&mut self, target: String) {
+        self.targets.push(target);
+    }
+
+    pub fn leave(&mut self) {
+        self.targets.pop();
+    }
+
+    //Field of the description given by the last element read, which keeps
+    //its keys when it is animated. Each attribute gives the fields written
+    //with it: ("x", "x"), ("x", "axis/x"), or ("r", "") for the field itself.
+    pub fn animate(&mut self, field: &str, attributes: &[(&str, &str)]) -> Result<(), SceneError> {
+        let Some((interpolation, tracks, at)) = self.keyed.take() else {
+            return Ok(());
+        };
+        for (attribute, keys) in tracks {
+            let fields: Vec<&str> = attributes.iter().filter(|(a, _)| *a == attribute).map(|(_, f)| *f).collect();
+            if fields.is_empty() {
+                return Err(SceneError::InvalidValue {
+                    location: at,
+                    attribute,
+                    value: String::new(),
+                    message: String::from("it cannot be animated"),
+                });
+            }
+            for subfield in fields {
+                let path = self.targets.iter().map(String::as_str).chain([field, subfield]);
+                self.animations.push(AnimationDescription {
+                    target: path.filter(|p| !p.is_empty()).collect::<Vec<&str>>().join("/"),
+                    keys: keys.clone(),
+                    interpolation,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    //animations of the fields read, once the scene is read, an error when
+    //an element is animated but gives no field
+    pub fn animations(&mut self) -> Result<Vec<AnimationDescription>, SceneError> {
+        if let Some(at) = self.keyed.take().map(|(_, _, at)| at).or(self.unclaimed.take()) {
+            return Err(SceneError::UnknownElement {
+                location: at,
+                element: String::from("key"),
+            });
+        }
+        Ok(std::mem::take(&mut self.animations))
+    }
+
+    //next event, Eof only after the last element is closed
+    pub fn next(&mut self) -> Result<Event<'static>, SceneError> {
+        if let Some((_, _, at)) = self.keyed.take() {
+            self.unclaimed.get_or_insert(at);
+        }
+        let (event, place) = match self.pending.take() {
+            Some(pending) => pending,
+            None => self.read()?,
+        };
+        self.place = place;
+        match event {
+            Event::Start(e) => {
+                self.open.push(element_name(&e));
+                self.path = self.open_path();
+                //an element is animated when its first child is a key
+                let (next, next_place) = self.read()?;
+                match next {
+                    Event::Empty(key) if key.name().as_ref() == b"key" => {
+                        let element = self.read_keys(&e, key, &next_place)?;
+                        Ok(Event::Empty(element))
+                    }
+                    next => {
+                        self.pending = Some((next, next_place));
+                        Ok(Event::Start(e))
+                    }
+                }
+            }
+            Event::Empty(e) => {
+                self.path = self.child_path(&e);
+                Ok(Event::Empty(e))
+            }
+            Event::End(e) => {
+                self.path = self.open_path();
+                self.open.pop();
+                Ok(Event::End(e))
+            }
+            Event::Eof => match self.open.last() {
+                Some(name) => Err(self.syntax(format!("end of file before the end of <{}>", name))),
+                None => Ok(Event::Eof),
+            },
+            event => Ok(event),
+        }
+    }
+
+    //next event of the scene, with the included files and the defines in place
+    fn read(&mut self) -> Result<(Event<'static>, Place), SceneError> {
+        loop {
+            if self.source().finished() {
+                self.close();
+                continue;
+            }
+            let (event, place) = self.raw()?;
+            let event = match event {
+                Event::Start(e) => Event::Start(self.substitute(e, &place)?),
+                Event::Empty(e) => Event::Empty(self.substitute(e, &place)?),
+                event => event,
+            };
+            let included = self.sources.len() > 1;
+            let source = self.sources.last_mut().expect("the scene is read");
+            match event {
+                Event::Eof if included => {
+                    if let Some(name) = source.open.last() {
+                        let message = format!("end of file before the end of <{}>", name);
+                        return Err(self.syntax_at(&place, message));
+                    }
+                    self.close();
+                }
+                Event::Start(e) if source.is_included_root() => source.open.push(element_name(&e)),
+                Event::Empty(_) if source.is_included_root() => (),
+                Event::Start(e) => match source.take_override(&e) {
+                    Some(fragment) => {
+                        self.skip(&e, &place)?;
+                        self.sources.push(Source::fragment(&fragment, Kind::Override));
+                    }
+                    None => match e.name().as_ref() {
+                        b"include" => {
+                            let overrides = self.read_overrides(&e, &place)?;
+                            self.include(&e, overrides, &place)?
+                        }
+                        b"define" => {
+                            let start = self.source().position();
+                            let (end, _) = self.skip(&e, &place)?;
+                            self.define(&e, &place, start, end)?
+                        }
+                        b"repeat" => {
+                            let start = self.source().position();
+                            let (end, _) = self.skip(&e, &place)?;
+                            self.repeat(&e, &place, start, end)?
+                        }
+                        _ => {
+                            source.open.push(element_name(&e));
+                            return Ok((Event::Start(e), place));
+                        }
+                    },
+                },
+                Event::Empty(e) => match source.take_override(&e) {
+                    Some(fragment) => self.sources.push(Source::fragment(&fragment, Kind::Override)),
+                    None => match e.name().as_ref() {
+                        b"include" => self.include(&e, Vec::new(), &place)?,
+                        b"define" => self.define(&e, &place, place.offset, place.offset)?,
+                        b"use" => self.use_define(&e, &place)?,
+                        b"var" => self.var(&e, &place)?,
+                        b"repeat" => self.repeat(&e, &place, place.offset, place.offset)?,
+                        _ => return Ok((Event::Empty(e), place)),
+                    },
+                },
+                Event::End(e) => {
+                    source.open.pop();
+                    if !source.is_included_root() {
+                        return Ok((Event::End(e), place));
+                    }
+                }
+                event => return Ok((event, place)),
+            }
+        }
+    }
+
+    fn source(&self) -> &Source {
+        self.sources.last().expect("the scene is read")
+    }
+
+    //next event of the current source, as it is written
+    fn raw(&mut self) -> Result<(Event<'static>, Place), SceneError> {
+        let source = self.sources.last_mut().expect("the scene is read");
+        let offset = source.next_offset();
+        self.buffer.clear();
+        match source.reader.read_event_into(&mut self.buffer) {
+            Ok(event) => Ok((event.into_owned(), Place {
+                text: source.text.clone(),
+                offset,
+            })),
+            Err(e) => {
+                let place = Place {
+                    text: source.text.clone(),
+                    offset: source.start + source.reader.error_position() as usize,
+                };
+                Err(self.syntax_at(&place, e.to_string()))
+            }
+        }
+    }
+
+    //goes to the end of the element just started, returns where its end
+    //starts and finishes
+    fn skip(&mut self, e: &BytesStart, place: &Place) -> Result<(usize, usize), SceneError> {
+        let mut depth = 0;
+        loop {
+            let (event, end) = self.raw()?;
+            match event {
+                Event::Start(_) => depth += 1,
+                Event::End(_) if depth == 0 => return Ok((end.offset, self.source().position())),
+                Event::End(_) => depth -= 1,
+                Event::Eof => {
+                    let message = format!("end of file before the end of <{}>", element_name(e));
+                    return Err(self.syntax_at(place, message));
+                }
+                _ => (),
+            }
+        }
+    }
+
+    //the children of an include replace the elements of the included file
+    fn read_overrides(&mut self, e: &BytesStart, place: &Place) -> Result<Vec<Override>, SceneError> {
+        let mut overrides = Vec::new();
+        loop {
+            let (event, child) = self.raw()?;
+            let (element, end) = match event {
+                Event::Start(element) => {
+                    let (_, end) = self.skip(&element, &child)?;
+                    (element, end)
+                }
+                Event::Empty(element) => (element, self.source().position()),
+                Event::End(_) => return Ok(overrides),
+                Event::Eof => {
+                    let message = format!("end of file before the end of <{}>", element_name(e));
+                    return Err(self.syntax_at(place, message));
+                }
+                _ => continue,
+            };
+            overrides.push(Override {
+                element: element.name().as_ref().to_vec(),
+                name: name_attribute(&element),
+                fragment: Fragment {
+                    text: child.text,
+                    start: child.offset,
+                    end,
+                },
+            });
+        }
+    }
+
+    //the included file is resolved from the file that includes it
+    fn include(&mut self, e: &BytesStart, overrides: Vec<Override>, place: &Place) -> Result<(), SceneError> {
+        let at = self.location_at(place, self.child_path(e));
+        let Some(file) = attribute(e, b"file") else {
+            return Err(SceneError::MissingAttribute {
+                location: at,
+                attribute: String::from("file"),
+            });
+        };
+        let path = place.text.dir.join(&file);
+        let io = |e: std::io::Error| SceneError::Io {
+            location: Some(at.clone()),
+            message: format!("{}: {}", path.display(), e),
+        };
+        let content = fs::read_to_string(&path).map_err(io)?;
+        let canonical = fs::canonicalize(&path).map_err(io)?;
+        let included = |s: &Source| matches!(&s.kind, Kind::Include(p, _) if *p == canonical);
+        if self.sources.iter().any(included) {
+            return Err(SceneError::InvalidValue {
+                location: at,
+                attribute: String::from("file"),
+                value: file,
+                message: String::from("the file includes itself"),
+            });
+        }
+        let text = Rc::new(Text {
+            content,
+            file: Some(path.display().to_string().into()),
+            dir: path.parent().unwrap_or(Path::new(".")).to_path_buf(),
+        });
+        self.sources.push(Source::new(text, 0, None, Kind::Include(canonical, overrides)));
+        Ok(())
+    }
+
+    fn define(&mut self, e: &BytesStart, place: &Place, start: usize, end: usize) -> Result<(), SceneError> {
+        let name = self.require_name(e, place)?;
+        let text = place.text.clone();
+        self.defines.insert(name, Fragment { text, start, end });
+        Ok(())
+    }
+
+    fn use_define(&mut self, e: &BytesStart, place: &Place) -> Result<(), SceneError> {
+        let name = self.require_name(e, place)?;
+        let invalid = |message: &str| SceneError::InvalidValue {
+            location: self.location_at(place, self.child_path(e)),
+            attribute: String::from("name"),
+            value: name.clone(),
+            message: String::from(message),
+        };
+        let Some(fragment) = self.defines.get(&name) else {
+            return Err(invalid("there is no define with this name before"));
+        };
+        if self.sources.iter().any(|s| matches!(&s.kind, Kind::Define(n) if *n == name)) {
+            return Err(invalid("the define uses itself"));
+        }
+        let source = Source::fragment(fragment, Kind::Define(name));
+        self.sources.push(source);
+        Ok(())
+    }
+
+    fn var(&mut self, e: &BytesStart, place: &Place) -> Result<(), SceneError> {
+        let name = self.require_name(e, place)?;
+        let at = self.location_at(place, self.child_path(e));
+        let invalid = |attribute: &str, value: &str, message: String| SceneError::InvalidValue {
+            location: at.clone(),
+            attribute: String::from(attribute),
+            value: String::from(value),
+            message,
+        };
+        if !expression::is_name(&name) {
+            let message = String::from("it must be made of letters, digits and _");
+            return Err(invalid("name", &name, message));
+        }
+        let Some(value) = attribute(e, b"value") else {
+            return Err(SceneError::MissingAttribute {
+                location: at,
+                attribute: String::from("value"),
+            });
+        };
+        let number = expression::evaluate(&value, &self.variables).map_err(|message| invalid("value", &value, message))?;
+        if !self.fixed.contains(&name) {
+            self.variables.insert(name, number);
+        }
+        Ok(())
+    }
+
+    fn repeat(&mut self, e: &BytesStart, place: &Place, start: usize, end: usize) -> Result<(), SceneError> {
+        let at = self.location_at(place, self.child_path(e));
+        let invalid = |attribute: &str, value: &str, message: String| SceneError::InvalidValue {
+            location: at.clone(),
+            attribute: String::from(attribute),
+            value: String::from(value),
+            message,
+        };
+        let Some(value) = attribute(e, b"count") else {
+            return Err(SceneError::MissingAttribute {
+                location: at,
+                attribute: String::from("count"),
+            });
+        };
+        let number = expression::evaluate(&value, &self.variables).map_err(|message| invalid("count", &value, message))?;
+        if number < 0. || number.fract() != 0. {
+            return Err(invalid("count", &value, String::from("it must be a whole number")));
+        }
+        let var = attribute(e, b"var");
+        if let Some(name) = var.as_ref().filter(|name| !expression::is_name(name)) {
+            let message = String::from("it must be made of letters, digits and _");
+            return Err(invalid("var", name, message));
+        }
+        if number == 0. || start == end {
+            return Ok(());
+        }
+        let repeat = Repeat {
+            fragment: Fragment {
+                text: place.text.clone(),
+                start,
+                end,
+            },
+            outer: var.as_ref().and_then(|name| self.variables.get(name).copied()),
+            var,
+            index: 0,
+            count: number as usize,
+        };
+        self.pass(repeat);
+        Ok(())
+    }
+
+    //reads the children of a repeat once more
+    fn pass(&mut self, repeat: Repeat) {
+        if let Some(name) = &repeat.var {
+            self.variables.insert(name.clone(), repeat.index as f64);
+        }
+        let fragment = repeat.fragment.clone();
+        self.sources.push(Source::fragment(&fragment, Kind::Repeat(repeat)));
+    }
+
+    //attributes with their variables replaced
+    fn substitute(&self, e: BytesStart<'static>, place: &Place) -> Result<BytesStart<'static>, SceneError> {
+        if !e.attributes_raw().windows(2).any(|w| w == b"${") {
+            return Ok(e);
+        }
+        let at = self.location_at(place, self.child_path(&e));
+        let mut element = BytesStart::new(element_name(&e));
+        for (key, value) in attributes(&e, &at)? {
+            let replaced = expression::substitute(&value, &self.variables).map_err(|message| SceneError::InvalidValue {
+                location: at.clone(),
+                attribute: key.clone(),
+                value: value.clone(),
+                message,
+            })?;
+            element.push_attribute((key.as_str(), replaced.as_str()));
+        }
+        Ok(element)
+    }
+
+    fn require_name(&self, e: &BytesStart, place: &Place) -> Result<String, SceneError> {
+        attribute(e, b"name").ok_or_else(|| SceneError::MissingAttribute {
+            location: self.location_at(place, self.child_path(e)),
+            attribute: String::from("name"),
+        })
+    }
+
+    //end of the current source: the elements of an include that replaced
+    //nothing are read after the included file, a repeat goes on until its
+    //last pass
+    fn close(&mut self) {
+        match self.sources.pop().map(|source| source.kind) {
+            Some(Kind::Include(_, overrides)) => {
+                let sources = overrides.iter().rev().map(|o| Source::fragment(&o.fragment, Kind::Override));
+                self.sources.extend(sources);
+            }
+            Some(Kind::Repeat(mut repeat)) if repeat.index + 1 < repeat.count => {
+                repeat.index += 1;
+                self.pass(repeat);
+            }
+            Some(Kind::Repeat(Repeat { var: Some(name), outer, .. })) => {
+                self.variables.remove(&name);
+                if let Some(value) = outer {
+                    self.variables.insert(name, value);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    //the keys of an animated element, up to its end, give its attributes
+    fn read_keys(
+        &mut self,
+        element: &BytesStart,
+        first_key: BytesStart<'static>,
+        first_place: &Place,
+    ) -> Result<BytesStart<'static>, SceneError> {
+        let (place, path) = (self.place.clone(), self.path.clone());
+        let at = self.location();
+        let mut keys = vec![(first_key, self.key_location(first_place))];
+        loop {
+            let (event, key_place) = self.read()?;
+            self.place = key_place;
+            match event {
+                Event::Empty(e) if e.name().as_ref() == b"key" => {
+                    keys.push((e, self.key_location(&self.place)))
+                }
+                Event::End(_) => break,
+                Event::Start(e) | Event::Empty(e) => {
+                    self.path = self.child_path(&e);
+                    return Err(self.unknown(&e));
+                }
+                Event::Eof => return Err(self.syntax(format!("end of file before the end of {}", at.path))),
+                _ => (),
+            }
+        }
+        (self.place, self.path) = (place, path);
+        self.open.pop();
+        let (element, interpolation, tracks) = animate(element, &keys, self.frame, &at)?;
+        self.keyed = Some((interpolation, tracks, at));
+        Ok(element)
+    }
+
+    fn key_location(&self, place: &Place) -> Location {
+        self.location_at(place, format!("{}/key", self.open_path()))
+    }
+
+    fn location_at(&self, place: &Place, path: String) -> Location {
+        Location {
+            file: place.text.file.clone(),
+            ..Location::at(&place.text.content, place.offset, path)
+        }
+    }
+
+    fn open_path(&self) -> String {
+        self.open.join("/")
+    }
+
+    fn child_path(&self, e: &BytesStart) -> String {
+        let name = element_name(e);
+        match self.open.is_empty() {
+            true => name,
+            false => format!("{}/{}", self.open_path(), name),
+        }
+    }
+
+    fn syntax(&self, message: String) -> SceneError {
+        SceneError::Syntax {
+            location: self.location(),
+            message,
+        }
+    }
+
+    fn syntax_at(&self, place: &Place, message: String) -> SceneError {
+        SceneError::Syntax {
+            location: self.location_at(place, self.open_path()),
+            message,
+        }
+    }
+}
+
+pub fn element_name(e: &BytesStart) -> String {
+    String::from_utf8_lossy(e.name().as_ref()).into_owned()
+}
+
+fn attribute(e: &BytesStart, name: &[u8]) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|a| a.key.as_ref() == name)
+        .map(|a| match a.unescape_value() {
+            Ok(value) => value.into_owned(),
+            Err(_) => String::from_utf8_lossy(&a.value).into_owned(),
+        })
+}
+
+fn name_attribute(e: &BytesStart) -> Option<String> {
+    attribute(e, b"name")
+}
+
+pub fn unknown(e: &BytesStart, at: &Location) -> SceneError {
+    SceneError::UnknownElement {
+        location: at.clone(),
+        element: element_name(e),
+    }
+}
+
+//names and values of the attributes of an element, with &amp; and the
+//other entities replaced
+pub fn attributes(e: &BytesStart, at: &Location) -> Result<Vec<(String, String)>, SceneError> {
+    let syntax = |message: String| SceneError::Syntax {
+        location: at.clone(),
+        message,
+    };
+    let mut attributes = Vec::new();
+    for a in e.attributes() {
+        let a = a.map_err(|e| syntax(e.to_string()))?;
+        let value = a.unescape_value().map_err(|e| syntax(e.to_string()))?;
+        attributes.push((String::from_utf8_lossy(a.key.as_ref()).into_owned(), value.into_owned()));
+    }
+    Ok(attributes)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn element_locations() {
+        let mut reader = XmlReader::new("<scene>\n  <camera>\n    <pos x=\"1\"/>\n  </camera>\n</scene>", Path::new("."), 0.);
+        reader.next().unwrap();
+        reader.next().unwrap();
+        assert_eq!(reader.location().to_string(), "line 2, column 3 (scene/camera)");
+        reader.next().unwrap();
+        assert_eq!(reader.location().to_string(), "line 3, column 5 (scene/camera/pos)");
+        reader.next().unwrap();
+        assert_eq!(reader.location().path, "scene/camera");
+        reader.next().unwrap();
+        assert_eq!(reader.next(), Ok(Event::Eof));
+    }
+
+    #[test]
+    fn unclosed_element() {
+        let mut reader = XmlReader::new("<scene>\n<camera>", Path::new("."), 0.);
+        reader.next().unwrap();
+        reader.next().unwrap();
+        let Err(SceneError::Syntax { location, .. }) = reader.next() else {
+            panic!("syntax error expected")
+        };
+        assert_eq!(location.path, "scene/camera");
+    }
+
+    #[test]
+    fn mismatched_end() {
+        //found when reading ahead of the camera
+        let mut reader = XmlReader::new("<scene>\n<camera></scene>", Path::new("."), 0.);
+        reader.next().unwrap();
+        let Err(SceneError::Syntax { location, .. }) = reader.next() else {
+            panic!("syntax error expected")
+        };
+        assert_eq!(location.line, 2);
+    }
+
+    #[test]
+    fn animated_element() {
+        let mut reader = XmlReader::new(
+            "<camera>
+            <pos x=\"0\" y=\"5\" z=\"1\"><key frame=\"0\" x=\"0\"/><key frame=\"10\" x=\"4\" z=\"3\"/></pos>
+            <dir x=\"1\" y=\"0\" z=\"0\"/>
+            </camera>",
+            Path::new("."),
+            5.,
+        );
+        reader.next().unwrap();
+        let Ok(Event::Empty(pos)) = reader.next() else {
+            panic!("animated pos expected")
+        };
+        assert_eq!(pos.name().as_ref(), b"pos");
+        assert_eq!(
+            attributes(&pos, &reader.location()).unwrap(),
+            vec![
+                (String::from("x"), String::from("2")),
+                (String::from("y"), String::from("5")),
+                (String::from("z"), String::from("3")),
+            ]
+        );
+        assert_eq!(reader.location().to_string(), "line 2, column 13 (camera/pos)");
+        let Ok(Event::Empty(dir)) = reader.next() else {
+            panic!("dir expected")
+        };
+        assert_eq!(dir.name().as_ref(), b"dir");
+        assert!(matches!(reader.next(), Ok(Event::End(_))));
+    }
+
+    //elements read, with their name attribute, up to the end of the file
+    fn elements(reader: &mut XmlReader) -> Result<Vec<String>, SceneError> {
+        let mut elements = Vec::new();
+        loop {
+            match reader.next()? {
+                Event::Start(e) | Event::Empty(e) => match name_attribute(&e) {
+                    Some(name) => elements.push(format!("{}:{}", element_name(&e), name)),
+                    None => elements.push(element_name(&e)),
+                },
+                Event::End(e) => elements.push(format!("/{}", String::from_utf8_lossy(e.name().as_ref()))),
+                Event::Eof => return Ok(elements),
+                _ => (),
+            }
+        }
+    }
+
+    #[test]
+    fn defines() {
+        let content = "<scene>
+            <define name=\"lamp\"><sphere/><point_light/></define>
+            <group><use name=\"lamp\"/></group>
+            <use name=\"lamp\"/>
+            </scene>";
+        let mut reader = XmlReader::new(content, Path::new("."), 0.);
+        assert_eq!(
+            elements(&mut reader).unwrap(),
+            ["scene", "group", "sphere", "point_light", "/group", "sphere", "point_light", "/scene"]
+        );
+
+        let mut unknown = XmlReader::new("<scene><use name=\"lamp\"/></scene>", Path::new("."), 0.);
+        assert!(matches!(
+            elements(&mut unknown),
+            Err(SceneError::InvalidValue { ref value, .. }) if value == "lamp"
+        ));
+        let recursive = "<scene><define name=\"a\"><use name=\"a\"/></define><use name=\"a\"/></scene>";
+        let Err(SceneError::InvalidValue { location, message, .. }) =
+            elements(&mut XmlReader::new(recursive, Path::new("."), 0.))
+        else {
+            panic!("recursive define expected")
+        };
+        assert_eq!(message, "the define uses itself");
+        assert_eq!(location.path, "scene/use");
+    }
+
+    #[test]
+    fn variables() {
+        let content = "<scene>
+            <var name=\"r\" value=\"2\"/><var name=\"n\" value=\"${r} * 3\"/>
+            <sphere name=\"ball${n}\" x=\"${r} / 4\" y=\"n + 1\"/>
+            </scene>";
+        let sphere = |variables: &[(String, f64)]| {
+            let mut reader = XmlReader::new(content, Path::new("."), 0.).with_variables(variables);
+            reader.next().unwrap();
+            let Ok(Event::Empty(sphere)) = reader.next() else {
+                panic!("sphere expected")
+            };
+            attributes(&sphere, &reader.location()).unwrap()
+        };
+        let value = |s: &str| String::from(s);
+        assert_eq!(
+            sphere(&[]),
+            vec![(value("name"), value("ball6")), (value("x"), value("0.5")), (value("y"), value("n + 1"))]
+        );
+        //given apart, the variable keeps its value
+        assert_eq!(sphere(&[(value("r"), 1.)])[0], (value("name"), value("ball3")));
+
+        let error = |content: &str| elements(&mut XmlReader::new(content, Path::new("."), 0.)).unwrap_err();
+        let SceneError::InvalidValue { message, location, .. } = error("<scene><sphere x=\"${r}\"/></scene>") else {
+            panic!("invalid value expected")
+        };
+        assert_eq!(message, "unknown variable r");
+        assert_eq!(location.path, "scene/sphere");
+        assert!(matches!(
+            error("<scene><var name=\"2r\" value=\"1\"/></scene>"),
+            SceneError::InvalidValue { ref attribute, .. } if attribute == "name"
+        ));
+        assert!(matches!(
+            error("<scene><var name=\"r\"/></scene>"),
+            SceneError::MissingAttribute { ref attribute, .. } if attribute == "value"
+        ));
+    }
+
+    #[test]
+    fn repeats() {
+        let content = "<scene>
+            <var name=\"i\" value=\"7\"/>
+            <repeat count=\"2\" var=\"i\">
+                <repeat count=\"${i + 1}\" var=\"j\"><sphere name=\"${i}${j}\"/></repeat>
+                <group/>
+            </repeat>
+            <repeat count=\"0\"><plane/></repeat>
+            <repeat count=\"1\"/>
+            <point_light name=\"${i}\"/>
+            </scene>";
+        let mut reader = XmlReader::new(content, Path::new("."), 0.);
+        assert_eq!(
+            elements(&mut reader).unwrap(),
+            ["scene", "sphere:00", "group", "sphere:10", "sphere:11", "group", "point_light:7", "/scene"]
+        );
+
+        let error = |content: &str| elements(&mut XmlReader::new(content, Path::new("."), 0.)).unwrap_err();
+        let SceneError::InvalidValue { message, location, .. } = error("<scene><repeat count=\"2.5\"><plane/></repeat></scene>") else {
+            panic!("invalid count expected")
+        };
+        assert_eq!(message, "it must be a whole number");
+        assert_eq!(location.path, "scene/repeat");
+        assert!(matches!(
+            error("<scene><repeat count=\"2\" var=\"a-b\"><plane/></repeat></scene>"),
+            SceneError::InvalidValue { ref attribute, .. } if attribute == "var"
+        ));
+        assert!(matches!(
+            error("<scene><repeat var=\"i\"><plane/></repeat></scene>"),
+            SceneError::MissingAttribute { ref attribute, .. } if attribute == "count"
+        ));
+        //the variable is only known in the repeat
+        assert!(matches!(
+            error("<scene><repeat count=\"1\" var=\"k\"><plane/></repeat><plane x=\"${k}\"/></scene>"),
+            SceneError::InvalidValue { ref message, .. } if message == "unknown variable k"
+        ));
+    }
+
+    #[test]
+    fn included_file() {
+        let dir = std::env::temp_dir();
+        fs::write(
+            dir.join("raytracer_xml_studio.xml"),
+            "<scene>\n<camera/>\n<point_light name=\"key\"><color/></point_light>\n<point_light name=\"fill\"/>\n</scene>",
+        )
+        .unwrap();
+        let content = "<scene>
+            <include file=\"raytracer_xml_studio.xml\">
+                <point_light name=\"fill\"><intensity/></point_light>
+                <sphere/>
+            </include>
+            <plane/>
+            </scene>";
+        let mut reader = XmlReader::new(content, &dir, 0.);
+        reader.next().unwrap();
+        reader.next().unwrap();
+        let location = reader.location();
+        assert!(location.file.unwrap().ends_with("raytracer_xml_studio.xml"));
+        assert_eq!((location.line, location.path.as_str()), (2, "scene/camera"));
+        assert_eq!(reader.base_dir(), dir);
+        assert_eq!(reader.file_path("wood.png"), Path::new("wood.png"));
+        assert_eq!(
+            elements(&mut reader).unwrap(),
+            [
+                "point_light:key",
+                "color",
+                "/point_light",
+                "point_light:fill",
+                "intensity",
+                "/point_light",
+                "sphere",
+                "plane",
+                "/scene"
+            ]
+        );
+    }
+
+    #[test]
+    fn include_errors() {
+        let dir = std::env::temp_dir();
+        fs::write(
+            dir.join("raytracer_xml_cycle.xml"),
+            "<scene><include file=\"raytracer_xml_cycle.xml\"/></scene>",
+        )
+        .unwrap();
+        let mut reader = XmlReader::new("<scene><include file=\"raytracer_xml_cycle.xml\"/></scene>", &dir, 0.);
+        let Err(SceneError::InvalidValue { location, message, .. }) = elements(&mut reader) else {
+            panic!("include cycle expected")
+        };
+        assert_eq!(message, "the file includes itself");
+        assert!(location.file.unwrap().ends_with("raytracer_xml_cycle.xml"));
+
+        let mut reader = XmlReader::new("<scene><include file=\"raytracer_missing.xml\"/></scene>", &dir, 0.);
+        assert!(matches!(elements(&mut reader), Err(SceneError::Io { location: Some(_), .. })));
+    }
+}
@@ -9,7 +9,7 @@ use crate::coord::Vec3d;
 use super::camera::Ray;
 use super::light::LightType;
 use super::object::material::Color;
-use super::object::{Intersect, Object};
+use super::object::Intersect;
 use super::Scene;
 use super::MAX_BOUNCES;
 
@@ -21,6 +21,11 @@ const NB_ITER: usize = 100;
 
 const NB_WORKERS: usize = 4;
 
+const CLEARCOAT_IOR: f64 = 1.5;
+const CLEARCOAT_ROUGHNESS: f64 = 0.03;
+//avoids hitting the surface the ray has just gone through
+const TRANSMISSION_OFFSET: f64 = 1e-9;
+
 impl Scene{
     pub fn render_rayon(&self) -> Vec<f64> {
 
@@ -95,26 +100,33 @@ impl Scene{
 
         let c = (c as f64) + rand::random::<f64>() - 0.5;
         let l = (l as f64) + rand::random::<f64>() - 0.5;
+        //each sample is taken at a random time while the shutter is open
+        let time = self.camera.shutter_time(rand::random::<f64>());
     
-        let ray = self.camera.ray((c, l));
+        let ray = self.camera.ray((c, l), time);
     
         self.send_ray(ray, MAX_BOUNCES)
     }
 
-    fn compute_diffuse(&self, i: &Intersect) -> Color{
+    fn compute_diffuse(&self, ray: Ray, i: &Intersect) -> Color{
+        i.material.diffuse * self.compute_irradiance(i, ray.time)
+    }
+
+    //light received at the intersection point, ambiant included,
+    //at the time of the ray that found the point
+    fn compute_irradiance(&self, i: &Intersect, time: f64) -> Color{
     
-        let mut color =
-            i.material.diffuse * self.ambiant_light.color * self.ambiant_light.intensity;
+        let mut color = self.ambiant_light.color * self.ambiant_light.intensity;
     
         for light in self.lights.iter() {
             if let LightType::PointLight { pos: light_pos } = light.light_type {
                 let light_dir = (i.pos - light_pos).normalize().unwrap();
-                let light_ray = Ray{start: light_pos, dir: light_dir};
+                let light_ray = Ray{start: light_pos, dir: light_dir, time};
                 let light_intersect = self.get_intersect(light_ray);
                 let dist_light = (light_pos - i.pos).norm();
     
                 //Intersect sooner
-                if let Some(Intersect{pos:_, dist: d, normal:_, material:_}) = light_intersect {
+                if let Some(Intersect{dist: d, ..}) = light_intersect {
                     if d < dist_light - 1e-9 {
                         continue;
                     }
@@ -126,7 +138,7 @@ impl Scene{
                 let factor = i.normal.dot(light_pos - i.pos);
                 if factor > 0. {
                     color = color
-                        + i.material.diffuse * light.color * light.intensity * (factor/(dist_light*dist_light));
+                        + light.color * light.intensity * (factor/(dist_light*dist_light));
                 }
             }
         }
@@ -135,9 +147,13 @@ impl Scene{
     
     
     fn compute_reflection(&self, ray: Ray, i: &Intersect, depth: u16) -> Color{
+        self.reflect(ray, i, i.material.roughness, depth)*i.material.specular
+    }
+
+    fn reflect(&self, ray: Ray, i: &Intersect, roughness: f64, depth: u16) -> Color{
         
         
-        let r = rand::random::<f64>() * i.material.roughness * PI / 2.;
+        let r = rand::random::<f64>() * roughness * PI / 2.;
         let alpha = rand::random::<f64>() * 2. * PI;
     
         let dir = ray.dir.symmetry(i.normal) * -1.;
@@ -160,13 +176,35 @@ impl Scene{
     
         let dir = r.cos()*dir + r.sin()*norm;
     
-        let symmetric_ray = Ray{start:i.pos, dir};
+        let symmetric_ray = Ray{start:i.pos, dir, time: ray.time};
     
-        self.send_ray(symmetric_ray, depth-1)*i.material.specular
+        self.send_ray(symmetric_ray, depth-1)
+    }
+
+    //grazing-angle retro-reflection of cloth-like surfaces
+    fn compute_sheen(&self, ray: Ray, i: &Intersect) -> Color{
+        let cos_view = ray.dir.dot(i.normal).abs();
+        self.compute_irradiance(i, ray.time) * i.material.sheen * (1. - cos_view).powi(5)
+    }
+
+    //thin glossy dielectric layer on top of the base material
+    fn compute_clearcoat(&self, ray: Ray, i: &Intersect, depth: u16) -> Color{
+        let cos_view = ray.dir.dot(i.normal).abs();
+        let fresnel = schlick(reflectance(CLEARCOAT_IOR), cos_view);
+        self.reflect(ray, i, CLEARCOAT_ROUGHNESS, depth) * (i.material.clearcoat * fresnel)
+    }
+
+    //transmission is thin-walled: the ray goes on unbent through the surface,
+    //the index of refraction only drives the fresnel term
+    fn compute_transmission(&self, ray: Ray, i: &Intersect, depth: u16) -> Color{
+        let cos_view = ray.dir.dot(i.normal).abs();
+        let fresnel = schlick(reflectance(i.material.ior), cos_view);
+        let through_ray = Ray{start: i.pos + ray.dir * TRANSMISSION_OFFSET, dir: ray.dir, time: ray.time};
+        self.send_ray(through_ray, depth-1) * i.material.transmission * (1. - fresnel)
     }
     
     fn compute_specular(&self, ray: Ray, i: &Intersect) -> Color{
-        let symmetric_ray = Ray{start:i.pos, dir:ray.dir.symmetry(i.normal) * -1.};
+        let symmetric_ray = Ray{start:i.pos, dir:ray.dir.symmetry(i.normal) * -1., time: ray.time};
         //let mut color = Color{r:0., g:0., b:0.};
         let mut color = i.material.specular * self.ambiant_light.color * self.ambiant_light.intensity;
     
@@ -174,12 +212,12 @@ impl Scene{
         for light in self.lights.iter() {
             if let LightType::PointLight { pos: light_pos } = light.light_type {
                 let light_dir = (i.pos - light_pos).normalize().unwrap();
-                let light_ray = Ray{start: light_pos, dir: light_dir};
+                let light_ray = Ray{start: light_pos, dir: light_dir, time: ray.time};
                 let light_intersect = self.get_intersect(light_ray);
                 let dist_light = (light_pos - i.pos).norm();
     
                 //Intersect sooner
-                if let Some(Intersect{pos:_, dist: d, normal:_, material:_}) = light_intersect {
+                if let Some(Intersect{dist: d, ..}) = light_intersect {
                     if d < dist_light - 1e-9 {
                         continue;
                     }
@@ -209,21 +247,12 @@ impl Scene{
         let mut intersect_dist: f64 = f64::INFINITY;
     
         for object in self.objects.iter() {
-            match object {
-                Object::Sphere(s) => match s.intersect(&ray) {
-                    Some(i) if i.dist < intersect_dist => {
-                        intersect_dist = i.dist;
-                        ray_intersect = Some(i);
-                    }
-                    _ => (),
-                },
-                Object::Rasterized(r) => match r.intersect(&ray){
-                    Some(i) if i.dist < intersect_dist => {
-                        intersect_dist = i.dist;
-                        ray_intersect = Some(i);
-                    }
-                    _ => (),
-                },
+            match object.intersect(&ray) {
+                Some(i) if i.dist < intersect_dist => {
+                    intersect_dist = i.dist;
+                    ray_intersect = Some(i);
+                }
+                _ => (),
             }
         }
     
@@ -238,15 +267,21 @@ impl Scene{
     
         let ray_intersect = self.get_intersect(ray);
     
-        match ray_intersect {
-            Some(i)=>{
-                color = self.compute_diffuse(&i) * (1. - i.material.reflectivity);
-                if i.material.reflectivity > 0. {
-                    color = color + self.compute_reflection(ray, &i, depth) * i.material.reflectivity;
-                    color = color + self.compute_specular(ray, &i) * i.material.reflectivity;
-                }
-            }, 
-            _ => (),
+        if let Some(i) = ray_intersect {
+            color = self.compute_diffuse(ray, &i) * (1. - i.material.reflectivity);
+            if i.material.reflectivity > 0. {
+                color = color + self.compute_reflection(ray, &i, depth) * i.material.reflectivity;
+                color = color + self.compute_specular(ray, &i) * i.material.reflectivity;
+            }
+            if i.material.sheen > 0. {
+                color = color + self.compute_sheen(ray, &i);
+            }
+            if i.material.clearcoat > 0. {
+                color = color + self.compute_clearcoat(ray, &i, depth);
+            }
+            if !i.material.transmission.is_black() {
+                color = color + self.compute_transmission(ray, &i, depth);
+            }
         }
     
         color
@@ -256,12 +291,21 @@ impl Scene{
     
     fn normalize(data: Vec<f64>) -> Vec<f64> {
         let max_intensity = data.iter().cloned().fold(f64::NAN, f64::max);
-        data.iter().map(|v| (*v / max_intensity)).collect()
+        data.iter().map(|v| *v / max_intensity).collect()
     }
 
 }
 
 
+//reflectance at normal incidence of a dielectric
+fn reflectance(ior: f64) -> f64 {
+    ((ior - 1.) / (ior + 1.)).powi(2)
+}
+
+//Schlick's approximation of the fresnel factor
+fn schlick(f0: f64, cos_theta: f64) -> f64 {
+    f0 + (1. - f0) * (1. - cos_theta).powi(5)
+}
 
 #[cfg(test)]
 mod tests {
@@ -275,7 +319,7 @@ mod tests {
         assert_eq!(Scene::normalize(vec), vec![0., 1., 0.5]);
     }
 
-    use crate::scene::{camera::Camera, light::Light, object::{material::Material, sphere::Sphere}};
+    use crate::scene::{camera::Camera, light::Light, object::{material::Material, sphere::Sphere, Object}};
 
     fn create_empty_scene() -> Scene{
         let camera = Camera::new(
@@ -291,8 +335,8 @@ mod tests {
             light_type: LightType::AmbiantLight,
         };
         Scene { 
-            camera: camera, 
-            ambiant_light: ambiant_light, 
+            camera, 
+            ambiant_light, 
             lights: vec![], 
             objects: vec![],
         }
@@ -325,6 +369,30 @@ mod tests {
         assert_eq!(colors[2], 0.);
     }
 
+    #[test]
+    fn test_fresnel(){
+        assert_eq!(reflectance(1.), 0.);
+        assert!((reflectance(1.5) - 0.04).abs() < 1e-12);
+        assert_eq!(schlick(0.04, 1.), 0.04);
+        assert_eq!(schlick(0.04, 0.), 1.);
+    }
+
+    #[test]
+    fn test_transmission(){
+        let mut scene = create_empty_scene();
+        let mut glass = Material::default();
+        glass.transmission = Color { r: 1., g: 1., b: 1. };
+        scene.objects.push(
+            Object::Sphere(Sphere::new(Vec3d { x: 3., y: 0., z: 0. }, 1., glass))
+        );
+        let ray = Ray{start: Vec3d { x: 0., y: 0., z: 0. }, dir: Vec3d { x: 1., y: 0., z: 0. }, time: 0.};
+        let color = scene.send_ray(ray, MAX_BOUNCES);
+        //red ambiant light seen through the sphere, minus the fresnel reflection
+        assert!(color.r > 0.9);
+        assert_eq!(color.g, 0.);
+        assert_eq!(color.b, 0.);
+    }
+
     #[test]
     fn test_diffuse(){
         let mut scene = create_empty_scene();
@@ -339,19 +407,19 @@ mod tests {
         sphere_material.diffuse.g = 1.;
         sphere_material.reflectivity = 0.;
         scene.objects.push(
-            Object::Sphere(Sphere{
-                center: Vec3d { x: 1., y: 0., z: 0. },
-                material: sphere_material.clone(),
-                radius: 1.,
-            })
+            Object::Sphere(Sphere::new(Vec3d { x: 1., y: 0., z: 0. }, 1., sphere_material.clone()))
         );
         let intersect = Intersect{
             dist: 1.,
             pos: Vec3d { x: 0., y: 0., z: 0. },
             normal: Vec3d { x: 1., y: 0., z: 0. },
-            material: sphere_material.clone(),
+            tangent: Vec3d { x: 0., y: 1., z: 0. },
+            bitangent: Vec3d { x: 0., y: 0., z: 1. },
+            uv: (0., 0.),
+            material: std::sync::Arc::new(sphere_material.clone()),
         };
-        let color = scene.compute_diffuse(&intersect);
+        let ray = Ray{start: Vec3d { x: -1., y: 0., z: 0. }, dir: Vec3d { x: 1., y: 0., z: 0. }, time: 0.};
+        let color = scene.compute_diffuse(ray, &intersect);
         assert_eq!(color.r, 0.);
         assert_eq!(color.g, 0.);
         assert_eq!(color.b, 0.);
@@ -0,0 +1,431 @@
+use std::fmt;
+use std::path::Path;
+
+use quick_xml::events::{BytesStart, Event};
+
+use super::error::{Location, SceneError};
+use super::light::LightType;
+use super::object::rasterized::polygon::triangulate;
+use super::object::texture::procedural::MAX_OCTAVES;
+use super::parser;
+use super::parser::xml::{self, XmlReader};
+use crate::coord::Vec3d;
+
+//attributes given to textured channels
+const TEXTURE: &[&str] = &["texture", "scale", "octaves", "wrap", "filter"];
+
+//Attributes of each element, whatever its parent (a sphere can be a shape
+//or an sdf primitive). Textured channels also take the TEXTURE attributes.
+const ELEMENTS: &[(&str, &[&str])] = &[
+    ("scene", &["cull_back_faces"]),
+    ("camera", &[]),
+    ("pos", &["x", "y", "z", "u", "v", "nx", "ny", "nz"]),
+    ("dir", &["x", "y", "z"]),
+    ("shutter", &["open", "close"]),
+    ("up", &["x", "y", "z"]),
+    ("resolution", &["width", "height"]),
+    ("fov", &["angle"]),
+    ("point_light", &["name"]),
+    ("ambiant_light", &["name"]),
+    ("color", &["r", "g", "b"]),
+    ("intensity", &["i"]),
+    ("library", &[]),
+    ("group", &["name", "material"]),
+    ("material", &["name", "type"]),
+    ("diffuse", &["r", "g", "b", "r2", "g2", "b2"]),
+    ("specular", &["r", "g", "b", "r2", "g2", "b2", "s"]),
+    ("transmission", &["r", "g", "b", "r2", "g2", "b2", "t"]),
+    ("reflectivity", &["r", "r2"]),
+    ("roughness", &["r", "r2"]),
+    ("sheen", &["s", "s2"]),
+    ("clearcoat", &["c", "c2"]),
+    ("ior", &["i", "i2"]),
+    ("normal_map", &[]),
+    ("bump", &["strength"]),
+    ("base_color", &["r", "g", "b"]),
+    ("metallic", &["m"]),
+    ("sphere", &["name", "material", "x", "y", "z", "r"]),
+    ("plane", &["name", "material"]),
+    ("disk", &["name", "material"]),
+    ("box", &["name", "material", "x", "y", "z", "sx", "sy", "sz", "round"]),
+    ("cylinder", &["name", "material", "x", "y", "z", "r", "h"]),
+    ("cone", &["name", "material"]),
+    ("torus", &["name", "material", "x", "y", "z", "r", "t"]),
+    ("heightfield", &["name", "material", "file"]),
+    ("object", &["name", "material", "double_sided"]),
+    ("face", &[]),
+    ("instance", &["name", "material", "object"]),
+    ("csg", &["name", "material", "op"]),
+    ("sdf", &["name", "material"]),
+    ("radius", &["r"]),
+    ("top_radius", &["r"]),
+    ("tube", &["r"]),
+    ("velocity", &["x", "y", "z"]),
+    ("normal", &["x", "y", "z"]),
+    ("axis", &["x", "y", "z"]),
+    ("top", &["x", "y", "z"]),
+    ("min", &["x", "y", "z"]),
+    ("max", &["x", "y", "z"]),
+    ("size", &["x", "y", "z"]),
+    ("translate", &["x", "y", "z", "k"]),
+    ("rotate", &["x", "y", "z", "angle"]),
+    ("scale", &["x", "y", "z", "s"]),
+    ("motion", &[]),
+    ("keyframe", &["time"]),
+    ("union", &["k"]),
+    ("intersection", &["k"]),
+    ("difference", &["k"]),
+    ("twist", &["rate", "k"]),
+    ("repetition", &["x", "y", "z", "k"]),
+];
+
+const TEXTURED: &[&str] = &[
+    "diffuse",
+    "specular",
+    "transmission",
+    "reflectivity",
+    "roughness",
+    "sheen",
+    "clearcoat",
+    "ior",
+    "normal_map",
+    "bump",
+];
+
+//Something that can be rendered, but probably not as expected
+#[derive(Debug, PartialEq)]
+pub struct Warning {
+    pub location: Location,
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+//Warnings about a scene, and the error that prevents loading it
+#[derive(Debug)]
+pub struct Validation {
+    pub warnings: Vec<Warning>,
+    pub error: Option<SceneError>,
+}
+
+//Checks a scene description without rendering it, the variables given
+//replace the ones of the file
+pub fn validate(content: &str, base_dir: &Path, variables: &[(String, f64)]) -> Validation {
+    let mut warnings = Vec::new();
+    let lights = match check_elements(content, base_dir, variables, &mut warnings) {
+        Ok(lights) => lights,
+        Err(error) => {
+            return Validation {
+                warnings,
+                error: Some(error),
+            }
+        }
+    };
+    let scene = match parser::load_from_xml_string(content, base_dir, 0., variables) {
+        Ok(scene) => scene,
+        Err(error) => {
+            //a warning with the same message is the error
+            warnings.retain(|w| Some(&w.location) != error.location() || !error.to_string().ends_with(&w.message));
+            return Validation {
+                warnings,
+                error: Some(error),
+            };
+        }
+    };
+
+    let point_lights = scene.lights.iter().filter_map(|l| match l.light_type {
+        LightType::PointLight { pos } => Some(pos),
+        LightType::AmbiantLight => None,
+    });
+    for (pos, location) in point_lights.zip(lights) {
+        if scene.objects.iter().any(|o| o.contains(pos)) {
+            warnings.push(Warning {
+                location,
+                message: String::from("the light is inside a closed object, which hides it"),
+            });
+        }
+    }
+    Validation { warnings, error: None }
+}
+
+//Warnings about each element, returns the location of the point lights
+fn check_elements(
+    content: &str,
+    base_dir: &Path,
+    variables: &[(String, f64)],
+    warnings: &mut Vec<Warning>,
+) -> Result<Vec<Location>, SceneError> {
+    let mut reader = XmlReader::new(content, base_dir, 0.).with_variables(variables);
+    let mut lights = Vec::new();
+    //points of the face being read
+    let mut face: Option<(Location, Vec<Vec3d>)> = None;
+    //names of the library objects read
+    let mut library = Vec::new();
+    loop {
+        match reader.next()? {
+            Event::Eof => break,
+            Event::Start(e) => {
+                let at = reader.location();
+                check_element(&e, &at, warnings)?;
+                check_instance(&e, &at, &mut library, warnings)?;
+                match e.name().as_ref() {
+                    b"face" => face = Some((at, Vec::new())),
+                    b"point_light" => lights.push(at),
+                    _ => (),
+                }
+            }
+            Event::Empty(e) => {
+                let at = reader.location();
+                check_element(&e, &at, warnings)?;
+                check_instance(&e, &at, &mut library, warnings)?;
+                if let (Some((_, points)), b"pos") = (&mut face, e.name().as_ref()) {
+                    points.push(point(&e, &at)?);
+                }
+            }
+            Event::End(e) if e.name().as_ref() == b"face" => {
+                if let Some((location, points)) = face.take() {
+                    if let Err(message) = triangulate(&points) {
+                        warnings.push(Warning { location, message });
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    Ok(lights)
+}
+
+//instances are only drawn where objects are expected, from the library
+//objects written before them
+fn check_instance(
+    e: &BytesStart,
+    at: &Location,
+    library: &mut Vec<String>,
+    warnings: &mut Vec<Warning>,
+) -> Result<(), SceneError> {
+    let mut parents = at.path.rsplit('/').skip(1);
+    let parent = parents.next().unwrap_or_default();
+    let attributes = xml::attributes(e, at)?;
+    let attribute = |name: &str| attributes.iter().find(|(a, _)| a == name).map(|(_, v)| v.clone());
+    if parent == "library" && parents.next() == Some("scene") {
+        library.extend(attribute("name"));
+    }
+    if e.name().as_ref() != b"instance" {
+        return Ok(());
+    }
+    let mut warn = |message: String| {
+        warnings.push(Warning {
+            location: at.clone(),
+            message,
+        })
+    };
+    if !["scene", "library", "group", "csg"].contains(&parent) {
+        warn(format!("<instance> is not drawn in <{}>, it is ignored", parent));
+    }
+    if let Some(object) = attribute("object").filter(|object| !library.contains(object)) {
+        warn(format!("invalid object {:?}: there is no library object with this name", object));
+    }
+    Ok(())
+}
+
+fn check_element(e: &BytesStart, at: &Location, warnings: &mut Vec<Warning>) -> Result<(), SceneError> {
+    let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+    let mut warn = |message: String| {
+        warnings.push(Warning {
+            location: at.clone(),
+            message,
+        })
+    };
+    let attributes = xml::attributes(e, at)?;
+    let Some((_, known)) = ELEMENTS.iter().find(|(element, _)| *element == name) else {
+        warn(format!("unknown element <{}>, it is ignored", name));
+        return Ok(());
+    };
+    let textured = TEXTURED.contains(&name.as_str());
+    let is_known = |attribute: &str| known.contains(&attribute) || (textured && TEXTURE.contains(&attribute));
+    for (attribute, _) in attributes.iter() {
+        let attribute = attribute.as_str();
+        if !is_known(attribute) {
+            warn(format!("unknown attribute {} of <{}>, it is ignored", attribute, name));
+        }
+    }
+
+    //values that can be parsed, the loader reports the others
+    let value = |attribute: &str| {
+        attributes
+            .iter()
+            .find(|(a, _)| a == attribute)
+            .and_then(|(_, v)| v.trim().parse::<f64>().ok())
+    };
+    if let Some(octaves) = value("octaves").filter(|o| textured && *o > MAX_OCTAVES as f64) {
+        warn(format!("octaves={} is clamped to {}", octaves, MAX_OCTAVES));
+    }
+    match name.as_str() {
+        "radius" | "top_radius" | "tube" | "sphere" | "cylinder" | "torus" => {
+            for attribute in ["r", "t"] {
+                if let Some(r) = value(attribute).filter(|r| *r < 0.) {
+                    warn(format!("negative radius {}={}", attribute, r));
+                }
+            }
+        }
+        "color" | "diffuse" | "specular" | "transmission" | "base_color" => {
+            for attribute in ["r", "g", "b", "r2", "g2", "b2"] {
+                if let Some(c) = value(attribute).filter(|c| !(0. ..=1.).contains(c)) {
+                    warn(format!("color {}={} is clamped to [0, 1]", attribute, c));
+                }
+            }
+        }
+        "roughness" if value("r") == Some(0.) && !attributes.iter().any(|(a, _)| a == "texture") => {
+            warn(String::from("a roughness of 0 divides by zero in the specular lighting"))
+        }
+        _ => (),
+    }
+    Ok(())
+}
+
+fn point(e: &BytesStart, at: &Location) -> Result<Vec3d, SceneError> {
+    let attributes = xml::attributes(e, at)?;
+    let coordinate = |name: &str| {
+        attributes
+            .iter()
+            .find(|(a, _)| a == name)
+            .and_then(|(_, v)| v.trim().parse::<f64>().ok())
+            .unwrap_or(0.)
+    };
+    Ok(Vec3d {
+        x: coordinate("x"),
+        y: coordinate("y"),
+        z: coordinate("z"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const CAMERA: &str = "<camera><pos x=\"0\" y=\"0\" z=\"0\"/><dir x=\"1\" y=\"0\" z=\"0\"/></camera>
+        <ambiant_light><color r=\"1\" g=\"1\" b=\"1\"/><intensity i=\"1\"/></ambiant_light>";
+
+    fn messages(validation: &Validation) -> Vec<(u32, &str)> {
+        validation
+            .warnings
+            .iter()
+            .map(|w| (w.location.line, w.message.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn valid_scene() {
+        let validation = validate(&format!("<scene>{}</scene>", CAMERA), Path::new("."), &[]);
+        assert_eq!(validation.warnings, vec![]);
+        assert_eq!(validation.error, None);
+    }
+
+    #[test]
+    fn warnings() {
+        let content = format!(
+            "<scene>{}
+            <sphere>
+                <pos x=\"0\" y=\"0\" z=\"0\" w=\"1\"/><radius r=\"-1\"/>
+                <material><diffuse r=\"2\" g=\"1\" b=\"1\"/><specular r=\"1\" g=\"1\" b=\"1\"/><roughness r=\"0\"/></material>
+                <bump texture=\"noise\" octaves=\"40\"/>
+                <colour r=\"1\"/>
+            </sphere>
+            </scene>",
+            CAMERA
+        );
+        let validation = validate(&content, Path::new("."), &[]);
+        assert_eq!(
+            messages(&validation),
+            vec![
+                (4, "unknown attribute w of <pos>, it is ignored"),
+                (4, "negative radius r=-1"),
+                (5, "color r=2 is clamped to [0, 1]"),
+                (5, "a roughness of 0 divides by zero in the specular lighting"),
+                (6, "octaves=40 is clamped to 30"),
+                (7, "unknown element <colour>, it is ignored"),
+            ]
+        );
+        assert_eq!(validation.error, None);
+    }
+
+    #[test]
+    fn light_inside_object() {
+        let content = format!(
+            "<scene>{}
+            <point_light><pos x=\"5\" y=\"0\" z=\"0\"/><color r=\"1\" g=\"1\" b=\"1\"/><intensity i=\"1\"/></point_light>
+            <point_light><pos x=\"0\" y=\"0\" z=\"5\"/><color r=\"1\" g=\"1\" b=\"1\"/><intensity i=\"1\"/></point_light>
+            <sphere>
+                <pos x=\"5\" y=\"0\" z=\"0\"/><radius r=\"1\"/>
+                <material><diffuse r=\"1\" g=\"1\" b=\"1\"/><specular r=\"1\" g=\"1\" b=\"1\"/></material>
+            </sphere>
+            </scene>",
+            CAMERA
+        );
+        let validation = validate(&content, Path::new("."), &[]);
+        assert_eq!(
+            messages(&validation),
+            vec![(3, "the light is inside a closed object, which hides it")]
+        );
+    }
+
+    #[test]
+    fn degenerate_faces() {
+        let face = "<face><pos x=\"0\" y=\"0\" z=\"0\"/><pos x=\"1\" y=\"0\" z=\"0\"/><pos x=\"2\" y=\"0\" z=\"0\"/></face>";
+        let content = format!(
+            "<scene>{}\n<object>\n{}\n{}\n<material><diffuse r=\"1\" g=\"1\" b=\"1\"/><specular r=\"1\" g=\"1\" b=\"1\"/></material>\n</object>\n</scene>",
+            CAMERA, face, face
+        );
+        let validation = validate(&content, Path::new("."), &[]);
+        let degenerate = "face with 3 points is degenerate (no area)";
+        //the first face prevents loading the scene
+        assert_eq!(messages(&validation), vec![(5, degenerate)]);
+        let Some(SceneError::InvalidValue { location, .. }) = validation.error else {
+            panic!("invalid points expected")
+        };
+        assert_eq!(location.line, 4);
+    }
+
+    #[test]
+    fn instances() {
+        let content = |object: &str| {
+            format!(
+                "<scene>{}
+                <library>
+                    <sphere name=\"ball\"><pos x=\"5\" y=\"0\" z=\"0\"/><radius r=\"1\"/>
+                        <material><diffuse r=\"1\" g=\"1\" b=\"1\"/><specular r=\"1\" g=\"1\" b=\"1\"/></material></sphere>
+                </library>
+                <group><instance object=\"{}\"/></group>
+                <sphere><pos x=\"0\" y=\"0\" z=\"5\"/><radius r=\"1\"/><instance object=\"ball\"/>
+                    <material><diffuse r=\"1\" g=\"1\" b=\"1\"/><specular r=\"1\" g=\"1\" b=\"1\"/></material></sphere>
+                </scene>",
+                CAMERA, object
+            )
+        };
+        let validation = validate(&content("ball"), Path::new("."), &[]);
+        assert_eq!(messages(&validation), vec![(8, "<instance> is not drawn in <sphere>, it is ignored")]);
+        assert_eq!(validation.error, None);
+
+        let validation = validate(&content("cube"), Path::new("."), &[]);
+        assert_eq!(messages(&validation), vec![(8, "<instance> is not drawn in <sphere>, it is ignored")]);
+        let Some(SceneError::InvalidValue { location, value, .. }) = validation.error else {
+            panic!("unknown object expected")
+        };
+        assert_eq!((location.line, value.as_str()), (7, "cube"));
+    }
+
+    #[test]
+    fn invalid_scene() {
+        let validation = validate("<scene>\n<ambient_light></ambient_light>\n</scene>", Path::new("."), &[]);
+        assert_eq!(
+            messages(&validation),
+            vec![(2, "unknown element <ambient_light>, it is ignored")]
+        );
+        assert!(matches!(validation.error, Some(SceneError::UnknownElement { .. })));
+    }
+}